@@ -0,0 +1,91 @@
+use crate::paths::tool_command;
+use crate::temp::TempFile;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Archive does not exist: {0}")]
+    NotFound(String),
+    #[error(
+        "Unsupported archive format: {0} (expected .zip, .tar, .tar.gz, .tgz, .tar.bz2 or .tar.xz)"
+    )]
+    Unsupported(String),
+    #[error("Could not read {archive}: {message}")]
+    Read { archive: String, message: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+}
+
+/// Archive format from the file name; tar picks the compression itself
+fn archive_kind(archive: &Path) -> Option<ArchiveKind> {
+    let name = archive.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") || name.ends_with(".jar") {
+        return Some(ArchiveKind::Zip);
+    }
+    [
+        ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz",
+    ]
+    .iter()
+    .any(|ext| name.ends_with(ext))
+    .then_some(ArchiveKind::Tar)
+}
+
+/// Run unzip/tar on `archive` and return stdout, or the trimmed stderr on failure
+fn run_archive_tool(program: &str, args: &[&str], archive: &Path) -> Result<Vec<u8>, ArchiveError> {
+    let output = tool_command(program, &[archive]).args(args).output()?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(ArchiveError::Read {
+            archive: archive.to_string_lossy().to_string(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+/// Entry name without a leading `./` or `/`, as the user would write it
+fn normalize_entry(name: &str) -> &str {
+    name.trim_start_matches("./").trim_start_matches('/')
+}
+
+/// Extract `entry` from a zip or tar archive into a temp file named after it.
+/// None when the archive has no such file.
+pub fn extract_entry(archive: &Path, entry: &str) -> Result<Option<TempFile>, ArchiveError> {
+    let archive_str = archive.to_string_lossy().to_string();
+    if !archive.is_file() {
+        return Err(ArchiveError::NotFound(archive_str));
+    }
+    let kind =
+        archive_kind(archive).ok_or_else(|| ArchiveError::Unsupported(archive_str.clone()))?;
+
+    // Look the entry up first, so a missing one isn't mistaken for a broken archive
+    let listing = match kind {
+        ArchiveKind::Zip => run_archive_tool("unzip", &["-Z1", &archive_str], archive)?,
+        ArchiveKind::Tar => run_archive_tool("tar", &["-tf", &archive_str], archive)?,
+    };
+    let wanted = normalize_entry(entry);
+    let listing = String::from_utf8_lossy(&listing);
+    let Some(name) = listing
+        .lines()
+        .find(|name| normalize_entry(name) == wanted && !name.ends_with('/'))
+    else {
+        return Ok(None);
+    };
+
+    let content = match kind {
+        ArchiveKind::Zip => run_archive_tool("unzip", &["-p", &archive_str, name], archive)?,
+        ArchiveKind::Tar => run_archive_tool("tar", &["-xOf", &archive_str, name], archive)?,
+    };
+    let file_name = Path::new(wanted)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "entry".to_string());
+    Ok(Some(TempFile::new(&file_name, &content)?))
+}
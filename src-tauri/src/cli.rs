@@ -0,0 +1,71 @@
+use crate::delta::DiffOptions;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+/// Compare two directories side by side
+#[derive(Debug, Parser)]
+#[command(name = "diff-rust", version)]
+struct LaunchArgs {
+    /// Left (old) directory
+    #[arg(value_name = "LEFT", conflicts_with = "left_dir")]
+    left: Option<String>,
+    /// Right (new) directory
+    #[arg(value_name = "RIGHT", conflicts_with = "right_dir")]
+    right: Option<String>,
+    /// Left (old) directory
+    #[arg(long = "left", value_name = "DIR")]
+    left_dir: Option<String>,
+    /// Right (new) directory
+    #[arg(long = "right", value_name = "DIR")]
+    right_dir: Option<String>,
+    /// Show diffs inline instead of side by side
+    #[arg(long)]
+    inline: bool,
+    /// Hide line numbers
+    #[arg(long)]
+    no_line_numbers: bool,
+    /// Show whole files instead of only the changed hunks
+    #[arg(long)]
+    full_context: bool,
+    /// Delta syntax theme, e.g. "Monokai Extended"
+    #[arg(long, value_name = "NAME")]
+    syntax_theme: Option<String>,
+}
+
+/// What the window should open with, from the command line
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LaunchConfig {
+    pub left: Option<String>,
+    pub right: Option<String>,
+    /// View options, starting from the viewer's defaults (side by side, line numbers, collapsed)
+    pub options: DiffOptions,
+}
+
+/// Parse command-line arguments (the first one being the program) into a LaunchConfig.
+/// Bad arguments, `--help` and `--version` come back as the message to show.
+pub fn parse_launch_args<I, T>(args: I) -> Result<LaunchConfig, String>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let args = LaunchArgs::try_parse_from(args).map_err(|e| e.render().to_string())?;
+    let left = args.left.or(args.left_dir);
+    let right = args.right.or(args.right_dir);
+    if left.is_some() != right.is_some() {
+        return Err(
+            "Both a left and a right directory are needed to start a comparison".to_string(),
+        );
+    }
+
+    Ok(LaunchConfig {
+        left,
+        right,
+        options: DiffOptions {
+            side_by_side: !args.inline,
+            line_numbers: !args.no_line_numbers,
+            collapsed: !args.full_context,
+            syntax_theme: args.syntax_theme,
+            ..Default::default()
+        },
+    })
+}
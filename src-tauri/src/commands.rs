@@ -1,9 +1,43 @@
-use crate::delta::{generate_diff, get_file_content, DiffOptions, DiffResult};
-use crate::diff::{build_file_tree, compare_directories, FileEntry, FileTreeNode};
+use crate::archive::extract_entry;
+use crate::cli::{parse_launch_args, LaunchConfig};
+use crate::delta::{
+    ansi_stylesheet, ansi_to_html_classed, ansi_to_themed_html, change_fingerprint,
+    count_line_changes, count_line_changes_until, diff_legend, generate_diff,
+    generate_diff_both_modes, generate_diff_page, generate_diff_preview, generate_file_diff_bundle,
+    generate_region_diff, get_file_content, highlight_file, run_diff_for_options, summarize_diff,
+    DiffOptions, DiffPage, DiffResult, DiffTheme, DualDiffResult, FileDiffBundle, LegendEntry,
+    ThemeMode,
+};
+use crate::diff::{
+    build_file_tree_with_options, change_heatmap, changed_extensions, classify_entries,
+    compare_directories_cancellable, compare_pairs, diff_stats, extension_breakdown,
+    identical_files, measure_rename, parse_nul_pairs, parse_recursive_diff, review_priorities,
+    status_transitions, CompareOptions, ComparisonCounts, ComparisonKind, CostEstimate,
+    DiffStatReport, DirHeat, ExtStats, FileEntry, FileStatus, FileTreeNode, IdenticalReport,
+    RenameInfo, StatusTransition, DEFAULT_FILE_TIMEOUT,
+};
+use crate::export::{comparison_patch, export_json, export_review, ExportOptions};
+use crate::git::{
+    attach_blame, branch_diff_entries, extract_blob, ref_diff_entries, BlobRef, PatchCheck,
+};
+use crate::hunks::{changed_lines, diff_lines, unchanged_ranges, DiffLine, LineChange};
+use crate::merge::{
+    build_merge_tree, compare_directories_three_way, MergeEntry, MergeStatus, MergeTreeNode,
+};
+use crate::remote::resolve_input;
+use crate::review::{run_review, ReviewEvent, ReviewOptions, ReviewSummary};
+use crate::syntax::apply_syntax_tokens;
+use crate::temp::TempFile;
+use crate::tools::{detect_capabilities, Capabilities};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::Response;
+use tauri::{AppHandle, Emitter, Theme, Window};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FileTreeResult {
     pub tree: Vec<FileTreeNode>,
     pub files: Vec<FileEntry>,
@@ -11,26 +45,82 @@ pub struct FileTreeResult {
     pub added: usize,
     pub deleted: usize,
     pub modified: usize,
+    /// `files` and `tree` stop at CompareOptions::max_files; the counts above don't
+    #[serde(default)]
+    pub truncated: bool,
+    /// Changed files before the max_files cap
+    #[serde(default)]
+    pub total_available: usize,
 }
 
-#[tauri::command]
-#[allow(non_snake_case)]
-pub fn get_file_tree(leftDir: &str, rightDir: &str) -> Result<FileTreeResult, String> {
-    let left_path = Path::new(leftDir);
-    let right_path = Path::new(rightDir);
-
-    if !left_path.exists() {
-        return Err(format!("Left directory does not exist: {}", leftDir));
+/// Error out unless both directories exist
+fn check_dirs(left_dir: &str, right_dir: &str) -> Result<(), String> {
+    if !Path::new(left_dir).exists() {
+        return Err(format!("Left directory does not exist: {}", left_dir));
     }
 
-    if !right_path.exists() {
-        return Err(format!("Right directory does not exist: {}", rightDir));
+    if !Path::new(right_dir).exists() {
+        return Err(format!("Right directory does not exist: {}", right_dir));
     }
 
-    let entries = compare_directories(left_path, right_path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The directory comparisons in progress: the token each was started with, if any, and
+/// its own cancel flag
+static RUNNING_COMPARISONS: Mutex<Vec<(Option<String>, Arc<AtomicBool>)>> = Mutex::new(Vec::new());
+
+/// Run `compare` with a cancel flag of its own, which cancel_comparison sets when given
+/// `token` (or no token)
+fn cancellable<T>(token: Option<&str>, compare: impl FnOnce(&AtomicBool) -> T) -> T {
+    let cancel = Arc::new(AtomicBool::new(false));
+    RUNNING_COMPARISONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((token.map(str::to_string), cancel.clone()));
+    let result = compare(&cancel);
+    RUNNING_COMPARISONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|(_, running)| !Arc::ptr_eq(running, &cancel));
+    result
+}
+
+/// Validate both directories exist and compare them, cancellable by `token`
+fn compare_checked(
+    left_dir: &str,
+    right_dir: &str,
+    options: &CompareOptions,
+    token: Option<&str>,
+) -> Result<Vec<FileEntry>, String> {
+    check_dirs(left_dir, right_dir)?;
 
-    let tree = build_file_tree(&entries);
+    cancellable(token, |cancel| {
+        compare_directories_cancellable(Path::new(left_dir), Path::new(right_dir), options, cancel)
+    })
+    .map_err(|e| e.to_string())
+}
 
+/// Stop the directory comparison started with `cancelToken`, or every one in progress
+/// without it; they fail with "Comparison cancelled". False when none matched.
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn cancel_comparison(cancelToken: Option<String>) -> bool {
+    let running = RUNNING_COMPARISONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let mut cancelled = false;
+    for (token, cancel) in running.iter() {
+        if cancelToken.is_none() || *token == cancelToken {
+            cancel.store(true, Ordering::Relaxed);
+            cancelled = true;
+        }
+    }
+    cancelled
+}
+
+/// Build the tree and change counts the frontend shows for a comparison
+fn file_tree_result(entries: Vec<FileEntry>, options: &CompareOptions) -> FileTreeResult {
     // Count changes by status
     let added = entries
         .iter()
@@ -45,35 +135,848 @@ pub fn get_file_tree(leftDir: &str, rightDir: &str) -> Result<FileTreeResult, St
         .filter(|e| matches!(e.status, crate::diff::FileStatus::Modified))
         .count();
 
-    Ok(FileTreeResult {
+    // Unchanged files never show in the tree, so max_files only counts changed ones
+    let mut changed: Vec<FileEntry> = entries
+        .into_iter()
+        .filter(|e| !matches!(e.status, crate::diff::FileStatus::Unchanged))
+        .collect();
+    let total_available = changed.len();
+    let truncated = options.max_files.is_some_and(|max| total_available > max);
+    if let Some(max) = options.max_files {
+        changed.truncate(max);
+    }
+
+    let tree = build_file_tree_with_options(&changed, options);
+
+    // The flat list repeats what the tree holds, so tree-only callers skip serializing it
+    let files = if options.tree_only {
+        Vec::new()
+    } else {
+        changed
+    };
+
+    FileTreeResult {
         tree,
-        files: entries
-            .into_iter()
-            .filter(|e| !matches!(e.status, crate::diff::FileStatus::Unchanged))
-            .collect(),
+        files,
         total_changes: added + deleted + modified,
         added,
         deleted,
         modified,
+        truncated,
+        total_available,
+    }
+}
+
+// Runs off the main thread so cancel_comparison can be handled meanwhile
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_file_tree(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+    cancelToken: Option<String>,
+) -> Result<FileTreeResult, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options, cancelToken.as_deref())?;
+
+    Ok(file_tree_result(entries, &options))
+}
+
+/// FileTreeResult as MessagePack, with field names kept so it decodes to the same shape as the JSON
+pub fn encode_file_tree(result: &FileTreeResult) -> Result<Vec<u8>, String> {
+    rmp_serde::to_vec_named(result).map_err(|e| format!("Failed to encode file tree: {}", e))
+}
+
+/// get_file_tree for large comparisons: the result goes over IPC as raw MessagePack bytes
+/// (an ArrayBuffer on the frontend) instead of JSON
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_file_tree_binary(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+    cancelToken: Option<String>,
+) -> Result<Response, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options, cancelToken.as_deref())?;
+
+    encode_file_tree(&file_tree_result(entries, &options)).map(Response::new)
+}
+
+/// Changed files of a three-way comparison, as a list and as a tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeTreeResult {
+    pub tree: Vec<MergeTreeNode>,
+    pub files: Vec<MergeEntry>,
+    pub conflicts: usize,
+}
+
+/// Compare a base directory with two changed copies of it, flagging the files both
+/// changed differently as conflicts
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_merge_tree(
+    baseDir: &str,
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+    cancelToken: Option<String>,
+) -> Result<MergeTreeResult, String> {
+    if !Path::new(baseDir).exists() {
+        return Err(format!("Base directory does not exist: {}", baseDir));
+    }
+    check_dirs(leftDir, rightDir)?;
+    let options = options.unwrap_or_default();
+
+    let mut files = cancellable(cancelToken.as_deref(), |cancel| {
+        compare_directories_three_way(
+            Path::new(baseDir),
+            Path::new(leftDir),
+            Path::new(rightDir),
+            &options,
+            cancel,
+        )
+    })
+    .map_err(|e| e.to_string())?;
+    files.retain(|f| f.status != MergeStatus::Unchanged);
+    Ok(MergeTreeResult {
+        tree: build_merge_tree(&files, &options),
+        conflicts: files
+            .iter()
+            .filter(|f| f.status == MergeStatus::Conflict)
+            .count(),
+        files,
+    })
+}
+
+/// Compare two directories, sending each changed file to the frontend as a `file-entry`
+/// event as soon as it's classified, then the totals as `comparison-complete`. Entries
+/// arrive unsorted and without cross-file move hints; unchanged files are only counted.
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn compare_streaming(
+    app: AppHandle,
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+    cancelToken: Option<String>,
+) -> Result<ComparisonCounts, String> {
+    check_dirs(leftDir, rightDir)?;
+    let options = options.unwrap_or_default();
+
+    let mut counts = ComparisonCounts::default();
+    let mut emit = |entry: FileEntry| {
+        counts.count(&entry);
+        if entry.status != FileStatus::Unchanged {
+            let _ = app.emit("file-entry", entry);
+        }
+    };
+    cancellable(cancelToken.as_deref(), |cancel| {
+        classify_entries(
+            Path::new(leftDir),
+            Path::new(rightDir),
+            &options,
+            cancel,
+            &mut emit,
+        )
     })
+    .map_err(|e| e.to_string())?;
+
+    let _ = app.emit("comparison-complete", counts.clone());
+    Ok(counts)
+}
+
+/// A whole review in one call: each changed file as a `file-entry` event, then (with
+/// precompute_diffs) each file's diff as `file-diff-ready` with progress, then the totals as
+/// `review-complete`. cancel_comparison stops it at any stage.
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn review_directories(
+    app: AppHandle,
+    window: Window,
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<ReviewOptions>,
+    cancelToken: Option<String>,
+) -> Result<ReviewSummary, String> {
+    check_dirs(leftDir, rightDir)?;
+    let mut options = options.unwrap_or_default();
+    options.diff = themed(&window, options.diff);
+
+    let mut emit = |event: ReviewEvent| {
+        let _ = match event {
+            ReviewEvent::Entry(entry) => app.emit("file-entry", entry),
+            ReviewEvent::DiffReady(ready) => app.emit("file-diff-ready", ready),
+            ReviewEvent::Complete(summary) => app.emit("review-complete", summary),
+        };
+    };
+    cancellable(cancelToken.as_deref(), |cancel| {
+        run_review(
+            Path::new(leftDir),
+            Path::new(rightDir),
+            &options,
+            cancel,
+            &mut emit,
+        )
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Files left untouched between the two directories: same path, or moved as-is
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_identical_files(leftDir: &str, rightDir: &str) -> Result<IdenticalReport, String> {
+    let entries = compare_checked(leftDir, rightDir, &CompareOptions::default(), None)?;
+    Ok(identical_files(&entries))
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_branch_diff(
+    repoPath: &str,
+    baseBranch: &str,
+    options: Option<CompareOptions>,
+) -> Result<FileTreeResult, String> {
+    let options = options.unwrap_or_default();
+    let entries = branch_diff_entries(Path::new(repoPath), baseBranch, &options)
+        .map_err(|e| e.to_string())?;
+
+    Ok(file_tree_result(entries, &options))
+}
+
+/// Changes between two commits, branches or tags of one repository
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_git_diff(
+    repoPath: &str,
+    leftRef: &str,
+    rightRef: &str,
+    options: Option<CompareOptions>,
+) -> Result<FileTreeResult, String> {
+    let options = options.unwrap_or_default();
+    let entries = ref_diff_entries(Path::new(repoPath), leftRef, rightRef, &options)
+        .map_err(|e| e.to_string())?;
+
+    Ok(file_tree_result(entries, &options))
+}
+
+/// Label a comparison (same project at two versions, partial overlap or unrelated) from a
+/// quick scan, e.g. to pick defaults such as rename detection
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn classify_comparison(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+) -> Result<ComparisonKind, String> {
+    let options = options.unwrap_or_default();
+    check_dirs(leftDir, rightDir)?;
+
+    crate::diff::classify_comparison(Path::new(leftDir), Path::new(rightDir), &options)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn estimate_comparison(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+) -> Result<CostEstimate, String> {
+    let options = options.unwrap_or_default();
+    check_dirs(leftDir, rightDir)?;
+
+    crate::diff::estimate_comparison(Path::new(leftDir), Path::new(rightDir), &options)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_extension_breakdown(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+) -> Result<Vec<(String, ExtStats)>, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options, None)?;
+
+    Ok(extension_breakdown(&entries, entry_line_stats))
+}
+
+/// Extensions among the changed files, for filtering the tree by type
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_changed_extensions(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+) -> Result<Vec<String>, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options, None)?;
+
+    Ok(changed_extensions(&entries))
+}
+
+/// Changed files and lines per directory, with totals that include subdirectories
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_change_heatmap(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+) -> Result<Vec<DirHeat>, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options, None)?;
+
+    Ok(change_heatmap(&entries, entry_line_stats))
+}
+
+/// A Renamed entry whose content didn't change
+fn is_unmodified_rename(entry: &FileEntry) -> bool {
+    entry.status == FileStatus::Renamed
+        && entry
+            .rename_info
+            .as_ref()
+            .is_none_or(|rename| !rename.modified)
+}
+
+/// (added, removed) lines for an entry, as used by the breakdown, heatmap and review priorities
+fn entry_line_stats(entry: &FileEntry) -> (usize, usize) {
+    // Renames without changes contribute no line changes; files over
+    // max_file_size_for_diff aren't read line by line
+    if is_unmodified_rename(entry) || entry.skip_content {
+        return (0, 0);
+    }
+    let left = entry.left_path.as_deref().map(Path::new);
+    let right = entry.right_path.as_deref().map(Path::new);
+    count_line_changes(left, right).unwrap_or((0, 0))
+}
+
+/// entry_line_stats with the count given up at `deadline` (None then)
+fn entry_line_stats_until(entry: &FileEntry, deadline: Instant) -> Option<(usize, usize)> {
+    if is_unmodified_rename(entry) || entry.skip_content {
+        return Some((0, 0));
+    }
+    let left = entry.left_path.as_deref().map(Path::new);
+    let right = entry.right_path.as_deref().map(Path::new);
+    count_line_changes_until(left, right, deadline).unwrap_or(Some((0, 0)))
+}
+
+/// Added/removed lines of every changed file. A file whose count takes longer than
+/// `timeoutMs` (DEFAULT_FILE_TIMEOUT if unset) is flagged `timed_out` and the rest carry on.
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_diff_stat(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+    timeoutMs: Option<u64>,
+) -> Result<DiffStatReport, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options, None)?;
+    let timeout = timeoutMs.map_or(DEFAULT_FILE_TIMEOUT, Duration::from_millis);
+
+    Ok(diff_stats(&entries, timeout, entry_line_stats_until))
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn prioritize_review(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<CompareOptions>,
+) -> Result<Vec<(String, f32)>, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options, None)?;
+
+    review_priorities(&entries, &options, entry_line_stats).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn compare_pairs_raw(
+    nulSeparated: String,
+    options: Option<CompareOptions>,
+) -> Result<Vec<FileEntry>, String> {
+    let options = options.unwrap_or_default();
+    let pairs = parse_nul_pairs(&nulSeparated).map_err(|e| e.to_string())?;
+
+    compare_pairs(&pairs, &options).map_err(|e| e.to_string())
 }
 
+/// The file tree for recursive `diff -r`/`diff -rq` output between the two directories
 #[tauri::command]
 #[allow(non_snake_case)]
+pub fn import_recursive_diff(leftDir: &str, rightDir: &str, output: String) -> FileTreeResult {
+    let entries = parse_recursive_diff(&output, Path::new(leftDir), Path::new(rightDir));
+    file_tree_result(entries, &CompareOptions::default())
+}
+
+#[tauri::command]
+pub fn diff_comparisons(prev: FileTreeResult, curr: FileTreeResult) -> Vec<StatusTransition> {
+    status_transitions(&prev.files, &curr.files)
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn export_comparison_json(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<ExportOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options.compare, None)?;
+
+    export_json(&entries, &options)
+}
+
+/// The comparison as one patch for `git apply`; only options.compare is used
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn export_patch(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<ExportOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options.compare, None)?;
+
+    comparison_patch(&entries).map_err(|e| e.to_string())
+}
+
+/// Changed regions of a comparison as versioned JSON for review tooling (see ReviewReport)
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn export_review_json(
+    leftDir: &str,
+    rightDir: &str,
+    options: Option<ExportOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let entries = compare_checked(leftDir, rightDir, &options.compare, None)?;
+
+    export_review(&entries, &options)
+}
+
+/// A diff input as a local file: a path, a downloaded URL or a git blob written to a temp
+/// file that's removed again when the input is dropped
+enum LocalInput {
+    Path(PathBuf),
+    Blob(TempFile),
+}
+
+impl std::ops::Deref for LocalInput {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        match self {
+            LocalInput::Path(path) => path,
+            LocalInput::Blob(file) => file.path(),
+        }
+    }
+}
+
+/// Local file for a diff input, downloading http(s) URLs and reading BlobRefs
+fn resolve_local(path: &str) -> Result<LocalInput, String> {
+    match BlobRef::parse(path) {
+        Some(blob) => blob.read().map(LocalInput::Blob).map_err(|e| e.to_string()),
+        None => resolve_input(path)
+            .map(LocalInput::Path)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// resolve_local for an optional diff input
+fn local_input(path: Option<&str>) -> Result<Option<LocalInput>, String> {
+    path.map(resolve_local).transpose()
+}
+
+/// `mode` with Auto resolved against the light or dark appearance of `window`
+fn window_theme(window: &Window, mode: ThemeMode) -> ThemeMode {
+    mode.resolve(matches!(window.theme(), Ok(Theme::Light)))
+}
+
+/// `options` with their theme resolved by window_theme
+fn themed(window: &Window, options: DiffOptions) -> DiffOptions {
+    DiffOptions {
+        theme: window_theme(window, options.theme),
+        ..options
+    }
+}
+
+#[tauri::command(async)]
+#[allow(non_snake_case)]
 pub fn get_diff(
+    window: Window,
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: DiffOptions,
+    rename: Option<RenameInfo>,
+) -> Result<DiffResult, String> {
+    diff_inputs(leftPath, rightPath, &themed(&window, options), rename)
+}
+
+/// get_diff with the theme already resolved
+pub(crate) fn diff_inputs(
+    left: Option<&str>,
+    right: Option<&str>,
+    options: &DiffOptions,
+    rename: Option<RenameInfo>,
+) -> Result<DiffResult, String> {
+    let left = local_input(left)?;
+    let right = local_input(right)?;
+
+    let mut result =
+        generate_diff(left.as_deref(), right.as_deref(), options).map_err(|e| e.to_string())?;
+    // Only the names come from the caller; how similar the files are is measured here
+    result.rename_info = match (rename, left.as_deref(), right.as_deref()) {
+        (Some(rename), Some(l), Some(r)) => {
+            Some(measure_rename(&rename.from, &rename.to, l, r).map_err(|e| e.to_string())?)
+        }
+        _ => None,
+    };
+    Ok(result)
+}
+
+/// The diff, its line counts, hunks and first change for a file being opened, in one call
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn open_file_diff(
+    window: Window,
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: DiffOptions,
+) -> Result<FileDiffBundle, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    let options = themed(&window, options);
+    generate_file_diff_bundle(left.as_deref(), right.as_deref(), &options)
+        .map_err(|e| e.to_string())
+}
+
+/// Hunks `hunkRange.0..hunkRange.1` of a file's diff and the total, to load huge diffs page by page
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_diff_page(
+    window: Window,
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: DiffOptions,
+    hunkRange: (usize, usize),
+) -> Result<DiffPage, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    let options = themed(&window, options);
+    generate_diff_page(left.as_deref(), right.as_deref(), &options, hunkRange)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_diff_both_modes(
+    window: Window,
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: DiffOptions,
+) -> Result<DualDiffResult, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    let options = themed(&window, options);
+    generate_diff_both_modes(left.as_deref(), right.as_deref(), &options).map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_diff_preview(
+    window: Window,
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    maxLines: usize,
+    options: DiffOptions,
+) -> Result<DiffResult, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    let options = themed(&window, options);
+    generate_diff_preview(left.as_deref(), right.as_deref(), maxLines, &options)
+        .map_err(|e| e.to_string())
+}
+
+/// One-line description of a file's changes for the tree; `rename` is the entry's rename_info
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn summarize_file_diff(
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: Option<DiffOptions>,
+    rename: Option<RenameInfo>,
+) -> Result<String, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    summarize_diff(
+        left.as_deref(),
+        right.as_deref(),
+        rename.as_ref(),
+        &options.unwrap_or_default(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Same fingerprint for the same change, wherever in the files it was made
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn diff_fingerprint(
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: Option<DiffOptions>,
+) -> Result<String, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    change_fingerprint(
+        left.as_deref(),
+        right.as_deref(),
+        &options.unwrap_or_default(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_diff_lines(
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: DiffOptions,
+) -> Result<Vec<DiffLine>, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    let diff_text = run_diff_for_options(
+        left.as_deref(),
+        right.as_deref(),
+        options.diff_context(),
+        &options,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut lines = diff_lines(&diff_text);
+
+    if options.semantic_highlight {
+        apply_syntax_tokens(&mut lines, left.as_deref(), right.as_deref());
+    }
+    if options.show_blame {
+        attach_blame(&mut lines, leftPath, rightPath);
+    }
+
+    Ok(lines)
+}
+
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_changed_lines(
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    options: DiffOptions,
+) -> Result<Vec<LineChange>, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    // Changes the view hides (e.g. with ignore_whitespace) aren't reported either
+    let diff_text = run_diff_for_options(left.as_deref(), right.as_deref(), 0, &options)
+        .map_err(|e| e.to_string())?;
+    Ok(changed_lines(&diff_text))
+}
+
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_unchanged_ranges(
     leftPath: Option<&str>,
     rightPath: Option<&str>,
     options: DiffOptions,
+) -> Result<Vec<(u32, u32)>, String> {
+    let left = local_input(leftPath)?;
+    let right = local_input(rightPath)?;
+
+    let new_total = match right.as_deref() {
+        Some(path) => {
+            let content = std::fs::read(path).map_err(|e| e.to_string())?;
+            let newlines = content.iter().filter(|&&b| b == b'\n').count();
+            (newlines + usize::from(content.last().is_some_and(|&b| b != b'\n'))) as u32
+        }
+        None => 0,
+    };
+
+    // Lines the view hides changes on count as unchanged; function context would only
+    // widen the hunks, so it's left out
+    let options = DiffOptions {
+        show_function_context: false,
+        ..options
+    };
+    let diff_text = run_diff_for_options(left.as_deref(), right.as_deref(), 0, &options)
+        .map_err(|e| e.to_string())?;
+    Ok(unchanged_ranges(&diff_text, new_total))
+}
+
+#[tauri::command]
+#[allow(non_snake_case, clippy::too_many_arguments)]
+pub fn get_cross_repo_diff(
+    window: Window,
+    repoA: &str,
+    refA: &str,
+    pathA: &str,
+    repoB: &str,
+    refB: &str,
+    pathB: &str,
+    options: DiffOptions,
 ) -> Result<DiffResult, String> {
-    let left = leftPath.map(Path::new);
-    let right = rightPath.map(Path::new);
+    let left = extract_blob(Path::new(repoA), refA, pathA, "left").map_err(|e| e.to_string())?;
+    let right = extract_blob(Path::new(repoB), refB, pathB, "right").map_err(|e| e.to_string())?;
 
-    generate_diff(left, right, &options).map_err(|e| e.to_string())
+    let options = themed(&window, options);
+    generate_diff(Some(left.path()), Some(right.path()), &options).map_err(|e| e.to_string())
 }
 
+/// Diff a local file against one entry of a zip or tar archive, the archived copy being
+/// the old side: a missing entry shows the file as added, a missing local file as deleted
 #[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_diff_vs_archive_entry(
+    window: Window,
+    localPath: &str,
+    archivePath: &str,
+    entryPath: &str,
+    options: DiffOptions,
+) -> Result<DiffResult, String> {
+    diff_vs_archive_entry(localPath, archivePath, entryPath, &themed(&window, options))
+}
+
+/// get_diff_vs_archive_entry with the theme already resolved
+pub(crate) fn diff_vs_archive_entry(
+    local_path: &str,
+    archive_path: &str,
+    entry_path: &str,
+    options: &DiffOptions,
+) -> Result<DiffResult, String> {
+    let archived = extract_entry(Path::new(archive_path), entry_path).map_err(|e| e.to_string())?;
+    let local = Path::new(local_path);
+    let local = local.is_file().then_some(local);
+    if archived.is_none() && local.is_none() {
+        return Err(format!(
+            "Neither {} nor {} in {} exists",
+            local_path, entry_path, archive_path
+        ));
+    }
+
+    generate_diff(archived.as_ref().map(|f| f.path()), local, options).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn get_self_region_diff(
+    window: Window,
+    path: &str,
+    regionA: (u64, u64),
+    regionB: (u64, u64),
+    options: DiffOptions,
+) -> Result<DiffResult, String> {
+    let options = themed(&window, options);
+    generate_region_diff(Path::new(path), regionA, regionB, &options).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn verify_patch(leftDir: &str, patch: String) -> Result<PatchCheck, String> {
+    if !Path::new(leftDir).is_dir() {
+        return Err(format!("Left directory does not exist: {}", leftDir));
+    }
+
+    crate::git::verify_patch(Path::new(leftDir), &patch).map_err(|e| e.to_string())
+}
+
+/// Both versions of a file highlighted for side-by-side reading; None for a missing side.
+/// `mode` picks delta's light or dark colors, Auto following the window's appearance.
+#[tauri::command(async)]
+#[allow(non_snake_case)]
+pub fn get_both_highlighted(
+    window: Window,
+    leftPath: Option<&str>,
+    rightPath: Option<&str>,
+    theme: Option<String>,
+    mode: Option<ThemeMode>,
+) -> Result<(Option<String>, Option<String>), String> {
+    let mode = window_theme(&window, mode.unwrap_or_default());
+    both_highlighted(leftPath, rightPath, theme.as_deref(), mode)
+}
+
+/// get_both_highlighted with `mode` already resolved
+pub(crate) fn both_highlighted(
+    left: Option<&str>,
+    right: Option<&str>,
+    theme: Option<&str>,
+    mode: ThemeMode,
+) -> Result<(Option<String>, Option<String>), String> {
+    let highlight = |path: Option<&str>| -> Result<Option<String>, String> {
+        local_input(path)?
+            .map(|p| highlight_file(&p, theme, mode).map_err(|e| e.to_string()))
+            .transpose()
+    };
+
+    Ok((highlight(left)?, highlight(right)?))
+}
+
+#[tauri::command(async)]
 pub fn read_file_content(path: &str) -> Result<String, String> {
-    get_file_content(Path::new(path)).map_err(|e| e.to_string())
+    let path = resolve_local(path)?;
+    get_file_content(&path).map_err(|e| e.to_string())
+}
+
+/// Whether a single file mixes CRLF and LF line endings, for the file view's warning badge
+#[tauri::command(async)]
+pub fn check_mixed_line_endings(path: &str) -> Result<bool, String> {
+    let path = resolve_local(path)?;
+    let content = std::fs::read(&*path).map_err(|e| e.to_string())?;
+    Ok(crate::diff::has_mixed_line_endings(&content))
+}
+
+/// Per-line hashes of a file (see hunks::line_hashes), to find the edited lines and
+/// re-render just their hunks with get_diff's `line_range` option
+#[tauri::command(async)]
+pub fn get_line_hashes(path: &str) -> Result<Vec<u64>, String> {
+    let path = resolve_local(path)?;
+    let content = std::fs::read(&*path).map_err(|e| e.to_string())?;
+    Ok(crate::hunks::line_hashes(&content))
+}
+
+#[tauri::command]
+pub fn ansi_to_html_command(input: String, theme: Option<DiffTheme>) -> String {
+    ansi_to_themed_html(&input, &theme.unwrap_or_default())
+}
+
+/// ANSI text as HTML with CSS classes in place of inline colors (see ansi_to_html_classed)
+#[tauri::command]
+pub fn ansi_to_classed_html(input: String) -> String {
+    ansi_to_html_classed(&input)
+}
+
+/// Stylesheet for ansi_to_classed_html output in the given theme (dark by default, Auto
+/// following the window's appearance)
+#[tauri::command]
+pub fn get_ansi_stylesheet(window: Window, theme: Option<ThemeMode>) -> String {
+    ansi_stylesheet(window_theme(&window, theme.unwrap_or_default()))
+}
+
+/// Colors the diff view uses for changes with the given syntax theme, for a legend, in
+/// light or dark `mode` (Auto following the window's appearance)
+#[tauri::command]
+pub fn get_diff_legend(
+    window: Window,
+    theme: Option<String>,
+    mode: Option<ThemeMode>,
+) -> Vec<LegendEntry> {
+    diff_legend(theme, window_theme(&window, mode.unwrap_or_default()))
 }
 
 #[tauri::command]
@@ -81,7 +984,19 @@ pub fn check_delta() -> bool {
     crate::delta::check_delta_installed()
 }
 
+#[tauri::command]
+pub fn get_capabilities() -> Capabilities {
+    detect_capabilities(None)
+}
+
 #[tauri::command]
 pub fn get_app_args() -> Vec<String> {
     std::env::args().collect()
 }
+
+/// Directories and view options given on the command line, or a message explaining
+/// what's wrong with them
+#[tauri::command]
+pub fn get_launch_config() -> Result<LaunchConfig, String> {
+    parse_launch_args(std::env::args_os())
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+/// How comments are written in a family of languages.
+///
+/// Only lines that are *entirely* a comment are recognized, so a line comment marker after
+/// code (`let url = "http://x";`) never makes a line one. The flip side: trailing comments
+/// after code aren't ignored, and comment-looking lines inside multi-line strings (Python
+/// docstrings, raw strings, heredocs) are. Block comments are followed from line to line by
+/// their `/*` and `*/` markers, without parsing strings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentStyle {
+    /// `//` and `/* */` block comments
+    CLike,
+    /// `/* */` block comments only
+    Css,
+    /// `#`
+    Hash,
+    /// `--`
+    DoubleDash,
+    /// `;`
+    Semicolon,
+}
+
+impl CommentStyle {
+    /// Recognize the comment syntax from the file name, or None for unknown types
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if matches!(name.as_str(), "makefile" | "dockerfile" | "cmakelists.txt") {
+            return Some(CommentStyle::Hash);
+        }
+
+        let ext = Path::new(&name).extension()?.to_str()?.to_string();
+        match ext.as_str() {
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "js" | "jsx" | "mjs"
+            | "ts" | "tsx" | "go" | "swift" | "kt" | "kts" | "scala" | "dart" | "php" | "scss"
+            | "less" | "vue" => Some(CommentStyle::CLike),
+            "css" => Some(CommentStyle::Css),
+            "py" | "rb" | "sh" | "bash" | "zsh" | "pl" | "r" | "yaml" | "yml" | "toml"
+            | "cmake" | "ps1" => Some(CommentStyle::Hash),
+            "sql" | "lua" | "hs" => Some(CommentStyle::DoubleDash),
+            "lisp" | "clj" | "el" | "asm" | "ini" => Some(CommentStyle::Semicolon),
+            _ => None,
+        }
+    }
+
+    /// Which of a file's lines, in order, hold nothing but comments. A line inside a
+    /// `/* */` block is one, blank or not; ` * text` outside a block is code.
+    pub fn comment_lines<'a>(self, lines: impl IntoIterator<Item = &'a str>) -> Vec<bool> {
+        let mut in_block = false;
+        lines
+            .into_iter()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                match self {
+                    CommentStyle::CLike => block_comment_line(line, true, &mut in_block),
+                    CommentStyle::Css => block_comment_line(line, false, &mut in_block),
+                    CommentStyle::Hash => trimmed.starts_with('#'),
+                    CommentStyle::DoubleDash => trimmed.starts_with("--"),
+                    CommentStyle::Semicolon => trimmed.starts_with(';'),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether `line` is all comment, given whether a `/* */` block is open where it starts
+/// (updated to whether one is open where it ends). `//` starts a line comment if
+/// `line_comments`.
+fn block_comment_line(line: &str, line_comments: bool, in_block: &mut bool) -> bool {
+    let mut commented = *in_block;
+    let mut code = false;
+    let mut rest = line;
+    loop {
+        if *in_block {
+            let Some(end) = rest.find("*/") else { break };
+            *in_block = false;
+            rest = &rest[end + 2..];
+            continue;
+        }
+        let block = rest.find("/*");
+        let line_comment = if line_comments { rest.find("//") } else { None };
+        let (start, opens_block) = match (block, line_comment) {
+            (Some(b), Some(l)) => (b.min(l), b < l),
+            (Some(b), None) => (b, true),
+            (None, Some(l)) => (l, false),
+            (None, None) => {
+                code |= !rest.trim().is_empty();
+                break;
+            }
+        };
+        code |= !rest[..start].trim().is_empty();
+        commented = true;
+        if !opens_block {
+            break;
+        }
+        *in_block = true;
+        rest = &rest[start + 2..];
+    }
+    commented && !code
+}
+
+/// The lines of `content` that aren't whole-line comments
+pub fn code_lines(content: &str, style: CommentStyle) -> Vec<&str> {
+    let lines: Vec<&str> = content.lines().collect();
+    let comments = style.comment_lines(lines.iter().copied());
+    lines
+        .into_iter()
+        .zip(comments)
+        .filter(|(_, comment)| !comment)
+        .map(|(line, _)| line)
+        .collect()
+}
@@ -0,0 +1,300 @@
+use crate::delta::{html_escape, DiffResult};
+use std::path::Path;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Above this many row comparisons the LCS table gets too big; use the text diff instead
+const MAX_ROW_PAIRS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnSide {
+    Both(usize, usize),
+    LeftOnly(usize),
+    RightOnly(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RowOp {
+    Same(usize, usize),
+    Modified(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+fn read_records(path: &Path) -> Option<Vec<Vec<String>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .ok()?;
+
+    reader
+        .records()
+        .map(|r| r.ok().map(|rec| rec.iter().map(str::to_string).collect()))
+        .collect()
+}
+
+/// Line up columns by header name so an inserted column doesn't shift every cell.
+/// Falls back to position when headers are missing or ambiguous.
+fn align_columns(left_header: &[String], right_header: &[String]) -> Vec<ColumnSide> {
+    let unique = |h: &[String]| {
+        let mut seen = std::collections::HashSet::new();
+        h.iter().all(|name| !name.is_empty() && seen.insert(name))
+    };
+
+    if !unique(left_header) || !unique(right_header) {
+        let width = left_header.len().max(right_header.len());
+        return (0..width)
+            .map(|i| match (i < left_header.len(), i < right_header.len()) {
+                (true, true) => ColumnSide::Both(i, i),
+                (true, false) => ColumnSide::LeftOnly(i),
+                _ => ColumnSide::RightOnly(i),
+            })
+            .collect();
+    }
+
+    // Right-hand order, with removed columns slotted in after their left-hand predecessor
+    let mut columns: Vec<ColumnSide> = right_header
+        .iter()
+        .enumerate()
+        .map(
+            |(r, name)| match left_header.iter().position(|l| l == name) {
+                Some(l) => ColumnSide::Both(l, r),
+                None => ColumnSide::RightOnly(r),
+            },
+        )
+        .collect();
+
+    for (l, name) in left_header.iter().enumerate() {
+        if right_header.contains(name) {
+            continue;
+        }
+        let insert_at = columns
+            .iter()
+            .position(
+                |c| matches!(c, ColumnSide::Both(cl, _) | ColumnSide::LeftOnly(cl) if *cl > l),
+            )
+            .unwrap_or(columns.len());
+        columns.insert(insert_at, ColumnSide::LeftOnly(l));
+    }
+
+    columns
+}
+
+/// Project a row onto the aligned columns (None where the column doesn't exist on that side)
+fn project<'a>(row: &'a [String], columns: &[ColumnSide], left: bool) -> Vec<Option<&'a str>> {
+    columns
+        .iter()
+        .map(|c| {
+            let idx = match (c, left) {
+                (ColumnSide::Both(l, _), true) | (ColumnSide::LeftOnly(l), true) => Some(*l),
+                (ColumnSide::Both(_, r), false) | (ColumnSide::RightOnly(r), false) => Some(*r),
+                _ => None,
+            };
+            idx.map(|i| row.get(i).map(String::as_str).unwrap_or(""))
+        })
+        .collect()
+}
+
+/// Rows are "equal" when every column present on both sides matches
+fn rows_equal(left: &[Option<&str>], right: &[Option<&str>]) -> bool {
+    left.iter().zip(right).all(|pair| match pair {
+        (Some(l), Some(r)) => l == r,
+        _ => true,
+    })
+}
+
+/// LCS over rows; unmatched removed/added rows between matches are paired up as modified
+fn diff_rows(left: &[Vec<Option<&str>>], right: &[Vec<Option<&str>>]) -> Vec<RowOp> {
+    let (n, m) = (left.len(), right.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if rows_equal(&left[i], &right[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let flush = |ops: &mut Vec<RowOp>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        let paired = removed.len().min(added.len());
+        ops.extend((0..paired).map(|k| RowOp::Modified(removed[k], added[k])));
+        ops.extend(removed[paired..].iter().map(|&i| RowOp::Removed(i)));
+        ops.extend(added[paired..].iter().map(|&j| RowOp::Added(j)));
+        removed.clear();
+        added.clear();
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && rows_equal(&left[i], &right[j]) {
+            flush(&mut ops, &mut removed, &mut added);
+            ops.push(RowOp::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(j);
+            j += 1;
+        } else {
+            removed.push(i);
+            i += 1;
+        }
+    }
+    flush(&mut ops, &mut removed, &mut added);
+
+    ops
+}
+
+/// Escaped cell text cut to `max_width` display columns with an ellipsis, plus a ` title`
+/// attribute holding the full text when it was cut (empty otherwise)
+fn fit_cell(value: &str, max_width: Option<usize>) -> (String, String) {
+    let Some(max) = max_width.filter(|&max| UnicodeWidthStr::width(value) > max) else {
+        return (html_escape(value), String::new());
+    };
+
+    let mut shown = String::new();
+    let mut width = 0;
+    for ch in value.chars() {
+        let w = UnicodeWidthChar::width(ch).unwrap_or(0);
+        // Leave a column for the ellipsis
+        if width + w + 1 > max {
+            break;
+        }
+        shown.push(ch);
+        width += w;
+    }
+    shown.push('…');
+    (
+        html_escape(&shown),
+        format!(" title=\"{}\"", html_escape(value)),
+    )
+}
+
+fn cell_html(value: Option<&str>, class: &str, max_width: Option<usize>) -> String {
+    let (text, title) = fit_cell(value.unwrap_or_default(), max_width);
+    if class.is_empty() {
+        format!("<td{}>{}</td>", title, text)
+    } else {
+        format!("<td class=\"{}\"{}>{}</td>", class, title, text)
+    }
+}
+
+/// Diff two CSV files row by row with columns aligned by header, rendered as an HTML
+/// table. Returns None if either file can't be parsed so the caller can fall back to text.
+/// With `max_cell_width`, longer cells are cut to that many display columns (see fit_cell).
+pub fn generate_csv_diff(
+    left: &Path,
+    right: &Path,
+    max_cell_width: Option<usize>,
+) -> Option<DiffResult> {
+    let left_rows = read_records(left)?;
+    let right_rows = read_records(right)?;
+    if left_rows.is_empty()
+        || right_rows.is_empty()
+        || left_rows.len() * right_rows.len() > MAX_ROW_PAIRS
+    {
+        return None;
+    }
+
+    let columns = align_columns(&left_rows[0], &right_rows[0]);
+    let left_cells: Vec<Vec<Option<&str>>> = left_rows
+        .iter()
+        .map(|r| project(r, &columns, true))
+        .collect();
+    let right_cells: Vec<Vec<Option<&str>>> = right_rows
+        .iter()
+        .map(|r| project(r, &columns, false))
+        .collect();
+    let ops = diff_rows(&left_cells, &right_cells);
+
+    let column_class = |c: &ColumnSide| match c {
+        ColumnSide::LeftOnly(_) => "csv-col-removed",
+        ColumnSide::RightOnly(_) => "csv-col-added",
+        ColumnSide::Both(..) => "",
+    };
+
+    let mut rows_html = Vec::new();
+    let mut has_changes = columns.iter().any(|c| !matches!(c, ColumnSide::Both(..)));
+    let mut hunk_count = 0;
+    let mut in_hunk = false;
+
+    for op in &ops {
+        let (row_class, cells): (&str, Vec<String>) = match *op {
+            RowOp::Same(i, j) => (
+                "csv-row",
+                left_cells[i]
+                    .iter()
+                    .zip(&right_cells[j])
+                    .zip(&columns)
+                    .map(|((l, r), c)| cell_html(r.or(*l), column_class(c), max_cell_width))
+                    .collect(),
+            ),
+            RowOp::Modified(i, j) => (
+                "csv-row csv-row-modified",
+                left_cells[i]
+                    .iter()
+                    .zip(&right_cells[j])
+                    .zip(&columns)
+                    .map(|((l, r), c)| match (l, r) {
+                        (Some(l), Some(r)) if l != r => {
+                            let (l, l_title) = fit_cell(l, max_cell_width);
+                            let (r, r_title) = fit_cell(r, max_cell_width);
+                            format!(
+                                "<td class=\"csv-cell-changed\"><del{}>{}</del><ins{}>{}</ins></td>",
+                                l_title, l, r_title, r
+                            )
+                        }
+                        (l, r) => cell_html(r.or(*l), column_class(c), max_cell_width),
+                    })
+                    .collect(),
+            ),
+            RowOp::Removed(i) => (
+                "csv-row csv-row-removed",
+                left_cells[i]
+                    .iter()
+                    .zip(&columns)
+                    .map(|(cell, c)| cell_html(*cell, column_class(c), max_cell_width))
+                    .collect(),
+            ),
+            RowOp::Added(j) => (
+                "csv-row csv-row-added",
+                right_cells[j]
+                    .iter()
+                    .zip(&columns)
+                    .map(|(cell, c)| cell_html(*cell, column_class(c), max_cell_width))
+                    .collect(),
+            ),
+        };
+
+        let changed = !matches!(op, RowOp::Same(..));
+        if changed && !in_hunk {
+            hunk_count += 1;
+        }
+        in_hunk = changed;
+        has_changes |= changed;
+
+        rows_html.push(format!(
+            "<tr class=\"{}\">{}</tr>",
+            row_class,
+            cells.join("")
+        ));
+    }
+
+    Some(DiffResult {
+        html: format!(
+            "<table class=\"csv-diff\">\n{}\n</table>",
+            rows_html.join("\n")
+        ),
+        has_changes,
+        hunk_count,
+        left_html: None,
+        right_html: None,
+        warnings: Vec::new(),
+        truncated: false,
+        hunks: Vec::new(),
+        rename_info: None,
+    })
+}
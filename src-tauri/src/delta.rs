@@ -1,8 +1,41 @@
+use crate::comments::CommentStyle;
+use crate::diff::{files_differ, is_binary, RenameInfo};
+use crate::diff_engine::{diff_files, EngineOptions};
+use crate::editorconfig::tab_width_for;
+use crate::fallback::{delta_like_output, FALLBACK_WARNING};
+use crate::hex::{hex_dump, read_byte_range};
+use crate::hunks::{
+    changed_lines, detect_moved_blocks, diff_lines, hunk_chunks, parse_hunk_header, parse_hunks,
+    split_hunks, DiffLineKind, HunkInfo, LineKind, MovedBlock,
+};
+use crate::normalize::{
+    normalize_pair, FileTypeNormalizer, JsonNormalizer, Normalizer, NumberNormalizer,
+};
+use crate::notebook::read_notebooks;
+use crate::paths::{absolute_path, normalize_path, tool_command};
+use crate::temp::TempFile;
+use crate::tools::probe_version;
 use serde::{Deserialize, Serialize};
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
+use unicode_width::UnicodeWidthStr;
+
+/// Inherited variables that can restyle or page delta's output
+const CLEARED_DELTA_ENV: &[&str] = &[
+    "DELTA_FEATURES",
+    "DELTA_NAVIGATE",
+    "DELTA_PAGER",
+    "BAT_THEME",
+    "BAT_PAGER",
+    "PAGER",
+    "NO_COLOR",
+    "GIT_CONFIG_PARAMETERS",
+];
 
 #[derive(Debug, Error)]
 pub enum DeltaError {
@@ -12,16 +45,213 @@ pub enum DeltaError {
     AnsiConversion(String),
     #[error("UTF-8 error: {0}")]
     Utf8(#[from] std::string::FromUtf8Error),
+    #[error("Binary file: {0}")]
+    BinaryFile(String),
     #[error("Delta not installed")]
     DeltaNotInstalled,
+    #[error("Process error: {0}")]
+    Process(String),
+    #[error("Invalid byte range: {0}")]
+    InvalidRange(String),
+}
+
+/// Light or dark colors for delta and for ANSI colors that don't name an RGB value
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ThemeMode {
+    #[default]
+    Dark,
+    Light,
+    /// Follow the system appearance
+    Auto,
+}
+
+impl ThemeMode {
+    /// Dark or Light, with Auto taking the appearance of the window it shows in
+    pub fn resolve(self, window_is_light: bool) -> ThemeMode {
+        match self {
+            ThemeMode::Auto if window_is_light => ThemeMode::Light,
+            ThemeMode::Auto => ThemeMode::Dark,
+            mode => mode,
+        }
+    }
+
+    /// Auto counts as dark here; resolve it first to follow the system
+    pub fn is_light(self) -> bool {
+        self == ThemeMode::Light
+    }
+
+    /// The 16 basic ANSI colors (normal then bright) for this theme
+    pub(crate) fn palette(self) -> &'static [&'static str; 16] {
+        if self.is_light() {
+            &LIGHT_PALETTE
+        } else {
+            &DARK_PALETTE
+        }
+    }
+
+    /// delta's removed/added line and word backgrounds for this theme (see DELTA_DARK_BACKGROUNDS)
+    pub(crate) fn diff_backgrounds(self) -> &'static [&'static str; 4] {
+        if self.is_light() {
+            &DELTA_LIGHT_BACKGROUNDS
+        } else {
+            &DELTA_DARK_BACKGROUNDS
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct DiffOptions {
     pub side_by_side: bool,
     pub line_numbers: bool,
     pub collapsed: bool,
     pub show_whitespace: bool,
+    /// Named delta syntax theme (e.g. "Monokai Extended"), passed as `--syntax-theme`
+    pub syntax_theme: Option<String>,
+    /// In side-by-side mode, show blocks moved within the file with their new lines beside
+    /// the old ones, both sides marked and linked by id
+    pub align_moves: bool,
+    /// Classify tokens with tree-sitter in get_diff_lines (needs the `tree-sitter` feature)
+    pub semantic_highlight: bool,
+    /// Render both files as a CSV table diff, falling back to text if either fails to parse
+    pub csv: bool,
+    /// In the CSV table, cut cells wider than this many display columns with an ellipsis,
+    /// keeping the full text in the cell's `title`
+    pub max_cell_width: Option<usize>,
+    /// Diff Jupyter notebooks by their cell sources only (no outputs or execution counts),
+    /// falling back to text if either fails to parse
+    pub ipynb: bool,
+    /// Name the enclosing function in each hunk header, like `diff -p`
+    pub show_function_context: bool,
+    /// Let delta see the user's DELTA_*/pager/color variables and git config
+    /// (by default they're stripped so output is the same in every shell)
+    pub inherit_env: bool,
+    /// Extra environment variables for the delta process, applied last
+    pub delta_env: HashMap<String, String>,
+    /// Give each line an `id` for deep links: `L<new>` for context/added lines and
+    /// `LR<old>` for removed ones (and the whole old panel in side-by-side). Needs line_numbers.
+    pub emit_line_ids: bool,
+    /// Stop rendering once the HTML reaches this many bytes (both panels together)
+    pub max_output_bytes: Option<usize>,
+    /// Cut unchanged runs before the first change and after the last one down to this many
+    /// lines, whatever the `-U` context, leaving an expandable marker in their place
+    pub edge_context_limit: Option<usize>,
+    /// Columns per tab stop, passed to delta as `--tabs`
+    pub tab_width: Option<usize>,
+    /// Take tab_width from the compared files' `.editorconfig` when it isn't set explicitly
+    pub respect_editorconfig: bool,
+    /// Drop hunks that only touch whole-line comments (`diff -I`), for known languages
+    pub ignore_comments: bool,
+    /// Prepend a `diff-meta` row naming each side's encoding and line endings
+    pub encoding_metadata: bool,
+    /// How changed words inside modified lines are marked
+    pub word_emphasis: WordEmphasis,
+    /// Word-level highlights inside modified lines: `Some(false)` turns off delta's pairing
+    /// of removed and added lines (`--max-line-distance 0`) for plain line backgrounds,
+    /// `Some(true)` asks for delta's default pairing; unset leaves delta's configuration alone
+    pub intra_line: Option<bool>,
+    /// Canonicalize decimal literals on both sides before diffing (see `normalize_numbers`),
+    /// so changes that only reformat numbers like `1.50` → `1.5` disappear
+    pub normalize_numbers: bool,
+    /// Reformat minified JSON (one line, or very long lines) to one value per line on both
+    /// sides before diffing, so a change shows up on its own line; raw diff if either fails to parse
+    pub pretty_print_before_diff: bool,
+    /// When one file's bytes start with the other's (a log that kept growing), show only the
+    /// lines after the last complete line they share, numbered as in the full files
+    pub treat_truncation_as_equal: bool,
+    /// For files in a git checkout, get_diff_lines sets each line's `blame`
+    /// (see `git::attach_blame`)
+    pub show_blame: bool,
+    /// Fold unchanged runs longer than this between two changes into an expandable group,
+    /// keeping FOLD_KEEP_LINES lines of context next to each change
+    pub context_fold_size: Option<usize>,
+    /// `--dark` or `--light` for delta, and the palette for basic ANSI colors. Commands
+    /// resolve Auto against their window's appearance; left unresolved it counts as dark
+    pub theme: ThemeMode,
+    /// Label each line for screen readers: a role and aria-label naming the change kind,
+    /// and a `+`/`-`/` ` prefix drawn by CSS so it isn't copied
+    pub accessible: bool,
+    /// Columns for both side-by-side panels together (delta's `--width`), e.g. the view's
+    /// width in characters; DEFAULT_SIDE_BY_SIDE_WIDTH when unset
+    pub width: Option<u32>,
+    /// Treat lines that differ only in whitespace as unchanged (`diff -w`)
+    pub ignore_whitespace: bool,
+    /// Leave out changes that only add or remove blank lines (`diff -B`)
+    pub ignore_blank_lines: bool,
+    /// Surface changes that affect how a script runs: a changed `#!` first line gets the
+    /// `shebang-change` class (needs line_numbers), and interpreter and file mode changes
+    /// are added to the warnings
+    pub mark_script_changes: bool,
+    /// Widen delta's word highlights to whole identifiers, so a change to `foo_old` →
+    /// `foo_new` marks the identifier rather than just `old`/`new`
+    pub token_granularity: bool,
+    /// Diff text files through the rule for their type, if any (see FileTypeNormalizer)
+    pub normalize_by_type: bool,
+    /// Lines of context around each hunk (`-U<n>`), capped at MAX_CONTEXT_LINES. Overrides
+    /// `collapsed` (3 lines) and the expanded default (the whole file)
+    pub context_lines: Option<usize>,
+    /// When delta isn't installed, render with the built-in layout (see
+    /// fallback::delta_like_output) instead of failing with DeltaNotInstalled
+    pub fallback_renderer: bool,
+    /// Mark colors with CSS classes (see ansi_to_html_classed) instead of inline styles, for
+    /// a page that styles them with ansi_stylesheet and restyles without rendering again
+    pub css_classes: bool,
+    /// Render only the hunks touching these new-file lines (1-based, inclusive), e.g. the
+    /// ones an editor found edited with get_line_hashes (generate_diff only)
+    pub line_range: Option<(u32, u32)>,
+    /// More normalizers for text files, run after the ones normalize_by_type,
+    /// pretty_print_before_diff and normalize_numbers switch on
+    #[serde(skip)]
+    pub normalizers: Vec<Arc<dyn Normalizer>>,
+}
+
+/// Side-by-side width when DiffOptions::width is unset
+pub(crate) const DEFAULT_SIDE_BY_SIDE_WIDTH: u32 = 160;
+
+/// delta's own `--max-line-distance`, how different two lines may be and still get word highlights
+const DEFAULT_MAX_LINE_DISTANCE: &str = "0.6";
+
+/// Largest context_lines honoured; more is clamped to this
+pub const MAX_CONTEXT_LINES: usize = 1000;
+
+impl DiffOptions {
+    /// The `-U` value for these options: context_lines, else 3 when collapsed, else enough
+    /// to show whole files
+    pub fn diff_context(&self) -> usize {
+        match self.context_lines {
+            Some(lines) => lines.min(MAX_CONTEXT_LINES),
+            None if self.collapsed => 3,
+            None => 99999,
+        }
+    }
+
+    /// The normalizers text files go through: those the flags switch on, then `normalizers`
+    pub(crate) fn active_normalizers(&self) -> Vec<Arc<dyn Normalizer>> {
+        let mut active: Vec<Arc<dyn Normalizer>> = Vec::new();
+        if self.normalize_by_type {
+            active.push(Arc::new(FileTypeNormalizer));
+        }
+        if self.pretty_print_before_diff {
+            active.push(Arc::new(JsonNormalizer));
+        }
+        if self.normalize_numbers {
+            active.push(Arc::new(NumberNormalizer));
+        }
+        active.extend(self.normalizers.iter().cloned());
+        active
+    }
+}
+
+/// Style for delta's word-level highlights within a changed line
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub enum WordEmphasis {
+    /// delta's emphasis background, as rendered
+    #[default]
+    Background,
+    /// Underline changed words on the plain line background
+    Underline,
+    /// Bold changed words on the plain line background
+    Bold,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,14 +263,40 @@ pub struct DiffResult {
     pub left_html: Option<String>,
     /// For custom side-by-side layout - right (new) file HTML
     pub right_html: Option<String>,
+    /// Non-fatal messages reported by delta on stderr (e.g. unknown syntax theme)
+    pub warnings: Vec<String>,
+    /// Only part of the diff was rendered (e.g. a preview)
+    pub truncated: bool,
+    /// Position of each hunk; `header` holds the enclosing function with show_function_context
+    pub hunks: Vec<HunkInfo>,
+    /// The rename this diff belongs to, when the caller passed one
+    #[serde(default)]
+    pub rename_info: Option<RenameInfo>,
 }
 
 pub fn check_delta_installed() -> bool {
-    Command::new("delta")
-        .arg("--version")
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false)
+    get_delta_version().is_some()
+}
+
+pub fn get_delta_version() -> Option<String> {
+    probe_version(Path::new("delta"))
+}
+
+/// Unified diff text ready for delta, or a result that needs no rendering
+/// (identical files, encoding-only changes, CSV tables)
+enum PreparedDiff {
+    Rendered(DiffResult),
+    Text {
+        diff_text: String,
+        hunk_count: usize,
+    },
+}
+
+/// Inline and side-by-side renderings of the same diff
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DualDiffResult {
+    pub inline: DiffResult,
+    pub side_by_side: DiffResult,
 }
 
 pub fn generate_diff(
@@ -48,8 +304,212 @@ pub fn generate_diff(
     right_path: Option<&Path>,
     options: &DiffOptions,
 ) -> Result<DiffResult, DeltaError> {
-    if !check_delta_installed() {
-        return Err(DeltaError::DeltaNotInstalled);
+    let options = &with_editorconfig(left_path, right_path, options);
+    let result = match prepare_diff(left_path, right_path, options)? {
+        PreparedDiff::Rendered(result) => result,
+        PreparedDiff::Text {
+            diff_text,
+            hunk_count,
+        } => match options.line_range {
+            Some(lines) => {
+                let (diff_text, hunk_count) = hunks_touching_lines(&diff_text, lines);
+                render_with_delta(diff_text, hunk_count, options)?
+            }
+            None => render_with_delta(diff_text, hunk_count, options)?,
+        },
+    };
+    let result = with_script_changes(result, left_path, right_path, options);
+    Ok(with_encoding_metadata(
+        result, left_path, right_path, options,
+    ))
+}
+
+/// The part of a unified diff with just the hunks whose new-file lines overlap
+/// `first..=last` (a removal counts as the line it follows), and how many there are
+pub(crate) fn hunks_touching_lines(diff_text: &str, (first, last): (u32, u32)) -> (String, usize) {
+    let (head, hunks) = hunk_chunks(diff_text);
+    let touching: Vec<&str> = hunks
+        .filter(|hunk| {
+            parse_hunk_header(hunk.lines().next().unwrap_or_default()).is_some_and(|info| {
+                let end = info.new_start + info.new_lines.max(1) - 1;
+                info.new_start <= last && first <= end
+            })
+        })
+        .collect();
+    let count = touching.len();
+    (std::iter::once(head).chain(touching).collect(), count)
+}
+
+/// A range of a diff's hunks, rendered on their own so huge diffs can be loaded lazily
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffPage {
+    /// The page's hunks rendered as by generate_diff; hunk_count and hunks cover the page only
+    pub diff: DiffResult,
+    /// Hunks in the whole diff
+    pub total_hunks: usize,
+    /// First hunk of the page and one past its last, clamped to total_hunks
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Render hunks `start..end` of a file's diff. Pages are hunks, so this is for collapsed
+/// diffs; results that aren't line diffs (identical files, CSV tables, ...) come back whole.
+pub fn generate_diff_page(
+    left_path: Option<&Path>,
+    right_path: Option<&Path>,
+    options: &DiffOptions,
+    hunk_range: (usize, usize),
+) -> Result<DiffPage, DeltaError> {
+    let options = &with_editorconfig(left_path, right_path, options);
+    let (result, total_hunks, start, end) = match prepare_diff(left_path, right_path, options)? {
+        PreparedDiff::Rendered(result) => {
+            let total = result.hunk_count;
+            (result, total, 0, total)
+        }
+        PreparedDiff::Text {
+            diff_text,
+            hunk_count,
+        } => {
+            let end = hunk_range.1.min(hunk_count);
+            let start = hunk_range.0.min(end);
+            let (head, hunks) = hunk_chunks(&diff_text);
+            let page: String = std::iter::once(head)
+                .chain(hunks.skip(start).take(end - start))
+                .collect();
+            (
+                render_with_delta(page, end - start, options)?,
+                hunk_count,
+                start,
+                end,
+            )
+        }
+    };
+    let result = with_script_changes(result, left_path, right_path, options);
+    Ok(DiffPage {
+        diff: with_encoding_metadata(result, left_path, right_path, options),
+        total_hunks,
+        start,
+        end,
+    })
+}
+
+/// Added/removed line counts of a file's diff
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub hunk_count: usize,
+}
+
+/// Everything the viewer needs when a file is opened, from one diff run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileDiffBundle {
+    pub diff: DiffResult,
+    pub summary: DiffSummary,
+    pub hunks: Vec<HunkInfo>,
+    /// New-file line of the first change (old-file line when it's a removal)
+    pub first_change_line: Option<u32>,
+}
+
+/// Render a file's diff together with its line counts and first change.
+/// Results that aren't line diffs (identical files, encoding-only changes, CSV tables)
+/// count no lines.
+pub fn generate_file_diff_bundle(
+    left_path: Option<&Path>,
+    right_path: Option<&Path>,
+    options: &DiffOptions,
+) -> Result<FileDiffBundle, DeltaError> {
+    let options = &with_editorconfig(left_path, right_path, options);
+    let (result, changes) = match prepare_diff(left_path, right_path, options)? {
+        PreparedDiff::Rendered(result) => (result, Vec::new()),
+        PreparedDiff::Text {
+            diff_text,
+            hunk_count,
+        } => {
+            let changes = changed_lines(&diff_text);
+            (render_with_delta(diff_text, hunk_count, options)?, changes)
+        }
+    };
+    let result = with_script_changes(result, left_path, right_path, options);
+    let diff = with_encoding_metadata(result, left_path, right_path, options);
+
+    let added = changes.iter().filter(|c| c.kind == LineKind::Added).count();
+    Ok(FileDiffBundle {
+        summary: DiffSummary {
+            added,
+            removed: changes.len() - added,
+            hunk_count: diff.hunk_count,
+        },
+        hunks: diff.hunks.clone(),
+        first_change_line: changes.first().and_then(|c| c.new_line.or(c.old_line)),
+        diff,
+    })
+}
+
+/// Fill in tab_width from `.editorconfig` (the new file's, else the old one's) when
+/// respect_editorconfig is set and no width was given
+pub(crate) fn with_editorconfig(
+    left: Option<&Path>,
+    right: Option<&Path>,
+    options: &DiffOptions,
+) -> DiffOptions {
+    let mut options = options.clone();
+    if options.respect_editorconfig && options.tab_width.is_none() {
+        options.tab_width = right.or(left).and_then(tab_width_for);
+    }
+    options
+}
+
+/// Run diff once and render it both inline and side by side, for instant toggling.
+/// `options.side_by_side` is ignored.
+pub fn generate_diff_both_modes(
+    left_path: Option<&Path>,
+    right_path: Option<&Path>,
+    options: &DiffOptions,
+) -> Result<DualDiffResult, DeltaError> {
+    let options = &with_editorconfig(left_path, right_path, options);
+    let inline_options = DiffOptions {
+        side_by_side: false,
+        ..options.clone()
+    };
+    let sbs_options = DiffOptions {
+        side_by_side: true,
+        ..options.clone()
+    };
+
+    let (inline, side_by_side) = match prepare_diff(left_path, right_path, options)? {
+        PreparedDiff::Rendered(result) => (result.clone(), result),
+        PreparedDiff::Text {
+            diff_text,
+            hunk_count,
+        } => (
+            render_with_delta(diff_text.clone(), hunk_count, &inline_options)?,
+            render_with_delta(diff_text, hunk_count, &sbs_options)?,
+        ),
+    };
+    Ok(DualDiffResult {
+        inline: with_encoding_metadata(
+            with_script_changes(inline, left_path, right_path, options),
+            left_path,
+            right_path,
+            options,
+        ),
+        side_by_side: with_encoding_metadata(
+            with_script_changes(side_by_side, left_path, right_path, options),
+            left_path,
+            right_path,
+            options,
+        ),
+    })
+}
+
+fn prepare_diff(
+    left_path: Option<&Path>,
+    right_path: Option<&Path>,
+    options: &DiffOptions,
+) -> Result<PreparedDiff, DeltaError> {
+    if let Some(result) = binary_diff_result(left_path, right_path)? {
+        return Ok(PreparedDiff::Rendered(result));
     }
 
     // Handle added/deleted/modified files
@@ -57,63 +517,873 @@ pub fn generate_diff(
         (Some(l), Some(r)) => (l, r),
         (None, Some(r)) => {
             // New file - diff against /dev/null
-            return generate_diff_with_delta(Path::new("/dev/null"), r, options, true);
+            return prepare_diff_with_delta(Path::new("/dev/null"), r, options, true);
         }
         (Some(l), None) => {
             // Deleted file - diff against /dev/null
-            return generate_diff_with_delta(l, Path::new("/dev/null"), options, true);
+            return prepare_diff_with_delta(l, Path::new("/dev/null"), options, true);
         }
         (None, None) => {
-            return Ok(DiffResult {
+            return Ok(PreparedDiff::Rendered(DiffResult {
                 html: String::new(),
                 has_changes: false,
                 hunk_count: 0,
                 left_html: None,
                 right_html: None,
-            });
+                warnings: Vec::new(),
+                truncated: false,
+                hunks: Vec::new(),
+                rename_info: None,
+            }));
         }
     };
 
-    generate_diff_with_delta(left, right, options, false)
+    if options.csv {
+        if let Some(result) =
+            crate::csv_diff::generate_csv_diff(left, right, options.max_cell_width)
+        {
+            return Ok(PreparedDiff::Rendered(result));
+        }
+    }
+
+    if options.treat_truncation_as_equal {
+        if let Some(prepared) = prepare_continuation_diff(left, right, options)? {
+            return Ok(prepared);
+        }
+    }
+
+    if options.ipynb {
+        if let Some((left_nb, right_nb)) = read_notebooks(left, right) {
+            // Named like the kernel's source files so delta still highlights the code
+            let left_file = TempFile::new(
+                &format!("left{}", left_nb.extension),
+                left_nb.text.as_bytes(),
+            )?;
+            let right_file = TempFile::new(
+                &format!("right{}", right_nb.extension),
+                right_nb.text.as_bytes(),
+            )?;
+            return prepare_diff_with_delta(left_file.path(), right_file.path(), options, false);
+        }
+    }
+
+    let normalizers = options.active_normalizers();
+    if !normalizers.is_empty() {
+        let normalized = match (
+            std::fs::read_to_string(left),
+            std::fs::read_to_string(right),
+        ) {
+            (Ok(left_text), Ok(right_text)) => {
+                normalize_pair(&normalizers, left, right, &left_text, &right_text)
+            }
+            _ => None,
+        };
+        if let Some((left_text, right_text)) = normalized {
+            let left_file = TempFile::new(&temp_name(left), left_text.as_bytes())?;
+            let right_file = TempFile::new(&temp_name(right), right_text.as_bytes())?;
+            return prepare_diff_with_delta(left_file.path(), right_file.path(), options, false);
+        }
+    }
+
+    prepare_diff_with_delta(left, right, options, false)
+}
+
+/// A message in place of a textual diff when either file is binary (see is_binary)
+fn binary_diff_result(
+    left: Option<&Path>,
+    right: Option<&Path>,
+) -> Result<Option<DiffResult>, DeltaError> {
+    if !left.into_iter().chain(right).any(is_binary) {
+        return Ok(None);
+    }
+    let (message, has_changes) = match (left, right) {
+        (Some(left), Some(right)) => {
+            let differ =
+                files_differ(left, right).map_err(|e| DeltaError::Process(e.to_string()))?;
+            if differ {
+                ("Binary files differ", true)
+            } else {
+                ("Files are identical", false)
+            }
+        }
+        (None, _) => ("New binary file", true),
+        (_, None) => ("Deleted binary file", true),
+    };
+
+    Ok(Some(DiffResult {
+        html: format!("<div class=\"no-changes binary\">{}</div>", message),
+        has_changes,
+        hunk_count: 0,
+        left_html: None,
+        right_html: None,
+        warnings: Vec::new(),
+        truncated: false,
+        hunks: Vec::new(),
+        rename_info: None,
+    }))
 }
 
-fn generate_diff_with_delta(
+/// Name for a temp copy of `path`: the original one, so delta still picks the syntax
+/// from the extension
+fn temp_name(path: &Path) -> String {
+    path.file_name()
+        .map_or("file".into(), |n| n.to_string_lossy().to_string())
+}
+
+/// The diff of just the appended tail when one file is a byte prefix of the other, or None
+fn prepare_continuation_diff(
     left: &Path,
     right: &Path,
     options: &DiffOptions,
-    _is_new_or_deleted: bool,
-) -> Result<DiffResult, DeltaError> {
-    // Generate unified diff
-    let context_lines = if options.collapsed { 3 } else { 99999 };
+) -> Result<Option<PreparedDiff>, DeltaError> {
+    let (left_bytes, right_bytes) = (std::fs::read(left)?, std::fs::read(right)?);
+    let shorter = if left_bytes == right_bytes {
+        return Ok(None);
+    } else if right_bytes.starts_with(&left_bytes) {
+        &left_bytes
+    } else if left_bytes.starts_with(&right_bytes) {
+        &right_bytes
+    } else {
+        return Ok(None);
+    };
 
-    let diff_output = Command::new("diff")
-        .arg(format!("-U{}", context_lines))
-        .arg(left)
-        .arg(right)
-        .output()?;
+    // A cut-off last line shows as replaced by its full version
+    let boundary = shorter
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let shared_lines = shorter[..boundary].iter().filter(|&&b| b == b'\n').count() as u32;
+    let left_tail = TempFile::new(&temp_name(left), &left_bytes[boundary..])?;
+    let right_tail = TempFile::new(&temp_name(right), &right_bytes[boundary..])?;
+
+    Ok(Some(
+        match prepare_diff_with_delta(left_tail.path(), right_tail.path(), options, false)? {
+            PreparedDiff::Text {
+                diff_text,
+                hunk_count,
+            } => PreparedDiff::Text {
+                diff_text: offset_hunk_headers(&diff_text, shared_lines),
+                hunk_count,
+            },
+            rendered => rendered,
+        },
+    ))
+}
 
-    let diff_text = String::from_utf8(diff_output.stdout)?;
+/// Move every hunk of a unified diff `offset` lines further into both files
+pub(crate) fn offset_hunk_headers(diff_text: &str, offset: u32) -> String {
+    let mut out = String::with_capacity(diff_text.len());
+    for line in diff_text.lines() {
+        match parse_hunk_header(line) {
+            Some(hunk) => {
+                out.push_str(&format!(
+                    "@@ -{},{} +{},{} @@",
+                    hunk.old_start + offset,
+                    hunk.old_lines,
+                    hunk.new_start + offset,
+                    hunk.new_lines
+                ));
+                if !hunk.header.is_empty() {
+                    out.push(' ');
+                    out.push_str(&hunk.header);
+                }
+            }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn prepare_diff_with_delta(
+    left: &Path,
+    right: &Path,
+    options: &DiffOptions,
+    _is_new_or_deleted: bool,
+) -> Result<PreparedDiff, DeltaError> {
+    // Generate unified diff
+    let diff_text = run_diff_for_options(Some(left), Some(right), options.diff_context(), options)?;
+    let (left, right) = (&absolute_path(left), &absolute_path(right));
 
     // No changes
-    if diff_text.is_empty() && diff_output.status.code() == Some(0) {
-        return Ok(DiffResult {
+    if diff_text.is_empty() {
+        return Ok(PreparedDiff::Rendered(DiffResult {
             html: "<div class=\"no-changes\">Files are identical</div>".to_string(),
             has_changes: false,
             hunk_count: 0,
             left_html: None,
             right_html: None,
+            warnings: Vec::new(),
+            truncated: false,
+            hunks: Vec::new(),
+            rename_info: None,
+        }));
+    }
+
+    let hunk_count = hunk_chunks(&diff_text).1.count();
+
+    // Every line replaced (or diff gave up on binary-looking input) but the files
+    // match once BOM/UTF-16/line endings are normalized: show a hint, not a wall of red/green
+    if is_whole_file_rewrite(&diff_text) && normalized_contents_equal(left, right) {
+        return Ok(PreparedDiff::Rendered(DiffResult {
+            html: "<div class=\"no-changes encoding-only\">File appears unchanged apart from encoding/line endings</div>".to_string(),
+            has_changes: true,
+            hunk_count,
+            left_html: None,
+            right_html: None,
+            warnings: Vec::new(),
+            truncated: false,
+            hunks: parse_hunks(&diff_text),
+            rename_info: None,
+        }));
+    }
+
+    Ok(PreparedDiff::Text {
+        diff_text,
+        hunk_count,
+    })
+}
+
+/// Run unified diff text through delta and convert its output to HTML
+fn render_with_delta(
+    diff_text: String,
+    hunk_count: usize,
+    options: &DiffOptions,
+) -> Result<DiffResult, DeltaError> {
+    let delta_installed = check_delta_installed();
+    if !delta_installed && !options.fallback_renderer {
+        return Err(DeltaError::DeltaNotInstalled);
+    }
+
+    let hunks = parse_hunks(&diff_text);
+    let (diff_text, leading, trailing) = match options.edge_context_limit {
+        Some(keep) => trim_edge_context(&diff_text, keep),
+        None => (diff_text, None, None),
+    };
+    let (diff_text, folds) = match options.context_fold_size {
+        Some(size) => fold_context(&diff_text, size),
+        None => (diff_text, Vec::new()),
+    };
+    let with_markers = |html: String| {
+        let leading = leading
+            .as_ref()
+            .map(elided_context_marker)
+            .unwrap_or_default();
+        let trailing = trailing
+            .as_ref()
+            .map(elided_context_marker)
+            .unwrap_or_default();
+        format!("{}{}{}", leading, html, trailing)
+    };
+
+    let moves = if options.side_by_side && options.align_moves {
+        detect_moved_blocks(&diff_lines(&diff_text))
+    } else {
+        Vec::new()
+    };
+
+    let (ansi_output, warnings) = if delta_installed {
+        let delta_cmd = build_delta_command(options);
+        let output = run_with_input(delta_cmd, diff_text.into_bytes())?;
+        let warnings: Vec<String> = String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        (String::from_utf8(output.stdout)?, warnings)
+    } else {
+        (
+            delta_like_output(&diff_text, options),
+            vec![FALLBACK_WARNING.to_string()],
+        )
+    };
+
+    let restyle = |html: String| {
+        if options.css_classes {
+            classify_inline_styles(&html, options.theme.palette())
+        } else {
+            html
+        }
+    };
+
+    // For side-by-side mode, split delta's output into left and right panels
+    if options.side_by_side {
+        let (left_html, right_html, truncated) =
+            split_side_by_side_output(&ansi_output, &moves, &folds, options)?;
+        return Ok(DiffResult {
+            html: String::new(),
+            has_changes: true,
+            hunk_count,
+            left_html: Some(with_markers(restyle(left_html))),
+            right_html: Some(with_markers(restyle(right_html))),
+            warnings: with_truncation_note(warnings, truncated, options),
+            truncated,
+            hunks,
+            rename_info: None,
         });
     }
 
-    let hunk_count = diff_text.lines().filter(|l| l.starts_with("@@")).count();
+    let (styled_html, truncated) = render_inline_output(&ansi_output, &folds, options);
+
+    Ok(DiffResult {
+        html: with_markers(restyle(styled_html)),
+        has_changes: true,
+        hunk_count,
+        left_html: None,
+        right_html: None,
+        warnings: with_truncation_note(warnings, truncated, options),
+        truncated,
+        hunks,
+        rename_info: None,
+    })
+}
+
+/// Render just the first `max_lines` lines of a file's diff, starting at the first change
+pub fn generate_diff_preview(
+    left_path: Option<&Path>,
+    right_path: Option<&Path>,
+    max_lines: usize,
+    options: &DiffOptions,
+) -> Result<DiffResult, DeltaError> {
+    let options = &with_editorconfig(left_path, right_path, options);
+    let result = match prepare_diff(left_path, right_path, options)? {
+        PreparedDiff::Rendered(result) => result,
+        PreparedDiff::Text {
+            diff_text,
+            hunk_count,
+        } => {
+            let (preview_text, truncated) = truncate_unified_diff(&diff_text, max_lines);
+            let mut result = render_with_delta(preview_text, hunk_count, options)?;
+            result.truncated = truncated;
+            result
+        }
+    };
+    let result = with_script_changes(result, left_path, right_path, options);
+    Ok(with_encoding_metadata(
+        result, left_path, right_path, options,
+    ))
+}
+
+/// Cut a unified diff down to at most `max_lines` body lines (each hunk header after the
+/// first counts as one, since it renders as a separator). Leading context before the
+/// first change is dropped so the preview opens on the change itself.
+pub(crate) fn truncate_unified_diff(diff_text: &str, max_lines: usize) -> (String, bool) {
+    let (mut out, hunks) = split_hunks(diff_text);
+
+    let mut budget = max_lines;
+    let mut truncated = false;
+
+    for (i, (mut hunk, lines)) in hunks.into_iter().enumerate() {
+        if i > 0 {
+            if budget <= 1 {
+                truncated = true;
+                break;
+            }
+            budget -= 1;
+        }
+
+        // Skip context leading up to the first change of the preview
+        let skip = if i == 0 {
+            lines.iter().take_while(|l| l.starts_with(' ')).count()
+        } else {
+            0
+        };
+        hunk.old_start += skip as u32;
+        hunk.new_start += skip as u32;
+
+        let body = &lines[skip..];
+        let mut kept = Vec::new();
+        for line in body {
+            // "\ No newline at end of file" annotates the previous line and doesn't render
+            if !line.starts_with('\\') {
+                if budget == 0 {
+                    truncated = true;
+                    break;
+                }
+                budget -= 1;
+            }
+            kept.push(*line);
+        }
+
+        hunk.old_lines = kept
+            .iter()
+            .filter(|l| l.starts_with(' ') || l.starts_with('-'))
+            .count() as u32;
+        hunk.new_lines = kept
+            .iter()
+            .filter(|l| l.starts_with(' ') || l.starts_with('+'))
+            .count() as u32;
+        if !kept.is_empty() {
+            out.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+            ));
+            for line in kept {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+
+        if truncated {
+            break;
+        }
+    }
+
+    (out, truncated)
+}
+
+/// Unchanged lines cut from the start or end of a diff by trim_edge_context
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ElidedContext {
+    pub old_start: u32,
+    pub new_start: u32,
+    pub len: u32,
+}
+
+/// Cut the context before the first change and after the last one down to the `keep`
+/// lines nearest the change. Returns the new diff text and the (leading, trailing) cuts.
+pub(crate) fn trim_edge_context(
+    diff_text: &str,
+    keep: usize,
+) -> (String, Option<ElidedContext>, Option<ElidedContext>) {
+    let (mut out, mut hunks) = split_hunks(diff_text);
+
+    let mut leading = None;
+    if let Some((hunk, lines)) = hunks.first_mut() {
+        let run = lines.iter().take_while(|l| l.starts_with(' ')).count();
+        if run > keep && run < lines.len() {
+            let cut = run - keep;
+            leading = Some(ElidedContext {
+                old_start: hunk.old_start,
+                new_start: hunk.new_start,
+                len: cut as u32,
+            });
+            lines.drain(..cut);
+            hunk.old_start += cut as u32;
+            hunk.new_start += cut as u32;
+            hunk.old_lines -= cut as u32;
+            hunk.new_lines -= cut as u32;
+        }
+    }
+
+    let mut trailing = None;
+    if let Some((hunk, lines)) = hunks.last_mut() {
+        // "\ No newline at end of file" follows the last context line and goes with it
+        let tail = lines
+            .iter()
+            .rev()
+            .take_while(|l| l.starts_with(' ') || l.starts_with('\\'))
+            .count();
+        let run_start = lines.len() - tail;
+        let run = lines[run_start..]
+            .iter()
+            .filter(|l| l.starts_with(' '))
+            .count();
+        if run > keep && run_start > 0 {
+            let cut = run - keep;
+            trailing = Some(ElidedContext {
+                old_start: hunk.old_start + hunk.old_lines - cut as u32,
+                new_start: hunk.new_start + hunk.new_lines - cut as u32,
+                len: cut as u32,
+            });
+            lines.truncate(run_start + keep);
+            hunk.old_lines -= cut as u32;
+            hunk.new_lines -= cut as u32;
+        }
+    }
+
+    for (hunk, lines) in &hunks {
+        write_hunk(&mut out, hunk, lines);
+    }
+
+    (out, leading, trailing)
+}
+
+/// Context lines fold_context always leaves next to a change
+pub(crate) const FOLD_KEEP_LINES: usize = 2;
+
+/// Cut runs of more than `fold_size` context lines between two changes down to the
+/// FOLD_KEEP_LINES lines on either end, splitting the hunk there. Returns the new diff
+/// text and the cuts. Context before the first and after the last change is left alone
+/// (that's edge_context_limit's job).
+pub(crate) fn fold_context(diff_text: &str, fold_size: usize) -> (String, Vec<ElidedContext>) {
+    let (mut out, hunks) = split_hunks(diff_text);
+    let mut folds = Vec::new();
+
+    for (hunk, lines) in &hunks {
+        let (mut old, mut new) = (hunk.old_start, hunk.new_start);
+        // Start of the part of the hunk not written yet
+        let (mut part, mut part_old, mut part_new) = (0, old, new);
+        let mut header = hunk.header.as_str();
+        let mut i = 0;
+
+        while i < lines.len() {
+            let run = lines[i..].iter().take_while(|l| l.starts_with(' ')).count();
+            if run == 0 {
+                match lines[i].as_bytes().first() {
+                    Some(b'-') => old += 1,
+                    Some(b'+') => new += 1,
+                    _ => {}
+                }
+                i += 1;
+                continue;
+            }
+
+            let between_changes = i > 0 && i + run < lines.len();
+            if between_changes && run > fold_size && run > 2 * FOLD_KEEP_LINES {
+                let cut = (run - 2 * FOLD_KEEP_LINES) as u32;
+                let keep = FOLD_KEEP_LINES as u32;
+                let end = i + FOLD_KEEP_LINES;
+                write_hunk(
+                    &mut out,
+                    &segment_header(part_old, part_new, header, &lines[part..end]),
+                    &lines[part..end],
+                );
+                folds.push(ElidedContext {
+                    old_start: old + keep,
+                    new_start: new + keep,
+                    len: cut,
+                });
+                part = end + cut as usize;
+                part_old = old + keep + cut;
+                part_new = new + keep + cut;
+                // The enclosing function is only named once
+                header = "";
+            }
+            old += run as u32;
+            new += run as u32;
+            i += run;
+        }
+        write_hunk(
+            &mut out,
+            &segment_header(part_old, part_new, header, &lines[part..]),
+            &lines[part..],
+        );
+    }
+
+    (out, folds)
+}
+
+/// Header for a hunk made of `lines` starting at the given line numbers
+fn segment_header(old_start: u32, new_start: u32, header: &str, lines: &[&str]) -> HunkInfo {
+    let count = |prefix: char| {
+        lines
+            .iter()
+            .filter(|l| l.starts_with(' ') || l.starts_with(prefix))
+            .count() as u32
+    };
+    HunkInfo {
+        old_start,
+        old_lines: count('-'),
+        new_start,
+        new_lines: count('+'),
+        header: header.to_string(),
+    }
+}
+
+/// Append a hunk's `@@` header and lines to `out`
+fn write_hunk(out: &mut String, hunk: &HunkInfo, lines: &[&str]) {
+    out.push_str(&format!(
+        "@@ -{},{} +{},{} @@",
+        hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+    ));
+    if !hunk.header.is_empty() {
+        out.push(' ');
+        out.push_str(&hunk.header);
+    }
+    out.push('\n');
+    for line in lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// Placeholder for context cut by trim_edge_context or fold_context; the data attributes locate the
+/// lines so the view can expand them in place
+fn elided_context_marker(elided: &ElidedContext) -> String {
+    format!(
+        "<div class=\"context-collapsed\" data-old-start=\"{}\" data-new-start=\"{}\" data-lines=\"{}\">\
+         \u{22ef} {} unchanged lines</div>",
+        elided.old_start, elided.new_start, elided.len, elided.len
+    )
+}
 
-    // Run through delta
-    let mut delta_cmd = Command::new("delta");
+/// Hex-diff two byte ranges `(start, len)` of the same file
+pub fn generate_region_diff(
+    path: &Path,
+    region_a: (u64, u64),
+    region_b: (u64, u64),
+    options: &DiffOptions,
+) -> Result<DiffResult, DeltaError> {
+    let bytes_a = read_byte_range(path, region_a.0, region_a.1)?;
+    let bytes_b = read_byte_range(path, region_b.0, region_b.1)?;
+
+    let left = TempFile::new(
+        &format!("{:x}.hex", region_a.0),
+        hex_dump(&bytes_a).as_bytes(),
+    )?;
+    let right = TempFile::new(
+        &format!("{:x}.hex", region_b.0),
+        hex_dump(&bytes_b).as_bytes(),
+    )?;
+
+    generate_diff(Some(left.path()), Some(right.path()), options)
+}
+
+/// Run `diff -U<context>` over two files (a missing side diffs against /dev/null)
+pub fn run_unified_diff(
+    left: Option<&Path>,
+    right: Option<&Path>,
+    context_lines: usize,
+) -> Result<String, DeltaError> {
+    run_diff_for_options(left, right, context_lines, &DiffOptions::default())
+}
+
+/// Like run_unified_diff, adding what `options` asks of the diff (e.g. function names)
+pub(crate) fn run_diff_for_options(
+    left: Option<&Path>,
+    right: Option<&Path>,
+    context_lines: usize,
+    options: &DiffOptions,
+) -> Result<String, DeltaError> {
+    let null = Path::new("/dev/null");
+    let side = |path: Option<&Path>| match path {
+        Some(path) if path != null => absolute_path(path),
+        _ => null.to_path_buf(),
+    };
+    let (left, right) = (side(left), side(right));
+    let engine = EngineOptions {
+        context: context_lines,
+        function_context: options.show_function_context,
+        ignore_comments: ignored_comment_style(&left, &right, options),
+        ignore_whitespace: options.ignore_whitespace,
+        ignore_blank_lines: options.ignore_blank_lines,
+    };
+
+    Ok(diff_files(
+        &left,
+        &right,
+        &left.to_string_lossy(),
+        &right.to_string_lossy(),
+        &engine,
+    )?)
+}
+
+/// Comment syntax to ignore with `ignore_comments`, taken from the new file's name
+/// (the old one's for a deletion)
+fn ignored_comment_style(left: &Path, right: &Path, options: &DiffOptions) -> Option<CommentStyle> {
+    if !options.ignore_comments {
+        return None;
+    }
+    let named = if right == Path::new("/dev/null") {
+        left
+    } else {
+        right
+    };
+    CommentStyle::for_path(named)
+}
+
+/// Count added and removed lines between two files
+pub fn count_line_changes(
+    left: Option<&Path>,
+    right: Option<&Path>,
+) -> Result<(usize, usize), DeltaError> {
+    let diff_text = run_unified_diff(left, right, 0)?;
+    let changes = changed_lines(&diff_text);
+    let added = changes.iter().filter(|c| c.kind == LineKind::Added).count();
+
+    Ok((added, changes.len() - added))
+}
+
+/// count_line_changes on a worker thread, None if it hasn't finished by `deadline`
+pub fn count_line_changes_until(
+    left: Option<&Path>,
+    right: Option<&Path>,
+    deadline: Instant,
+) -> Result<Option<(usize, usize)>, DeltaError> {
+    let (left, right) = (left.map(Path::to_path_buf), right.map(Path::to_path_buf));
+    run_until(deadline, move || {
+        count_line_changes(left.as_deref(), right.as_deref())
+    })
+    .transpose()
+}
+
+/// "1 line" / "3 lines"
+fn plural(count: usize, word: &str) -> String {
+    format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+}
+
+/// Function name from a `diff -p` hunk header: the identifier before the first `(`,
+/// else the whole header (e.g. "class Foo")
+fn function_name(header: &str) -> &str {
+    match header.split_once('(') {
+        Some((before, _)) => before.split_whitespace().last().unwrap_or(before),
+        None => header.trim_end_matches(['{', ':', ' ']),
+    }
+}
+
+/// One-sentence summary of a file's diff for the tree, e.g.
+/// "2 functions changed (parse, render), 3 lines added, 1 removed in 2 hunks".
+/// `rename` is the entry's rename_info; only a Renamed entry is summarized as renamed.
+/// Function names need show_function_context.
+pub fn summarize_diff(
+    left: Option<&Path>,
+    right: Option<&Path>,
+    rename: Option<&RenameInfo>,
+    options: &DiffOptions,
+) -> Result<String, DeltaError> {
+    let diff_text = run_diff_for_options(left, right, 3, options)?;
+    let binary = diff_text.starts_with("Binary files");
+    let changes = changed_lines(&diff_text);
+    let added = changes.iter().filter(|c| c.kind == LineKind::Added).count();
+    let removed = changes.len() - added;
+    let renamed = rename.is_some() && left.is_some() && right.is_some();
+
+    let summary = match (left, right) {
+        (None, Some(_)) if binary => "New binary file".to_string(),
+        (None, Some(_)) => format!("New file, {}", plural(added, "line")),
+        (Some(_), None) if binary => "Deleted binary file".to_string(),
+        (Some(_), None) => format!("Deleted file, {}", plural(removed, "line")),
+        _ if binary => "Binary file changed".to_string(),
+        _ if diff_text.is_empty() => "No changes".to_string(),
+        _ => {
+            let hunks = parse_hunks(&diff_text);
+            let mut functions: Vec<&str> = Vec::new();
+            for hunk in hunks.iter().filter(|h| !h.header.is_empty()) {
+                let name = function_name(&hunk.header);
+                if !functions.contains(&name) {
+                    functions.push(name);
+                }
+            }
+
+            let mut parts = Vec::new();
+            if !functions.is_empty() {
+                parts.push(format!(
+                    "{} changed ({})",
+                    plural(functions.len(), "function"),
+                    functions.join(", ")
+                ));
+            }
+            parts.push(format!("{} added", plural(added, "line")));
+            parts.push(format!(
+                "{} removed in {}",
+                removed,
+                plural(hunks.len(), "hunk")
+            ));
+            parts.join(", ")
+        }
+    };
+
+    Ok(if renamed {
+        format!("Renamed; {}{}", summary[..1].to_lowercase(), &summary[1..])
+    } else {
+        summary
+    })
+}
+
+/// Stable digest of what a diff changes: its removed and added lines, hunk by hunk, without
+/// file names, timestamps, line numbers or context. The same edit made at a different place
+/// in a file (or in another copy of it) gets the same fingerprint. 16 hex digits of FNV-1a.
+pub fn change_fingerprint(
+    left: Option<&Path>,
+    right: Option<&Path>,
+    options: &DiffOptions,
+) -> Result<String, DeltaError> {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = FNV_OFFSET;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash = (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME);
+        }
+    };
+
+    let diff_text = run_diff_for_options(left, right, 0, options)?;
+    if diff_text.starts_with("Binary files") {
+        // diff only names binary files, so hash what they hold
+        feed(b"binary\0");
+        for path in [left, right] {
+            feed(&path.map(std::fs::read).transpose()?.unwrap_or_default());
+            feed(b"\0");
+        }
+    } else {
+        for line in diff_text.lines() {
+            if line.starts_with("---") || line.starts_with("+++") {
+                continue;
+            }
+            // Keep hunk boundaries, but not where the hunks are
+            let line = if line.starts_with("@@") { "@@" } else { line };
+            feed(line.as_bytes());
+            feed(b"\n");
+        }
+    }
+
+    Ok(format!("{:016x}", hash))
+}
+
+/// Spawn `cmd`, feed `input` to its stdin and collect its output.
+/// stdin is written from a separate thread while stdout/stderr are drained, so large
+/// inputs can't deadlock with the child blocking on a full stdout pipe.
+pub(crate) fn run_with_input(
+    mut cmd: Command,
+    input: Vec<u8>,
+) -> Result<std::process::Output, DeltaError> {
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    let mut child = cmd.spawn()?;
+
+    let writer = child.stdin.take().map(|mut stdin| {
+        std::thread::spawn(move || -> std::io::Result<()> {
+            // Dropping stdin at the end of the closure closes the pipe (EOF for the child)
+            stdin.write_all(&input)
+        })
+    });
+
+    let output = child.wait_with_output()?;
+
+    if let Some(writer) = writer {
+        match writer.join() {
+            Ok(Ok(())) => {}
+            // The child may exit without reading all of its input; that's not our error
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => {
+                return Err(DeltaError::Process(
+                    "stdin writer thread panicked".to_string(),
+                ))
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Run `work` on a worker thread and wait for its result until `deadline`, None if it's
+/// still running then. The worker is detached and its result dropped when it finishes.
+pub(crate) fn run_until<T: Send + 'static>(
+    deadline: Instant,
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(work());
+    });
+    rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+/// Build the delta command for the given view options (stdio is configured by the caller)
+pub(crate) fn build_delta_command(options: &DiffOptions) -> Command {
+    // delta only reads stdin, so it runs from the temp directory
+    let mut delta_cmd = tool_command("delta", &[]);
 
     if options.side_by_side {
         delta_cmd.arg("--side-by-side");
-        // Use a reasonable width - each side gets half
-        delta_cmd.args(["--width", "160"]);
+        // Each side gets half
+        let width = options.width.unwrap_or(DEFAULT_SIDE_BY_SIDE_WIDTH);
+        delta_cmd.args(["--width", &width.to_string()]);
     }
 
     if options.line_numbers {
@@ -124,39 +1394,241 @@ fn generate_diff_with_delta(
     delta_cmd.args(["--file-style", "omit"]);
     delta_cmd.args(["--hunk-header-style", "omit"]);
 
-    // Use a dark theme
-    delta_cmd.args(["--dark"]);
+    delta_cmd.arg(if options.theme.is_light() {
+        "--light"
+    } else {
+        "--dark"
+    });
 
-    delta_cmd.stdin(Stdio::piped());
-    delta_cmd.stdout(Stdio::piped());
-    delta_cmd.stderr(Stdio::piped());
+    if let Some(theme) = options.syntax_theme.as_deref().filter(|t| !t.is_empty()) {
+        delta_cmd.args(["--syntax-theme", theme]);
+    }
 
-    let mut child = delta_cmd.spawn()?;
+    if let Some(width) = options.tab_width {
+        delta_cmd.args(["--tabs", &width.to_string()]);
+    }
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(diff_text.as_bytes())?;
+    if let Some(intra_line) = options.intra_line {
+        let distance = if intra_line {
+            DEFAULT_MAX_LINE_DISTANCE
+        } else {
+            "0"
+        };
+        delta_cmd.args(["--max-line-distance", distance]);
     }
 
-    let output = child.wait_with_output()?;
-    let ansi_output = String::from_utf8(output.stdout)?;
+    if !options.inherit_env {
+        delta_cmd.arg("--no-gitconfig");
+    }
+    apply_delta_env(&mut delta_cmd, options);
 
-    // For side-by-side mode, split delta's output into left and right panels
-    if options.side_by_side {
-        let (left_html, right_html) = split_side_by_side_output(&ansi_output)?;
-        return Ok(DiffResult {
-            html: String::new(),
-            has_changes: true,
-            hunk_count,
-            left_html: Some(left_html),
-            right_html: Some(right_html),
-        });
+    delta_cmd
+}
+
+/// Strip inherited variables that change delta's output and pin the terminal type,
+/// unless `inherit_env` is set; then apply the explicit `delta_env` overrides
+pub(crate) fn apply_delta_env(cmd: &mut Command, options: &DiffOptions) {
+    if !options.inherit_env {
+        for var in CLEARED_DELTA_ENV {
+            cmd.env_remove(var);
+        }
+        cmd.env("TERM", "xterm-256color");
+        cmd.env("COLORTERM", "truecolor");
+    }
+    cmd.envs(&options.delta_env);
+}
+
+/// True when a unified diff has no context lines at all, i.e. every line was replaced
+fn is_whole_file_rewrite(diff_text: &str) -> bool {
+    if diff_text.starts_with("Binary files") {
+        return true;
+    }
+
+    let mut has_removed = false;
+    let mut has_added = false;
+    for line in diff_text.lines() {
+        if line.starts_with("---") || line.starts_with("+++") || line.starts_with("@@") {
+            continue;
+        }
+        match line.chars().next() {
+            Some(' ') => return false,
+            Some('-') => has_removed = true,
+            Some('+') => has_added = true,
+            _ => {}
+        }
+    }
+    has_removed && has_added
+}
+
+/// Decode a file to text by its BOM (UTF-8 without one), with the encoding's name
+fn decode_text(bytes: &[u8]) -> Option<(String, &'static str)> {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        Some((String::from_utf8(rest.to_vec()).ok()?, "UTF-8 with BOM"))
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        Some((String::from_utf16(&units).ok()?, "UTF-16LE"))
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        Some((String::from_utf16(&units).ok()?, "UTF-16BE"))
+    } else {
+        Some((String::from_utf8(bytes.to_vec()).ok()?, "UTF-8"))
+    }
+}
+
+/// Decode a file to text ignoring BOMs, UTF-16 encoding and CR/CRLF line endings
+fn normalize_text_encoding(bytes: &[u8]) -> Option<String> {
+    let (text, _) = decode_text(bytes)?;
+    Some(text.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+/// Line ending style of decoded text: "LF", "CRLF", "CR", "mixed" or "none"
+fn line_ending_style(text: &str) -> &'static str {
+    let crlf = text.matches("\r\n").count();
+    let cr = text.matches('\r').count() - crlf;
+    let lf = text.matches('\n').count() - crlf;
+    match (crlf > 0, cr > 0, lf > 0) {
+        (false, false, false) => "none",
+        (true, false, false) => "CRLF",
+        (false, true, false) => "CR",
+        (false, false, true) => "LF",
+        _ => "mixed",
+    }
+}
+
+/// "UTF-16LE, CRLF" for a file, "binary" when it isn't text, "none" for a missing side
+pub(crate) fn describe_encoding(path: Option<&Path>) -> String {
+    let Some(bytes) = path.and_then(|p| std::fs::read(p).ok()) else {
+        return "none".to_string();
+    };
+    match decode_text(&bytes) {
+        Some((text, encoding)) => format!("{}, {}", encoding, line_ending_style(&text)),
+        None => "binary".to_string(),
+    }
+}
+
+/// Prepend the encoding_metadata row: one per panel in side-by-side, or one naming
+/// both sides inline. It's informational only and doesn't affect has_changes.
+fn with_encoding_metadata(
+    mut result: DiffResult,
+    left: Option<&Path>,
+    right: Option<&Path>,
+    options: &DiffOptions,
+) -> DiffResult {
+    if !options.encoding_metadata {
+        return result;
+    }
+    let (left_desc, right_desc) = (describe_encoding(left), describe_encoding(right));
+    let row = |text: String| format!("<div class=\"diff-meta\">{}</div>", text);
+
+    match (result.left_html.as_mut(), result.right_html.as_mut()) {
+        (Some(left_html), Some(right_html)) => {
+            left_html.insert_str(0, &row(left_desc));
+            right_html.insert_str(0, &row(right_desc));
+        }
+        _ => result
+            .html
+            .insert_str(0, &row(format!("{} \u{2192} {}", left_desc, right_desc))),
+    }
+    result
+}
+
+/// Add mark_script_changes warnings for a modified file whose `#!` interpreter line or
+/// executable bits changed
+fn with_script_changes(
+    mut result: DiffResult,
+    left: Option<&Path>,
+    right: Option<&Path>,
+    options: &DiffOptions,
+) -> DiffResult {
+    let (Some(left), Some(right)) = (left, right) else {
+        return result;
+    };
+    if !options.mark_script_changes {
+        return result;
+    }
+
+    let (old_line, new_line) = (first_line(left), first_line(right));
+    if old_line != new_line && (old_line.starts_with("#!") || new_line.starts_with("#!")) {
+        result.warnings.push(format!(
+            "Interpreter changed from '{}' to '{}'",
+            old_line, new_line
+        ));
+    }
+    if let (Some(old_mode), Some(new_mode)) = (file_mode(left), file_mode(right)) {
+        if old_mode != new_mode {
+            result.warnings.push(format!(
+                "File mode changed from {} to {}",
+                old_mode, new_mode
+            ));
+        }
+    }
+    result
+}
+
+/// First line of a file without its line ending ("" if unreadable)
+fn first_line(path: &Path) -> String {
+    let mut line = String::new();
+    if let Ok(file) = std::fs::File::open(path) {
+        let _ = std::io::BufReader::new(file).read_line(&mut line);
+    }
+    line.trim_end_matches(['\n', '\r']).to_string()
+}
+
+/// Git-style mode of a regular file: 100755 when anyone may execute it, else 100644.
+/// None where files carry no executable bit.
+pub(crate) fn file_mode(path: &Path) -> Option<&'static str> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path).ok()?.permissions().mode();
+        Some(if mode & 0o111 != 0 {
+            "100755"
+        } else {
+            "100644"
+        })
     }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
 
-    // Inline mode: process each line to separate line numbers from content
+fn normalized_contents_equal(left: &Path, right: &Path) -> bool {
+    let (Ok(left_bytes), Ok(right_bytes)) = (std::fs::read(left), std::fs::read(right)) else {
+        return false;
+    };
+    match (
+        normalize_text_encoding(&left_bytes),
+        normalize_text_encoding(&right_bytes),
+    ) {
+        (Some(l), Some(r)) => l == r,
+        _ => false,
+    }
+}
+
+/// Convert delta's inline ANSI output to HTML, separating line numbers from content.
+/// Also returns whether rendering stopped early at `max_output_bytes`.
+pub(crate) fn render_inline_output(
+    ansi_output: &str,
+    folds: &[ElidedContext],
+    options: &DiffOptions,
+) -> (String, bool) {
     let mut lines: Vec<String> = Vec::new();
     let mut prev_line_num: Option<u32> = None;
+    let mut budget = OutputBudget::new(options.max_output_bytes);
+    let mut truncated = false;
+    let palette = options.theme.palette();
 
     for line in ansi_output.lines() {
+        let mark = lines.len();
+
         // In inline mode with line numbers, delta uses │ before the content
         // Format: "  1 ⋮  2 │content" where ⋮ separates old/new line numbers
         if let Some(pipe_pos) = line.rfind('│') {
@@ -169,7 +1641,7 @@ fn generate_diff_with_delta(
             // Check for gaps in line numbers (indicating hidden context)
             if let (Some(prev), Some(curr)) = (prev_line_num, curr_line_num) {
                 if curr > prev + 1 {
-                    lines.push(create_hunk_separator());
+                    lines.push(list_item(gap_marker(prev, curr, folds), options));
                 }
             }
 
@@ -185,41 +1657,142 @@ fn generate_diff_with_delta(
                 None => String::new(),
             };
 
-            let line_num_html = ansi_to_html(line_num_part);
-            let content_html = ansi_to_html(content_part);
+            let line_num_html = ansi_to_html(line_num_part, palette);
+            let content_html = content_to_html(content_part, options, palette);
 
             // Add newline at end for proper copying
-            lines.push(format!(
+            let line_html = format!(
                 "<div class=\"diff-line\"{}><span class=\"line-num\">{}</span><span class=\"line-content\">{}\n</span></div>",
                 style,
                 line_num_html,
                 content_html
-            ));
+            );
+
+            // Context/added lines are addressed by their new number, removed lines by their old one
+            let (old_num, new_num) = inline_line_numbers(line_num_part);
+            let id = match (old_num, new_num) {
+                (_, Some(new)) => Some(format!("L{}", new)),
+                (Some(old), None) => Some(format!("LR{}", old)),
+                (None, None) => None,
+            };
+            let changed_line = match (old_num, new_num) {
+                (Some(old), None) => Some(old),
+                (None, Some(new)) => Some(new),
+                _ => None,
+            };
+            let line_html =
+                if options.mark_script_changes && is_shebang_change(changed_line, content_part) {
+                    mark_shebang(&line_html)
+                } else {
+                    line_html
+                };
+            let line_html = match id.filter(|_| options.emit_line_ids) {
+                Some(id) => with_line_id(&line_html, &id),
+                None => line_html,
+            };
+            let kind = match (old_num, new_num) {
+                (Some(_), Some(_)) => Some(DiffLineKind::Context),
+                (Some(_), None) => Some(DiffLineKind::Removed),
+                (None, Some(_)) => Some(DiffLineKind::Added),
+                (None, None) => None,
+            };
+            match kind.filter(|_| options.accessible) {
+                Some(kind) => lines.push(mark_change(&line_html, kind)),
+                None => lines.push(line_html),
+            }
         } else {
             // No │ found, treat entire line as content (headers, separators, etc.)
             let line_bg = extract_line_background(line);
-            let style = match line_bg {
+            let style = match &line_bg {
                 Some(bg) => format!(" style='background:{}'", bg),
                 None => String::new(),
             };
-            let html = ansi_to_html(line);
-            lines.push(format!("<div class=\"diff-line\"{}><span class=\"line-content\">{}\n</span></div>", style, html));
+            let html = ansi_to_html(line, palette);
+            let line_html = format!(
+                "<div class=\"diff-line\"{}><span class=\"line-content\">{}\n</span></div>",
+                style, html
+            );
+            // Without line numbers every row is a diff line, told apart by its background
+            if options.accessible && !options.line_numbers {
+                lines.push(mark_change(&line_html, background_kind(line_bg.as_deref())));
+            } else {
+                lines.push(line_html);
+            }
+        }
+
+        if !budget.take(&lines[mark..]) {
+            lines.truncate(mark);
+            lines.push(list_item(truncation_marker(options), options));
+            truncated = true;
+            break;
         }
     }
 
     // Wrap in container div
-    let styled_html = format!(
-        "<div class=\"delta-output\">{}</div>",
+    let html = format!(
+        "<div class=\"delta-output\"{}>{}</div>",
+        list_role(options),
         lines.join("\n")
     );
+    (html, truncated)
+}
+
+/// Running size of rendered HTML lines against `max_output_bytes`
+struct OutputBudget {
+    max: Option<usize>,
+    used: usize,
+}
+
+impl OutputBudget {
+    fn new(max: Option<usize>) -> Self {
+        OutputBudget { max, used: 0 }
+    }
+
+    /// Count newly rendered lines (plus their joining newline); false once over the cap
+    fn take(&mut self, lines: &[String]) -> bool {
+        self.used += lines.iter().map(|l| l.len() + 1).sum::<usize>();
+        self.max.is_none_or(|max| self.used <= max)
+    }
+}
+
+fn truncation_marker(options: &DiffOptions) -> String {
+    format!(
+        "<div class=\"diff-truncated\">Diff truncated: output exceeded {} bytes</div>",
+        options.max_output_bytes.unwrap_or_default()
+    )
+}
+
+/// Add a warning explaining the cut when rendering stopped at `max_output_bytes`
+fn with_truncation_note(
+    mut warnings: Vec<String>,
+    truncated: bool,
+    options: &DiffOptions,
+) -> Vec<String> {
+    if truncated {
+        warnings.push(format!(
+            "Diff output truncated at {} bytes",
+            options.max_output_bytes.unwrap_or_default()
+        ));
+    }
+    warnings
+}
+
+/// Old and new line numbers from an inline line-number column like "  3 ⋮  4 "
+fn inline_line_numbers(line_num_part: &str) -> (Option<u32>, Option<u32>) {
+    let visible = strip_ansi_codes(line_num_part);
+    match visible.split_once('⋮') {
+        Some((old, new)) => (old.trim().parse().ok(), new.trim().parse().ok()),
+        None => (None, extract_line_number(line_num_part)),
+    }
+}
 
-    Ok(DiffResult {
-        html: styled_html,
-        has_changes: true,
-        hunk_count,
-        left_html: None,
-        right_html: None,
-    })
+/// Give a rendered diff-line div an `id` for deep links
+fn with_line_id(line_html: &str, id: &str) -> String {
+    line_html.replacen(
+        "<div class=\"diff-line",
+        &format!("<div id=\"{}\" class=\"diff-line", id),
+        1,
+    )
 }
 
 /// Extract line number from the line number part of delta output
@@ -229,7 +1802,7 @@ fn extract_line_number(line_num_part: &str) -> Option<u32> {
     visible
         .split_whitespace()
         .filter_map(|s| s.parse::<u32>().ok())
-        .last()
+        .next_back()
 }
 
 /// Create a separator row to indicate hidden lines between hunks
@@ -237,14 +1810,50 @@ fn create_hunk_separator() -> String {
     "<div class=\"diff-separator\"></div>".to_string()
 }
 
-/// Split delta's side-by-side ANSI output into left and right panels
-fn split_side_by_side_output(ansi_output: &str) -> Result<(String, String), DeltaError> {
+/// What fills a gap in line numbers from `prev` to `curr`: the expandable group when
+/// fold_context cut exactly those lines, otherwise a plain separator between hunks
+fn gap_marker(prev: u32, curr: u32, folds: &[ElidedContext]) -> String {
+    let hidden = |start: u32, len: u32| start == prev + 1 && start + len == curr;
+    match folds
+        .iter()
+        .find(|f| hidden(f.old_start, f.len) || hidden(f.new_start, f.len))
+    {
+        Some(fold) => elided_context_marker(fold),
+        None => create_hunk_separator(),
+    }
+}
+
+/// Split delta's side-by-side ANSI output into left and right panels.
+/// Also returns whether rendering stopped early at `max_output_bytes`.
+pub(crate) fn split_side_by_side_output(
+    ansi_output: &str,
+    moves: &[MovedBlock],
+    folds: &[ElidedContext],
+    options: &DiffOptions,
+) -> Result<(String, String, bool), DeltaError> {
+    // Old/new line number -> id of the moved block it belongs to
+    let mut moved_old: HashMap<u32, usize> = HashMap::new();
+    let mut moved_new: HashMap<u32, usize> = HashMap::new();
+    for block in moves {
+        for offset in 0..block.len {
+            moved_old.insert(block.old_start + offset, block.id);
+            moved_new.insert(block.new_start + offset, block.id);
+        }
+    }
+
     let mut left_lines: Vec<String> = Vec::new();
     let mut right_lines: Vec<String> = Vec::new();
+    // Old and new line number shown on each row, for aligning moved blocks afterwards
+    let mut row_numbers: Vec<(Option<u32>, Option<u32>)> = Vec::new();
     let mut prev_left_line_num: Option<u32> = None;
     let mut prev_right_line_num: Option<u32> = None;
+    let mut budget = OutputBudget::new(options.max_output_bytes);
+    let mut truncated = false;
+    let palette = options.theme.palette();
 
     for line in ansi_output.lines() {
+        let mark = left_lines.len();
+
         // Delta uses │ (box drawing character) as the separator between left and right
         // Find the middle separator - it's typically at the midpoint
         if let Some((left, right)) = split_at_middle_separator(line) {
@@ -261,19 +1870,19 @@ fn split_side_by_side_output(ansi_output: &str) -> Result<(String, String), Delt
             };
 
             // Check for gaps in line numbers (indicating hidden context)
-            let left_gap = match (prev_left_line_num, left_line_num) {
-                (Some(prev), Some(curr)) => curr > prev + 1,
-                _ => false,
-            };
-            let right_gap = match (prev_right_line_num, right_line_num) {
-                (Some(prev), Some(curr)) => curr > prev + 1,
-                _ => false,
+            let gap = |prev: Option<u32>, curr: Option<u32>| match (prev, curr) {
+                (Some(prev), Some(curr)) if curr > prev + 1 => Some((prev, curr)),
+                _ => None,
             };
 
             // Insert separator if there's a gap on either side
-            if left_gap || right_gap {
-                left_lines.push(create_hunk_separator());
-                right_lines.push(create_hunk_separator());
+            let hidden =
+                gap(prev_left_line_num, left_line_num).or(gap(prev_right_line_num, right_line_num));
+            if let Some((prev, curr)) = hidden {
+                let marker = list_item(gap_marker(prev, curr, folds), options);
+                left_lines.push(marker.clone());
+                right_lines.push(marker);
+                row_numbers.push((None, None));
             }
 
             // Update previous line numbers
@@ -285,69 +1894,315 @@ fn split_side_by_side_output(ansi_output: &str) -> Result<(String, String), Delt
             }
 
             // Further split each side into line number and content at │
-            let left_structured = split_line_number_and_content(&left);
-            let right_structured = split_line_number_and_content(&right);
+            let mut left_structured = split_line_number_and_content(&left, options, palette);
+            let mut right_structured = split_line_number_and_content(&right, options, palette);
+
+            // Tag both ends of a moved block with the same id so the UI can connect them
+            if let Some(id) = left_line_num.and_then(|n| moved_old.get(&n)) {
+                left_structured = mark_moved(&left_structured, *id);
+            }
+            if let Some(id) = right_line_num.and_then(|n| moved_new.get(&n)) {
+                right_structured = mark_moved(&right_structured, *id);
+            }
+
+            if options.mark_script_changes {
+                let content = |side: &str| {
+                    side[side.rfind('│').map_or(0, |p| p + '│'.len_utf8())..].to_string()
+                };
+                let changed = |side: &str| extract_line_background(&content(side)).is_some();
+                if changed(&left) && is_shebang_change(left_line_num, &content(&left)) {
+                    left_structured = mark_shebang(&left_structured);
+                }
+                if changed(&right) && is_shebang_change(right_line_num, &content(&right)) {
+                    right_structured = mark_shebang(&right_structured);
+                }
+            }
+
+            // Both panels share one document, so the old side always uses the LR prefix
+            if options.emit_line_ids {
+                if let Some(n) = left_line_num {
+                    left_structured = with_line_id(&left_structured, &format!("LR{}", n));
+                }
+                if let Some(n) = right_line_num {
+                    right_structured = with_line_id(&right_structured, &format!("L{}", n));
+                }
+            }
+
+            // A numbered line is changed when delta gave its content a background
+            let numbered = left_line_num.is_some() || right_line_num.is_some();
+            if options.accessible && numbered {
+                let changed = |side: &str| {
+                    extract_line_background(&side[side.rfind('│').unwrap_or(0)..]).is_some()
+                };
+                if left_line_num.is_some() {
+                    let kind = if changed(&left) {
+                        DiffLineKind::Removed
+                    } else {
+                        DiffLineKind::Context
+                    };
+                    left_structured = mark_change(&left_structured, kind);
+                }
+                if right_line_num.is_some() {
+                    let kind = if changed(&right) {
+                        DiffLineKind::Added
+                    } else {
+                        DiffLineKind::Context
+                    };
+                    right_structured = mark_change(&right_structured, kind);
+                }
+            }
+            // Without numbers, a side with no background is a filler unless neither side has one
+            if options.accessible && !numbered {
+                let left_bg = extract_line_background(&left);
+                let right_bg = extract_line_background(&right);
+                let context = left_bg.is_none() && right_bg.is_none();
+                if left_bg.is_some() || context {
+                    left_structured =
+                        mark_change(&left_structured, background_kind(left_bg.as_deref()));
+                }
+                if right_bg.is_some() || context {
+                    right_structured =
+                        mark_change(&right_structured, background_kind(right_bg.as_deref()));
+                }
+            }
+
             left_lines.push(left_structured);
             right_lines.push(right_structured);
+            row_numbers.push((left_line_num, right_line_num));
         } else {
             // No separator found, put entire line in both panels
-            let html = ansi_to_html(line);
+            let html = ansi_to_html(line, palette);
             let trimmed = trim_html_trailing_whitespace(&html);
-            left_lines.push(format!("<div class=\"diff-line\"><span class=\"line-content\">{}</span></div>", trimmed));
-            right_lines.push(format!("<div class=\"diff-line\"><span class=\"line-content\">{}</span></div>", trimmed));
+            left_lines.push(format!(
+                "<div class=\"diff-line\"><span class=\"line-content\">{}</span></div>",
+                trimmed
+            ));
+            right_lines.push(format!(
+                "<div class=\"diff-line\"><span class=\"line-content\">{}</span></div>",
+                trimmed
+            ));
+            row_numbers.push((None, None));
+        }
+
+        // Panels stay row-aligned, so both are cut at the same row
+        if !(budget.take(&left_lines[mark..]) && budget.take(&right_lines[mark..])) {
+            left_lines.truncate(mark);
+            right_lines.truncate(mark);
+            row_numbers.truncate(mark);
+            left_lines.push(list_item(truncation_marker(options), options));
+            right_lines.push(list_item(truncation_marker(options), options));
+            row_numbers.push((None, None));
+            truncated = true;
+            break;
         }
     }
 
+    align_moved_rows(
+        &mut left_lines,
+        &mut right_lines,
+        &row_numbers,
+        moves,
+        options,
+    );
+
     let left_html = format!(
-        "<div class=\"sbs-panel\">{}</div>",
+        "<div class=\"sbs-panel\"{}>{}</div>",
+        list_role(options),
         left_lines.join("\n")
     );
     let right_html = format!(
-        "<div class=\"sbs-panel\">{}</div>",
+        "<div class=\"sbs-panel\"{}>{}</div>",
+        list_role(options),
         right_lines.join("\n")
     );
 
-    Ok((left_html, right_html))
+    Ok((left_html, right_html, truncated))
 }
 
-/// Extract the first background color from ANSI codes (line-level highlight)
-fn extract_line_background(ansi: &str) -> Option<String> {
-    let mut in_escape = false;
-    let mut escape_buf = String::new();
+/// Show each moved block's new lines beside its old ones: the right panel's rows for the
+/// block move up (or down) next to the left panel's, and where they were a `move-anchor`
+/// row in both panels points back to them. Only blocks whose old and new rows are each
+/// one-sided (nothing on the other panel) are moved; others keep just their `moved` marks.
+fn align_moved_rows(
+    left_lines: &mut Vec<String>,
+    right_lines: &mut Vec<String>,
+    row_numbers: &[(Option<u32>, Option<u32>)],
+    moves: &[MovedBlock],
+    options: &DiffOptions,
+) {
+    let mut anchors: HashMap<usize, String> = HashMap::new();
+    let mut dropped: HashSet<usize> = HashSet::new();
+
+    for block in moves {
+        let find_rows =
+            |numbers: &dyn Fn(u32) -> (Option<u32>, Option<u32>)| -> Option<Vec<usize>> {
+                (0..block.len)
+                    .map(|k| row_numbers.iter().position(|row| *row == numbers(k)))
+                    .collect()
+            };
+        let old_rows = find_rows(&|k| (Some(block.old_start + k), None));
+        let new_rows = find_rows(&|k| (None, Some(block.new_start + k)));
+        let (Some(old_rows), Some(new_rows)) = (old_rows, new_rows) else {
+            continue;
+        };
 
-    for c in ansi.chars() {
-        if c == '\x1b' {
-            in_escape = true;
-            escape_buf.clear();
-            escape_buf.push(c);
-        } else if in_escape {
-            escape_buf.push(c);
-            if c == 'm' {
-                // Parse the escape sequence for background color
-                if escape_buf.len() > 2 {
-                    let seq = &escape_buf[2..escape_buf.len() - 1];
-                    let parts: Vec<&str> = seq.split(';').collect();
-                    let mut i = 0;
-                    while i < parts.len() {
-                        if parts[i] == "48" && i + 4 < parts.len() && parts[i + 1] == "2" {
-                            // RGB background: 48;2;r;g;b
-                            let r: u8 = parts[i + 2].parse().unwrap_or(0);
-                            let g: u8 = parts[i + 3].parse().unwrap_or(0);
-                            let b: u8 = parts[i + 4].parse().unwrap_or(0);
-                            return Some(format!("#{:02x}{:02x}{:02x}", r, g, b));
+        for (&old_row, &new_row) in old_rows.iter().zip(&new_rows) {
+            right_lines[old_row] = std::mem::take(&mut right_lines[new_row]);
+            dropped.insert(new_row);
+        }
+        anchors.insert(new_rows[0], list_item(move_anchor(block), options));
+    }
+
+    if dropped.is_empty() {
+        return;
+    }
+    for lines in [left_lines, right_lines] {
+        *lines = std::mem::take(lines)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(row, line)| match anchors.get(&row) {
+                Some(anchor) => Some(anchor.clone()),
+                None => (!dropped.contains(&row)).then_some(line),
+            })
+            .collect();
+    }
+}
+
+/// Row left where a moved block's new lines were before align_moved_rows took them
+fn move_anchor(block: &MovedBlock) -> String {
+    format!(
+        "<div class=\"diff-line move-anchor\" data-move-id=\"{}\"><span class=\"line-content\">\
+         Lines {}–{} moved here are shown beside old line {}</span></div>",
+        block.id,
+        block.new_start,
+        block.new_start + block.len - 1,
+        block.old_start
+    )
+}
+
+/// Add the `moved` class and block id to a rendered diff-line div
+fn mark_moved(line_html: &str, id: usize) -> String {
+    line_html.replacen(
+        "<div class=\"diff-line\"",
+        &format!("<div class=\"diff-line moved\" data-move-id=\"{}\"", id),
+        1,
+    )
+}
+
+/// Label a rendered diff-line div with its change kind for assistive tech. The `+`/`-`/` `
+/// prefix is an empty span whose `data-prefix` CSS shows, so copying the line skips it.
+fn mark_change(line_html: &str, kind: DiffLineKind) -> String {
+    let (label, prefix) = match kind {
+        DiffLineKind::Added => ("Added line", "+"),
+        DiffLineKind::Removed => ("Removed line", "-"),
+        DiffLineKind::Context => ("Unchanged line", " "),
+    };
+    line_html
+        .replacen("<div ", &format!("<div role=\"listitem\" aria-label=\"{}\" ", label), 1)
+        .replacen(
+            "<span class=\"line-content\">",
+            &format!("<span class=\"line-content\"><span class=\"diff-prefix\" data-prefix=\"{}\"></span>", prefix),
+            1,
+        )
+}
+
+/// `role` for a container of lines labelled by mark_change
+/// Make a row that isn't a diff line (separator, fold, move anchor) an item of the
+/// accessible list, so the list only holds list items
+fn list_item(row_html: String, options: &DiffOptions) -> String {
+    if options.accessible {
+        row_html.replacen("<div ", "<div role=\"listitem\" ", 1)
+    } else {
+        row_html
+    }
+}
+
+/// The change kind of a line known only by its background (rows without line numbers):
+/// unchanged without one, removed on one of delta's removed backgrounds, otherwise added
+fn background_kind(background: Option<&str>) -> DiffLineKind {
+    let is_removed = |bg: &str| {
+        [DELTA_DARK_BACKGROUNDS, DELTA_LIGHT_BACKGROUNDS]
+            .iter()
+            .any(|[removed, removed_word, _, _]| bg == *removed || bg == *removed_word)
+    };
+    match background {
+        None => DiffLineKind::Context,
+        Some(bg) if is_removed(bg) => DiffLineKind::Removed,
+        Some(_) => DiffLineKind::Added,
+    }
+}
+
+fn list_role(options: &DiffOptions) -> &'static str {
+    if options.accessible {
+        " role=\"list\""
+    } else {
+        ""
+    }
+}
+
+/// Whether a changed line (by its old or new number) is a script's `#!` first line
+fn is_shebang_change(line_num: Option<u32>, content_ansi: &str) -> bool {
+    line_num == Some(1) && strip_ansi_codes(content_ansi).starts_with("#!")
+}
+
+/// Add the `shebang-change` class to a rendered diff-line div
+fn mark_shebang(line_html: &str) -> String {
+    line_html.replacen("class=\"diff-line", "class=\"diff-line shebang-change", 1)
+}
+
+/// The true-color background of a line as a whole (line-level highlight), telling it
+/// apart from the word highlights on top of it with line_background
+fn extract_line_background(ansi: &str) -> Option<String> {
+    let mut style = SgrStyle::default();
+    // Every background set, in order, so the last one is what the line ends on
+    let mut backgrounds: Vec<String> = Vec::new();
+    let mut chars = ansi.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            chars.next();
+            let mut seq = String::new();
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    if c == 'm' {
+                        style = parse_ansi_codes(&seq, &style);
+                        if let Some(SgrColor::Rgb(r, g, b)) = style.bg {
+                            backgrounds.push(format!("#{:02x}{:02x}{:02x}", r, g, b));
                         }
-                        i += 1;
                     }
+                    break;
                 }
-                in_escape = false;
+                seq.push(c);
             }
         }
     }
-    None
+
+    line_background(&backgrounds)
+}
+
+/// Which of a line's backgrounds (in order of appearance) is the line's own rather than a
+/// changed word's: one of delta's removed/added line backgrounds for either theme when
+/// present, otherwise the one the line ends on, as delta fills the rest of a line with it.
+/// How much of the line each covers says nothing, since a changed word can span most of it.
+fn line_background(backgrounds: &[String]) -> Option<String> {
+    let is_line_background = |bg: &str| {
+        [DELTA_DARK_BACKGROUNDS, DELTA_LIGHT_BACKGROUNDS]
+            .iter()
+            .any(|[removed, _, added, _]| bg == *removed || bg == *added)
+    };
+    backgrounds
+        .iter()
+        .find(|bg| is_line_background(bg))
+        .or_else(|| backgrounds.last())
+        .cloned()
 }
 
 /// Split a panel line into line number (non-selectable) and content parts
-fn split_line_number_and_content(line: &str) -> String {
+fn split_line_number_and_content(
+    line: &str,
+    options: &DiffOptions,
+    palette: &[&str; 16],
+) -> String {
     // Line format: "│  1 │content" or "  1 │content" or just "content"
     // Find the last │ which separates line number from content
 
@@ -363,8 +2218,8 @@ fn split_line_number_and_content(line: &str) -> String {
         let line_bg = extract_line_background(content_part);
 
         // Convert ANSI to HTML for both parts
-        let line_num_html = ansi_to_html(line_num_part);
-        let content_html = ansi_to_html(content_part);
+        let line_num_html = ansi_to_html(line_num_part, palette);
+        let content_html = content_to_html(content_part, options, palette);
 
         // Trim trailing whitespace from content
         let content_trimmed = trim_html_trailing_whitespace(&content_html);
@@ -389,14 +2244,176 @@ fn split_line_number_and_content(line: &str) -> String {
     } else {
         // No │ found, treat entire line as content
         let line_bg = extract_line_background(line);
-        let html = ansi_to_html(line);
+        let html = ansi_to_html(line, palette);
         let trimmed = trim_html_trailing_whitespace(&html);
         let style = match line_bg {
             Some(bg) => format!(" style='background:{}'", bg),
             None => String::new(),
         };
-        format!("<div class=\"diff-line\"{}><span class=\"line-content\">{}\n</span></div>", style, trimmed)
+        format!(
+            "<div class=\"diff-line\"{}><span class=\"line-content\">{}\n</span></div>",
+            style, trimmed
+        )
+    }
+}
+
+/// A line's content as HTML, with word-level highlights adjusted per token_granularity
+/// and word_emphasis
+fn content_to_html(content: &str, options: &DiffOptions, palette: &[&str; 16]) -> String {
+    let html = ansi_to_html(content, palette);
+    let html = if options.token_granularity {
+        snap_to_tokens(&html)
+    } else {
+        html
+    };
+    apply_word_emphasis(&html, options.word_emphasis)
+}
+
+/// Widen the word-level highlights in a line converted by ansi_to_html to whole tokens:
+/// a run of letters, digits and `_` with any highlighted character is highlighted
+/// throughout, on the background of its first highlighted character.
+pub(crate) fn snap_to_tokens(html: &str) -> String {
+    let Some(line_bg) = highlighted_line_background(html) else {
+        return html.to_string();
+    };
+
+    // One (text, span style) cell per visible character; an HTML entity is one character
+    let mut cells: Vec<(&str, String)> = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let span = rest
+            .strip_prefix("<span style='")
+            .and_then(|r| r.split_once("'>"))
+            .map(|(style, r)| (style, r.split_once("</span>").unwrap_or((r, ""))));
+        let (style, text, after) = match span {
+            Some((style, (text, after))) => (style, text, after),
+            None => {
+                let skip = rest.chars().next().map_or(0, char::len_utf8);
+                let end = rest[skip..]
+                    .find("<span style='")
+                    .map_or(rest.len(), |i| i + skip);
+                ("", &rest[..end], &rest[end..])
+            }
+        };
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let mut end = start + c.len_utf8();
+            if c == '&' {
+                if let Some(semi) = text[start..].find(';') {
+                    end = start + semi + 1;
+                    while chars.peek().is_some_and(|(i, _)| *i < end) {
+                        chars.next();
+                    }
+                }
+            }
+            cells.push((&text[start..end], style.to_string()));
+        }
+        rest = after;
+    }
+
+    let highlight = |style: &str| span_background(style).filter(|bg| *bg != line_bg);
+    let is_word = |text: &str| text.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let mut widened = false;
+    let mut start = 0;
+    while start < cells.len() {
+        if !is_word(cells[start].0) {
+            start += 1;
+            continue;
+        }
+        let end = (start..cells.len())
+            .find(|&i| !is_word(cells[i].0))
+            .unwrap_or(cells.len());
+        if let Some(word_bg) = cells[start..end]
+            .iter()
+            .find_map(|(_, style)| highlight(style))
+        {
+            for (_, style) in &mut cells[start..end] {
+                if highlight(style).is_none() {
+                    let without_bg = match span_background(style) {
+                        Some(bg) => style.replacen(&format!("background:{};", bg), "", 1),
+                        None => style.clone(),
+                    };
+                    *style = format!("background:{};{}", word_bg, without_bg);
+                    widened = true;
+                }
+            }
+        }
+        start = end;
+    }
+    if !widened {
+        return html.to_string();
+    }
+
+    // Rebuild, merging neighbouring cells that now share a style
+    let mut out = String::new();
+    let mut i = 0;
+    while i < cells.len() {
+        let style = &cells[i].1;
+        let run = cells[i..].iter().take_while(|(_, s)| s == style).count();
+        let text: String = cells[i..i + run].iter().map(|(t, _)| *t).collect();
+        if style.is_empty() {
+            out.push_str(&text);
+        } else {
+            out.push_str(&format!("<span style='{}'>{}</span>", style, text));
+        }
+        i += run;
+    }
+    out
+}
+
+/// Restyle word-level highlights in a line converted by ansi_to_html. Spans on any
+/// background but the line's own (see line_background) are delta's changed words.
+pub(crate) fn apply_word_emphasis(html: &str, emphasis: WordEmphasis) -> String {
+    let style = match emphasis {
+        WordEmphasis::Background => return html.to_string(),
+        WordEmphasis::Underline => "text-decoration:underline;",
+        WordEmphasis::Bold => "font-weight:bold;",
+    };
+
+    let Some(line_bg) = highlighted_line_background(html) else {
+        return html.to_string();
+    };
+
+    let mut parts = html.split("<span style='");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    for rest in parts {
+        out.push_str("<span style='");
+        match span_background(rest) {
+            Some(bg) if bg != line_bg => {
+                out.push_str(&rest.replacen(&format!("background:{};", bg), style, 1))
+            }
+            _ => out.push_str(rest),
+        }
     }
+    out
+}
+
+/// Background of a span whose style starts at `span_style`
+fn span_background(span_style: &str) -> Option<String> {
+    span_style
+        .strip_prefix("background:")
+        .and_then(|s| s.split(';').next())
+        .map(str::to_string)
+}
+
+/// Backgrounds of the spans in a line converted by ansi_to_html, in order
+fn span_backgrounds(html: &str) -> Vec<String> {
+    // ansi_to_html never nests spans, so each one is `<span style='...'>text</span>`
+    html.split("<span style='")
+        .skip(1)
+        .filter_map(|rest| span_background(rest.split_once("'>")?.0))
+        .collect()
+}
+
+/// The line's own background in a line converted by ansi_to_html, if it has any other
+/// background (a changed word) on it
+fn highlighted_line_background(html: &str) -> Option<String> {
+    let backgrounds = span_backgrounds(html);
+    let line_bg = line_background(&backgrounds)?;
+    backgrounds
+        .iter()
+        .any(|bg| *bg != line_bg)
+        .then_some(line_bg)
 }
 
 /// Trim trailing whitespace from HTML content
@@ -417,8 +2434,8 @@ fn split_at_middle_separator(line: &str) -> Option<(String, String)> {
         return None;
     }
 
-    // Calculate the visible length (excluding ANSI escape codes)
-    let visible_len = strip_ansi_codes(line).chars().count();
+    // Calculate the visible width (excluding ANSI escape codes)
+    let visible_len = display_width(line);
     let target_mid = visible_len / 2;
 
     // Find the separator closest to the visual middle
@@ -427,7 +2444,7 @@ fn split_at_middle_separator(line: &str) -> Option<(String, String)> {
 
     for (idx, &byte_pos) in separators.iter().enumerate() {
         let prefix = &line[..byte_pos];
-        let visible_pos = strip_ansi_codes(prefix).chars().count();
+        let visible_pos = display_width(prefix);
         let distance = (visible_pos as isize - target_mid as isize).unsigned_abs();
 
         if distance < best_distance {
@@ -446,39 +2463,307 @@ fn split_at_middle_separator(line: &str) -> Option<(String, String)> {
     Some((left.to_string(), right.to_string()))
 }
 
-/// Strip ANSI escape codes from a string
-fn strip_ansi_codes(s: &str) -> String {
+/// Terminal columns taken by a line once ANSI codes are stripped; CJK and emoji
+/// take two, like in delta's own layout
+pub(crate) fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(strip_ansi_codes(s).as_str())
+}
+
+/// Strip ANSI escape sequences from a string: CSI sequences (`ESC [` up to a final byte in
+/// `@`..=`~`, so cursor moves and erase-line too, not just SGR), OSC sequences (`ESC ]` up to
+/// BEL or `ESC \`) and other two-byte escapes
+pub(crate) fn strip_ansi_codes(s: &str) -> String {
     let mut result = String::new();
-    let mut in_escape = false;
+    let mut chars = s.chars().peekable();
 
-    for c in s.chars() {
-        if c == '\x1b' {
-            in_escape = true;
-        } else if in_escape {
-            if c == 'm' {
-                in_escape = false;
-            }
-        } else {
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
             result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Intermediate bytes, then the final one (e.g. `ESC ( B`)
+            Some(mut c) => {
+                while ('\x20'..='\x2f').contains(&c) {
+                    match chars.next() {
+                        Some(next) => c = next,
+                        None => break,
+                    }
+                }
+            }
+            None => {}
         }
     }
 
     result
 }
 
-fn html_escape(s: &str) -> String {
+pub(crate) fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
         .replace('"', "&quot;")
 }
 
+/// Default colors for standalone ANSI conversion, used wherever the input sets none
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DiffTheme {
+    pub foreground: String,
+    pub background: String,
+    /// Palette for basic ANSI colors (see ThemeMode::palette)
+    pub mode: ThemeMode,
+}
+
+impl Default for DiffTheme {
+    fn default() -> Self {
+        DiffTheme::for_mode(ThemeMode::Dark)
+    }
+}
+
+impl DiffTheme {
+    /// The diff view's colors in `mode`
+    pub fn for_mode(mode: ThemeMode) -> Self {
+        let (foreground, background) = if mode.is_light() {
+            ("#1f2328", "#ffffff")
+        } else {
+            ("#c9d1d9", "#0d1117")
+        };
+        DiffTheme {
+            foreground: foreground.to_string(),
+            background: background.to_string(),
+            mode,
+        }
+    }
+}
+
+/// One color of the diff view, for a legend
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LegendEntry {
+    pub label: String,
+    pub color: String,
+}
+
+/// delta's `--dark` defaults for minus-style, minus-emph-style, plus-style and plus-emph-style
+const DELTA_DARK_BACKGROUNDS: [&str; 4] = ["#3f0001", "#901011", "#002800", "#006000"];
+
+/// delta's `--light` defaults, in the same order
+const DELTA_LIGHT_BACKGROUNDS: [&str; 4] = ["#ffe0e0", "#ffc0c0", "#d0ffd0", "#a0efa0"];
+
+/// Unified diff rendered to find the colors delta uses: one context line and a
+/// one-word change
+const LEGEND_PROBE_DIFF: &str = concat!(
+    "--- a/legend.txt\n",
+    "+++ b/legend.txt\n",
+    "@@ -1,2 +1,2 @@\n",
+    " context\n",
+    "-let value = 1;\n",
+    "+let value = 2;\n",
+);
+
+fn legend(colors: [&str; 5]) -> Vec<LegendEntry> {
+    [
+        "Removed line",
+        "Removed word",
+        "Added line",
+        "Added word",
+        "Unchanged line",
+    ]
+    .iter()
+    .zip(colors)
+    .map(|(label, color)| LegendEntry {
+        label: label.to_string(),
+        color: color.to_string(),
+    })
+    .collect()
+}
+
+/// Legend from delta's rendering of LEGEND_PROBE_DIFF in `theme`: each changed line's main
+/// background and the other background on it (the changed word). None if a line is missing
+/// or plain.
+pub(crate) fn legend_from_ansi(ansi: &str, theme: ThemeMode) -> Option<Vec<LegendEntry>> {
+    let backgrounds = |marker: &str| -> Option<(String, String)> {
+        let line = ansi
+            .lines()
+            .find(|l| strip_ansi_codes(l).contains(marker))?;
+        let backgrounds = span_backgrounds(&ansi_to_html(line, theme.palette()));
+        let line_bg = line_background(&backgrounds)?;
+        let word_bg = backgrounds.iter().find(|bg| **bg != line_bg).cloned();
+        Some((line_bg.clone(), word_bg.unwrap_or(line_bg)))
+    };
+    let (removed, removed_word) = backgrounds("value = 1")?;
+    let (added, added_word) = backgrounds("value = 2")?;
+    let unchanged = DiffTheme::for_mode(theme).background;
+    Some(legend([
+        &removed,
+        &removed_word,
+        &added,
+        &added_word,
+        &unchanged,
+    ]))
+}
+
+/// Colors of removed/added lines and words and unchanged lines as the diff view will show
+/// them in `theme`, found by rendering a small diff with delta. Falls back to delta's
+/// defaults for the theme when delta isn't available.
+pub fn diff_legend(syntax_theme: Option<String>, theme: ThemeMode) -> Vec<LegendEntry> {
+    let options = DiffOptions {
+        side_by_side: false,
+        line_numbers: false,
+        syntax_theme,
+        theme,
+        ..Default::default()
+    };
+    let rendered = check_delta_installed()
+        .then(|| {
+            run_with_input(
+                build_delta_command(&options),
+                LEGEND_PROBE_DIFF.as_bytes().to_vec(),
+            )
+            .ok()
+        })
+        .flatten()
+        .and_then(|output| legend_from_ansi(&String::from_utf8_lossy(&output.stdout), theme));
+
+    rendered.unwrap_or_else(|| {
+        let [removed, removed_word, added, added_word] = *theme.diff_backgrounds();
+        legend([
+            removed,
+            removed_word,
+            added,
+            added_word,
+            &DiffTheme::for_mode(theme).background,
+        ])
+    })
+}
+
+/// Convert arbitrary ANSI-colored text (e.g. another tool's output) to HTML with the
+/// same converter the diff view uses, inside a block carrying the theme's colors
+pub fn ansi_to_themed_html(input: &str, theme: &DiffTheme) -> String {
+    format!(
+        "<pre class=\"ansi-output\" style=\"color:{};background:{};\">{}</pre>",
+        html_escape(&theme.foreground),
+        html_escape(&theme.background),
+        ansi_to_html(input, theme.mode.palette())
+    )
+}
+
 /// Convert ANSI escape codes to HTML spans
 /// Custom implementation to fix word-level highlighting (the ansi-to-html crate has bugs)
-fn ansi_to_html(input: &str) -> String {
+pub(crate) fn ansi_to_html(input: &str, palette: &[&str; 16]) -> String {
+    convert_ansi(input, &|style| {
+        format!("<span style='{}'>", style.css(palette))
+    })
+}
+
+/// Like ansi_to_html, but with CSS classes instead of inline colors so the page's
+/// stylesheet (see ansi_stylesheet) decides them: `ansi-fg-<n>`/`ansi-bg-<n>` for the
+/// 256 indexed colors, `diff-del`, `diff-del-emph`, `diff-add` and `diff-add-emph` for
+/// delta's line and word backgrounds (dark or light), and `ansi-bold`, `ansi-faint`,
+/// `ansi-italic`, `ansi-underline` and `ansi-strike`. Other true colors stay inline.
+pub fn ansi_to_html_classed(input: &str) -> String {
+    convert_ansi(input, &classed_span)
+}
+
+fn classed_span(style: &SgrStyle) -> String {
+    let (classes, inline) = style.classes();
+    match (classes.is_empty(), inline.is_empty()) {
+        (false, true) => format!("<span class='{}'>", classes),
+        (true, false) => format!("<span style='{}'>", inline),
+        _ => format!("<span class='{}' style='{}'>", classes, inline),
+    }
+}
+
+/// Rewrite the inline-styled spans and line backgrounds of rendered diff HTML to the
+/// classes ansi_to_html_classed uses. Colors from `palette` become basic color classes;
+/// styles with anything else CSS-specific are left as they are.
+pub(crate) fn classify_inline_styles(html: &str, palette: &[&str; 16]) -> String {
+    const SPAN: &str = "<span style='";
+    const LINE: &str = "class=\"diff-line\" style='background:";
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    loop {
+        let next = [SPAN, LINE]
+            .iter()
+            .filter_map(|tag| Some((rest.find(tag)?, *tag)))
+            .min();
+        let Some((at, tag)) = next else { break };
+        let Some(len) = rest[at + tag.len()..].find("'>") else {
+            break;
+        };
+        let value = &rest[at + tag.len()..at + tag.len() + len];
+        out.push_str(&rest[..at]);
+        let replaced = if tag == SPAN {
+            SgrStyle::from_css(value, palette).map(|style| classed_span(&style))
+        } else {
+            diff_background_class(value).map(|class| format!("class=\"diff-line {}\">", class))
+        };
+        match replaced {
+            Some(replaced) => out.push_str(&replaced),
+            None => out.push_str(&rest[at..at + tag.len() + len + 2]),
+        }
+        rest = &rest[at + tag.len() + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Rules for every class ansi_to_html_classed emits, with `theme`'s basic colors; swap
+/// it to restyle classed output without converting again
+pub fn ansi_stylesheet(theme: ThemeMode) -> String {
+    let palette = theme.palette();
+    let mut css = String::new();
+    for n in 0..=255u8 {
+        let color = ansi_256_to_rgb(n, palette);
+        css.push_str(&format!(
+            ".ansi-fg-{n}{{color:{color}}}\n.ansi-bg-{n}{{background:{color}}}\n"
+        ));
+    }
+    for (class, color) in DIFF_BACKGROUND_CLASSES.iter().zip(theme.diff_backgrounds()) {
+        css.push_str(&format!(".{}{{background:{}}}\n", class, color));
+    }
+    css.push_str(
+        ".ansi-bold{font-weight:bold}\n.ansi-faint{opacity:0.6}\n.ansi-italic{font-style:italic}\n\
+         .ansi-underline{text-decoration:underline}\n.ansi-strike{text-decoration:line-through}\n\
+         .ansi-underline.ansi-strike{text-decoration:underline line-through}\n",
+    );
+    css
+}
+
+/// Classes for DELTA_DARK_BACKGROUNDS and DELTA_LIGHT_BACKGROUNDS, in the same order
+const DIFF_BACKGROUND_CLASSES: [&str; 4] =
+    ["diff-del", "diff-del-emph", "diff-add", "diff-add-emph"];
+
+/// The class for one of delta's line or word backgrounds in either theme
+fn diff_background_class(hex: &str) -> Option<&'static str> {
+    [DELTA_DARK_BACKGROUNDS, DELTA_LIGHT_BACKGROUNDS]
+        .iter()
+        .find_map(|backgrounds| backgrounds.iter().position(|bg| *bg == hex))
+        .map(|i| DIFF_BACKGROUND_CLASSES[i])
+}
+
+/// Escape `input` as HTML, opening a span from `open_span` wherever the SGR style changes
+fn convert_ansi(input: &str, open_span: &dyn Fn(&SgrStyle) -> String) -> String {
     let mut result = String::new();
-    let mut current_fg: Option<String> = None;
-    let mut current_bg: Option<String> = None;
+    let mut current = SgrStyle::default();
     let mut in_escape = false;
     let mut escape_buf = String::new();
 
@@ -493,24 +2778,16 @@ fn ansi_to_html(input: &str) -> String {
                 // Parse the escape sequence
                 if escape_buf.len() > 2 {
                     let seq = &escape_buf[2..escape_buf.len() - 1]; // Remove \x1b[ and m
-                    let (new_fg, new_bg) = parse_ansi_codes(seq, &current_fg, &current_bg);
+                    let new = parse_ansi_codes(seq, &current);
 
-                    // If colors changed, close old span and open new
-                    if new_bg != current_bg || new_fg != current_fg {
-                        if current_bg.is_some() || current_fg.is_some() {
+                    // If the style changed, close old span and open new
+                    if new != current {
+                        if !current.is_plain() {
                             result.push_str("</span>");
                         }
-                        current_bg = new_bg;
-                        current_fg = new_fg;
-                        if current_bg.is_some() || current_fg.is_some() {
-                            result.push_str("<span style='");
-                            if let Some(ref bg) = current_bg {
-                                result.push_str(&format!("background:{};", bg));
-                            }
-                            if let Some(ref fg) = current_fg {
-                                result.push_str(&format!("color:{};", fg));
-                            }
-                            result.push_str("'>");
+                        current = new;
+                        if !current.is_plain() {
+                            result.push_str(&open_span(&current));
                         }
                     }
                 }
@@ -529,21 +2806,155 @@ fn ansi_to_html(input: &str) -> String {
     }
 
     // Close any remaining span
-    if current_bg.is_some() || current_fg.is_some() {
+    if !current.is_plain() {
         result.push_str("</span>");
     }
 
     result
 }
 
-/// Parse ANSI SGR codes and return new foreground/background colors
-fn parse_ansi_codes(
-    seq: &str,
-    current_fg: &Option<String>,
-    current_bg: &Option<String>,
-) -> (Option<String>, Option<String>) {
-    let mut fg = current_fg.clone();
-    let mut bg = current_bg.clone();
+/// A color as an SGR sequence gives it: one of the 256 indexed colors, or a true color
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SgrColor {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl SgrColor {
+    fn hex(self, palette: &[&str; 16]) -> String {
+        match self {
+            SgrColor::Indexed(n) => ansi_256_to_rgb(n, palette),
+            SgrColor::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+
+    /// A CSS `#rrggbb` color: the basic color it is in `palette`, or a true color
+    fn from_css(hex: &str, palette: &[&str; 16]) -> Option<Self> {
+        if let Some(n) = palette.iter().position(|color| *color == hex) {
+            return Some(SgrColor::Indexed(n as u8));
+        }
+        let value =
+            u32::from_str_radix(hex.strip_prefix('#').filter(|h| h.len() == 6)?, 16).ok()?;
+        Some(SgrColor::Rgb(
+            (value >> 16) as u8,
+            (value >> 8) as u8,
+            value as u8,
+        ))
+    }
+}
+
+/// Colors and text attributes set by SGR codes, carried from one escape sequence to the next
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SgrStyle {
+    fg: Option<SgrColor>,
+    bg: Option<SgrColor>,
+    bold: bool,
+    /// Faint (SGR 2), rendered at reduced opacity on top of any color
+    faint: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl SgrStyle {
+    fn is_plain(&self) -> bool {
+        *self == SgrStyle::default()
+    }
+
+    /// Inline CSS for the style; background comes first, as span_background expects
+    fn css(&self, palette: &[&str; 16]) -> String {
+        let mut css = String::new();
+        if let Some(bg) = self.bg {
+            css.push_str(&format!("background:{};", bg.hex(palette)));
+        }
+        if let Some(fg) = self.fg {
+            css.push_str(&format!("color:{};", fg.hex(palette)));
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.faint {
+            css.push_str("opacity:0.6;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        match (self.underline, self.strikethrough) {
+            (true, true) => css.push_str("text-decoration:underline line-through;"),
+            (true, false) => css.push_str("text-decoration:underline;"),
+            (false, true) => css.push_str("text-decoration:line-through;"),
+            (false, false) => {}
+        }
+        css
+    }
+
+    /// The style `css` (as written by SgrStyle::css with `palette`) stands for, or None if
+    /// it has a declaration SgrStyle::css doesn't write
+    fn from_css(css: &str, palette: &[&str; 16]) -> Option<Self> {
+        let mut style = SgrStyle::default();
+        for declaration in css.split(';').filter(|d| !d.is_empty()) {
+            match declaration.split_once(':')? {
+                ("background", color) => style.bg = Some(SgrColor::from_css(color, palette)?),
+                ("color", color) => style.fg = Some(SgrColor::from_css(color, palette)?),
+                ("font-weight", "bold") => style.bold = true,
+                ("opacity", "0.6") => style.faint = true,
+                ("font-style", "italic") => style.italic = true,
+                ("text-decoration", decoration) => {
+                    for line in decoration.split(' ') {
+                        match line {
+                            "underline" => style.underline = true,
+                            "line-through" => style.strikethrough = true,
+                            _ => return None,
+                        }
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(style)
+    }
+
+    /// Space-separated classes for the style (see ansi_to_html_classed), and inline CSS
+    /// for the true colors no class covers
+    fn classes(&self) -> (String, String) {
+        let mut classes = Vec::new();
+        let mut inline = String::new();
+        match self.bg {
+            Some(SgrColor::Indexed(n)) => classes.push(format!("ansi-bg-{}", n)),
+            Some(SgrColor::Rgb(r, g, b)) => {
+                let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+                match diff_background_class(&hex) {
+                    Some(class) => classes.push(class.to_string()),
+                    None => inline.push_str(&format!("background:{};", hex)),
+                }
+            }
+            None => {}
+        }
+        match self.fg {
+            Some(SgrColor::Indexed(n)) => classes.push(format!("ansi-fg-{}", n)),
+            Some(SgrColor::Rgb(r, g, b)) => {
+                inline.push_str(&format!("color:#{:02x}{:02x}{:02x};", r, g, b))
+            }
+            None => {}
+        }
+        for (on, class) in [
+            (self.bold, "ansi-bold"),
+            (self.faint, "ansi-faint"),
+            (self.italic, "ansi-italic"),
+            (self.underline, "ansi-underline"),
+            (self.strikethrough, "ansi-strike"),
+        ] {
+            if on {
+                classes.push(class.to_string());
+            }
+        }
+        (classes.join(" "), inline)
+    }
+}
+
+/// Apply ANSI SGR codes to `current` and return the resulting style
+fn parse_ansi_codes(seq: &str, current: &SgrStyle) -> SgrStyle {
+    let mut style = current.clone();
     let parts: Vec<&str> = seq.split(';').collect();
     let mut i = 0;
 
@@ -551,8 +2962,7 @@ fn parse_ansi_codes(
         match parts[i] {
             "0" => {
                 // Reset all attributes
-                fg = None;
-                bg = None;
+                style = SgrStyle::default();
             }
             "38" => {
                 // Foreground color
@@ -561,12 +2971,12 @@ fn parse_ansi_codes(
                     let r: u8 = parts[i + 2].parse().unwrap_or(0);
                     let g: u8 = parts[i + 3].parse().unwrap_or(0);
                     let b: u8 = parts[i + 4].parse().unwrap_or(0);
-                    fg = Some(format!("#{:02x}{:02x}{:02x}", r, g, b));
+                    style.fg = Some(SgrColor::Rgb(r, g, b));
                     i += 4;
                 } else if i + 1 < parts.len() && parts[i + 1] == "5" && i + 2 < parts.len() {
                     // 256 color: 38;5;n - convert to approximate RGB
                     let n: u8 = parts[i + 2].parse().unwrap_or(0);
-                    fg = Some(ansi_256_to_rgb(n));
+                    style.fg = Some(SgrColor::Indexed(n));
                     i += 2;
                 }
             }
@@ -577,61 +2987,61 @@ fn parse_ansi_codes(
                     let r: u8 = parts[i + 2].parse().unwrap_or(0);
                     let g: u8 = parts[i + 3].parse().unwrap_or(0);
                     let b: u8 = parts[i + 4].parse().unwrap_or(0);
-                    bg = Some(format!("#{:02x}{:02x}{:02x}", r, g, b));
+                    style.bg = Some(SgrColor::Rgb(r, g, b));
                     i += 4;
                 } else if i + 1 < parts.len() && parts[i + 1] == "5" && i + 2 < parts.len() {
                     // 256 color: 48;5;n
                     let n: u8 = parts[i + 2].parse().unwrap_or(0);
-                    bg = Some(ansi_256_to_rgb(n));
+                    style.bg = Some(SgrColor::Indexed(n));
                     i += 2;
                 }
             }
-            // Basic colors (30-37 foreground, 40-47 background)
-            "30" => fg = Some("#000000".to_string()),
-            "31" => fg = Some("#aa0000".to_string()),
-            "32" => fg = Some("#00aa00".to_string()),
-            "33" => fg = Some("#aaaa00".to_string()),
-            "34" => fg = Some("#0000aa".to_string()),
-            "35" => fg = Some("#aa00aa".to_string()),
-            "36" => fg = Some("#00aaaa".to_string()),
-            "37" => fg = Some("#aaaaaa".to_string()),
-            "40" => bg = Some("#000000".to_string()),
-            "41" => bg = Some("#aa0000".to_string()),
-            "42" => bg = Some("#00aa00".to_string()),
-            "43" => bg = Some("#aaaa00".to_string()),
-            "44" => bg = Some("#0000aa".to_string()),
-            "45" => bg = Some("#aa00aa".to_string()),
-            "46" => bg = Some("#00aaaa".to_string()),
-            "47" => bg = Some("#aaaaaa".to_string()),
-            // Bright colors (90-97 foreground, 100-107 background)
-            "90" => fg = Some("#555555".to_string()),
-            "91" => fg = Some("#ff5555".to_string()),
-            "92" => fg = Some("#55ff55".to_string()),
-            "93" => fg = Some("#ffff55".to_string()),
-            "94" => fg = Some("#5555ff".to_string()),
-            "95" => fg = Some("#ff55ff".to_string()),
-            "96" => fg = Some("#55ffff".to_string()),
-            "97" => fg = Some("#ffffff".to_string()),
-            _ => {}
+            code => match code.parse::<u8>() {
+                // Text attributes and their resets; 22 ends both bold and faint
+                Ok(1) => style.bold = true,
+                Ok(2) => style.faint = true,
+                Ok(3) => style.italic = true,
+                Ok(4) => style.underline = true,
+                Ok(9) => style.strikethrough = true,
+                Ok(22) => {
+                    style.bold = false;
+                    style.faint = false;
+                }
+                Ok(23) => style.italic = false,
+                Ok(24) => style.underline = false,
+                Ok(29) => style.strikethrough = false,
+                // Basic colors (30-37 foreground, 40-47 background)
+                Ok(n @ 30..=37) => style.fg = Some(SgrColor::Indexed(n - 30)),
+                Ok(n @ 40..=47) => style.bg = Some(SgrColor::Indexed(n - 40)),
+                // Bright colors (90-97 foreground, 100-107 background)
+                Ok(n @ 90..=97) => style.fg = Some(SgrColor::Indexed(n - 90 + 8)),
+                Ok(n @ 100..=107) => style.bg = Some(SgrColor::Indexed(n - 100 + 8)),
+                _ => {}
+            },
         }
         i += 1;
     }
 
-    (fg, bg)
+    style
 }
 
+/// Basic ANSI colors on a dark background (VGA)
+const DARK_PALETTE: [&str; 16] = [
+    "#000000", "#aa0000", "#00aa00", "#aaaa00", "#0000aa", "#aa00aa", "#00aaaa", "#aaaaaa",
+    "#555555", "#ff5555", "#55ff55", "#ffff55", "#5555ff", "#ff55ff", "#55ffff", "#ffffff",
+];
+
+/// Basic ANSI colors readable on a light background
+const LIGHT_PALETTE: [&str; 16] = [
+    "#24292f", "#cf222e", "#116329", "#4d2d00", "#0969da", "#8250df", "#1b7c83", "#6e7781",
+    "#57606a", "#a40e26", "#1a7f37", "#633c01", "#218bff", "#a475f9", "#3192aa", "#8c959f",
+];
+
 /// Convert ANSI 256 color code to RGB hex
-fn ansi_256_to_rgb(n: u8) -> String {
+fn ansi_256_to_rgb(n: u8, palette: &[&str; 16]) -> String {
     match n {
-        0..=15 => {
-            // Standard colors
-            let colors = [
-                "#000000", "#aa0000", "#00aa00", "#aaaa00", "#0000aa", "#aa00aa", "#00aaaa",
-                "#aaaaaa", "#555555", "#ff5555", "#55ff55", "#ffff55", "#5555ff", "#ff55ff",
-                "#55ffff", "#ffffff",
-            ];
-            colors[n as usize].to_string()
-        }
+        // Standard colors
+        0..=15 => palette[n as usize].to_string(),
         16..=231 => {
             // 216 color cube: 6x6x6
             let n = n - 16;
@@ -650,5 +3060,53 @@ fn ansi_256_to_rgb(n: u8) -> String {
 }
 
 pub fn get_file_content(path: &Path) -> Result<String, DeltaError> {
+    let path = normalize_path(path);
+    if is_binary(&path) {
+        return Err(DeltaError::BinaryFile(path.to_string_lossy().to_string()));
+    }
     Ok(std::fs::read_to_string(path)?)
 }
+
+/// A whole file as numbered, syntax-highlighted HTML lines, for reading rather than
+/// diffing. delta highlights it as an all-added diff with the added-line colors turned
+/// off; without delta the lines come back as plain text.
+pub fn highlight_file(
+    path: &Path,
+    syntax_theme: Option<&str>,
+    theme: ThemeMode,
+) -> Result<String, DeltaError> {
+    let content = get_file_content(path)?;
+    let lines: Vec<String> = if check_delta_installed() {
+        let diff_text = run_unified_diff(None, Some(path), 0)?;
+        let options = DiffOptions {
+            syntax_theme: syntax_theme.map(str::to_string),
+            theme,
+            ..Default::default()
+        };
+        let mut delta_cmd = build_delta_command(&options);
+        delta_cmd.args(["--plus-style", "syntax", "--plus-emph-style", "syntax"]);
+        let output = run_with_input(delta_cmd, diff_text.into_bytes())?;
+        String::from_utf8(output.stdout)?
+            .lines()
+            .map(|line| ansi_to_html(line, theme.palette()))
+            .collect()
+    } else {
+        content.lines().map(html_escape).collect()
+    };
+
+    let rows: Vec<String> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                "<div class=\"diff-line\"><span class=\"line-num\">{:>4} </span><span class=\"line-content\">{}\n</span></div>",
+                i + 1,
+                line
+            )
+        })
+        .collect();
+    Ok(format!(
+        "<div class=\"delta-output file-view\">{}</div>",
+        rows.join("\n")
+    ))
+}
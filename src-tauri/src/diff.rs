@@ -1,6 +1,16 @@
+use crate::hunks::MIN_MOVED_BLOCK_LINES;
+use crate::normalize::{normalize_pair, CommentNormalizer, FileTypeNormalizer, Normalizer};
+use crate::paths::{display_path, normalize_path};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use walkdir::WalkDir;
 
@@ -12,6 +22,10 @@ pub enum DiffError {
     Walk(#[from] walkdir::Error),
     #[error("Path error: {0}")]
     Path(String),
+    #[error("Invalid pattern: {0}")]
+    Pattern(#[from] globset::Error),
+    #[error("Comparison cancelled")]
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -20,10 +34,16 @@ pub enum FileStatus {
     Deleted,
     Modified,
     Renamed,
+    /// A new file that git's copy detection (detect_copies) traced to `from`, an existing
+    /// file; `similarity` is git's score as a fraction
+    Copied {
+        from: String,
+        similarity: f32,
+    },
     Unchanged,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FileEntry {
     pub path: String,
     pub name: String,
@@ -31,9 +51,73 @@ pub struct FileEntry {
     pub is_dir: bool,
     pub left_path: Option<String>,
     pub right_path: Option<String>,
+    /// Where lines removed here were added (or added lines came from), with detect_cross_file_moves
+    #[serde(default)]
+    pub cross_file_move_hint: Option<String>,
+    /// Either version mixes `\r\n` and bare `\n` line endings (only known where content was compared)
+    #[serde(default)]
+    pub mixed_line_endings: bool,
+    /// Old and new path of a Renamed entry, with the detector's similarity score
+    #[serde(default)]
+    pub rename_info: Option<RenameInfo>,
+    /// Larger than max_file_size_for_diff on a side: the status comes from size and bytes
+    /// alone and the content shouldn't be diffed
+    #[serde(default)]
+    pub skip_content: bool,
+    /// One side is a byte prefix of the other (with treat_truncation_as_equal), e.g. a log
+    /// that kept growing; diff it with the same DiffOptions flag to see only the tail
+    #[serde(default)]
+    pub truncated_continuation: bool,
+    /// Either version looks like a binary file (see is_binary); it's diffed by bytes only
+    #[serde(default)]
+    pub is_binary: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A rename found by the detector; `similarity` is 1.0 for identical content
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RenameInfo {
+    pub from: String,
+    pub to: String,
+    pub similarity: f32,
+    /// The content changed too (renamed with changes), if only in what the compare options
+    /// ignore
+    #[serde(default)]
+    pub modified: bool,
+}
+
+/// A rename of `from` to `to` measured from the files themselves: unmodified with
+/// similarity 1.0 when their bytes are equal, else modified with their line_similarity
+pub fn measure_rename(
+    from: &str,
+    to: &str,
+    left: &Path,
+    right: &Path,
+) -> Result<RenameInfo, DiffError> {
+    let modified = files_differ(left, right)?;
+    Ok(RenameInfo {
+        from: from.to_string(),
+        to: to.to_string(),
+        similarity: if modified {
+            file_similarity(left, right)
+        } else {
+            1.0
+        },
+        modified,
+    })
+}
+
+/// line_similarity of two files' text; 0.0 when either is binary or unreadable
+fn file_similarity(left: &Path, right: &Path) -> f32 {
+    if is_binary(left) || is_binary(right) {
+        return 0.0;
+    }
+    match (LineProfile::read(left), LineProfile::read(right)) {
+        (Some(left), Some(right)) => left.similarity(&right),
+        _ => 0.0,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FileTreeNode {
     pub name: String,
     pub path: String,
@@ -42,52 +126,335 @@ pub struct FileTreeNode {
     pub children: Vec<FileTreeNode>,
     pub left_path: Option<String>,
     pub right_path: Option<String>,
+    /// Changed files below a directory by kind (copies count as added, renames as
+    /// modified); 0 on files
+    #[serde(default)]
+    pub added: usize,
+    #[serde(default)]
+    pub deleted: usize,
+    #[serde(default)]
+    pub modified: usize,
+}
+
+/// Options controlling how two directories are compared and presented as a tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompareOptions {
+    /// Group files moved between the same pair of directories into one summary node
+    pub collapse_renames: bool,
+    /// Treat files that differ only by a final newline as unchanged
+    pub ignore_trailing_newline: bool,
+    /// Leave out lockfiles and generated files (see DEFAULT_GENERATED_PATTERNS)
+    pub skip_known_generated: bool,
+    /// Patterns used by skip_known_generated instead of the defaults
+    pub generated_patterns: Option<Vec<String>>,
+    /// Hint on changed files when a block removed from one shows up added in another
+    pub detect_cross_file_moves: bool,
+    /// Globs for files to leave out on both sides (matched like generated_patterns)
+    pub ignore_patterns: Vec<String>,
+    /// Walk the two directories on separate threads
+    pub parallel_walk: bool,
+    /// Return only the tree and counts from get_file_tree, leaving `files` empty
+    pub tree_only: bool,
+    /// Treat files that differ only in whole-line comments as unchanged (known languages only)
+    pub ignore_comments: bool,
+    /// Relative file paths to leave out on both sides, matched exactly with either separator
+    pub exclude_paths: Vec<String>,
+    /// In branch diffs, report tracked files copied from another file (`git diff -C -C`)
+    /// as Copied instead of Added
+    pub detect_copies: bool,
+    /// Return at most this many changed files (in path order) from get_file_tree
+    pub max_files: Option<usize>,
+    /// Bytes above which a file is compared by size and raw bytes only (no ignore_*
+    /// normalization or move hints) and marked skip_content
+    pub max_file_size_for_diff: Option<u64>,
+    /// Flag Modified files where one side's bytes are a prefix of the other's as
+    /// truncated_continuation
+    pub treat_truncation_as_equal: bool,
+    /// Treat files whose lines differ only in whitespace as unchanged
+    pub ignore_whitespace: bool,
+    /// Treat files that differ only in blank lines as unchanged
+    pub ignore_blank_lines: bool,
+    /// Also pair deleted and added files whose content is similar, not just equal, as
+    /// renames (see rename_threshold). On by default.
+    pub detect_similar_renames: bool,
+    /// Share of lines two files must have in common for detect_similar_renames;
+    /// DEFAULT_RENAME_THRESHOLD when unset
+    pub rename_threshold: Option<f32>,
+    /// Compare text files through the rule for their type, if any (see FileTypeNormalizer)
+    pub normalize_by_type: bool,
+    /// More normalizers for text files, run after the ones the flags above switch on
+    #[serde(skip)]
+    pub normalizers: Vec<Arc<dyn Normalizer>>,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions {
+            collapse_renames: false,
+            ignore_trailing_newline: false,
+            skip_known_generated: false,
+            generated_patterns: None,
+            detect_cross_file_moves: false,
+            ignore_patterns: Vec::new(),
+            parallel_walk: false,
+            tree_only: false,
+            ignore_comments: false,
+            exclude_paths: Vec::new(),
+            detect_copies: false,
+            max_files: None,
+            max_file_size_for_diff: None,
+            treat_truncation_as_equal: false,
+            ignore_whitespace: false,
+            ignore_blank_lines: false,
+            detect_similar_renames: true,
+            rename_threshold: None,
+            normalize_by_type: false,
+            normalizers: Vec::new(),
+        }
+    }
+}
+
+impl CompareOptions {
+    /// The normalizers text files are compared through: ignore_comments' and
+    /// normalize_by_type's, then `normalizers`
+    pub(crate) fn active_normalizers(&self) -> Vec<Arc<dyn Normalizer>> {
+        let mut active: Vec<Arc<dyn Normalizer>> = Vec::new();
+        if self.ignore_comments {
+            active.push(Arc::new(CommentNormalizer));
+        }
+        if self.normalize_by_type {
+            active.push(Arc::new(FileTypeNormalizer));
+        }
+        active.extend(self.normalizers.iter().cloned());
+        active
+    }
+}
+
+/// rename_threshold when unset
+pub const DEFAULT_RENAME_THRESHOLD: f32 = 0.6;
+
+/// Globs for files skipped by skip_known_generated, matched against the file name
+/// and the relative path
+pub const DEFAULT_GENERATED_PATTERNS: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "*.generated.*",
+    "*.min.js",
+    "*.min.css",
+];
+
+/// Per-extension change counts for a comparison
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ExtStats {
+    pub added: usize,
+    pub deleted: usize,
+    pub modified: usize,
+    pub renamed: usize,
+    pub copied: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
 }
 
-pub fn compare_directories(
+/// Change weight of one directory (path "" is the root). The plain fields include every
+/// subdirectory; the `own_` ones count only files directly inside.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct DirHeat {
+    pub path: String,
+    /// Changed files
+    pub changes: usize,
+    /// Added lines
+    pub additions: usize,
+    /// Removed lines
+    pub deletions: usize,
+    pub own_changes: usize,
+    pub own_additions: usize,
+    pub own_deletions: usize,
+}
+
+/// Line counts of one changed file (see diff_stats)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileDiffStat {
+    pub path: String,
+    pub status: FileStatus,
+    pub added: usize,
+    pub removed: usize,
+    /// Counting took longer than the per-file timeout, so added/removed are 0
+    pub timed_out: bool,
+}
+
+/// Line counts for every changed file of a comparison, with totals
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct DiffStatReport {
+    pub files: Vec<FileDiffStat>,
+    pub added: usize,
+    pub removed: usize,
+    /// Files given up on after the per-file timeout
+    pub timed_out: usize,
+}
+
+/// How long diff_stats waits for one file when no timeout is given
+pub const DEFAULT_FILE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A file whose status differs between two comparisons of the same trees.
+/// None means the file wasn't listed as changed (unchanged, or absent on both sides).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StatusTransition {
+    pub path: String,
+    pub from: Option<FileStatus>,
+    pub to: Option<FileStatus>,
+}
+
+/// Minimum number of files sharing an old-dir → new-dir mapping before they are collapsed
+const RENAME_GROUP_MIN_FILES: usize = 5;
+
+/// compare_directories_with_options with the default options
+#[cfg(test)]
+pub fn compare_directories(left_dir: &Path, right_dir: &Path) -> Result<Vec<FileEntry>, DiffError> {
+    compare_directories_with_options(left_dir, right_dir, &CompareOptions::default())
+}
+
+/// compare_directories_cancellable without a way to cancel, for tests
+#[cfg(test)]
+pub fn compare_directories_with_options(
     left_dir: &Path,
     right_dir: &Path,
+    options: &CompareOptions,
 ) -> Result<Vec<FileEntry>, DiffError> {
-    let mut left_files: HashMap<PathBuf, PathBuf> = HashMap::new();
-    let mut right_files: HashMap<PathBuf, PathBuf> = HashMap::new();
+    compare_directories_cancellable(left_dir, right_dir, options, &AtomicBool::new(false))
+}
 
-    // Walk left directory
-    for entry in WalkDir::new(left_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-    {
-        let relative = entry
-            .path()
-            .strip_prefix(left_dir)
-            .map_err(|e| DiffError::Path(e.to_string()))?;
-        left_files.insert(relative.to_path_buf(), entry.path().to_path_buf());
+/// compare_directories that gives up with DiffError::Cancelled once `cancel` is set.
+/// The walks and the per-file and rename checks look at the flag before each file.
+pub fn compare_directories_cancellable(
+    left_dir: &Path,
+    right_dir: &Path,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+) -> Result<Vec<FileEntry>, DiffError> {
+    let mut entries = Vec::new();
+    classify_entries(left_dir, right_dir, options, cancel, &mut |entry| {
+        entries.push(entry)
+    })?;
+
+    // Sort by path
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if options.detect_cross_file_moves {
+        annotate_cross_file_moves(&mut entries);
     }
 
-    // Walk right directory
-    for entry in WalkDir::new(right_dir)
+    Ok(entries)
+}
+
+/// The comparison behind compare_directories, handing each entry to `emit` as soon as it's
+/// classified instead of collecting them: files on both sides first, then renames, deletions
+/// and additions, in no particular order. detect_cross_file_moves is not applied, as it
+/// needs every entry.
+pub fn classify_entries(
+    left_dir: &Path,
+    right_dir: &Path,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    emit: &mut dyn FnMut(FileEntry),
+) -> Result<(), DiffError> {
+    // Long/UNC paths on Windows need the extended-length prefix for WalkDir and std::fs
+    let left_dir = &normalize_path(left_dir);
+    let right_dir = &normalize_path(right_dir);
+
+    let excluded = excluded_paths(options);
+    let (left_scan, right_scan) = scan_both(
+        left_dir,
+        right_dir,
+        compile_ignore(options)?,
+        &excluded,
+        options.parallel_walk,
+        cancel,
+    )?;
+    let left_files: HashMap<PathBuf, PathBuf> = left_scan
         .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-    {
-        let relative = entry
-            .path()
-            .strip_prefix(right_dir)
-            .map_err(|e| DiffError::Path(e.to_string()))?;
-        right_files.insert(relative.to_path_buf(), entry.path().to_path_buf());
-    }
+        .map(|(relative, entry)| (relative, entry.into_path()))
+        .collect();
+    let right_files: HashMap<PathBuf, PathBuf> = right_scan
+        .into_iter()
+        .map(|(relative, entry)| (relative, entry.into_path()))
+        .collect();
+
+    classify_files(&left_files, &right_files, options, cancel, emit)
+}
+
+/// compare_directories over files listed by the caller instead of walked, e.g. from git:
+/// (relative path, file) pairs for each side, left out by the same ignore rules as a walk
+pub fn compare_file_lists(
+    left: Vec<(PathBuf, PathBuf)>,
+    right: Vec<(PathBuf, PathBuf)>,
+    options: &CompareOptions,
+) -> Result<Vec<FileEntry>, DiffError> {
+    let ignore = compile_ignore(options)?;
+    let excluded = excluded_paths(options);
+    let kept = |files: Vec<(PathBuf, PathBuf)>| -> HashMap<PathBuf, PathBuf> {
+        files
+            .into_iter()
+            .filter(|(relative, _)| !is_skipped(relative, ignore.as_deref(), &excluded))
+            .collect()
+    };
 
     let mut entries = Vec::new();
+    classify_files(
+        &kept(left),
+        &kept(right),
+        options,
+        &AtomicBool::new(false),
+        &mut |entry| entries.push(entry),
+    )?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    if options.detect_cross_file_moves {
+        annotate_cross_file_moves(&mut entries);
+    }
+    Ok(entries)
+}
 
+/// classify_entries once both sides are listed, by relative path
+fn classify_files(
+    left_files: &HashMap<PathBuf, PathBuf>,
+    right_files: &HashMap<PathBuf, PathBuf>,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    emit: &mut dyn FnMut(FileEntry),
+) -> Result<(), DiffError> {
     // Collect deleted and added files for rename detection
     let mut deleted_files: Vec<(PathBuf, PathBuf)> = Vec::new();
     let mut added_files: Vec<(PathBuf, PathBuf)> = Vec::new();
 
-    // Find files that exist in both directories (modified or unchanged)
-    for (relative, right_path) in &right_files {
-        if let Some(left_path) = left_files.get(relative) {
-            // File exists in both - check if modified
-            let status = if files_differ(left_path, right_path)? {
+    // Files that exist in both directories (modified or unchanged); the rest of the right
+    // side are potentially added or renamed
+    let mut common: Vec<(&PathBuf, &PathBuf, &PathBuf)> = Vec::new();
+    for (relative, right_path) in right_files {
+        match left_files.get(relative) {
+            Some(left_path) => common.push((relative, left_path, right_path)),
+            None => added_files.push((relative.clone(), right_path.clone())),
+        }
+    }
+    common.sort();
+
+    let pairs: Vec<(&Path, &Path)> = common
+        .iter()
+        .map(|(_, left, right)| (left.as_path(), right.as_path()))
+        .collect();
+    check_contents(
+        &pairs,
+        options,
+        compare_workers(),
+        cancel,
+        &mut |index, check| {
+            let (relative, left_path, right_path) = common[index];
+            let status = if check.differ {
                 FileStatus::Modified
             } else {
                 FileStatus::Unchanged
@@ -98,59 +465,77 @@ pub fn compare_directories(
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_default();
 
-            entries.push(FileEntry {
+            emit(FileEntry {
                 path: relative.to_string_lossy().to_string(),
                 name,
                 status,
                 is_dir: false,
-                left_path: Some(left_path.to_string_lossy().to_string()),
-                right_path: Some(right_path.to_string_lossy().to_string()),
+                left_path: Some(display_path(left_path)),
+                right_path: Some(display_path(right_path)),
+                cross_file_move_hint: None,
+                mixed_line_endings: check.mixed_line_endings,
+                rename_info: None,
+                skip_content: check.skip_content,
+                truncated_continuation: check.truncated_continuation,
+                is_binary: check.binary,
             });
-        } else {
-            // File only in right - potentially added or renamed
-            added_files.push((relative.clone(), right_path.clone()));
-        }
-    }
+        },
+    )?;
 
     // Find deleted files (in left but not in right)
-    for (relative, left_path) in &left_files {
+    for (relative, left_path) in left_files {
         if !right_files.contains_key(relative) {
             deleted_files.push((relative.clone(), left_path.clone()));
         }
     }
 
-    // Detect renames: match deleted files with added files by content
-    let mut renamed_left: std::collections::HashSet<PathBuf> = std::collections::HashSet::new();
-    let mut renamed_right: std::collections::HashSet<PathBuf> = std::collections::HashSet::new();
+    // Detect renames: match deleted files with added files by content. Sorting first makes
+    // the greedy pairing independent of HashMap order.
+    deleted_files.sort();
+    added_files.sort();
+    let mut renamed_left: HashSet<PathBuf> = HashSet::new();
+    let mut renamed_right: HashSet<PathBuf> = HashSet::new();
 
-    for (deleted_rel, deleted_path) in &deleted_files {
-        for (added_rel, added_path) in &added_files {
-            if renamed_right.contains(added_rel) {
-                continue;
-            }
+    for found in match_renames(
+        &deleted_files,
+        &added_files,
+        options,
+        compare_workers(),
+        cancel,
+    )? {
+        let (deleted_rel, deleted_path) = &deleted_files[found.deleted];
+        let (added_rel, added_path) = &added_files[found.added];
+        let name = added_rel
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
 
-            // Check if files have identical content
-            if !files_differ(deleted_path, added_path)? {
-                // Found a rename!
-                let name = added_rel
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_default();
-
-                entries.push(FileEntry {
-                    path: format!("{} → {}", deleted_rel.to_string_lossy(), added_rel.to_string_lossy()),
-                    name,
-                    status: FileStatus::Renamed,
-                    is_dir: false,
-                    left_path: Some(deleted_path.to_string_lossy().to_string()),
-                    right_path: Some(added_path.to_string_lossy().to_string()),
-                });
+        emit(FileEntry {
+            path: format!(
+                "{} → {}",
+                deleted_rel.to_string_lossy(),
+                added_rel.to_string_lossy()
+            ),
+            name,
+            status: FileStatus::Renamed,
+            is_dir: false,
+            left_path: Some(display_path(deleted_path)),
+            right_path: Some(display_path(added_path)),
+            cross_file_move_hint: None,
+            mixed_line_endings: found.mixed_line_endings,
+            rename_info: Some(RenameInfo {
+                from: deleted_rel.to_string_lossy().to_string(),
+                to: added_rel.to_string_lossy().to_string(),
+                similarity: found.similarity,
+                modified: found.modified,
+            }),
+            skip_content: found.skip_content,
+            truncated_continuation: false,
+            is_binary: is_binary(added_path),
+        });
 
-                renamed_left.insert(deleted_rel.clone());
-                renamed_right.insert(added_rel.clone());
-                break;
-            }
-        }
+        renamed_left.insert(deleted_rel.clone());
+        renamed_right.insert(added_rel.clone());
     }
 
     // Add remaining deleted files (not renamed)
@@ -160,14 +545,21 @@ pub fn compare_directories(
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_default();
+            let check = check_one_side(left_path, options);
 
-            entries.push(FileEntry {
+            emit(FileEntry {
                 path: relative.to_string_lossy().to_string(),
                 name,
                 status: FileStatus::Deleted,
                 is_dir: false,
-                left_path: Some(left_path.to_string_lossy().to_string()),
+                left_path: Some(display_path(left_path)),
                 right_path: None,
+                cross_file_move_hint: None,
+                mixed_line_endings: check.mixed_line_endings,
+                rename_info: None,
+                skip_content: check.skip_content,
+                truncated_continuation: false,
+                is_binary: check.binary,
             });
         }
     }
@@ -179,46 +571,1216 @@ pub fn compare_directories(
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_default();
+            let check = check_one_side(right_path, options);
 
-            entries.push(FileEntry {
+            emit(FileEntry {
                 path: relative.to_string_lossy().to_string(),
                 name,
                 status: FileStatus::Added,
                 is_dir: false,
                 left_path: None,
-                right_path: Some(right_path.to_string_lossy().to_string()),
+                right_path: Some(display_path(right_path)),
+                cross_file_move_hint: None,
+                mixed_line_endings: check.mixed_line_endings,
+                rename_info: None,
+                skip_content: check.skip_content,
+                truncated_continuation: false,
+                is_binary: check.binary,
             });
         }
     }
 
-    // Sort by path
+    Ok(())
+}
+
+/// Files per status, for a comparison whose entries aren't kept (see classify_entries)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ComparisonCounts {
+    /// Everything but unchanged files
+    pub total_changes: usize,
+    pub added: usize,
+    pub deleted: usize,
+    pub modified: usize,
+    pub renamed: usize,
+    pub unchanged: usize,
+}
+
+impl ComparisonCounts {
+    pub fn count(&mut self, entry: &FileEntry) {
+        match entry.status {
+            // A copy is a new file
+            FileStatus::Added | FileStatus::Copied { .. } => self.added += 1,
+            FileStatus::Deleted => self.deleted += 1,
+            FileStatus::Modified => self.modified += 1,
+            FileStatus::Renamed => self.renamed += 1,
+            FileStatus::Unchanged => {
+                self.unchanged += 1;
+                return;
+            }
+        }
+        self.total_changes += 1;
+    }
+}
+
+/// A deleted file paired with an added file of the same or similar content, by index into
+/// the lists given to match_renames
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RenameMatch {
+    pub deleted: usize,
+    pub added: usize,
+    pub mixed_line_endings: bool,
+    pub skip_content: bool,
+    /// 1.0 for equal bytes, else the line_similarity score
+    pub similarity: f32,
+    /// The bytes differ (see RenameInfo::modified)
+    pub modified: bool,
+}
+
+/// Threads used to compare file contents and verify rename candidates
+fn compare_workers() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// compare_contents for each (left, right) pair on up to `workers` threads.
+/// `done` gets the pair's index with the results on the calling thread, in the order
+/// the checks finish; the first error stops the remaining checks.
+fn check_contents(
+    pairs: &[(&Path, &Path)],
+    options: &CompareOptions,
+    workers: usize,
+    cancel: &AtomicBool,
+    done: &mut dyn FnMut(usize, ContentCheck),
+) -> Result<(), DiffError> {
+    let check = |(left, right): (&Path, &Path)| -> Result<ContentCheck, DiffError> {
+        check_cancelled(cancel)?;
+        compare_contents(left, right, options)
+    };
+
+    if workers <= 1 || pairs.len() <= 1 {
+        for (index, &pair) in pairs.iter().enumerate() {
+            done(index, check(pair)?);
+        }
+        return Ok(());
+    }
+
+    // Workers take the next unchecked pair until none are left or the receiver is gone
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..workers.min(pairs.len()) {
+            let tx = tx.clone();
+            let (next, check) = (&next, &check);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&pair) = pairs.get(index) else {
+                    break;
+                };
+                let result = check(pair);
+                let failed = result.is_err();
+                if tx.send((index, result)).is_err() || failed {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        for (index, result) in rx {
+            done(index, result?);
+        }
+        Ok(())
+    })
+}
+
+/// Pair each deleted file with the first still-unmatched added file whose content
+/// matches. The candidate pairs are checked on up to `workers` threads; the greedy
+/// pairing then runs single-threaded in list order, so the result doesn't depend on
+/// `workers`. With detect_similar_renames, the files left over are then paired by
+/// similarity (see match_similar_renames).
+pub(crate) fn match_renames(
+    deleted: &[(PathBuf, PathBuf)],
+    added: &[(PathBuf, PathBuf)],
+    options: &CompareOptions,
+    workers: usize,
+    cancel: &AtomicBool,
+) -> Result<Vec<RenameMatch>, DiffError> {
+    // Files compare_contents would call equal share a content_key, so only those pairs need
+    // checking. Normalizers may take their rules from the added file's name, so every pair
+    // is checked with any of them.
+    let candidates: Vec<(usize, usize)> = if !options.active_normalizers().is_empty() {
+        (0..deleted.len())
+            .flat_map(|d| (0..added.len()).map(move |a| (d, a)))
+            .collect()
+    } else {
+        let keys = |files: &[(PathBuf, PathBuf)]| -> Result<Vec<u64>, DiffError> {
+            files
+                .iter()
+                .map(|(_, path)| {
+                    check_cancelled(cancel)?;
+                    content_key(path, options)
+                })
+                .collect()
+        };
+        let deleted_keys = keys(deleted)?;
+        let mut added_by_key: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (a, key) in keys(added)?.into_iter().enumerate() {
+            added_by_key.entry(key).or_default().push(a);
+        }
+        deleted_keys
+            .iter()
+            .enumerate()
+            .flat_map(|(d, key)| {
+                added_by_key
+                    .get(key)
+                    .into_iter()
+                    .flatten()
+                    .map(move |&a| (d, a))
+            })
+            .collect()
+    };
+
+    let verify = |pairs: &[(usize, usize)]| -> Result<Vec<RenameMatch>, DiffError> {
+        let mut matches = Vec::new();
+        for &(d, a) in pairs {
+            check_cancelled(cancel)?;
+            let check = compare_contents(&deleted[d].1, &added[a].1, options)?;
+            if !check.differ {
+                // Equal under the options only; files too large to diff were compared by bytes
+                let modified = !check.skip_content && files_differ(&deleted[d].1, &added[a].1)?;
+                matches.push(RenameMatch {
+                    deleted: d,
+                    added: a,
+                    mixed_line_endings: check.mixed_line_endings,
+                    skip_content: check.skip_content,
+                    similarity: if modified {
+                        file_similarity(&deleted[d].1, &added[a].1)
+                    } else {
+                        1.0
+                    },
+                    modified,
+                });
+            }
+        }
+        Ok(matches)
+    };
+
+    let chunk_size = candidates.len().div_ceil(workers.max(1)).max(1);
+    let verified: Vec<RenameMatch> = if workers <= 1 {
+        verify(&candidates)?
+    } else {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || verify(chunk)))
+                .collect();
+            let mut verified = Vec::new();
+            for handle in handles {
+                let chunk = handle.join().unwrap_or_else(|_| {
+                    Err(DiffError::Path("rename verification panicked".to_string()))
+                })?;
+                verified.extend(chunk);
+            }
+            Ok::<_, DiffError>(verified)
+        })?
+    };
+
+    // Chunks are joined in order, so `verified` is sorted by (deleted, added)
+    let mut taken = HashSet::new();
+    let mut matched = HashSet::new();
+    let mut renames = Vec::new();
+    for found in verified {
+        if matched.contains(&found.deleted) || taken.contains(&found.added) {
+            continue;
+        }
+        matched.insert(found.deleted);
+        taken.insert(found.added);
+        renames.push(found);
+    }
+
+    if options.detect_similar_renames {
+        renames.extend(match_similar_renames(
+            deleted, added, &matched, &taken, options, cancel,
+        )?);
+    }
+
+    Ok(renames)
+}
+
+/// Pair the deleted and added files not yet matched (by index) whose line similarity
+/// reaches rename_threshold, best score first. Binary files and ones over
+/// max_file_size_for_diff (SIMILAR_RENAME_MAX_BYTES when unset) aren't considered, nor are
+/// pairs whose sizes are too far apart to plausibly reach the threshold. Files are held as
+/// LineProfiles rather than text while every pair is scored.
+fn match_similar_renames(
+    deleted: &[(PathBuf, PathBuf)],
+    added: &[(PathBuf, PathBuf)],
+    matched: &HashSet<usize>,
+    taken: &HashSet<usize>,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+) -> Result<Vec<RenameMatch>, DiffError> {
+    let threshold = options.rename_threshold.unwrap_or(DEFAULT_RENAME_THRESHOLD);
+    let max_bytes = options
+        .max_file_size_for_diff
+        .unwrap_or(SIMILAR_RENAME_MAX_BYTES);
+    let profiles =
+        |files: &[(PathBuf, PathBuf)], skip: &HashSet<usize>| -> Result<Vec<_>, DiffError> {
+            let mut profiles: Vec<(usize, LineProfile)> = Vec::new();
+            for (i, (_, path)) in files.iter().enumerate() {
+                check_cancelled(cancel)?;
+                let small = std::fs::metadata(path).is_ok_and(|m| m.len() <= max_bytes);
+                if !skip.contains(&i) && small && !is_binary(path) {
+                    profiles.extend(LineProfile::read(path).map(|profile| (i, profile)));
+                }
+            }
+            Ok(profiles)
+        };
+    let (old_profiles, new_profiles) = (profiles(deleted, matched)?, profiles(added, taken)?);
+
+    // (score, index into old_profiles, index into new_profiles); those follow the file lists' order
+    let mut scored = Vec::new();
+    for (o, (_, old)) in old_profiles.iter().enumerate() {
+        for (n, (_, new)) in new_profiles.iter().enumerate() {
+            check_cancelled(cancel)?;
+            let (small, large) = (old.bytes.min(new.bytes), old.bytes.max(new.bytes));
+            if (small as f32) < threshold * large as f32 {
+                continue;
+            }
+            let similarity = old.similarity(new);
+            if similarity >= threshold {
+                scored.push((similarity, o, n));
+            }
+        }
+    }
+    scored.sort_by(|x, y| y.0.total_cmp(&x.0).then(x.1.cmp(&y.1)).then(x.2.cmp(&y.2)));
+
+    let (mut paired_old, mut paired_new) = (HashSet::new(), HashSet::new());
+    let mut renames = Vec::new();
+    for (similarity, o, n) in scored {
+        if paired_old.contains(&o) || paired_new.contains(&n) {
+            continue;
+        }
+        paired_old.insert(o);
+        paired_new.insert(n);
+        let ((d, old), (a, new)) = (&old_profiles[o], &new_profiles[n]);
+        // Equal files were paired by match_renames already, so even a 1.0 (the same lines
+        // reordered) is a rename with changes
+        renames.push(RenameMatch {
+            deleted: *d,
+            added: *a,
+            mixed_line_endings: old.mixed_line_endings || new.mixed_line_endings,
+            skip_content: false,
+            similarity,
+            modified: true,
+        });
+    }
+    Ok(renames)
+}
+
+/// Largest file match_similar_renames considers when max_file_size_for_diff isn't set
+pub const SIMILAR_RENAME_MAX_BYTES: u64 = 1024 * 1024;
+
+/// A text's lines for line_similarity: each distinct line (with its line ending) as a hash
+/// and a count, sorted by hash
+struct LineProfile {
+    lines: Vec<(u64, usize)>,
+    bytes: u64,
+    mixed_line_endings: bool,
+}
+
+impl LineProfile {
+    fn new<'a>(lines: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        let (mut bytes, mut crlf, mut lf) = (0, false, false);
+        for line in lines {
+            bytes += line.len() as u64;
+            if line.ends_with(b"\r\n") {
+                crlf = true;
+            } else if line.ends_with(b"\n") {
+                lf = true;
+            }
+            let mut hasher = DefaultHasher::new();
+            line.hash(&mut hasher);
+            *counts.entry(hasher.finish()).or_default() += 1;
+        }
+        let mut lines: Vec<(u64, usize)> = counts.into_iter().collect();
+        lines.sort_unstable();
+        LineProfile {
+            lines,
+            bytes,
+            mixed_line_endings: crlf && lf,
+        }
+    }
+
+    /// Profile a file read a line at a time; None if it can't be read or isn't UTF-8
+    fn read(path: &Path) -> Option<Self> {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path).ok()?);
+        let mut lines = Vec::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line).ok()? == 0 {
+                break;
+            }
+            std::str::from_utf8(&line).ok()?;
+            lines.push(std::mem::take(&mut line));
+        }
+        Some(Self::new(lines.iter().map(Vec::as_slice)))
+    }
+
+    /// Lines in both (counting repeats) over lines in either; 1.0 for two empty texts
+    fn similarity(&self, other: &Self) -> f32 {
+        let (mut i, mut j, mut shared) = (0, 0, 0);
+        while i < self.lines.len() && j < other.lines.len() {
+            let ((left, left_count), (right, right_count)) = (self.lines[i], other.lines[j]);
+            match left.cmp(&right) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    shared += left_count.min(right_count);
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        let count = |profile: &Self| profile.lines.iter().map(|(_, n)| n).sum::<usize>();
+        let total = count(self) + count(other) - shared;
+        if total == 0 {
+            1.0
+        } else {
+            shared as f32 / total as f32
+        }
+    }
+}
+
+/// Share of lines two texts have in common: lines in both (counting repeats) over lines
+/// in either. 1.0 for two empty texts. Line endings count, so only equal texts score 1.0
+/// unless lines were reordered.
+#[cfg(test)]
+pub(crate) fn line_similarity(left: &str, right: &str) -> f32 {
+    let profile = |text: &str| LineProfile::new(text.split_inclusive('\n').map(str::as_bytes));
+    profile(left).similarity(&profile(right))
+}
+
+/// Hashes of every run of MIN_MOVED_BLOCK_LINES lines (whitespace-trimmed) that doesn't
+/// start on a blank line
+fn line_run_hashes(content: &str) -> HashSet<u64> {
+    let lines: Vec<&str> = content.lines().map(str::trim).collect();
+    lines
+        .windows(MIN_MOVED_BLOCK_LINES)
+        .filter(|run| !run[0].is_empty())
+        .map(|run| {
+            let mut hasher = DefaultHasher::new();
+            run.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Set `cross_file_move_hint` on changed files where a run of lines that disappeared
+/// from one file appears in another file where it's new
+fn annotate_cross_file_moves(entries: &mut [FileEntry]) {
+    let read = |path: &Option<String>| {
+        path.as_ref()
+            .and_then(|p| std::fs::read(p).ok())
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default()
+    };
+
+    // Runs only on the old side are removed, runs only on the new side are added
+    let mut removed: Vec<(usize, HashSet<u64>)> = Vec::new();
+    let mut added: Vec<(usize, HashSet<u64>)> = Vec::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        let changed = matches!(
+            entry.status,
+            FileStatus::Modified | FileStatus::Added | FileStatus::Deleted
+        );
+        if !changed || entry.skip_content {
+            continue;
+        }
+        let old = line_run_hashes(&read(&entry.left_path));
+        let new = line_run_hashes(&read(&entry.right_path));
+        removed.push((idx, old.difference(&new).copied().collect()));
+        added.push((idx, new.difference(&old).copied().collect()));
+    }
+
+    let mut moved_to: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut moved_from: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (from, removed_runs) in &removed {
+        for (to, added_runs) in &added {
+            if from != to && !removed_runs.is_disjoint(added_runs) {
+                moved_to.entry(*from).or_default().push(*to);
+                moved_from.entry(*to).or_default().push(*from);
+            }
+        }
+    }
+
+    let paths: Vec<String> = entries.iter().map(|e| e.path.clone()).collect();
+    let names = |idxs: &[usize]| {
+        idxs.iter()
+            .map(|&i| paths[i].as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        let mut hints = Vec::new();
+        if let Some(to) = moved_to.get(&idx) {
+            hints.push(format!("moved to {}", names(to)));
+        }
+        if let Some(from) = moved_from.get(&idx) {
+            hints.push(format!("moved from {}", names(from)));
+        }
+        if !hints.is_empty() {
+            entry.cross_file_move_hint = Some(hints.join("; "));
+        }
+    }
+}
+
+/// Compare explicit (left, right) file pairs. A side that doesn't exist makes the pair
+/// Added or Deleted; entries are keyed by the right path (left if the right is missing).
+pub fn compare_pairs(
+    pairs: &[(PathBuf, PathBuf)],
+    options: &CompareOptions,
+) -> Result<Vec<FileEntry>, DiffError> {
+    pairs
+        .iter()
+        .map(|(left, right)| {
+            let (left_exists, right_exists) = (left.is_file(), right.is_file());
+            let check = match (left_exists, right_exists) {
+                (true, true) => compare_contents(left, right, options)?,
+                (false, true) => check_one_side(right, options),
+                _ => check_one_side(left, options),
+            };
+            let status = match (left_exists, right_exists) {
+                (true, true) if check.differ => FileStatus::Modified,
+                (true, true) => FileStatus::Unchanged,
+                (false, true) => FileStatus::Added,
+                (true, false) => FileStatus::Deleted,
+                (false, false) => {
+                    return Err(DiffError::Path(format!(
+                        "Neither file exists: {} / {}",
+                        left.to_string_lossy(),
+                        right.to_string_lossy()
+                    )))
+                }
+            };
+
+            let shown = if right_exists { right } else { left };
+            Ok(FileEntry {
+                path: display_path(shown),
+                name: shown
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                status,
+                is_dir: false,
+                left_path: left_exists.then(|| display_path(left)),
+                right_path: right_exists.then(|| display_path(right)),
+                cross_file_move_hint: None,
+                mixed_line_endings: check.mixed_line_endings,
+                rename_info: None,
+                skip_content: check.skip_content,
+                truncated_continuation: check.truncated_continuation,
+                is_binary: check.binary,
+            })
+        })
+        .collect()
+}
+
+/// Parse `left\0right\0left\0right[\0]` (as produced by `find -print0` and friends)
+/// into path pairs. Paths are taken verbatim, so spaces and newlines survive.
+pub fn parse_nul_pairs(input: &str) -> Result<Vec<(PathBuf, PathBuf)>, DiffError> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fields: Vec<&str> = input
+        .strip_suffix('\0')
+        .unwrap_or(input)
+        .split('\0')
+        .collect();
+    if !fields.len().is_multiple_of(2) {
+        return Err(DiffError::Path(format!(
+            "Expected left/right path pairs, got {} paths",
+            fields.len()
+        )));
+    }
+    if fields.iter().any(|f| f.is_empty()) {
+        return Err(DiffError::Path(
+            "Empty path in NUL-separated input".to_string(),
+        ));
+    }
+
+    Ok(fields
+        .chunks_exact(2)
+        .map(|pair| (PathBuf::from(pair[0]), PathBuf::from(pair[1])))
+        .collect())
+}
+
+/// Entries for the file-level lines of recursive diff output (`diff -r`, `diff -rq`) between
+/// `left_dir` and `right_dir`, so they're listed rather than shown as text. "Only in <dir>:
+/// <name>" is Deleted or Added by which root `<dir>` is under (a directory stands for every
+/// file in it), while "Files <left> and <right> differ" (or "Binary files ...") and the
+/// "diff -r <left> <right>" line before a file's hunks are Modified. Hunks are skipped.
+pub fn parse_recursive_diff(output: &str, left_dir: &Path, right_dir: &Path) -> Vec<FileEntry> {
+    let mut entries = Vec::new();
+    let entry = |relative: &Path, status: FileStatus, is_binary: bool| FileEntry {
+        path: relative.to_string_lossy().to_string(),
+        name: relative
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        left_path: (status != FileStatus::Added).then(|| display_path(&left_dir.join(relative))),
+        right_path: (status != FileStatus::Deleted)
+            .then(|| display_path(&right_dir.join(relative))),
+        status,
+        is_dir: false,
+        cross_file_move_hint: None,
+        mixed_line_endings: false,
+        rename_info: None,
+        skip_content: false,
+        truncated_continuation: false,
+        is_binary,
+    };
+
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("Only in ") {
+            // Either part may contain ": ", so try each split until the directory is under a root
+            let found = rest.match_indices(": ").find_map(|(at, _)| {
+                let (dir, name) = (Path::new(&rest[..at]), &rest[at + 2..]);
+                let side = |root: &Path| dir.strip_prefix(root).ok().map(|d| d.join(name));
+                match (side(left_dir), side(right_dir)) {
+                    // With one root inside the other, the deeper one is meant
+                    (Some(_), Some(r)) if right_dir.starts_with(left_dir) => {
+                        Some((r, FileStatus::Added))
+                    }
+                    (Some(l), _) => Some((l, FileStatus::Deleted)),
+                    (None, Some(r)) => Some((r, FileStatus::Added)),
+                    (None, None) => None,
+                }
+            });
+            let Some((relative, status)) = found else {
+                continue;
+            };
+            let root = if status == FileStatus::Added {
+                right_dir
+            } else {
+                left_dir
+            };
+            let full = root.join(&relative);
+            if full.is_dir() {
+                let files = WalkDir::new(&full)
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|e| e.file_type().is_file());
+                for file in files {
+                    if let Ok(inner) = file.path().strip_prefix(root) {
+                        entries.push(entry(inner, status.clone(), false));
+                    }
+                }
+            } else {
+                entries.push(entry(&relative, status, false));
+            }
+        } else {
+            // The same file under both roots, split at whichever `separator` makes it so
+            let same_file = |pair: &str, separator: &str| {
+                pair.match_indices(separator).find_map(|(at, _)| {
+                    let left = Path::new(&pair[..at]).strip_prefix(left_dir).ok()?;
+                    let right = Path::new(&pair[at + separator.len()..])
+                        .strip_prefix(right_dir)
+                        .ok()?;
+                    (left == right).then(|| left.to_path_buf())
+                })
+            };
+            let binary = line.starts_with("Binary files ");
+            let differ = line
+                .strip_prefix("Files ")
+                .or_else(|| line.strip_prefix("Binary files "))
+                .and_then(|rest| rest.strip_suffix(" differ"))
+                .and_then(|pair| same_file(pair, " and "));
+            // "diff -r <left> <right>" heads a modified file's hunks without -q
+            let heading = line.strip_prefix("diff ").and_then(|rest| {
+                let mut pair = rest;
+                while let Some(flag_end) = pair.strip_prefix('-').and_then(|_| pair.find(' ')) {
+                    pair = &pair[flag_end + 1..];
+                }
+                same_file(pair, " ")
+            });
+            if let Some(relative) = differ.or(heading) {
+                entries.push(entry(&relative, FileStatus::Modified, binary));
+            }
+        }
+    }
+
     entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
 
-    Ok(entries)
+type ScannedFiles = Vec<(PathBuf, walkdir::DirEntry)>;
+
+/// Every file under `dir` with its path relative to `dir`, minus the ignored ones.
+/// Only names and metadata are touched, never contents.
+pub(crate) fn scan_dir(
+    dir: &Path,
+    ignore: Option<&GlobSet>,
+    excluded: &HashSet<String>,
+    cancel: &AtomicBool,
+) -> Result<ScannedFiles, DiffError> {
+    let mut files = Vec::new();
+
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        check_cancelled(cancel)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(dir)
+            .map_err(|e| DiffError::Path(e.to_string()))?
+            .to_path_buf();
+        if is_skipped(&relative, ignore, excluded) {
+            continue;
+        }
+        files.push((relative, entry));
+    }
+
+    Ok(files)
+}
+
+/// Err(Cancelled) once the flag is set
+pub(crate) fn check_cancelled(cancel: &AtomicBool) -> Result<(), DiffError> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err(DiffError::Cancelled);
+    }
+    Ok(())
+}
+
+/// Scan both sides against the same compiled ignore rules, optionally on two threads
+fn scan_both(
+    left_dir: &Path,
+    right_dir: &Path,
+    ignore: Option<Arc<GlobSet>>,
+    excluded: &HashSet<String>,
+    parallel: bool,
+    cancel: &AtomicBool,
+) -> Result<(ScannedFiles, ScannedFiles), DiffError> {
+    if !parallel {
+        return Ok((
+            scan_dir(left_dir, ignore.as_deref(), excluded, cancel)?,
+            scan_dir(right_dir, ignore.as_deref(), excluded, cancel)?,
+        ));
+    }
+
+    std::thread::scope(|scope| {
+        let walk = |dir: &Path| {
+            let ignore = ignore.clone();
+            let dir = dir.to_path_buf();
+            scope.spawn(move || scan_dir(&dir, ignore.as_deref(), excluded, cancel))
+        };
+        let left = walk(left_dir);
+        let right = walk(right_dir);
+        let join = |handle: std::thread::ScopedJoinHandle<'_, Result<ScannedFiles, DiffError>>| {
+            handle
+                .join()
+                .unwrap_or_else(|_| Err(DiffError::Path("directory walk panicked".to_string())))
+        };
+        Ok((join(left)?, join(right)?))
+    })
+}
+
+/// Rough cost of a comparison, from a name+size scan
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CostEstimate {
+    /// Distinct relative paths across both sides
+    pub file_count: usize,
+    /// Bytes that may need reading, both sides together
+    pub total_bytes: u64,
+    pub estimated_ms: u64,
+}
+
+/// Per-file overhead (stat, open, tree node) in microseconds
+const ESTIMATE_US_PER_FILE: u64 = 50;
+/// Content comparison throughput in bytes per millisecond (~200 MB/s)
+const ESTIMATE_BYTES_PER_MS: u64 = 200_000;
+
+/// Estimate how long compare_directories will take without reading any file contents
+pub fn estimate_comparison(
+    left_dir: &Path,
+    right_dir: &Path,
+    options: &CompareOptions,
+) -> Result<CostEstimate, DiffError> {
+    let left_dir = &normalize_path(left_dir);
+    let right_dir = &normalize_path(right_dir);
+    let excluded = excluded_paths(options);
+    let not_cancelled = AtomicBool::new(false);
+    let (left_scan, right_scan) = scan_both(
+        left_dir,
+        right_dir,
+        compile_ignore(options)?,
+        &excluded,
+        options.parallel_walk,
+        &not_cancelled,
+    )?;
+
+    let mut paths = HashSet::new();
+    let mut total_bytes = 0;
+    for (relative, entry) in left_scan.into_iter().chain(right_scan) {
+        total_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        paths.insert(relative);
+    }
+
+    let file_count = paths.len();
+    Ok(CostEstimate {
+        file_count,
+        total_bytes,
+        estimated_ms: file_count as u64 * ESTIMATE_US_PER_FILE / 1000
+            + total_bytes / ESTIMATE_BYTES_PER_MS,
+    })
+}
+
+/// What a comparison looks like from its directory layout, see classify_comparison
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ComparisonKind {
+    /// Most paths in common and most common files untouched: one project at two versions
+    SameProjectNewVersion,
+    /// A fair share of paths in common, but too much moved or rewritten to be sure
+    PartialOverlap,
+    /// Hardly any paths in common
+    UnrelatedTrees,
+    /// No files on either side, so nothing to go on
+    Empty,
+}
+
+/// Share of all paths that must exist on both sides for SameProjectNewVersion
+const SAME_PROJECT_SHARED: f64 = 0.5;
+/// Share of the common paths that must look unchanged for SameProjectNewVersion
+const SAME_PROJECT_UNCHANGED: f64 = 0.5;
+/// Share of all paths in common below which the trees are UnrelatedTrees
+const UNRELATED_SHARED: f64 = 0.1;
+
+/// Guess how two directories relate from a name+size scan, without reading any
+/// contents: common files of the same size count as unchanged
+pub fn classify_comparison(
+    left_dir: &Path,
+    right_dir: &Path,
+    options: &CompareOptions,
+) -> Result<ComparisonKind, DiffError> {
+    let left_dir = &normalize_path(left_dir);
+    let right_dir = &normalize_path(right_dir);
+    let excluded = excluded_paths(options);
+    let not_cancelled = AtomicBool::new(false);
+    let (left_scan, right_scan) = scan_both(
+        left_dir,
+        right_dir,
+        compile_ignore(options)?,
+        &excluded,
+        options.parallel_walk,
+        &not_cancelled,
+    )?;
+
+    let size = |entry: &walkdir::DirEntry| entry.metadata().map(|m| m.len()).ok();
+    let left_sizes: HashMap<PathBuf, Option<u64>> = left_scan
+        .iter()
+        .map(|(relative, entry)| (relative.clone(), size(entry)))
+        .collect();
+    let (mut shared, mut unchanged) = (0usize, 0usize);
+    for (relative, entry) in &right_scan {
+        if let Some(left_size) = left_sizes.get(relative) {
+            shared += 1;
+            if left_size.is_some() && *left_size == size(entry) {
+                unchanged += 1;
+            }
+        }
+    }
+
+    let total = left_scan.len() + right_scan.len() - shared;
+    if total == 0 {
+        return Ok(ComparisonKind::Empty);
+    }
+    let shared_share = shared as f64 / total as f64;
+    let unchanged_share = if shared == 0 {
+        0.0
+    } else {
+        unchanged as f64 / shared as f64
+    };
+    Ok(
+        if shared_share >= SAME_PROJECT_SHARED && unchanged_share >= SAME_PROJECT_UNCHANGED {
+            ComparisonKind::SameProjectNewVersion
+        } else if shared_share < UNRELATED_SHARED {
+            ComparisonKind::UnrelatedTrees
+        } else {
+            ComparisonKind::PartialOverlap
+        },
+    )
+}
+
+/// Compile ignore_patterns and (with skip_known_generated) the generated-file globs into
+/// one immutable set shared by both walkers, or None when nothing is ignored
+pub(crate) fn compile_ignore(options: &CompareOptions) -> Result<Option<Arc<GlobSet>>, DiffError> {
+    let mut patterns: Vec<&str> = options.ignore_patterns.iter().map(String::as_str).collect();
+    if options.skip_known_generated {
+        patterns.extend(generated_patterns(options));
+    }
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(Arc::new(build_globset(&patterns)?)))
+}
+
+/// generated_patterns if set, otherwise DEFAULT_GENERATED_PATTERNS
+fn generated_patterns(options: &CompareOptions) -> Vec<&str> {
+    match &options.generated_patterns {
+        Some(generated) => generated.iter().map(String::as_str).collect(),
+        None => DEFAULT_GENERATED_PATTERNS.to_vec(),
+    }
+}
+
+fn build_globset(patterns: &[&str]) -> Result<GlobSet, DiffError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Match on the bare file name (so "Cargo.lock" hits nested lockfiles) or the full path
+/// exclude_paths in the form normalize_relative gives scanned paths
+pub(crate) fn excluded_paths(options: &CompareOptions) -> HashSet<String> {
+    options
+        .exclude_paths
+        .iter()
+        .map(|p| normalize_relative(p))
+        .collect()
+}
+
+/// A relative path with `/` separators and no leading `./`, empty or trailing components
+fn normalize_relative(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Left out of a comparison by the compiled ignore globs or exclude_paths
+pub(crate) fn is_skipped(
+    relative: &Path,
+    ignore: Option<&GlobSet>,
+    excluded: &HashSet<String>,
+) -> bool {
+    ignore.is_some_and(|set| is_ignored(set, relative))
+        || (!excluded.is_empty()
+            && excluded.contains(&normalize_relative(&relative.to_string_lossy())))
+}
+
+fn is_ignored(set: &GlobSet, relative: &Path) -> bool {
+    let by_name = relative.file_name().is_some_and(|name| set.is_match(name));
+    by_name || set.is_match(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// What one read of a file pair tells us
+struct ContentCheck {
+    differ: bool,
+    mixed_line_endings: bool,
+    /// Compared by size and bytes only, see max_file_size_for_diff
+    skip_content: bool,
+    /// Different, but one is a byte prefix of the other (treat_truncation_as_equal)
+    truncated_continuation: bool,
+    /// Either side looks binary; with skip_content only sniffed when the files differ
+    binary: bool,
+}
+
+/// Larger than max_file_size_for_diff (unreadable metadata counts as not larger)
+fn exceeds_diff_size(path: &Path, options: &CompareOptions) -> bool {
+    options
+        .max_file_size_for_diff
+        .is_some_and(|max| std::fs::metadata(path).is_ok_and(|m| m.len() > max))
+}
+
+/// Bytes sampled from the start of a file by is_binary
+const BINARY_SAMPLE_BYTES: usize = 8 * 1024;
+
+/// Whether a file looks binary: its first BINARY_SAMPLE_BYTES hold a NUL byte or aren't
+/// valid UTF-8. Files with a UTF-16 BOM count as text. False when the file can't be read.
+pub fn is_binary(path: &Path) -> bool {
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut sample = Vec::with_capacity(BINARY_SAMPLE_BYTES);
+    if file
+        .take(BINARY_SAMPLE_BYTES as u64)
+        .read_to_end(&mut sample)
+        .is_err()
+    {
+        return false;
+    }
+    looks_binary(&sample)
+}
+
+/// is_binary for content already in memory; only the first BINARY_SAMPLE_BYTES count
+pub(crate) fn looks_binary(content: &[u8]) -> bool {
+    let sample = &content[..content.len().min(BINARY_SAMPLE_BYTES)];
+    if sample.starts_with(&[0xFF, 0xFE]) || sample.starts_with(&[0xFE, 0xFF]) {
+        return false;
+    }
+    // A character cut off at the end of the sample is still text
+    sample.contains(&0) || std::str::from_utf8(sample).is_err_and(|e| e.error_len().is_some())
+}
+
+/// Byte comparison of two files, stopping at a size mismatch or the first difference,
+/// without holding either file on the heap. With the `mmap` feature the files are mapped
+/// and compared directly, streaming only where mapping fails.
+pub(crate) fn files_differ(left: &Path, right: &Path) -> Result<bool, DiffError> {
+    let len = std::fs::metadata(left)?.len();
+    if len != std::fs::metadata(right)?.len() {
+        return Ok(true);
+    }
+    #[cfg(feature = "mmap")]
+    if let Some(differ) = mapped_differ(left, right, len) {
+        return Ok(differ);
+    }
+    streamed_differ(left, right)
+}
+
+/// Compare two files of `len` bytes through read-only memory maps. None when either can't
+/// be mapped (pipes, some network filesystems) or its size changed around the mapping.
+#[cfg(feature = "mmap")]
+pub(crate) fn mapped_differ(left: &Path, right: &Path, len: u64) -> Option<bool> {
+    let (left, right) = (
+        std::fs::File::open(left).ok()?,
+        std::fs::File::open(right).ok()?,
+    );
+    // SAFETY: the maps are only read, and only while both files still have the size they
+    // were compared at; a file truncated by another process during the comparison can still
+    // fault, as with any mapping of a file we don't own.
+    let left_map = unsafe { memmap2::Mmap::map(&left) }.ok()?;
+    let right_map = unsafe { memmap2::Mmap::map(&right) }.ok()?;
+    let unchanged = |file: &std::fs::File, map: &memmap2::Mmap| {
+        map.len() as u64 == len && file.metadata().is_ok_and(|m| m.len() == len)
+    };
+    if !unchanged(&left, &left_map) || !unchanged(&right, &right_map) {
+        return None;
+    }
+    Some(left_map[..] != right_map[..])
+}
+
+/// files_differ by reading both files in blocks
+fn streamed_differ(left: &Path, right: &Path) -> Result<bool, DiffError> {
+    let (mut left, mut right) = (std::fs::File::open(left)?, std::fs::File::open(right)?);
+    let (mut left_buf, mut right_buf) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    loop {
+        let read = left.read(&mut left_buf)?;
+        if read == 0 {
+            // Same length, so the right side is exhausted too unless it grew meanwhile
+            return Ok(right.read(&mut right_buf[..1])? != 0);
+        }
+        right.read_exact(&mut right_buf[..read])?;
+        if left_buf[..read] != right_buf[..read] {
+            return Ok(true);
+        }
+    }
+}
+
+/// What compare_contents tells about a file that exists on one side only (added or
+/// deleted), which differs by definition. Unreadable files count as text.
+fn check_one_side(path: &Path, options: &CompareOptions) -> ContentCheck {
+    let skip_content = exceeds_diff_size(path, options);
+    let content = if skip_content {
+        None
+    } else {
+        std::fs::read(path).ok()
+    };
+    ContentCheck {
+        differ: true,
+        mixed_line_endings: content.as_deref().is_some_and(has_mixed_line_endings),
+        skip_content,
+        truncated_continuation: false,
+        binary: match &content {
+            Some(content) => looks_binary(content),
+            None => is_binary(path),
+        },
+    }
 }
 
-fn files_differ(left: &Path, right: &Path) -> Result<bool, DiffError> {
-    let left_content = std::fs::read(left)?;
-    let right_content = std::fs::read(right)?;
-    Ok(left_content != right_content)
+/// Whether two files differ once the options' ignore and normalization rules apply
+pub(crate) fn contents_differ(
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+) -> Result<bool, DiffError> {
+    Ok(compare_contents(left, right, options)?.differ)
 }
 
+fn compare_contents(
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+) -> Result<ContentCheck, DiffError> {
+    if exceeds_diff_size(left, options) || exceeds_diff_size(right, options) {
+        let differ = files_differ(left, right)?;
+        return Ok(ContentCheck {
+            differ,
+            mixed_line_endings: false,
+            skip_content: true,
+            truncated_continuation: false,
+            binary: differ && (is_binary(left) || is_binary(right)),
+        });
+    }
+
+    let mut left_content = std::fs::read(left)?;
+    let mut right_content = std::fs::read(right)?;
+    let mixed_line_endings =
+        has_mixed_line_endings(&left_content) || has_mixed_line_endings(&right_content);
+    let binary = looks_binary(&left_content) || looks_binary(&right_content);
+    let (left_text, right_text) = (
+        std::str::from_utf8(&left_content),
+        std::str::from_utf8(&right_content),
+    );
+    if let (Ok(left_text), Ok(right_text)) = (left_text, right_text) {
+        if let Some((l, r)) = normalize_pair(
+            &options.active_normalizers(),
+            left,
+            right,
+            left_text,
+            right_text,
+        ) {
+            (left_content, right_content) = (l.into_bytes(), r.into_bytes());
+        }
+    }
+
+    let by_lines = options.ignore_whitespace || options.ignore_blank_lines;
+    let differ = match (
+        std::str::from_utf8(&left_content),
+        std::str::from_utf8(&right_content),
+    ) {
+        (Ok(left_text), Ok(right_text)) if by_lines => {
+            significant_lines(left_text, options) != significant_lines(right_text, options)
+        }
+        _ if options.ignore_trailing_newline => {
+            strip_trailing_newline(&left_content) != strip_trailing_newline(&right_content)
+        }
+        _ => left_content != right_content,
+    };
+
+    Ok(ContentCheck {
+        differ,
+        mixed_line_endings,
+        binary,
+        skip_content: false,
+        truncated_continuation: differ
+            && options.treat_truncation_as_equal
+            && (left_content.starts_with(&right_content)
+                || right_content.starts_with(&left_content)),
+    })
+}
+
+/// Hash of what compare_contents compares for `path`, so files it calls equal get the
+/// same key. Not valid with any active normalizers, whose rules may depend on the other
+/// file's name.
+fn content_key(path: &Path, options: &CompareOptions) -> Result<u64, DiffError> {
+    let mut hasher = DefaultHasher::new();
+    if exceeds_diff_size(path, options) {
+        let mut file = std::fs::File::open(path)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                return Ok(hasher.finish());
+            }
+            hasher.write(&buf[..read]);
+        }
+    }
+
+    let content = std::fs::read(path)?;
+    match std::str::from_utf8(&content) {
+        Ok(text) if options.ignore_whitespace || options.ignore_blank_lines => {
+            significant_lines(text, options).hash(&mut hasher)
+        }
+        _ if options.ignore_trailing_newline => hasher.write(strip_trailing_newline(&content)),
+        _ => hasher.write(&content),
+    }
+    Ok(hasher.finish())
+}
+
+/// The lines compared under ignore_whitespace and ignore_blank_lines
+fn significant_lines(text: &str, options: &CompareOptions) -> Vec<String> {
+    text.lines()
+        .map(|line| {
+            if options.ignore_whitespace {
+                line.chars().filter(|c| !c.is_whitespace()).collect()
+            } else {
+                line.to_string()
+            }
+        })
+        .filter(|line| !(options.ignore_blank_lines && line.trim().is_empty()))
+        .collect()
+}
+
+/// True when the content has both `\r\n` and bare `\n` line endings
+pub fn has_mixed_line_endings(content: &[u8]) -> bool {
+    let mut crlf = false;
+    let mut lf = false;
+    for (i, _) in content.iter().enumerate().filter(|(_, &b)| b == b'\n') {
+        if i > 0 && content[i - 1] == b'\r' {
+            crlf = true;
+        } else {
+            lf = true;
+        }
+        if crlf && lf {
+            return true;
+        }
+    }
+    false
+}
+
+/// Drop a single trailing "\n" or "\r\n"
+fn strip_trailing_newline(content: &[u8]) -> &[u8] {
+    content
+        .strip_suffix(b"\r\n")
+        .or_else(|| content.strip_suffix(b"\n"))
+        .unwrap_or(content)
+}
+
+/// Split a renamed entry's "old → new" path into its two halves
+fn split_rename_path(path: &str) -> Option<(&str, &str)> {
+    path.split_once(" → ")
+}
+
+/// Parent directory of a relative path ("" for top-level files)
+fn parent_dir(path: &str) -> &str {
+    path.rfind('/').map(|pos| &path[..pos]).unwrap_or("")
+}
+
+#[cfg(test)]
 pub fn build_file_tree(entries: &[FileEntry]) -> Vec<FileTreeNode> {
+    build_file_tree_with_options(entries, &CompareOptions::default())
+}
+
+pub fn build_file_tree_with_options(
+    entries: &[FileEntry],
+    options: &CompareOptions,
+) -> Vec<FileTreeNode> {
     let mut root_children: Vec<FileTreeNode> = Vec::new();
 
+    let collapsed = if options.collapse_renames {
+        collapse_rename_groups(&mut root_children, entries)
+    } else {
+        HashSet::new()
+    };
+
     for entry in entries {
         // Skip unchanged files
         if entry.status == FileStatus::Unchanged {
             continue;
         }
 
+        // Already placed under a "Moved N files" summary node
+        if collapsed.contains(entry.path.as_str()) {
+            continue;
+        }
+
         // For renamed files, use the NEW path (after →) for tree placement
         let tree_path = if entry.status == FileStatus::Renamed {
-            if let Some(arrow_pos) = entry.path.find(" → ") {
-                &entry.path[arrow_pos + " → ".len()..]
-            } else {
-                &entry.path
-            }
+            split_rename_path(&entry.path)
+                .map(|(_, new)| new)
+                .unwrap_or(&entry.path)
         } else {
             &entry.path
         };
@@ -229,10 +1791,119 @@ pub fn build_file_tree(entries: &[FileEntry]) -> Vec<FileTreeNode> {
 
     // Sort children recursively
     sort_tree(&mut root_children);
+    roll_up_status(&mut root_children);
 
     root_children
 }
 
+/// Insert one summary node per large group of files renamed between the same two
+/// directories, returning the entry paths that were placed under a summary node
+fn collapse_rename_groups<'a>(
+    nodes: &mut Vec<FileTreeNode>,
+    entries: &'a [FileEntry],
+) -> HashSet<&'a str> {
+    let mut groups: HashMap<(&str, &str), Vec<&FileEntry>> = HashMap::new();
+
+    for entry in entries.iter().filter(|e| e.status == FileStatus::Renamed) {
+        if let Some((old, new)) = split_rename_path(&entry.path) {
+            let (old_dir, new_dir) = (parent_dir(old), parent_dir(new));
+            if old_dir != new_dir {
+                groups.entry((old_dir, new_dir)).or_default().push(entry);
+            }
+        }
+    }
+
+    let mut collapsed = HashSet::new();
+
+    for ((old_dir, new_dir), members) in groups {
+        if members.len() < RENAME_GROUP_MIN_FILES {
+            continue;
+        }
+
+        let display_dir = |dir: &str| {
+            if dir.is_empty() {
+                "./".to_string()
+            } else {
+                format!("{}/", dir)
+            }
+        };
+
+        let children = members
+            .iter()
+            .map(|entry| FileTreeNode {
+                name: entry.name.clone(),
+                path: entry.path.clone(),
+                status: Some(FileStatus::Renamed),
+                is_dir: false,
+                children: Vec::new(),
+                left_path: entry.left_path.clone(),
+                right_path: entry.right_path.clone(),
+                added: 0,
+                deleted: 0,
+                modified: 0,
+            })
+            .collect();
+
+        let summary = FileTreeNode {
+            name: format!(
+                "Moved {} files: {} → {}",
+                members.len(),
+                display_dir(old_dir),
+                display_dir(new_dir)
+            ),
+            path: format!("{} → {}", old_dir, new_dir),
+            status: Some(FileStatus::Renamed),
+            is_dir: true,
+            children,
+            left_path: None,
+            right_path: None,
+            added: 0,
+            deleted: 0,
+            modified: 0,
+        };
+
+        // Place the summary where the new directory itself would appear
+        let parent_parts: Vec<&str> = parent_dir(new_dir)
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .collect();
+        insert_node_at(nodes, &parent_parts, summary);
+
+        collapsed.extend(members.iter().map(|e| e.path.as_str()));
+    }
+
+    collapsed
+}
+
+/// Insert a prebuilt node under the directory given by `dir_parts`, creating directories as needed
+fn insert_node_at(nodes: &mut Vec<FileTreeNode>, dir_parts: &[&str], node: FileTreeNode) {
+    let Some((first, rest)) = dir_parts.split_first() else {
+        nodes.push(node);
+        return;
+    };
+
+    let idx = match nodes.iter().position(|n| n.is_dir && n.name == *first) {
+        Some(idx) => idx,
+        None => {
+            nodes.push(FileTreeNode {
+                name: first.to_string(),
+                path: first.to_string(),
+                status: None,
+                is_dir: true,
+                children: Vec::new(),
+                left_path: None,
+                right_path: None,
+                added: 0,
+                deleted: 0,
+                modified: 0,
+            });
+            nodes.len() - 1
+        }
+    };
+
+    insert_node_at(&mut nodes[idx].children, rest, node);
+}
+
 fn insert_into_tree(nodes: &mut Vec<FileTreeNode>, parts: &[&str], entry: &FileEntry) {
     if parts.is_empty() {
         return;
@@ -258,6 +1929,9 @@ fn insert_into_tree(nodes: &mut Vec<FileTreeNode>, parts: &[&str], entry: &FileE
                 children: Vec::new(),
                 left_path: entry.left_path.clone(),
                 right_path: entry.right_path.clone(),
+                added: 0,
+                deleted: 0,
+                modified: 0,
             }
         } else {
             // Build path for directory
@@ -270,6 +1944,9 @@ fn insert_into_tree(nodes: &mut Vec<FileTreeNode>, parts: &[&str], entry: &FileE
                 children: Vec::new(),
                 left_path: None,
                 right_path: None,
+                added: 0,
+                deleted: 0,
+                modified: 0,
             }
         };
 
@@ -281,17 +1958,313 @@ fn insert_into_tree(nodes: &mut Vec<FileTreeNode>, parts: &[&str], entry: &FileE
     }
 }
 
+/// Fill in each directory's counts and, unless it has one already, its status: Added or
+/// Deleted when every file below it was, else Modified. Returns the (added, deleted,
+/// modified) totals of `nodes`.
+fn roll_up_status(nodes: &mut [FileTreeNode]) -> (usize, usize, usize) {
+    let mut totals = (0, 0, 0);
+    for node in nodes.iter_mut() {
+        let counts = if node.is_dir {
+            let counts = roll_up_status(&mut node.children);
+            (node.added, node.deleted, node.modified) = counts;
+            if node.status.is_none() {
+                node.status = match counts {
+                    (0, 0, 0) => None,
+                    (_, 0, 0) => Some(FileStatus::Added),
+                    (0, _, 0) => Some(FileStatus::Deleted),
+                    _ => Some(FileStatus::Modified),
+                };
+            }
+            counts
+        } else {
+            match node.status {
+                Some(FileStatus::Added | FileStatus::Copied { .. }) => (1, 0, 0),
+                Some(FileStatus::Deleted) => (0, 1, 0),
+                Some(FileStatus::Modified | FileStatus::Renamed) => (0, 0, 1),
+                Some(FileStatus::Unchanged) | None => (0, 0, 0),
+            }
+        };
+        totals = (
+            totals.0 + counts.0,
+            totals.1 + counts.1,
+            totals.2 + counts.2,
+        );
+    }
+    totals
+}
+
 fn sort_tree(nodes: &mut [FileTreeNode]) {
     // Directories first, then alphabetically
-    nodes.sort_by(|a, b| {
-        match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-        }
+    nodes.sort_by(|a, b| match (a.is_dir, b.is_dir) {
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
     });
 
     for node in nodes.iter_mut() {
         sort_tree(&mut node.children);
     }
 }
+
+/// Broad kind of file, weighting how much a change to it needs review
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileCategory {
+    Source,
+    Config,
+    Generated,
+}
+
+/// Extensions of data/config files that rank below source code
+const CONFIG_EXTENSIONS: &[&str] = &[
+    "json",
+    "toml",
+    "yaml",
+    "yml",
+    "ini",
+    "cfg",
+    "conf",
+    "xml",
+    "env",
+    "properties",
+    "lock",
+];
+
+/// Generated files match generated_patterns (or the defaults); config files go by extension
+pub fn file_category(entry: &FileEntry, generated: &GlobSet) -> FileCategory {
+    let new_rel = entry
+        .path
+        .split_once(" → ")
+        .map_or(entry.path.as_str(), |(_, new)| new);
+    if is_ignored(generated, Path::new(new_rel)) {
+        return FileCategory::Generated;
+    }
+
+    let ext = Path::new(&entry.name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if CONFIG_EXTENSIONS.contains(&ext.as_str()) {
+        FileCategory::Config
+    } else {
+        FileCategory::Source
+    }
+}
+
+/// Rank changed files for review by churn, path depth and category, highest first.
+/// Scores are normalized so the top file gets 1.0.
+pub fn review_priorities<F>(
+    entries: &[FileEntry],
+    options: &CompareOptions,
+    mut line_stats: F,
+) -> Result<Vec<(String, f32)>, DiffError>
+where
+    F: FnMut(&FileEntry) -> (usize, usize),
+{
+    let generated = build_globset(&generated_patterns(options))?;
+
+    let mut scored: Vec<(String, f32)> = entries
+        .iter()
+        .filter(|e| e.status != FileStatus::Unchanged)
+        .map(|entry| {
+            let (added, removed) = line_stats(entry);
+            let churn = 1.0 + ((added + removed) as f32).ln_1p();
+            let weight = match file_category(entry, &generated) {
+                FileCategory::Source => 1.0,
+                FileCategory::Config => 0.5,
+                FileCategory::Generated => 0.05,
+            };
+            // Deeply nested files tend to be leaf details; top-level ones affect more
+            let depth = entry.path.matches('/').count() as f32;
+            (entry.path.clone(), churn * weight / (1.0 + 0.1 * depth))
+        })
+        .collect();
+
+    let max = scored.iter().map(|(_, score)| *score).fold(0.0, f32::max);
+    if max > 0.0 {
+        for (_, score) in &mut scored {
+            *score /= max;
+        }
+    }
+    scored.sort_by(|(a_path, a), (b_path, b)| b.total_cmp(a).then_with(|| a_path.cmp(b_path)));
+
+    Ok(scored)
+}
+
+/// Added/removed line counts of each changed entry, in entry order. `line_stats` gets the
+/// entry and the moment to give up at, and returns None if it had to; that file is flagged
+/// `timed_out` so one pathological file can't stall the batch.
+pub fn diff_stats<F>(entries: &[FileEntry], timeout: Duration, mut line_stats: F) -> DiffStatReport
+where
+    F: FnMut(&FileEntry, Instant) -> Option<(usize, usize)>,
+{
+    let mut report = DiffStatReport::default();
+
+    for entry in entries.iter().filter(|e| e.status != FileStatus::Unchanged) {
+        let counted = line_stats(entry, Instant::now() + timeout);
+        let (added, removed) = counted.unwrap_or((0, 0));
+        report.added += added;
+        report.removed += removed;
+        report.timed_out += usize::from(counted.is_none());
+        report.files.push(FileDiffStat {
+            path: entry.path.clone(),
+            status: entry.status.clone(),
+            added,
+            removed,
+            timed_out: counted.is_none(),
+        });
+    }
+
+    report
+}
+
+/// Per-directory change totals for a heatmap, in path order. Renamed files count in
+/// their new directory. `line_stats` returns the (added, removed) line counts for an entry.
+pub fn change_heatmap<F>(entries: &[FileEntry], mut line_stats: F) -> Vec<DirHeat>
+where
+    F: FnMut(&FileEntry) -> (usize, usize),
+{
+    let mut dirs: BTreeMap<String, DirHeat> = BTreeMap::new();
+
+    for entry in entries.iter().filter(|e| e.status != FileStatus::Unchanged) {
+        let path = match &entry.rename_info {
+            Some(rename) => rename.to.replace('\\', "/"),
+            None => entry.path.replace('\\', "/"),
+        };
+        let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let (added, removed) = line_stats(entry);
+
+        let own = dirs.entry(dir.to_string()).or_default();
+        own.own_changes += 1;
+        own.own_additions += added;
+        own.own_deletions += removed;
+
+        // The directory itself and each ancestor up to the root
+        let mut ancestor = Some(dir);
+        while let Some(current) = ancestor {
+            let heat = dirs.entry(current.to_string()).or_default();
+            heat.changes += 1;
+            heat.additions += added;
+            heat.deletions += removed;
+            ancestor = match current.rsplit_once('/') {
+                Some((parent, _)) => Some(parent),
+                None if !current.is_empty() => Some(""),
+                None => None,
+            };
+        }
+    }
+
+    dirs.into_iter()
+        .map(|(path, heat)| DirHeat { path, ..heat })
+        .collect()
+}
+
+/// An entry's extension as ".rs", or "(none)"
+fn extension_label(entry: &FileEntry) -> String {
+    // entry.name is already the new name for renamed files
+    Path::new(&entry.name)
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+/// Distinct extensions of the changed entries, lowercased and sorted (see extension_label)
+pub fn changed_extensions(entries: &[FileEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|e| e.status != FileStatus::Unchanged)
+        .map(|e| extension_label(e).to_lowercase())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Group changed entries by file extension (".rs", or "(none)"), most-changed first.
+/// `line_stats` returns the (added, removed) line counts for an entry.
+pub fn extension_breakdown<F>(entries: &[FileEntry], mut line_stats: F) -> Vec<(String, ExtStats)>
+where
+    F: FnMut(&FileEntry) -> (usize, usize),
+{
+    let mut groups: HashMap<String, ExtStats> = HashMap::new();
+
+    for entry in entries {
+        if entry.status == FileStatus::Unchanged {
+            continue;
+        }
+
+        let stats = groups.entry(extension_label(entry)).or_default();
+        match entry.status {
+            FileStatus::Added => stats.added += 1,
+            FileStatus::Deleted => stats.deleted += 1,
+            FileStatus::Modified => stats.modified += 1,
+            FileStatus::Renamed => stats.renamed += 1,
+            FileStatus::Copied { .. } => stats.copied += 1,
+            FileStatus::Unchanged => {}
+        }
+
+        let (lines_added, lines_removed) = line_stats(entry);
+        stats.lines_added += lines_added;
+        stats.lines_removed += lines_removed;
+    }
+
+    let mut breakdown: Vec<(String, ExtStats)> = groups.into_iter().collect();
+    breakdown.sort_by(|(a_ext, a), (b_ext, b)| {
+        let a_total = a.added + a.deleted + a.modified + a.renamed + a.copied;
+        let b_total = b.added + b.deleted + b.modified + b.renamed + b.copied;
+        b_total.cmp(&a_total).then_with(|| a_ext.cmp(b_ext))
+    });
+
+    breakdown
+}
+
+/// Files whose content is byte-for-byte the same on both sides
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct IdenticalReport {
+    /// Relative paths present and identical on both sides
+    pub same_path: Vec<String>,
+    /// (old, new) relative paths of identical files that moved
+    pub moved: Vec<(String, String)>,
+}
+
+/// Collect the unchanged and exact-rename entries of a comparison made without any
+/// ignore_* options, in path order
+pub fn identical_files(entries: &[FileEntry]) -> IdenticalReport {
+    let mut report = IdenticalReport::default();
+    for entry in entries {
+        match (&entry.status, &entry.rename_info) {
+            (FileStatus::Unchanged, _) => report.same_path.push(entry.path.clone()),
+            (FileStatus::Renamed, Some(rename)) if !rename.modified => {
+                report.moved.push((rename.from.clone(), rename.to.clone()))
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+/// Files that became changed, stopped being changed, or changed status between two
+/// comparisons, sorted by path
+pub fn status_transitions(prev: &[FileEntry], curr: &[FileEntry]) -> Vec<StatusTransition> {
+    let changed = |entries: &[FileEntry]| -> HashMap<String, FileStatus> {
+        entries
+            .iter()
+            .filter(|e| e.status != FileStatus::Unchanged)
+            .map(|e| (e.path.clone(), e.status.clone()))
+            .collect()
+    };
+    let before = changed(prev);
+    let after = changed(curr);
+
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let from = before.get(path).cloned();
+            let to = after.get(path).cloned();
+            (from != to).then(|| StatusTransition {
+                path: path.clone(),
+                from,
+                to,
+            })
+        })
+        .collect()
+}
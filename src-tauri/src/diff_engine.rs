@@ -0,0 +1,826 @@
+use crate::comments::CommentStyle;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+/// Longest enclosing-function line kept after a hunk's `@@`, as in GNU diff
+const FUNCTION_CONTEXT_LENGTH: usize = 40;
+
+/// What unified_diff shows, after GNU diff's `-U`, `-p`, `-I`, `-w` and `-B`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EngineOptions {
+    /// Unchanged lines around each change
+    pub context: usize,
+    /// Name the enclosing function after each hunk's `@@`, like `diff -p`
+    pub function_context: bool,
+    /// Leave out hunks whose changed lines are all whole-line comments, like `diff -I`
+    pub ignore_comments: Option<CommentStyle>,
+    /// Match lines that differ only in whitespace, like `diff -w`
+    pub ignore_whitespace: bool,
+    /// Leave out hunks whose changed lines are all blank, like `diff -B`
+    pub ignore_blank_lines: bool,
+}
+
+/// Hunks of a unified diff from `left` to `right`, without the `---`/`+++` header, with
+/// the same `@@` lines GNU diff writes. Empty when the texts are equal.
+pub fn unified_diff(left: &str, right: &str, options: &EngineOptions) -> String {
+    // Lines keep their `\n`, so a last line without one differs from the same line with it
+    let old: Vec<&str> = left.split_inclusive('\n').collect();
+    let new: Vec<&str> = right.split_inclusive('\n').collect();
+    let edits = if options.ignore_whitespace {
+        let old_keys: Vec<String> = old.iter().map(|l| without_whitespace(l)).collect();
+        let new_keys: Vec<String> = new.iter().map(|l| without_whitespace(l)).collect();
+        let old_keys: Vec<&str> = old_keys.iter().map(String::as_str).collect();
+        let new_keys: Vec<&str> = new_keys.iter().map(String::as_str).collect();
+        edit_script(&old_keys, &new_keys, options.context)
+    } else {
+        edit_script(&old, &new, options.context)
+    };
+    // diff -B and -I: a change is ignorable when all its lines are blank or comments.
+    // Comments are found per file, as block comments span lines.
+    let comment_lines = |lines: &[&str]| match options.ignore_comments {
+        Some(style) => style.comment_lines(lines.iter().copied()),
+        None => vec![false; lines.len()],
+    };
+    let (old_comments, new_comments) = (comment_lines(&old), comment_lines(&new));
+    let ignorable = |edit: &Edit| {
+        let (line, comment) = match *edit {
+            Edit::Delete(i, _) => (old[i], old_comments[i]),
+            Edit::Insert(_, j) => (new[j], new_comments[j]),
+            Edit::Equal(..) => return false,
+        };
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        // Whitespace-only lines only count as blank when whitespace is ignored too
+        let blank = if options.ignore_whitespace {
+            line.trim().is_empty()
+        } else {
+            line.is_empty()
+        };
+        (options.ignore_blank_lines && blank) || comment
+    };
+
+    let mut out = String::new();
+    let mut function: Option<&str> = None;
+    let mut searched = 0;
+
+    for range in hunk_ranges(&edits, options.context, ignorable) {
+        let hunk = &edits[range];
+        let (old_start, new_start) = hunk[0].position();
+        let old_count = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(..)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(..)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        ));
+
+        if options.function_context {
+            // The last function line before the hunk, remembered from earlier hunks
+            if let Some(found) = old[searched.min(old_start)..old_start]
+                .iter()
+                .rev()
+                .find(|l| is_function_line(l))
+            {
+                function = Some(found);
+            }
+            searched = searched.max(old_start);
+            if let Some(function) = function {
+                out.push(' ');
+                out.push_str(&function_context(function));
+            }
+        }
+        out.push('\n');
+
+        for edit in hunk {
+            let (prefix, line) = match *edit {
+                Edit::Equal(i, _) => (' ', old[i]),
+                Edit::Delete(i, _) => ('-', old[i]),
+                Edit::Insert(_, j) => ('+', new[j]),
+            };
+            out.push(prefix);
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+                None => {
+                    out.push_str(line);
+                    out.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Unified diff of two files as `diff -u --label <left_label> --label <right_label>` prints it.
+/// `/dev/null` reads as empty, and files with a NUL byte or that aren't UTF-8 get diff's
+/// one-line "Binary files ... differ" rather than a lossy text diff. Empty when the files
+/// are the same.
+pub fn diff_files(
+    left: &Path,
+    right: &Path,
+    left_label: &str,
+    right_label: &str,
+    options: &EngineOptions,
+) -> std::io::Result<String> {
+    let read = |path: &Path| {
+        if path == Path::new("/dev/null") {
+            Ok(Vec::new())
+        } else {
+            std::fs::read(path)
+        }
+    };
+    let (left_bytes, right_bytes) = (read(left)?, read(right)?);
+    if left_bytes == right_bytes {
+        return Ok(String::new());
+    }
+    let text = |bytes| {
+        std::str::from_utf8(bytes)
+            .ok()
+            .filter(|text| !text.contains('\0'))
+    };
+    let (Some(left_text), Some(right_text)) = (text(&left_bytes[..]), text(&right_bytes[..]))
+    else {
+        return Ok(format!(
+            "Binary files {} and {} differ\n",
+            left_label, right_label
+        ));
+    };
+
+    let hunks = unified_diff(left_text, right_text, options);
+    if hunks.is_empty() {
+        return Ok(hunks);
+    }
+    Ok(format!(
+        "--- {}\n+++ {}\n{}",
+        left_label, right_label, hunks
+    ))
+}
+
+/// One step from the old lines to the new, by 0-based line index
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Equal(usize, usize),
+    /// Old line removed, before new line `.1`
+    Delete(usize, usize),
+    /// New line added, after old line `.0 - 1`
+    Insert(usize, usize),
+}
+
+impl Edit {
+    /// Old and new line index where this step happens
+    fn position(&self) -> (usize, usize) {
+        match *self {
+            Edit::Equal(i, j) | Edit::Delete(i, j) | Edit::Insert(i, j) => (i, j),
+        }
+    }
+}
+
+/// A line as `-w` compares it
+fn without_whitespace(line: &str) -> String {
+    line.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// `start,count` for a hunk header: a single line drops the count, and an empty range names
+/// the line before it
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// GNU diff's default `-p` pattern, `^[[:alpha:]$_]`
+fn is_function_line(line: &str) -> bool {
+    line.starts_with(|c: char| c.is_ascii_alphabetic() || c == '$' || c == '_')
+}
+
+/// A function line as shown after `@@`: at most FUNCTION_CONTEXT_LENGTH bytes, trailing
+/// whitespace trimmed
+fn function_context(line: &str) -> String {
+    let mut end = line.len().min(FUNCTION_CONTEXT_LENGTH);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line[..end].trim_end().to_string()
+}
+
+/// Edit ranges of the hunks to print, grouped as diff's find_hunk does: each run of changes
+/// with `context` equal lines either side, merging runs at most `2 * context` lines apart
+/// (`context` when the later run is ignorable). Hunks with only ignorable runs are left out.
+fn hunk_ranges(
+    edits: &[Edit],
+    context: usize,
+    ignorable: impl Fn(&Edit) -> bool,
+) -> Vec<Range<usize>> {
+    let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+    for (index, edit) in edits.iter().enumerate() {
+        if matches!(edit, Edit::Equal(..)) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((run, ignore)) if run.end == index => {
+                run.end += 1;
+                *ignore &= ignorable(edit);
+            }
+            _ => runs.push((index..index + 1, ignorable(edit))),
+        }
+    }
+
+    let mut hunks: Vec<(Range<usize>, bool)> = Vec::new();
+    for (run, ignore) in runs {
+        let threshold = if ignore { context } else { 2 * context + 1 };
+        match hunks.last_mut() {
+            Some((hunk, all_ignorable)) if run.start - hunk.end < threshold => {
+                hunk.end = run.end;
+                *all_ignorable &= ignore;
+            }
+            _ => hunks.push((run, ignore)),
+        }
+    }
+
+    hunks
+        .into_iter()
+        .filter(|(_, ignore)| !ignore)
+        .map(|(hunk, _)| hunk.start.saturating_sub(context)..(hunk.end + context).min(edits.len()))
+        .collect()
+}
+
+/// Edit script from `old` to `new`, worked out the way GNU diff does so the hunks match
+/// `diff -U`: identical ends are set aside down to `horizon` lines, lines that can't match
+/// are discarded, Myers' search runs with diff's tie-breaking and cost cut-off, and each
+/// change is then slid to line up with a change on the other side. Deletions come before
+/// insertions within each change.
+fn edit_script(old: &[&str], new: &[&str], horizon: usize) -> Vec<Edit> {
+    // Like diff, keep `horizon` lines of the identical ends, and don't let the suffix reach
+    // back past what's left of the prefix
+    let prefix = old.iter().zip(new).take_while(|(x, y)| x == y).count();
+    let start = prefix - prefix.min(horizon);
+    let suffix = old[start..]
+        .iter()
+        .rev()
+        .zip(new[start..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let skipped = suffix - suffix.min(horizon);
+
+    // Compare numbers instead of strings
+    fn intern<'a>(ids: &mut HashMap<&'a str, usize>, lines: &[&'a str]) -> Vec<usize> {
+        lines
+            .iter()
+            .map(|line| {
+                let next = ids.len();
+                *ids.entry(line).or_insert(next)
+            })
+            .collect()
+    }
+    let mut ids = HashMap::new();
+    let a = intern(&mut ids, &old[start..old.len() - skipped]);
+    let b = intern(&mut ids, &new[start..new.len() - skipped]);
+
+    let mut old_changed = Changed::new(a.len());
+    let mut new_changed = Changed::new(b.len());
+    compare(&a, &b, ids.len(), &mut old_changed, &mut new_changed);
+    shift_boundaries(&a, &mut old_changed, &new_changed);
+    shift_boundaries(&b, &mut new_changed, &old_changed);
+
+    // Lines outside the compared middle are unchanged
+    let changed = |flags: &Changed, line: usize| {
+        line >= start && flags.0.get(line - start + 1) == Some(&true)
+    };
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let (i0, j0) = (i, j);
+        while i < old.len() && changed(&old_changed, i) {
+            edits.push(Edit::Delete(i, j));
+            i += 1;
+        }
+        while j < new.len() && changed(&new_changed, j) {
+            edits.push(Edit::Insert(i, j));
+            j += 1;
+        }
+        if (i, j) == (i0, j0) {
+            edits.push(Edit::Equal(i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// Changed flag per line, with an unchanged line either side at -1 and `len`
+struct Changed(Vec<bool>);
+
+impl Changed {
+    fn new(len: usize) -> Self {
+        Changed(vec![false; len + 2])
+    }
+}
+
+impl std::ops::Index<isize> for Changed {
+    type Output = bool;
+    fn index(&self, i: isize) -> &bool {
+        &self.0[(i + 1) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for Changed {
+    fn index_mut(&mut self, i: isize) -> &mut bool {
+        &mut self.0[(i + 1) as usize]
+    }
+}
+
+/// How diff treats a line before the search
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Discard {
+    Keep,
+    /// Matches nothing on the other side, so it's changed without searching
+    Yes,
+    /// Matches so many lines it's only discarded in the middle of a run of discards
+    Maybe,
+}
+
+/// diff's discard_confusing_lines for the lines of one side: which of them skip the search
+fn discarded_lines(lines: &[usize], other: &[usize], classes: usize) -> Vec<bool> {
+    let mut counts = vec![0; classes];
+    for &line in other {
+        counts[line] += 1;
+    }
+    // Lines matching more than about the square root of the line count are confusing
+    let mut many = 5;
+    let mut tem = lines.len() / 64;
+    while {
+        tem >>= 2;
+        tem > 0
+    } {
+        many *= 2;
+    }
+    let mut discards: Vec<Discard> = lines
+        .iter()
+        .map(|&line| match counts[line] {
+            0 => Discard::Yes,
+            n if n > many => Discard::Maybe,
+            _ => Discard::Keep,
+        })
+        .collect();
+
+    // Provisional discards only stand inside a run of discards that starts and ends with
+    // definite ones, and then not in long stretches or near the ends of the run
+    let end = discards.len();
+    let mut i = 0;
+    while i < end {
+        if discards[i] == Discard::Maybe {
+            discards[i] = Discard::Keep;
+        } else if discards[i] == Discard::Yes {
+            let mut j = i;
+            let mut provisional = 0;
+            while j < end && discards[j] != Discard::Keep {
+                if discards[j] == Discard::Maybe {
+                    provisional += 1;
+                }
+                j += 1;
+            }
+            while j > i && discards[j - 1] == Discard::Maybe {
+                j -= 1;
+                discards[j] = Discard::Keep;
+                provisional -= 1;
+            }
+            let length = j - i;
+
+            if provisional * 4 > length {
+                while j > i {
+                    j -= 1;
+                    if discards[j] == Discard::Maybe {
+                        discards[j] = Discard::Keep;
+                    }
+                }
+            } else {
+                let mut minimum = 1;
+                let mut tem = length >> 2;
+                while {
+                    tem >>= 2;
+                    tem > 0
+                } {
+                    minimum <<= 1;
+                }
+                minimum += 1;
+
+                // Cancel every subrun of `minimum` or more provisionals
+                let (mut j, mut consec) = (0, 0);
+                while j < length {
+                    if discards[i + j] != Discard::Maybe {
+                        consec = 0;
+                    } else {
+                        consec += 1;
+                        if consec == minimum {
+                            j -= consec;
+                        } else if consec > minimum {
+                            discards[i + j] = Discard::Keep;
+                        }
+                    }
+                    j += 1;
+                }
+
+                // From each end, cancel provisionals until 3 definite discards in a row or
+                // the first definite one at least 8 lines in
+                let mut consec = 0;
+                for j in 0..length {
+                    if j >= 8 && discards[i + j] == Discard::Yes {
+                        break;
+                    }
+                    match discards[i + j] {
+                        Discard::Maybe => {
+                            consec = 0;
+                            discards[i + j] = Discard::Keep;
+                        }
+                        Discard::Keep => consec = 0,
+                        Discard::Yes => consec += 1,
+                    }
+                    if consec == 3 {
+                        break;
+                    }
+                }
+                i += length - 1;
+                let mut consec = 0;
+                for j in 0..length {
+                    if j >= 8 && discards[i - j] == Discard::Yes {
+                        break;
+                    }
+                    match discards[i - j] {
+                        Discard::Maybe => {
+                            consec = 0;
+                            discards[i - j] = Discard::Keep;
+                        }
+                        Discard::Keep => consec = 0,
+                        Discard::Yes => consec += 1,
+                    }
+                    if consec == 3 {
+                        break;
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+
+    discards.into_iter().map(|d| d != Discard::Keep).collect()
+}
+
+/// Mark the changed lines of `a` and `b`: discarded lines, then whatever the search leaves
+/// unmatched among the rest
+fn compare(
+    a: &[usize],
+    b: &[usize],
+    classes: usize,
+    old_changed: &mut Changed,
+    new_changed: &mut Changed,
+) {
+    let kept = |lines: &[usize], other: &[usize], changed: &mut Changed| {
+        let mut real = Vec::new();
+        for (i, discarded) in discarded_lines(lines, other, classes)
+            .into_iter()
+            .enumerate()
+        {
+            if discarded {
+                changed[i as isize] = true;
+            } else {
+                real.push(i);
+            }
+        }
+        real
+    };
+    let x_real = kept(a, b, old_changed);
+    let y_real = kept(b, a, new_changed);
+
+    // diff gives up on the search past about the square root of the input size
+    let diags = x_real.len() + y_real.len() + 3;
+    let mut too_expensive = 1;
+    let mut rest = diags;
+    while rest != 0 {
+        too_expensive <<= 1;
+        rest >>= 2;
+    }
+
+    let mut search = Search {
+        xv: x_real.iter().map(|&i| a[i]).collect(),
+        yv: y_real.iter().map(|&j| b[j]).collect(),
+        fd: V::new(diags, y_real.len() + 1),
+        bd: V::new(diags, y_real.len() + 1),
+        too_expensive: too_expensive.max(4096),
+        x_real,
+        y_real,
+        old_changed,
+        new_changed,
+    };
+    let (n, m) = (search.xv.len() as isize, search.yv.len() as isize);
+    search.compareseq(0, n, 0, m, false);
+}
+
+/// Furthest reaching x per diagonal x - y, for diagonals from `-offset`
+struct V {
+    offset: isize,
+    v: Vec<isize>,
+}
+
+impl V {
+    fn new(len: usize, offset: usize) -> Self {
+        V {
+            offset: offset as isize,
+            v: vec![0; len],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for V {
+    type Output = isize;
+    fn index(&self, k: isize) -> &isize {
+        &self.v[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut isize {
+        &mut self.v[(k + self.offset) as usize]
+    }
+}
+
+/// Where diag splits a problem, and whether each half must be solved without giving up
+struct Partition {
+    xmid: isize,
+    ymid: isize,
+    lo_minimal: bool,
+    hi_minimal: bool,
+}
+
+/// diff's compareseq over the lines left after discarding, marking unmatched ones through
+/// their real indexes
+struct Search<'a> {
+    xv: Vec<usize>,
+    yv: Vec<usize>,
+    x_real: Vec<usize>,
+    y_real: Vec<usize>,
+    fd: V,
+    bd: V,
+    too_expensive: isize,
+    old_changed: &'a mut Changed,
+    new_changed: &'a mut Changed,
+}
+
+impl Search<'_> {
+    fn equal(&self, x: isize, y: isize) -> bool {
+        self.xv[x as usize] == self.yv[y as usize]
+    }
+
+    fn compareseq(
+        &mut self,
+        mut xoff: isize,
+        mut xlim: isize,
+        mut yoff: isize,
+        mut ylim: isize,
+        minimal: bool,
+    ) {
+        while xoff < xlim && yoff < ylim && self.equal(xoff, yoff) {
+            xoff += 1;
+            yoff += 1;
+        }
+        while xoff < xlim && yoff < ylim && self.equal(xlim - 1, ylim - 1) {
+            xlim -= 1;
+            ylim -= 1;
+        }
+
+        if xoff == xlim {
+            for y in yoff..ylim {
+                self.new_changed[self.y_real[y as usize] as isize] = true;
+            }
+        } else if yoff == ylim {
+            for x in xoff..xlim {
+                self.old_changed[self.x_real[x as usize] as isize] = true;
+            }
+        } else {
+            let part = self.diag(xoff, xlim, yoff, ylim, minimal);
+            self.compareseq(xoff, part.xmid, yoff, part.ymid, part.lo_minimal);
+            self.compareseq(part.xmid, xlim, part.ymid, ylim, part.hi_minimal);
+        }
+    }
+
+    /// Where the forward and backward searches meet, or past `too_expensive` steps (unless
+    /// `minimal`) the furthest either got
+    fn diag(
+        &mut self,
+        xoff: isize,
+        xlim: isize,
+        yoff: isize,
+        ylim: isize,
+        minimal: bool,
+    ) -> Partition {
+        let (dmin, dmax) = (xoff - ylim, xlim - yoff);
+        let (fmid, bmid) = (xoff - yoff, xlim - ylim);
+        let (mut fmin, mut fmax, mut bmin, mut bmax) = (fmid, fmid, bmid, bmid);
+        let odd = (fmid - bmid) & 1 != 0;
+        self.fd[fmid] = xoff;
+        self.bd[bmid] = xlim;
+
+        let mut cost = 1;
+        loop {
+            // Extend the forward search by an edit step in each diagonal
+            if fmin > dmin {
+                fmin -= 1;
+                self.fd[fmin - 1] = -1;
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                self.fd[fmax + 1] = -1;
+            } else {
+                fmax -= 1;
+            }
+            for d in (fmin..=fmax).rev().step_by(2) {
+                let (tlo, thi) = (self.fd[d - 1], self.fd[d + 1]);
+                let x0 = if tlo < thi { thi } else { tlo + 1 };
+                let (mut x, mut y) = (x0, x0 - d);
+                while x < xlim && y < ylim && self.equal(x, y) {
+                    x += 1;
+                    y += 1;
+                }
+                self.fd[d] = x;
+                if odd && bmin <= d && d <= bmax && self.bd[d] <= x {
+                    return Partition {
+                        xmid: x,
+                        ymid: y,
+                        lo_minimal: true,
+                        hi_minimal: true,
+                    };
+                }
+            }
+
+            // And the backward one
+            if bmin > dmin {
+                bmin -= 1;
+                self.bd[bmin - 1] = isize::MAX;
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                self.bd[bmax + 1] = isize::MAX;
+            } else {
+                bmax -= 1;
+            }
+            for d in (bmin..=bmax).rev().step_by(2) {
+                let (tlo, thi) = (self.bd[d - 1], self.bd[d + 1]);
+                let x0 = if tlo < thi { tlo } else { thi - 1 };
+                let (mut x, mut y) = (x0, x0 - d);
+                while xoff < x && yoff < y && self.equal(x - 1, y - 1) {
+                    x -= 1;
+                    y -= 1;
+                }
+                self.bd[d] = x;
+                if !odd && fmin <= d && d <= fmax && x <= self.fd[d] {
+                    return Partition {
+                        xmid: x,
+                        ymid: y,
+                        lo_minimal: true,
+                        hi_minimal: true,
+                    };
+                }
+            }
+
+            if !minimal && cost >= self.too_expensive {
+                // Forward diagonal furthest along, and backward one furthest back
+                let (mut fxybest, mut fxbest) = (-1, 0);
+                for d in (fmin..=fmax).rev().step_by(2) {
+                    let x = self.fd[d].min(xlim);
+                    let (x, y) = if x - d > ylim {
+                        (ylim + d, ylim)
+                    } else {
+                        (x, x - d)
+                    };
+                    if fxybest < x + y {
+                        fxybest = x + y;
+                        fxbest = x;
+                    }
+                }
+                let (mut bxybest, mut bxbest) = (isize::MAX, 0);
+                for d in (bmin..=bmax).rev().step_by(2) {
+                    let x = self.bd[d].max(xoff);
+                    let (x, y) = if x - d < yoff {
+                        (yoff + d, yoff)
+                    } else {
+                        (x, x - d)
+                    };
+                    if x + y < bxybest {
+                        bxybest = x + y;
+                        bxbest = x;
+                    }
+                }
+                return if (xlim + ylim) - bxybest < fxybest - (xoff + yoff) {
+                    Partition {
+                        xmid: fxbest,
+                        ymid: fxybest - fxbest,
+                        lo_minimal: true,
+                        hi_minimal: false,
+                    }
+                } else {
+                    Partition {
+                        xmid: bxbest,
+                        ymid: bxybest - bxbest,
+                        lo_minimal: false,
+                        hi_minimal: true,
+                    }
+                };
+            }
+            cost += 1;
+        }
+    }
+}
+
+/// diff's shift_boundaries for one side: slide each run of changes back to merge with
+/// earlier runs and forward to merge with later ones, then back again as far as it still
+/// lines up with a change on the other side
+fn shift_boundaries(equivs: &[usize], changed: &mut Changed, other: &Changed) {
+    let end = equivs.len() as isize;
+    let equiv = |i: isize| equivs[i as usize];
+    let (mut i, mut j) = (0, 0);
+
+    loop {
+        // Find the next run of changes, keeping `j` at the matching line of the other side
+        while i < end && !changed[i] {
+            while other[j] {
+                j += 1;
+            }
+            j += 1;
+            i += 1;
+        }
+        if i == end {
+            break;
+        }
+        let mut start = i;
+        i += 1;
+        while changed[i] {
+            i += 1;
+        }
+        while other[j] {
+            j += 1;
+        }
+
+        let mut corresponding;
+        loop {
+            let run_length = i - start;
+            while start > 0 && equiv(start - 1) == equiv(i - 1) {
+                start -= 1;
+                changed[start] = true;
+                i -= 1;
+                changed[i] = false;
+                while changed[start - 1] {
+                    start -= 1;
+                }
+                j -= 1;
+                while other[j] {
+                    j -= 1;
+                }
+            }
+
+            // The end of the run where it last lined up with a change on the other side
+            corresponding = if other[j - 1] { i } else { end };
+            while i != end && equiv(start) == equiv(i) {
+                changed[start] = false;
+                start += 1;
+                changed[i] = true;
+                i += 1;
+                while changed[i] {
+                    i += 1;
+                }
+                j += 1;
+                while other[j] {
+                    corresponding = i;
+                    j += 1;
+                }
+            }
+            if run_length == i - start {
+                break;
+            }
+        }
+
+        while corresponding < i {
+            start -= 1;
+            changed[start] = true;
+            i -= 1;
+            changed[i] = false;
+            j -= 1;
+            while other[j] {
+                j -= 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::paths::absolute_path;
+use globset::GlobBuilder;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// `.editorconfig` files that apply to `file`, outermost first, stopping at one marked
+/// `root = true`
+fn config_files(file: &Path) -> Vec<(PathBuf, String)> {
+    let mut configs = Vec::new();
+    let mut dir = file.parent();
+
+    while let Some(current) = dir {
+        let candidate = current.join(".editorconfig");
+        if let Ok(content) = std::fs::read_to_string(&candidate) {
+            let is_root = parse(&content).iter().any(|(section, key, value)| {
+                section.is_none() && key == "root" && value.eq_ignore_ascii_case("true")
+            });
+            configs.push((current.to_path_buf(), content));
+            if is_root {
+                break;
+            }
+        }
+        dir = current.parent();
+    }
+
+    configs.reverse();
+    configs
+}
+
+/// (section glob, key, value) for every property, in file order. Keys and values are
+/// lowercased like the spec requires; the preamble has no section.
+fn parse(content: &str) -> Vec<(Option<String>, String, String)> {
+    let mut properties = Vec::new();
+    let mut section = None;
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(glob.to_string());
+        } else if let Some((key, value)) = line.split_once('=') {
+            properties.push((
+                section.clone(),
+                key.trim().to_lowercase(),
+                value.trim().to_lowercase(),
+            ));
+        }
+    }
+
+    properties
+}
+
+/// Does a section glob match `relative` (the file's path below the .editorconfig)?
+/// A glob without `/` matches the file name in any directory.
+fn section_matches(glob: &str, relative: &str) -> bool {
+    let pattern = match glob.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if glob.contains('/') => glob.to_string(),
+        None => format!("**/{}", glob),
+    };
+
+    GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .build()
+        .map(|g| g.compile_matcher().is_match(relative))
+        .unwrap_or(false)
+}
+
+/// Resolved editorconfig properties for `file`; nearer files and later sections win
+pub fn properties_for(file: &Path) -> HashMap<String, String> {
+    let file = absolute_path(file);
+    let mut resolved = HashMap::new();
+
+    for (dir, content) in config_files(&file) {
+        let Ok(relative) = file.strip_prefix(&dir) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        for (section, key, value) in parse(&content) {
+            if section.is_some_and(|glob| section_matches(&glob, &relative)) {
+                resolved.insert(key, value);
+            }
+        }
+    }
+
+    resolved
+}
+
+/// Display width of a tab for `file`: `tab_width`, else a numeric `indent_size`
+pub fn tab_width_for(file: &Path) -> Option<usize> {
+    let properties = properties_for(file);
+    properties
+        .get("tab_width")
+        .or_else(|| properties.get("indent_size"))
+        .and_then(|width| width.parse().ok())
+}
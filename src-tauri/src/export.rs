@@ -0,0 +1,306 @@
+use crate::delta::{file_mode, run_unified_diff};
+use crate::diff::{CompareOptions, FileEntry, FileStatus};
+use crate::diff_engine::{diff_files, EngineOptions};
+use crate::hunks::parse_hunks;
+use crate::paths::absolute_path;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// How the two directories are compared
+    pub compare: CompareOptions,
+    /// Also export unchanged files (with empty patches) for a full manifest
+    pub include_unchanged: bool,
+    /// Replace every character of diff body lines with a placeholder, keeping the `+`/`-`/` `
+    /// prefix, whitespace and line lengths so only the shape of the change is shared
+    pub redact_content: bool,
+}
+
+/// One file of an exported comparison
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedFile {
+    pub path: String,
+    pub status: FileStatus,
+    pub left_path: Option<String>,
+    pub right_path: Option<String>,
+    /// Unified diff with `a/` and `b/` prefixed headers (empty when content is identical)
+    pub patch: String,
+}
+
+/// Identifies export_review_json output
+pub const REVIEW_SCHEMA: &str = "diff-rust/review";
+/// Bumped on any change to the review report that could break a consumer
+pub const REVIEW_SCHEMA_VERSION: u32 = 1;
+
+/// Changed regions of a comparison for code-review tooling.
+///
+/// Schema (version 1):
+/// - `schema`: always "diff-rust/review"; `version`: REVIEW_SCHEMA_VERSION
+/// - `files[]`: `path` (relative, "old → new" for renames), `status`, `old_path` /
+///   `new_path` (relative, null for the missing side) and `regions`
+/// - `regions[]`: `kind` (Added/Removed/Modified) and 1-based line ranges. A range with
+///   0 lines is a position between lines: `start` is the line it follows (0 = top).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReviewReport {
+    pub schema: String,
+    pub version: u32,
+    pub files: Vec<ReviewFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReviewFile {
+    pub path: String,
+    pub status: FileStatus,
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub regions: Vec<ReviewRegion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RegionKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// One changed block: a hunk of a zero-context diff
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReviewRegion {
+    pub kind: RegionKind,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+}
+
+/// Unified diff for one entry, labelled with its relative paths like `git diff`
+pub fn entry_patch(entry: &FileEntry) -> std::io::Result<String> {
+    if entry.status == FileStatus::Unchanged {
+        return Ok(String::new());
+    }
+
+    let (old_rel, new_rel) = patch_paths(entry);
+    let left_label = match entry.left_path {
+        Some(_) => format!("a/{}", old_rel),
+        None => "/dev/null".to_string(),
+    };
+    let right_label = match entry.right_path {
+        Some(_) => format!("b/{}", new_rel),
+        None => "/dev/null".to_string(),
+    };
+
+    // Like git, a binary file gets a one-line note instead of hunks (none when the bytes match)
+    if entry.is_binary {
+        let unmodified = match &entry.status {
+            FileStatus::Renamed => entry.rename_info.as_ref().is_some_and(|r| !r.modified),
+            FileStatus::Copied { similarity, .. } => *similarity >= 1.0,
+            _ => false,
+        };
+        return Ok(match unmodified {
+            true => String::new(),
+            false => format!("Binary files {} and {} differ\n", left_label, right_label),
+        });
+    }
+
+    let side = |path: &Option<String>| match path {
+        Some(path) => absolute_path(Path::new(path)),
+        None => "/dev/null".into(),
+    };
+    let options = EngineOptions {
+        context: 3,
+        ..Default::default()
+    };
+
+    diff_files(
+        &side(&entry.left_path),
+        &side(&entry.right_path),
+        &left_label,
+        &right_label,
+        &options,
+    )
+}
+
+/// Old and new relative path of an entry, with forward slashes as patches expect
+fn patch_paths(entry: &FileEntry) -> (String, String) {
+    let (old_rel, new_rel) = match &entry.status {
+        FileStatus::Copied { from, .. } => (from.as_str(), entry.path.as_str()),
+        _ => entry
+            .path
+            .split_once(" → ")
+            .unwrap_or((&entry.path, &entry.path)),
+    };
+    (old_rel.replace('\\', "/"), new_rel.replace('\\', "/"))
+}
+
+/// `git diff` extended header of an entry: the `diff --git` line, then new/deleted file,
+/// mode change and rename/copy lines as they apply
+fn git_header(entry: &FileEntry) -> String {
+    let (old_rel, new_rel) = patch_paths(entry);
+    let mut header = format!("diff --git a/{} b/{}\n", old_rel, new_rel);
+    let mode = |path: &Option<String>| path.as_deref().and_then(|p| file_mode(Path::new(p)));
+
+    match (&entry.left_path, &entry.right_path) {
+        (None, Some(_)) => {
+            let new = mode(&entry.right_path).unwrap_or("100644");
+            header.push_str(&format!("new file mode {}\n", new));
+        }
+        (Some(_), None) => {
+            let old = mode(&entry.left_path).unwrap_or("100644");
+            header.push_str(&format!("deleted file mode {}\n", old));
+        }
+        _ => {
+            if let (Some(old), Some(new)) = (mode(&entry.left_path), mode(&entry.right_path)) {
+                if old != new {
+                    header.push_str(&format!("old mode {}\nnew mode {}\n", old, new));
+                }
+            }
+        }
+    }
+
+    match &entry.status {
+        FileStatus::Renamed => {
+            let similarity = entry.rename_info.as_ref().map_or(1.0, |r| r.similarity);
+            header.push_str(&format!(
+                "similarity index {}%\nrename from {}\nrename to {}\n",
+                (similarity * 100.0).round(),
+                old_rel,
+                new_rel
+            ));
+        }
+        FileStatus::Copied { similarity, .. } => header.push_str(&format!(
+            "similarity index {}%\ncopy from {}\ncopy to {}\n",
+            (similarity * 100.0).round(),
+            old_rel,
+            new_rel
+        )),
+        _ => {}
+    }
+    header
+}
+
+/// The whole comparison as one `git diff`-style patch that `git apply` (or `patch -p1`)
+/// can replay on the left directory. Unchanged files are left out.
+pub fn comparison_patch(entries: &[FileEntry]) -> std::io::Result<String> {
+    let mut patch = String::new();
+    for entry in entries.iter().filter(|e| e.status != FileStatus::Unchanged) {
+        let header = git_header(entry);
+        let body = entry_patch(entry)?;
+        // A header alone means nothing to apply unless it records a rename, copy or mode change
+        if body.is_empty() && header.lines().count() == 1 {
+            continue;
+        }
+        patch.push_str(&header);
+        patch.push_str(&body);
+    }
+    Ok(patch)
+}
+
+/// Blank out the content of a unified diff's body lines; file and hunk headers are kept
+pub fn redact_patch(patch: &str) -> String {
+    let mut in_hunk = false;
+    patch
+        .split_inclusive('\n')
+        .map(|line| {
+            in_hunk |= line.starts_with("@@");
+            match line.chars().next() {
+                Some(prefix @ ('+' | '-' | ' ')) if in_hunk => {
+                    let body: String = line[1..]
+                        .chars()
+                        .map(|c| if c.is_whitespace() { c } else { '\u{2588}' })
+                        .collect();
+                    format!("{}{}", prefix, body)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Collect the entries to export, each with its patch
+pub fn export_files(
+    entries: &[FileEntry],
+    options: &ExportOptions,
+) -> std::io::Result<Vec<ExportedFile>> {
+    entries
+        .iter()
+        .filter(|e| options.include_unchanged || e.status != FileStatus::Unchanged)
+        .map(|e| {
+            Ok(ExportedFile {
+                path: e.path.clone(),
+                status: e.status.clone(),
+                left_path: e.left_path.clone(),
+                right_path: e.right_path.clone(),
+                patch: if options.redact_content {
+                    redact_patch(&entry_patch(e)?)
+                } else {
+                    entry_patch(e)?
+                },
+            })
+        })
+        .collect()
+}
+
+/// Serialize a comparison as pretty-printed JSON
+pub fn export_json(entries: &[FileEntry], options: &ExportOptions) -> Result<String, String> {
+    let files = export_files(entries, options).map_err(|e| e.to_string())?;
+    serde_json::to_string_pretty(&files).map_err(|e| e.to_string())
+}
+
+/// Changed regions of one entry, from its `-U0` hunks
+fn review_regions(entry: &FileEntry) -> Result<Vec<ReviewRegion>, String> {
+    if entry.status == FileStatus::Unchanged {
+        return Ok(Vec::new());
+    }
+    let left = entry.left_path.as_deref().map(Path::new);
+    let right = entry.right_path.as_deref().map(Path::new);
+    let diff_text = run_unified_diff(left, right, 0).map_err(|e| e.to_string())?;
+
+    Ok(parse_hunks(&diff_text)
+        .into_iter()
+        .map(|hunk| ReviewRegion {
+            kind: match (hunk.old_lines, hunk.new_lines) {
+                (0, _) => RegionKind::Added,
+                (_, 0) => RegionKind::Removed,
+                _ => RegionKind::Modified,
+            },
+            old_start: hunk.old_start,
+            old_lines: hunk.old_lines,
+            new_start: hunk.new_start,
+            new_lines: hunk.new_lines,
+        })
+        .collect())
+}
+
+/// Build the review report for a comparison; files keep the entries' order
+pub fn review_report(
+    entries: &[FileEntry],
+    options: &ExportOptions,
+) -> Result<ReviewReport, String> {
+    let files = entries
+        .iter()
+        .filter(|e| options.include_unchanged || e.status != FileStatus::Unchanged)
+        .map(|e| {
+            let (old_rel, new_rel) = e.path.split_once(" → ").unwrap_or((&e.path, &e.path));
+            Ok(ReviewFile {
+                path: e.path.clone(),
+                status: e.status.clone(),
+                old_path: e.left_path.as_ref().map(|_| old_rel.to_string()),
+                new_path: e.right_path.as_ref().map(|_| new_rel.to_string()),
+                regions: review_regions(e)?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(ReviewReport {
+        schema: REVIEW_SCHEMA.to_string(),
+        version: REVIEW_SCHEMA_VERSION,
+        files,
+    })
+}
+
+/// Serialize the review report as pretty-printed JSON
+pub fn export_review(entries: &[FileEntry], options: &ExportOptions) -> Result<String, String> {
+    serde_json::to_string_pretty(&review_report(entries, options)?).map_err(|e| e.to_string())
+}
@@ -0,0 +1,99 @@
+use crate::delta::DiffOptions;
+use crate::hunks::{diff_lines, DiffLine, DiffLineKind};
+use unicode_width::UnicodeWidthStr;
+
+/// Shown with diffs rendered by delta_like_output
+pub const FALLBACK_WARNING: &str =
+    "delta is not installed; showing a plain diff without syntax highlighting";
+
+/// Lay out unified diff text the way `delta` prints it with our flags (line numbers split
+/// by `⋮` and `│`, changed lines on a background), so the usual inline and side-by-side
+/// conversion can render it when delta itself isn't available. No syntax or word highlights.
+pub(crate) fn delta_like_output(diff_text: &str, options: &DiffOptions) -> String {
+    let lines = diff_lines(diff_text);
+    let tab = " ".repeat(options.tab_width.unwrap_or(4));
+    let content = |line: &DiffLine| line.content.replace('\t', &tab);
+    // delta's removed and added line backgrounds for the theme
+    let [removed, _, added, _] = options.theme.diff_backgrounds();
+    let (removed_bg, added_bg) = (background_sgr(removed), background_sgr(added));
+    let paint = |text: &str, kind: &DiffLineKind| match kind {
+        DiffLineKind::Removed => format!("\x1b[{}m{}\x1b[0m", removed_bg, text),
+        DiffLineKind::Added => format!("\x1b[{}m{}\x1b[0m", added_bg, text),
+        DiffLineKind::Context => text.to_string(),
+    };
+    let number = |n: Option<u32>| n.map_or_else(|| " ".repeat(4), |n| format!("{:>4}", n));
+
+    if !options.side_by_side {
+        return lines
+            .iter()
+            .map(|line| {
+                let text = paint(&content(line), &line.kind);
+                if options.line_numbers {
+                    format!(
+                        "{} ⋮{} │{}\n",
+                        number(line.old_line),
+                        number(line.new_line),
+                        text
+                    )
+                } else {
+                    format!("{}\n", text)
+                }
+            })
+            .collect();
+    }
+
+    // Rows of (old, new) lines: context on both sides, removed lines next to the added ones
+    // that replace them, and an empty cell where one side has fewer
+    let mut rows: Vec<(Option<&DiffLine>, Option<&DiffLine>)> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].kind == DiffLineKind::Context {
+            rows.push((Some(&lines[i]), Some(&lines[i])));
+            i += 1;
+            continue;
+        }
+        let removed: Vec<&DiffLine> = lines[i..]
+            .iter()
+            .take_while(|l| l.kind == DiffLineKind::Removed)
+            .collect();
+        i += removed.len();
+        let added: Vec<&DiffLine> = lines[i..]
+            .iter()
+            .take_while(|l| l.kind == DiffLineKind::Added)
+            .collect();
+        i += added.len();
+        for k in 0..removed.len().max(added.len()) {
+            rows.push((removed.get(k).copied(), added.get(k).copied()));
+        }
+    }
+
+    // Both halves padded to the same width, so the middle `│` is the one in the middle
+    let width = lines
+        .iter()
+        .map(|l| UnicodeWidthStr::width(content(l).as_str()))
+        .max()
+        .unwrap_or(0);
+    let cell = |line: Option<&DiffLine>, old: bool| match line {
+        Some(line) => {
+            let text = content(line);
+            let pad = " ".repeat(width - UnicodeWidthStr::width(text.as_str()));
+            let n = if old { line.old_line } else { line.new_line };
+            format!("│{} │{}{}", number(n), paint(&text, &line.kind), pad)
+        }
+        None => format!("│{} │{}", number(None), " ".repeat(width)),
+    };
+    rows.into_iter()
+        .map(|(old, new)| format!("{}{}\n", cell(old, true), cell(new, false)))
+        .collect()
+}
+
+/// A `#rrggbb` color as `48;2` SGR background parameters
+fn background_sgr(hex: &str) -> String {
+    let value = u32::from_str_radix(hex.trim_start_matches('#'), 16).unwrap_or(0);
+    format!(
+        "48;2;{};{};{}",
+        value >> 16,
+        (value >> 8) & 0xff,
+        value & 0xff
+    )
+}
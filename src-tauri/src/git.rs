@@ -0,0 +1,840 @@
+use crate::diff::{
+    compare_file_lists, compile_ignore, excluded_paths, has_mixed_line_endings, is_skipped,
+    looks_binary, CompareOptions, FileEntry, FileStatus, RenameInfo,
+};
+use crate::hunks::{BlameInfo, DiffLine, DiffLineKind};
+use crate::paths::{absolute_path, tool_command};
+use crate::temp::{TempDir, TempFile};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GitError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{side}: not a git repository: {repo}")]
+    NotARepo { side: String, repo: String },
+    #[error("{side}: unknown revision '{rev}' in {repo}")]
+    BadRevision {
+        side: String,
+        repo: String,
+        rev: String,
+    },
+    #[error("{side}: path '{path}' does not exist at {rev}")]
+    MissingPath {
+        side: String,
+        path: String,
+        rev: String,
+    },
+    #[error("no common ancestor between HEAD and '{base}' in {repo}")]
+    NoMergeBase { base: String, repo: String },
+    #[error("git failed: {0}")]
+    Command(String),
+    #[error("{0}")]
+    Compare(#[from] crate::diff::DiffError),
+}
+
+/// Run git in `repo` and return stdout, or the trimmed stderr on failure
+fn run_git(repo: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let repo = absolute_path(repo);
+    let output = tool_command("git", &[&repo])
+        .arg("-C")
+        .arg(&repo)
+        .args(args)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Extract `rev:path` from `repo` into a temp file named after the original.
+/// `side` ("left"/"right") is only used to make errors point at the right input.
+pub fn extract_blob(repo: &Path, rev: &str, path: &str, side: &str) -> Result<TempFile, GitError> {
+    if run_git(repo, &["rev-parse", "--git-dir"]).is_err() {
+        return Err(GitError::NotARepo {
+            side: side.to_string(),
+            repo: repo.to_string_lossy().to_string(),
+        });
+    }
+
+    if run_git(
+        repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", rev),
+        ],
+    )
+    .is_err()
+    {
+        return Err(GitError::BadRevision {
+            side: side.to_string(),
+            repo: repo.to_string_lossy().to_string(),
+            rev: rev.to_string(),
+        });
+    }
+
+    // Git object paths are always relative to the repo root with forward slashes
+    let path = path.trim_start_matches("./").replace('\\', "/");
+    let spec = format!("{}:{}", rev, path);
+
+    if run_git(repo, &["cat-file", "-e", &spec]).is_err() {
+        return Err(GitError::MissingPath {
+            side: side.to_string(),
+            path,
+            rev: rev.to_string(),
+        });
+    }
+
+    let content = run_git(repo, &["show", &spec]).map_err(GitError::Command)?;
+
+    let file_name = Path::new(&path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "blob".to_string());
+    Ok(TempFile::new(&file_name, &content)?)
+}
+
+/// Resolve `git merge-base HEAD <base>` to a commit hash
+pub fn merge_base(repo: &Path, base: &str) -> Result<String, GitError> {
+    if run_git(repo, &["rev-parse", "--git-dir"]).is_err() {
+        return Err(GitError::NotARepo {
+            side: "base".to_string(),
+            repo: repo.to_string_lossy().to_string(),
+        });
+    }
+
+    if run_git(
+        repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", base),
+        ],
+    )
+    .is_err()
+    {
+        return Err(GitError::BadRevision {
+            side: "base".to_string(),
+            repo: repo.to_string_lossy().to_string(),
+            rev: base.to_string(),
+        });
+    }
+
+    let output =
+        run_git(repo, &["merge-base", "HEAD", base]).map_err(|_| GitError::NoMergeBase {
+            base: base.to_string(),
+            repo: repo.to_string_lossy().to_string(),
+        })?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Prefix of the diff inputs written by BlobRef
+const BLOB_REF_PREFIX: &str = "git-blob:";
+
+/// A file at a commit of a repository. Entries of git comparisons point at their old (and,
+/// between refs, new) versions with these instead of a checked-out copy; the string form
+/// goes in `left_path`/`right_path` and is read back by diff commands as a diff input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobRef {
+    pub repo: PathBuf,
+    pub commit: String,
+    /// Relative to the repository root, with `/` separators
+    pub path: String,
+}
+
+impl BlobRef {
+    /// The reference written by to_string, or None for any other input
+    pub fn parse(input: &str) -> Option<BlobRef> {
+        // Fields are separated by NUL, which no path can contain
+        let mut fields = input.strip_prefix(BLOB_REF_PREFIX)?.splitn(3, '\0');
+        Some(BlobRef {
+            repo: PathBuf::from(fields.next()?),
+            commit: fields.next()?.to_string(),
+            path: fields.next()?.to_string(),
+        })
+    }
+
+    /// Write the blob to a temp file named like the original, so delta can pick a syntax
+    pub fn read(&self) -> Result<TempFile, GitError> {
+        let content = run_git(
+            &self.repo,
+            &["show", &format!("{}:{}", self.commit, self.path)],
+        )
+        .map_err(GitError::Command)?;
+        let name = self.path.rsplit('/').next().unwrap_or("blob");
+        Ok(TempFile::new(name, &content)?)
+    }
+}
+
+impl std::fmt::Display for BlobRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}\0{}\0{}",
+            BLOB_REF_PREFIX,
+            self.repo.to_string_lossy(),
+            self.commit,
+            self.path
+        )
+    }
+}
+
+/// Check out the tree of `commit` into a temp directory, removed when the guard is dropped
+pub fn export_commit(repo: &Path, commit: &str) -> Result<TempDir, GitError> {
+    let repo = absolute_path(repo);
+    let dir = TempDir::new()?;
+
+    let mut archive = tool_command("git", &[&repo])
+        .arg("-C")
+        .arg(&repo)
+        .args(["archive", "--format=tar", commit])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let tar = archive
+        .stdout
+        .take()
+        .map(Stdio::from)
+        .unwrap_or_else(Stdio::null);
+    let untar = tool_command("tar", &[dir.path()])
+        .args(["-xf", "-", "-C"])
+        .arg(dir.path())
+        .stdin(tar)
+        .output()?;
+    let archived = archive.wait_with_output()?;
+
+    if !archived.status.success() {
+        return Err(GitError::Command(
+            String::from_utf8_lossy(&archived.stderr).trim().to_string(),
+        ));
+    }
+    if !untar.status.success() {
+        return Err(GitError::Command(
+            String::from_utf8_lossy(&untar.stderr).trim().to_string(),
+        ));
+    }
+    Ok(dir)
+}
+
+/// Relative paths of the files git lists with `args` (an `ls-files` or `ls-tree` call with `-z`)
+fn listed_files(repo: &Path, args: &[&str]) -> Result<Vec<String>, GitError> {
+    let output = run_git(repo, args).map_err(GitError::Command)?;
+
+    Ok(output
+        .split(|&b| b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| String::from_utf8_lossy(p).to_string())
+        .collect())
+}
+
+/// Compare the working tree of `repo` against its merge-base with `base`.
+/// Only files git lists are compared: tracked ones, and untracked but not ignored ones
+/// (which show up as Added). Old versions are BlobRefs into the merge-base.
+pub fn branch_diff_entries(
+    repo: &Path,
+    base: &str,
+    options: &CompareOptions,
+) -> Result<Vec<FileEntry>, GitError> {
+    let commit = merge_base(repo, base)?;
+    let repo = &absolute_path(repo);
+    let snapshot = export_commit(repo, &commit)?;
+
+    let side = |dir: &Path, files: Vec<String>| -> Vec<(PathBuf, PathBuf)> {
+        files
+            .into_iter()
+            .map(|relative| (PathBuf::from(&relative), dir.join(&relative)))
+            .filter(|(_, file)| file.is_file())
+            .collect()
+    };
+    let left = side(
+        snapshot.path(),
+        listed_files(repo, &["ls-tree", "-r", "-z", "--name-only", &commit])?,
+    );
+    let worktree = listed_files(
+        repo,
+        &[
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ],
+    )?;
+    let right = side(repo, worktree);
+
+    let copies = if options.detect_copies {
+        git_copies(repo, &commit)?
+    } else {
+        HashMap::new()
+    };
+    let old_version = |path: &str| BlobRef {
+        repo: repo.clone(),
+        commit: commit.clone(),
+        path: path.replace('\\', "/"),
+    };
+
+    let entries = compare_file_lists(left, right, options)?;
+    Ok(entries
+        .into_iter()
+        .map(|mut entry| {
+            // Old versions are read from git on demand; the snapshot goes away with this call
+            entry.left_path = entry.left_path.as_deref().and_then(|left| {
+                let relative = Path::new(left).strip_prefix(snapshot.path()).ok()?;
+                Some(old_version(&relative.to_string_lossy()).to_string())
+            });
+            if entry.status == FileStatus::Added {
+                if let Some((from, similarity)) = copies.get(&entry.path.replace('\\', "/")) {
+                    // Diff the copy against its source, like a rename
+                    entry.left_path = Some(old_version(from).to_string());
+                    entry.status = FileStatus::Copied {
+                        from: from.clone(),
+                        similarity: *similarity,
+                    };
+                }
+            }
+            entry
+        })
+        .collect())
+}
+
+/// Resolve `rev` in `repo` to a commit hash; `side` names the input in errors
+fn resolve_commit(repo: &Path, rev: &str, side: &str) -> Result<String, GitError> {
+    if run_git(repo, &["rev-parse", "--git-dir"]).is_err() {
+        return Err(GitError::NotARepo {
+            side: side.to_string(),
+            repo: repo.to_string_lossy().to_string(),
+        });
+    }
+
+    let output = run_git(
+        repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", rev),
+        ],
+    )
+    .map_err(|_| GitError::BadRevision {
+        side: side.to_string(),
+        repo: repo.to_string_lossy().to_string(),
+        rev: rev.to_string(),
+    })?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Hand each of the blobs `ids` of `repo` to `visit` with its index, from one
+/// `git cat-file --batch` instead of a process per blob
+fn for_each_blob(
+    repo: &Path,
+    ids: &[&str],
+    mut visit: impl FnMut(usize, &[u8]),
+) -> Result<(), GitError> {
+    let repo = absolute_path(repo);
+    let mut child = tool_command("git", &[&repo])
+        .arg("-C")
+        .arg(&repo)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // Written from a separate thread, as git answers while it reads
+    let request: String = ids.iter().map(|id| format!("{}\n", id)).collect();
+    let writer = child.stdin.take().map(|mut stdin| {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(request.as_bytes());
+        })
+    });
+
+    let mut output = BufReader::new(
+        child
+            .stdout
+            .take()
+            .ok_or_else(|| GitError::Command("no stdout".into()))?,
+    );
+    let mut content = Vec::new();
+    for index in 0..ids.len() {
+        // "<id> blob <size>", then the content and a newline; "<id> missing" for unknown ids
+        let mut header = String::new();
+        output.read_line(&mut header)?;
+        let size = match header.trim_end().split(' ').collect::<Vec<_>>()[..] {
+            [_, "blob", size] => size.parse::<usize>().ok(),
+            _ => None,
+        };
+        let Some(size) = size else {
+            return Err(GitError::Command(format!(
+                "git cat-file: {}",
+                header.trim()
+            )));
+        };
+        content.resize(size + 1, 0);
+        output.read_exact(&mut content)?;
+        visit(index, &content[..size]);
+    }
+
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// One record of `git diff --raw -z`: status letters with the score, blob ids of both
+/// sides (all zeros for a missing one), the path and, for renames and copies, the new path
+struct RawChange {
+    status: String,
+    ids: (String, String),
+    path: String,
+    new_path: Option<String>,
+}
+
+fn parse_raw_diff(output: &[u8]) -> Vec<RawChange> {
+    let mut fields = output
+        .split(|&b| b == 0)
+        .filter(|f| !f.is_empty())
+        .map(|f| String::from_utf8_lossy(f).into_owned());
+    let mut changes = Vec::new();
+
+    // ":<old mode> <new mode> <old id> <new id> <status>", then the path(s)
+    while let Some(meta) = fields.next() {
+        let meta: Vec<&str> = meta.trim_start_matches(':').split(' ').collect();
+        let (&[_, _, old_id, new_id, status], Some(path)) = (&meta[..], fields.next()) else {
+            break;
+        };
+        let new_path = if status.starts_with(['R', 'C']) {
+            fields.next()
+        } else {
+            None
+        };
+        changes.push(RawChange {
+            status: status.to_string(),
+            ids: (old_id.to_string(), new_id.to_string()),
+            path,
+            new_path,
+        });
+    }
+
+    changes
+}
+
+/// Compare two commits, branches or tags of `repo` from `git diff --raw`, renames and copies
+/// being git's own. Both sides are BlobRefs read only when a file is diffed; the changed
+/// blobs are read once here to flag binary files and mixed line endings.
+pub fn ref_diff_entries(
+    repo: &Path,
+    left_ref: &str,
+    right_ref: &str,
+    options: &CompareOptions,
+) -> Result<Vec<FileEntry>, GitError> {
+    let left_commit = resolve_commit(repo, left_ref, "left")?;
+    let right_commit = resolve_commit(repo, right_ref, "right")?;
+    let repo = &absolute_path(repo);
+
+    let mut args = vec!["diff", "--raw", "-z", "--no-abbrev", "-M"];
+    if options.detect_copies {
+        args.push("-C");
+    }
+    args.extend([left_commit.as_str(), right_commit.as_str(), "--"]);
+    let output = run_git(repo, &args).map_err(GitError::Command)?;
+
+    let ignore = compile_ignore(options)?;
+    let excluded = excluded_paths(options);
+    let skipped = |path: &str| is_skipped(Path::new(path), ignore.as_deref(), &excluded);
+    let version = |commit: &str, path: &str| {
+        BlobRef {
+            repo: repo.clone(),
+            commit: commit.to_string(),
+            path: path.to_string(),
+        }
+        .to_string()
+    };
+
+    let mut entries = Vec::new();
+    let mut ids: Vec<[Option<String>; 2]> = Vec::new();
+    for change in parse_raw_diff(&output) {
+        let kind = change.status.chars().next().unwrap_or('M');
+        let score = change.status[kind.len_utf8()..]
+            .parse::<u32>()
+            .unwrap_or(100) as f32
+            / 100.0;
+        let (old, new) = match &change.new_path {
+            Some(to) => (change.path.clone(), to.clone()),
+            None => (change.path.clone(), change.path.clone()),
+        };
+
+        // A rename with one side ignored is the other side's deletion or addition
+        let kind = match (kind, skipped(&old), skipped(&new)) {
+            (_, _, true) if kind != 'R' => continue,
+            ('R', true, true) => continue,
+            ('R', true, false) => 'A',
+            ('R', false, true) => 'D',
+            _ => kind,
+        };
+
+        let (path, status, left, right) = match kind {
+            'A' => (new.clone(), FileStatus::Added, None, Some(new)),
+            'D' => (old.clone(), FileStatus::Deleted, Some(old), None),
+            'R' => (
+                format!("{} → {}", old, new),
+                FileStatus::Renamed,
+                Some(old),
+                Some(new),
+            ),
+            'C' => (
+                new.clone(),
+                FileStatus::Copied {
+                    from: old.clone(),
+                    similarity: score,
+                },
+                Some(old),
+                Some(new),
+            ),
+            // M, and T (type change) which diffs the same way
+            _ => (new.clone(), FileStatus::Modified, Some(old), Some(new)),
+        };
+
+        ids.push([
+            left.as_ref().map(|_| change.ids.0.clone()),
+            right.as_ref().map(|_| change.ids.1.clone()),
+        ]);
+        entries.push(FileEntry {
+            name: path.rsplit('/').next().unwrap_or_default().to_string(),
+            path,
+            rename_info: match (&status, &left, &right) {
+                (FileStatus::Renamed, Some(from), Some(to)) => Some(RenameInfo {
+                    from: from.clone(),
+                    to: to.clone(),
+                    similarity: score,
+                    modified: change.ids.0 != change.ids.1,
+                }),
+                _ => None,
+            },
+            status,
+            is_dir: false,
+            left_path: left.map(|p| version(&left_commit, &p)),
+            right_path: right.map(|p| version(&right_commit, &p)),
+            cross_file_move_hint: None,
+            mixed_line_endings: false,
+            skip_content: false,
+            truncated_continuation: false,
+            is_binary: false,
+        });
+    }
+
+    // Flag binary files and mixed line endings from either side's content, as for directories
+    let blobs: Vec<(usize, &str)> = ids
+        .iter()
+        .enumerate()
+        .flat_map(|(entry, sides)| sides.iter().flatten().map(move |id| (entry, id.as_str())))
+        .collect();
+    let blob_ids: Vec<&str> = blobs.iter().map(|(_, id)| *id).collect();
+    for_each_blob(repo, &blob_ids, |index, content| {
+        let entry = &mut entries[blobs[index].0];
+        entry.is_binary |= looks_binary(content);
+        entry.mixed_line_endings |= has_mixed_line_endings(content);
+        entry.skip_content |= options
+            .max_file_size_for_diff
+            .is_some_and(|max| content.len() as u64 > max);
+    })?;
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Line number → blame of one file
+type FileBlame = Arc<HashMap<u32, BlameInfo>>;
+
+/// Whole-file blames by (repo, revision, path). Working tree blames ("" revision) are also
+/// keyed by the file's size and modification time, so edits invalidate them.
+static BLAME_CACHE: Mutex<Vec<(String, FileBlame)>> = Mutex::new(Vec::new());
+
+/// Most blames kept in BLAME_CACHE; the oldest go first
+const BLAME_CACHE_SIZE: usize = 64;
+
+/// Parse `git blame --line-porcelain` into line number → blame
+pub(crate) fn parse_line_porcelain(output: &[u8]) -> HashMap<u32, BlameInfo> {
+    let text = String::from_utf8_lossy(output);
+    let mut blame = HashMap::new();
+    let mut current: Option<(u32, BlameInfo)> = None;
+
+    for line in text.lines() {
+        if line.starts_with('\t') {
+            // The line's content ends its record
+            if let Some((number, info)) = current.take() {
+                blame.insert(number, info);
+            }
+        } else if let Some((_, info)) = current.as_mut() {
+            if let Some(author) = line.strip_prefix("author ") {
+                info.author = author.to_string();
+            } else if let Some(time) = line.strip_prefix("author-time ") {
+                info.time = time.parse().unwrap_or(0);
+            } else if let Some(summary) = line.strip_prefix("summary ") {
+                info.summary = summary.to_string();
+            }
+        } else {
+            // "<hash> <original line> <final line>[ <group size>]"
+            let mut fields = line.split(' ');
+            let (Some(commit), Some(_), Some(final_line)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            if let Ok(number) = final_line.parse() {
+                let info = BlameInfo {
+                    commit: commit.to_string(),
+                    author: String::new(),
+                    time: 0,
+                    summary: String::new(),
+                };
+                current = Some((number, info));
+            }
+        }
+    }
+    blame
+}
+
+/// Blame every line of `path` (relative to `repo`) at `rev`, or in the working tree when
+/// `rev` is None. None if git can't blame it (untracked, or not in the revision).
+fn blame_file(repo: &Path, rev: Option<&str>, path: &str) -> Option<FileBlame> {
+    let mut key = format!(
+        "{}\0{}\0{}",
+        repo.to_string_lossy(),
+        rev.unwrap_or(""),
+        path
+    );
+    if rev.is_none() {
+        let meta = std::fs::metadata(repo.join(path)).ok()?;
+        let modified = meta
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        key.push_str(&format!("\0{}\0{}", meta.len(), modified.as_nanos()));
+    }
+
+    {
+        let cached = BLAME_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, blame)) = cached.iter().find(|(k, _)| *k == key) {
+            return Some(blame.clone());
+        }
+    }
+
+    let mut args = vec!["blame", "--line-porcelain"];
+    args.extend(rev);
+    args.extend(["--", path]);
+    let blame: FileBlame = Arc::new(parse_line_porcelain(&run_git(repo, &args).ok()?));
+
+    let mut cached = BLAME_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if cached.len() >= BLAME_CACHE_SIZE {
+        cached.remove(0);
+    }
+    cached.push((key, blame.clone()));
+    Some(blame)
+}
+
+/// Root of the repository containing `file`, and the file's path relative to it
+fn repo_of(file: &Path) -> Option<(PathBuf, String)> {
+    let file = absolute_path(file);
+    let output = run_git(file.parent()?, &["rev-parse", "--show-toplevel"]).ok()?;
+    let root = PathBuf::from(String::from_utf8_lossy(&output).trim());
+    // Compare canonical forms so symlinked temp dirs (e.g. /tmp on macOS) still match
+    let canonical_file = file.canonicalize().ok()?;
+    let relative = canonical_file
+        .strip_prefix(root.canonicalize().ok()?)
+        .ok()?;
+    Some((root, relative.to_string_lossy().replace('\\', "/")))
+}
+
+/// Repository, revision (None for the working tree) and relative path to blame a diff input
+/// with: a BlobRef's commit, or the working tree of the checkout a plain file is in
+fn blame_source(input: &str) -> Option<(PathBuf, Option<String>, String)> {
+    match BlobRef::parse(input) {
+        Some(blob) => Some((blob.repo, Some(blob.commit), blob.path)),
+        None => repo_of(Path::new(input)).map(|(repo, path)| (repo, None, path)),
+    }
+}
+
+/// Set `blame` on diff lines of files in a git repository: removed lines from the old
+/// file's blame, added and context lines from the new file's. Inputs are the diff's
+/// `left_path`/`right_path`, so BlobRefs are blamed at their commit and plain files in
+/// their working tree. Each file is blamed once and cached; lines git can't blame
+/// (untracked files, plain directories) are left without.
+pub fn attach_blame(lines: &mut [DiffLine], left: Option<&str>, right: Option<&str>) {
+    let blame = |input: Option<&str>| {
+        let (repo, rev, path) = blame_source(input?)?;
+        blame_file(&repo, rev.as_deref(), &path)
+    };
+    let (old_blame, new_blame) = (blame(left), blame(right));
+
+    for line in lines {
+        line.blame = match (line.kind.clone(), line.old_line, line.new_line) {
+            (DiffLineKind::Removed, Some(old), _) => {
+                old_blame.as_ref().and_then(|b| b.get(&old).cloned())
+            }
+            (_, _, Some(new)) => new_blame.as_ref().and_then(|b| b.get(&new).cloned()),
+            _ => None,
+        };
+    }
+}
+
+/// Copies git finds between `commit` and the tracked files of the working tree:
+/// destination → (source, similarity). Untracked files aren't considered.
+fn git_copies(repo: &Path, commit: &str) -> Result<HashMap<String, (String, f32)>, GitError> {
+    let output = run_git(
+        repo,
+        &["diff", "-z", "--name-status", "-C", "-C", commit, "--"],
+    )
+    .map_err(GitError::Command)?;
+    Ok(parse_copies(&output)
+        .into_iter()
+        .map(|(from, to, score)| (to, (from, score as f32 / 100.0)))
+        .collect())
+}
+
+/// (source, destination, score) of every `C<score>` record in `git diff --name-status -z`
+/// output. Copy and rename records carry two paths, all others one.
+pub(crate) fn parse_copies(output: &[u8]) -> Vec<(String, String, u32)> {
+    let mut fields = output
+        .split(|&b| b == 0)
+        .filter(|f| !f.is_empty())
+        .map(|f| String::from_utf8_lossy(f).into_owned());
+    let mut copies = Vec::new();
+
+    while let Some(status) = fields.next() {
+        let (Some(first), kind) = (fields.next(), status.chars().next()) else {
+            break;
+        };
+        if !matches!(kind, Some('C' | 'R')) {
+            continue;
+        }
+        let Some(second) = fields.next() else {
+            break;
+        };
+        if kind == Some('C') {
+            copies.push((first, second, status[1..].parse().unwrap_or(0)));
+        }
+    }
+
+    copies
+}
+
+/// Result of dry-running a patch against a directory
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PatchCheck {
+    pub applies: bool,
+    /// Files the patch would fail on
+    pub rejects: Vec<String>,
+}
+
+/// Check whether `patch` (with `a/`/`b/` prefixes, as exported) applies cleanly to `dir`
+/// using `git apply --check`, or `patch --dry-run` when git isn't installed. Neither
+/// writes to the tree.
+pub fn verify_patch(dir: &Path, patch: &str) -> Result<PatchCheck, GitError> {
+    let dir = absolute_path(dir);
+    let patch_file = TempFile::new("check.patch", patch.as_bytes())?;
+
+    // Keep git from treating a repository above `dir` as the root the paths are relative to
+    let output = tool_command("git", &[&dir])
+        .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap_or(&dir))
+        .args(["apply", "--check"])
+        .arg(patch_file.path())
+        .output();
+
+    match output {
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let rejects = git_apply_rejects(&stderr);
+            if !output.status.success() && rejects.is_empty() {
+                // Nothing to blame on a file: the patch itself is malformed
+                return Err(GitError::Command(stderr.trim().to_string()));
+            }
+            Ok(PatchCheck {
+                applies: output.status.success(),
+                rejects,
+            })
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            patch_dry_run(&dir, patch_file.path())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Files named in `error: <path>: <reason>` lines of `git apply` output
+fn git_apply_rejects(stderr: &str) -> Vec<String> {
+    let mut rejects: Vec<String> = Vec::new();
+    for line in stderr.lines() {
+        let Some((path, reason)) = line
+            .strip_prefix("error: ")
+            .and_then(|l| l.rsplit_once(": "))
+        else {
+            continue;
+        };
+        let per_file = [
+            "patch does not apply",
+            "No such file or directory",
+            "already exists in working directory",
+        ];
+        if per_file.contains(&reason) && !rejects.iter().any(|r| r == path) {
+            rejects.push(path.to_string());
+        }
+    }
+    rejects
+}
+
+fn patch_dry_run(dir: &Path, patch_file: &Path) -> Result<PatchCheck, GitError> {
+    let output = match tool_command("patch", &[dir])
+        .args(["--dry-run", "--force", "-p1", "-i"])
+        .arg(patch_file)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(GitError::Command(
+                "verifying a patch needs git or patch installed".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // "checking file X" precedes its hunk failures; a missing file is reported with the
+    // quoted "|--- a/X" header instead
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut rejects: Vec<String> = Vec::new();
+    let mut current = None;
+    let mut missing = false;
+    for line in stdout.lines() {
+        if let Some(file) = line.strip_prefix("checking file ") {
+            current = Some(file.to_string());
+        } else if line.starts_with("can't find file to patch") {
+            missing = true;
+        } else if let Some(header) = line.strip_prefix("|--- ").filter(|_| missing) {
+            let path = header.split('\t').next().unwrap_or(header);
+            current = Some(path.strip_prefix("a/").unwrap_or(path).to_string());
+            missing = false;
+        }
+
+        let failed = line.contains("FAILED") || line.starts_with("No file to patch");
+        if let Some(file) = current.as_ref().filter(|f| failed && !rejects.contains(f)) {
+            rejects.push(file.clone());
+        }
+    }
+
+    Ok(PatchCheck {
+        applies: output.status.success(),
+        rejects,
+    })
+}
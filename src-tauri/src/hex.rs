@@ -0,0 +1,59 @@
+use crate::delta::DeltaError;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Read `len` bytes starting at `start`, rejecting ranges that run past the end of the file
+pub fn read_byte_range(path: &Path, start: u64, len: u64) -> Result<Vec<u8>, DeltaError> {
+    let mut file = std::fs::File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let end = start
+        .checked_add(len)
+        .ok_or_else(|| DeltaError::InvalidRange(format!("range {}+{} overflows", start, len)))?;
+    if end > file_len {
+        return Err(DeltaError::InvalidRange(format!(
+            "range {}..{} is outside the file ({} bytes)",
+            start, end, file_len
+        )));
+    }
+
+    let mut buf = vec![0; len as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Render bytes as an `xxd`-style dump, one line per 16 bytes.
+/// Offsets are relative to the start of `bytes` so equal content at different
+/// file positions lines up when two dumps are diffed.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+
+    for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        out.push_str(&format!("{:08x}:", i * BYTES_PER_LINE));
+        for (j, byte) in chunk.iter().enumerate() {
+            if j % 2 == 0 {
+                out.push(' ');
+            }
+            out.push_str(&format!("{:02x}", byte));
+        }
+
+        // Pad short final lines so the ASCII column stays aligned
+        let missing = BYTES_PER_LINE - chunk.len();
+        out.push_str(&" ".repeat(missing * 2 + missing / 2));
+
+        out.push_str("  ");
+        out.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push('\n');
+    }
+
+    out
+}
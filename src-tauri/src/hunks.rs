@@ -0,0 +1,343 @@
+use crate::syntax::Token;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Shortest run of identical removed/added lines reported as a moved block
+pub(crate) const MIN_MOVED_BLOCK_LINES: usize = 3;
+
+/// Position and header of one `@@ -a,b +c,d @@ header` hunk in a unified diff
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HunkInfo {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Text after the closing `@@` (e.g. the enclosing function with `diff -p`)
+    pub header: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum LineKind {
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LineChange {
+    /// Line number in the new file (None for removed lines)
+    pub new_line: Option<u32>,
+    /// Line number in the old file (None for added lines)
+    pub old_line: Option<u32>,
+    pub kind: LineKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// The commit that last changed a line, from `git blame`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlameInfo {
+    /// Full hash; all zeros for lines not committed yet
+    pub commit: String,
+    pub author: String,
+    /// Author time, seconds since the epoch
+    pub time: i64,
+    /// First line of the commit message
+    pub summary: String,
+}
+
+/// One line of a unified diff with its position on each side, for structured rendering
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    pub content: String,
+    /// Content split into highlighted tokens (a single unclassified token by default)
+    pub tokens: Vec<Token>,
+    /// Who last changed the line, with show_blame for files in a git repository
+    #[serde(default)]
+    pub blame: Option<BlameInfo>,
+}
+
+/// Parse a `start[,count]` range from a hunk header; count defaults to 1
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Parse a single `@@ -a,b +c,d @@ header` line
+pub fn parse_hunk_header(line: &str) -> Option<HunkInfo> {
+    let rest = line.strip_prefix("@@ ")?;
+    let (ranges, header) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(' ')?;
+    let (old_start, old_lines) = parse_range(old.strip_prefix('-')?)?;
+    let (new_start, new_lines) = parse_range(new.strip_prefix('+')?)?;
+
+    Some(HunkInfo {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        header: header.trim().to_string(),
+    })
+}
+
+/// Every hunk header in a unified diff, in order
+pub fn parse_hunks(diff_text: &str) -> Vec<HunkInfo> {
+    let (_, hunks) = hunk_chunks(diff_text);
+    hunks
+        .filter_map(|hunk| parse_hunk_header(hunk.lines().next()?))
+        .collect()
+}
+
+/// Split unified diff text into its file header and its hunks' headers and lines
+pub(crate) fn split_hunks(diff_text: &str) -> (String, Vec<(HunkInfo, Vec<&str>)>) {
+    let (head, chunks) = hunk_chunks(diff_text);
+    let hunks = chunks
+        .filter_map(|chunk| {
+            let mut lines = chunk.lines();
+            Some((parse_hunk_header(lines.next()?)?, lines.collect()))
+        })
+        .collect();
+    (
+        head.lines().map(|line| format!("{}\n", line)).collect(),
+        hunks,
+    )
+}
+
+/// Split unified diff text into its file header and its hunks, each an `@@` line with
+/// the lines under it, as slices of `diff_text`. Hunks are found as the iterator is
+/// advanced, so taking the first few doesn't scan the rest.
+pub(crate) fn hunk_chunks(diff_text: &str) -> (&str, impl Iterator<Item = &str>) {
+    let head_end = next_hunk_start(diff_text, 0);
+    let mut rest = &diff_text[head_end..];
+    let hunks = std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let header_end = rest.find('\n').map_or(rest.len(), |at| at + 1);
+        let (hunk, tail) = rest.split_at(next_hunk_start(rest, header_end));
+        rest = tail;
+        Some(hunk)
+    });
+    (&diff_text[..head_end], hunks)
+}
+
+/// Offset of the first hunk header in `text` from line start `from` on, else text.len()
+fn next_hunk_start(text: &str, from: usize) -> usize {
+    let mut offset = from;
+    for line in text[from..].split_inclusive('\n') {
+        if parse_hunk_header(line.trim_end_matches(['\n', '\r'])).is_some() {
+            return offset;
+        }
+        offset += line.len();
+    }
+    text.len()
+}
+
+/// Every context/added/removed line in a unified diff with its old/new line number
+pub fn diff_lines(diff_text: &str) -> Vec<DiffLine> {
+    let mut lines = Vec::new();
+    let mut old_line = 0;
+    let mut new_line = 0;
+    let mut in_hunk = false;
+
+    for line in diff_text.lines() {
+        if let Some(hunk) = parse_hunk_header(line) {
+            old_line = hunk.old_start;
+            new_line = hunk.new_start;
+            in_hunk = true;
+            continue;
+        }
+        if !in_hunk {
+            // File headers (--- / +++) before the first hunk
+            continue;
+        }
+
+        let (kind, old, new) = match line.chars().next() {
+            Some('+') => (DiffLineKind::Added, None, Some(new_line)),
+            Some('-') => (DiffLineKind::Removed, Some(old_line), None),
+            Some(' ') => (DiffLineKind::Context, Some(old_line), Some(new_line)),
+            // "\ No newline at end of file"
+            _ => continue,
+        };
+        if old.is_some() {
+            old_line += 1;
+        }
+        if new.is_some() {
+            new_line += 1;
+        }
+
+        let content = line[1..].to_string();
+        lines.push(DiffLine {
+            kind,
+            old_line: old,
+            new_line: new,
+            tokens: vec![Token {
+                text: content.clone(),
+                kind: None,
+            }],
+            content,
+            blame: None,
+        });
+    }
+
+    lines
+}
+
+/// Every added/removed line in a unified diff with its old/new line number
+pub fn changed_lines(diff_text: &str) -> Vec<LineChange> {
+    diff_lines(diff_text)
+        .into_iter()
+        .filter_map(|line| {
+            let kind = match line.kind {
+                DiffLineKind::Added => LineKind::Added,
+                DiffLineKind::Removed => LineKind::Removed,
+                DiffLineKind::Context => return None,
+            };
+            Some(LineChange {
+                new_line: line.new_line,
+                old_line: line.old_line,
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Inclusive new-file line ranges untouched by a `-U0` diff, for a file of `new_total` lines.
+/// A pure deletion (`+c,0`) sits between lines and doesn't split the surrounding range.
+pub fn unchanged_ranges(diff_text: &str, new_total: u32) -> Vec<(u32, u32)> {
+    let mut ranges = Vec::new();
+    let mut next = 1;
+
+    for hunk in parse_hunks(diff_text) {
+        if hunk.new_lines == 0 {
+            continue;
+        }
+        if hunk.new_start > next {
+            ranges.push((next, hunk.new_start - 1));
+        }
+        next = hunk.new_start + hunk.new_lines;
+    }
+    if next <= new_total {
+        ranges.push((next, new_total));
+    }
+
+    ranges
+}
+
+/// Line hashes are cut to 53 bits so they survive as JavaScript numbers
+const LINE_HASH_MASK: u64 = (1 << 53) - 1;
+
+/// 64-bit FNV-1a of one line, masked to LINE_HASH_MASK. Spelled out rather than using
+/// std's hasher so the values stay the same across builds and can be recomputed by the
+/// frontend.
+fn line_hash(line: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in line {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash & LINE_HASH_MASK
+}
+
+/// One hash per line of `content`, ignoring the `\n` / `\r\n` terminator, so an editor
+/// can tell which lines changed since the last diff
+pub fn line_hashes(content: &[u8]) -> Vec<u64> {
+    if content.is_empty() {
+        return Vec::new();
+    }
+    content
+        .strip_suffix(b"\n")
+        .unwrap_or(content)
+        .split(|&b| b == b'\n')
+        .map(|line| line_hash(line.strip_suffix(b"\r").unwrap_or(line)))
+        .collect()
+}
+
+/// A run of lines removed in one place and added verbatim in another
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MovedBlock {
+    pub id: usize,
+    pub old_start: u32,
+    pub new_start: u32,
+    pub len: u32,
+}
+
+/// Find blocks of at least MIN_MOVED_BLOCK_LINES consecutive removed lines that
+/// reappear as consecutive added lines elsewhere. Blank lines never start a block.
+pub fn detect_moved_blocks(lines: &[DiffLine]) -> Vec<MovedBlock> {
+    let removed: Vec<(u32, &str)> = lines
+        .iter()
+        .filter(|l| l.kind == DiffLineKind::Removed)
+        .filter_map(|l| Some((l.old_line?, l.content.as_str())))
+        .collect();
+    let added: Vec<(u32, &str)> = lines
+        .iter()
+        .filter(|l| l.kind == DiffLineKind::Added)
+        .filter_map(|l| Some((l.new_line?, l.content.as_str())))
+        .collect();
+
+    let mut added_by_content: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, (_, content)) in added.iter().enumerate() {
+        added_by_content
+            .entry(content.trim())
+            .or_default()
+            .push(idx);
+    }
+
+    let mut blocks = Vec::new();
+    let mut used_added: HashSet<usize> = HashSet::new();
+    let mut i = 0;
+
+    while i < removed.len() {
+        let content = removed[i].1.trim();
+        let candidates = if content.is_empty() {
+            None
+        } else {
+            added_by_content.get(content)
+        };
+
+        // Longest run of consecutive lines matching on both sides, starting here
+        let mut best: Option<(usize, usize)> = None;
+        for &j in candidates.into_iter().flatten() {
+            let mut len = 0;
+            while i + len < removed.len()
+                && j + len < added.len()
+                && !used_added.contains(&(j + len))
+                && removed[i + len].1.trim() == added[j + len].1.trim()
+                && (len == 0
+                    || (removed[i + len].0 == removed[i + len - 1].0 + 1
+                        && added[j + len].0 == added[j + len - 1].0 + 1))
+            {
+                len += 1;
+            }
+            if len >= MIN_MOVED_BLOCK_LINES && best.is_none_or(|(_, best_len)| len > best_len) {
+                best = Some((j, len));
+            }
+        }
+
+        match best {
+            Some((j, len)) => {
+                blocks.push(MovedBlock {
+                    id: blocks.len(),
+                    old_start: removed[i].0,
+                    new_start: added[j].0,
+                    len: len as u32,
+                });
+                used_added.extend(j..j + len);
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+
+    blocks
+}
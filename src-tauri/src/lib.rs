@@ -1,10 +1,84 @@
+mod archive;
+mod cli;
 mod commands;
+mod comments;
+mod csv_diff;
 mod delta;
 mod diff;
+mod diff_engine;
+mod editorconfig;
+mod export;
+mod fallback;
+mod git;
+mod hex;
+mod hunks;
+mod merge;
+mod normalize;
+mod notebook;
+mod numbers;
+mod paths;
+mod pretty;
+mod remote;
+mod review;
+mod syntax;
+mod temp;
+#[cfg(test)]
+mod test_archive;
+#[cfg(test)]
+mod test_cli;
+#[cfg(test)]
+mod test_delta;
 #[cfg(test)]
 mod test_diff;
+#[cfg(test)]
+mod test_diff_engine;
+#[cfg(test)]
+mod test_editorconfig;
+#[cfg(test)]
+mod test_export;
+#[cfg(test)]
+mod test_fallback;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(test)]
+mod test_git;
+#[cfg(test)]
+mod test_hunks;
+#[cfg(test)]
+mod test_merge;
+#[cfg(test)]
+mod test_normalize;
+#[cfg(test)]
+mod test_notebook;
+#[cfg(test)]
+mod test_numbers;
+#[cfg(test)]
+mod test_paths;
+#[cfg(test)]
+mod test_pretty;
+#[cfg(test)]
+mod test_remote;
+#[cfg(test)]
+mod test_review;
+#[cfg(test)]
+mod test_syntax;
+#[cfg(test)]
+mod test_tools;
+mod tools;
 
-use commands::{check_delta, get_app_args, get_diff, get_file_tree, read_file_content};
+use commands::{
+    ansi_to_classed_html, ansi_to_html_command, cancel_comparison, check_delta,
+    check_mixed_line_endings, classify_comparison, compare_pairs_raw, compare_streaming,
+    diff_comparisons, diff_fingerprint, estimate_comparison, export_comparison_json, export_patch,
+    export_review_json, get_ansi_stylesheet, get_app_args, get_both_highlighted, get_branch_diff,
+    get_capabilities, get_change_heatmap, get_changed_extensions, get_changed_lines,
+    get_cross_repo_diff, get_diff, get_diff_both_modes, get_diff_legend, get_diff_lines,
+    get_diff_page, get_diff_preview, get_diff_stat, get_diff_vs_archive_entry,
+    get_extension_breakdown, get_file_tree, get_file_tree_binary, get_git_diff,
+    get_identical_files, get_launch_config, get_line_hashes, get_merge_tree, get_self_region_diff,
+    get_unchanged_ranges, import_recursive_diff, open_file_diff, prioritize_review,
+    read_file_content, review_directories, summarize_file_diff, verify_patch,
+};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -13,10 +87,53 @@ pub fn run() {
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
             get_file_tree,
+            get_file_tree_binary,
+            get_merge_tree,
+            compare_streaming,
+            review_directories,
+            cancel_comparison,
+            get_identical_files,
+            get_branch_diff,
+            get_git_diff,
+            get_extension_breakdown,
+            get_changed_extensions,
+            get_change_heatmap,
+            get_diff_stat,
+            prioritize_review,
+            estimate_comparison,
+            classify_comparison,
+            diff_comparisons,
+            export_comparison_json,
+            export_patch,
+            export_review_json,
+            compare_pairs_raw,
+            import_recursive_diff,
             get_diff,
+            open_file_diff,
+            get_diff_page,
+            get_diff_both_modes,
+            get_diff_preview,
+            summarize_file_diff,
+            diff_fingerprint,
+            get_diff_lines,
+            get_changed_lines,
+            get_unchanged_ranges,
+            get_cross_repo_diff,
+            get_diff_vs_archive_entry,
+            get_self_region_diff,
             read_file_content,
+            get_both_highlighted,
+            verify_patch,
+            check_mixed_line_endings,
+            get_line_hashes,
             check_delta,
+            get_capabilities,
             get_app_args,
+            get_launch_config,
+            ansi_to_html_command,
+            ansi_to_classed_html,
+            get_ansi_stylesheet,
+            get_diff_legend,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
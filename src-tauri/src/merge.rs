@@ -0,0 +1,174 @@
+use crate::diff::{
+    build_file_tree_with_options, check_cancelled, compile_ignore, contents_differ, excluded_paths,
+    scan_dir, CompareOptions, DiffError, FileEntry, FileStatus, FileTreeNode,
+};
+use crate::paths::{display_path, normalize_path};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// How a path changed on the left and right sides relative to the base
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum MergeStatus {
+    Unchanged,
+    /// Changed, added or deleted on the left only
+    ChangedLeft,
+    /// Changed, added or deleted on the right only
+    ChangedRight,
+    /// Changed the same way on both sides
+    ChangedBoth,
+    /// Changed differently on the two sides
+    Conflict,
+}
+
+/// A file in any of the three directories; a path is None where the file doesn't exist
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MergeEntry {
+    pub path: String,
+    pub name: String,
+    pub status: MergeStatus,
+    pub base_path: Option<String>,
+    pub left_path: Option<String>,
+    pub right_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MergeTreeNode {
+    pub name: String,
+    pub path: String,
+    /// None for directories
+    pub status: Option<MergeStatus>,
+    pub is_dir: bool,
+    pub children: Vec<MergeTreeNode>,
+    /// Conflicting files at or below this node
+    pub conflicts: usize,
+}
+
+/// Classify every file in `base`, `left` or `right` by how the two sides changed it from
+/// the base, in path order. A file missing from a side counts as a change there unless
+/// it's missing from the base too. Files are walked and compared under `options` as in a
+/// two-way comparison, and setting `cancel` stops the comparison with DiffError::Cancelled.
+pub fn compare_directories_three_way(
+    base: &Path,
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+) -> Result<Vec<MergeEntry>, DiffError> {
+    let ignore = compile_ignore(options)?;
+    let excluded = excluded_paths(options);
+    let scan = |dir: &Path| -> Result<HashMap<PathBuf, PathBuf>, DiffError> {
+        let files = scan_dir(&normalize_path(dir), ignore.as_deref(), &excluded, cancel)?;
+        Ok(files
+            .into_iter()
+            .map(|(relative, entry)| (relative, entry.into_path()))
+            .collect())
+    };
+    let (base_files, left_files, right_files) = (scan(base)?, scan(left)?, scan(right)?);
+    let all: HashSet<&PathBuf> = base_files
+        .keys()
+        .chain(left_files.keys())
+        .chain(right_files.keys())
+        .collect();
+
+    // Same content under the options, or missing from both
+    let same = |a: Option<&PathBuf>, b: Option<&PathBuf>| -> Result<bool, DiffError> {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                check_cancelled(cancel)?;
+                Ok(!contents_differ(a, b, options)?)
+            }
+            (a, b) => Ok(a.is_none() && b.is_none()),
+        }
+    };
+    let path_string = |path: Option<&PathBuf>| path.map(|p| display_path(p));
+
+    let mut entries = Vec::new();
+    for relative in all {
+        let (base_path, left_path, right_path) = (
+            base_files.get(relative),
+            left_files.get(relative),
+            right_files.get(relative),
+        );
+        let status = match (same(base_path, left_path)?, same(base_path, right_path)?) {
+            (true, true) => MergeStatus::Unchanged,
+            (false, true) => MergeStatus::ChangedLeft,
+            (true, false) => MergeStatus::ChangedRight,
+            (false, false) if same(left_path, right_path)? => MergeStatus::ChangedBoth,
+            (false, false) => MergeStatus::Conflict,
+        };
+
+        entries.push(MergeEntry {
+            path: relative.to_string_lossy().to_string(),
+            name: relative
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            status,
+            base_path: path_string(base_path),
+            left_path: path_string(left_path),
+            right_path: path_string(right_path),
+        });
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Tree of the changed entries, laid out as build_file_tree_with_options lays out a
+/// comparison, each directory counting the conflicts below it
+pub fn build_merge_tree(entries: &[MergeEntry], options: &CompareOptions) -> Vec<MergeTreeNode> {
+    let changed: Vec<FileEntry> = entries
+        .iter()
+        .filter(|e| e.status != MergeStatus::Unchanged)
+        .map(|e| FileEntry {
+            path: e.path.clone(),
+            name: e.name.clone(),
+            status: FileStatus::Modified,
+            is_dir: false,
+            left_path: e.left_path.clone(),
+            right_path: e.right_path.clone(),
+            cross_file_move_hint: None,
+            mixed_line_endings: false,
+            rename_info: None,
+            skip_content: false,
+            truncated_continuation: false,
+            is_binary: false,
+        })
+        .collect();
+    let statuses: HashMap<&str, MergeStatus> = entries
+        .iter()
+        .map(|e| (e.path.as_str(), e.status))
+        .collect();
+    build_file_tree_with_options(&changed, options)
+        .into_iter()
+        .map(|node| merge_node(node, &statuses))
+        .collect()
+}
+
+/// A comparison tree node with its files' merge statuses in place of their two-way ones
+fn merge_node(node: FileTreeNode, statuses: &HashMap<&str, MergeStatus>) -> MergeTreeNode {
+    let children: Vec<MergeTreeNode> = node
+        .children
+        .into_iter()
+        .map(|child| merge_node(child, statuses))
+        .collect();
+    let status = if node.is_dir {
+        None
+    } else {
+        statuses.get(node.path.as_str()).copied()
+    };
+    let conflicts = match status {
+        Some(MergeStatus::Conflict) => 1,
+        _ => children.iter().map(|child| child.conflicts).sum(),
+    };
+    MergeTreeNode {
+        name: node.name,
+        path: node.path,
+        status,
+        is_dir: node.is_dir,
+        children,
+        conflicts,
+    }
+}
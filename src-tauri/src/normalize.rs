@@ -0,0 +1,157 @@
+use crate::comments::{code_lines, CommentStyle};
+use crate::numbers::normalize_numbers;
+use crate::pretty::pretty_print_pair;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Rewrites both sides of a file comparison to a canonical form, so that differences that
+/// don't matter disappear (e.g. key order in a `.properties` file). The built-in ones are
+/// switched on by option flags; others go in the options' `normalizers` list and run after
+/// those (see CompareOptions::active_normalizers and DiffOptions::active_normalizers).
+pub trait Normalizer: Send + Sync + fmt::Debug {
+    /// The old and new texts in canonical form, or None to leave them as they are
+    fn normalize(
+        &self,
+        left: &Path,
+        right: &Path,
+        left_text: &str,
+        right_text: &str,
+    ) -> Option<(String, String)>;
+}
+
+/// Run `normalizers` over two texts in order. None when none of them applied.
+pub fn normalize_pair(
+    normalizers: &[Arc<dyn Normalizer>],
+    left: &Path,
+    right: &Path,
+    left_text: &str,
+    right_text: &str,
+) -> Option<(String, String)> {
+    let mut normalized: Option<(String, String)> = None;
+    for normalizer in normalizers {
+        let (l, r) = normalized
+            .as_ref()
+            .map_or((left_text, right_text), |(l, r)| (l.as_str(), r.as_str()));
+        if let Some(pair) = normalizer.normalize(left, right, l, r) {
+            normalized = Some(pair);
+        }
+    }
+    normalized
+}
+
+/// Rewrites one file's text
+type Rule = fn(&str) -> String;
+
+/// Rules by lowercase extension, or by name without the leading dot for dotfiles such as
+/// `.gitmodules`. To add one, write the rewrite and list it here.
+const FILE_TYPE_RULES: &[(&str, Rule)] =
+    &[("properties", sort_lines), ("gitmodules", sort_sections)];
+
+/// `normalize_by_type`: the rule in FILE_TYPE_RULES for the new file's type, if any
+#[derive(Debug)]
+pub struct FileTypeNormalizer;
+
+impl Normalizer for FileTypeNormalizer {
+    fn normalize(
+        &self,
+        _left: &Path,
+        right: &Path,
+        left_text: &str,
+        right_text: &str,
+    ) -> Option<(String, String)> {
+        let key = match right.extension() {
+            Some(ext) => ext.to_string_lossy().to_lowercase(),
+            None => right
+                .file_name()?
+                .to_string_lossy()
+                .strip_prefix('.')?
+                .to_lowercase(),
+        };
+        let (_, rule) = FILE_TYPE_RULES.iter().find(|(k, _)| *k == key)?;
+        Some((rule(left_text), rule(right_text)))
+    }
+}
+
+/// `pretty_print_before_diff`: minified JSON pretty-printed (see pretty_print_pair)
+#[derive(Debug)]
+pub struct JsonNormalizer;
+
+impl Normalizer for JsonNormalizer {
+    fn normalize(
+        &self,
+        left: &Path,
+        right: &Path,
+        left_text: &str,
+        right_text: &str,
+    ) -> Option<(String, String)> {
+        pretty_print_pair(left, right, left_text, right_text)
+    }
+}
+
+/// `normalize_numbers`: decimal literals in one spelling (see normalize_numbers)
+#[derive(Debug)]
+pub struct NumberNormalizer;
+
+impl Normalizer for NumberNormalizer {
+    fn normalize(
+        &self,
+        _left: &Path,
+        _right: &Path,
+        left_text: &str,
+        right_text: &str,
+    ) -> Option<(String, String)> {
+        Some((normalize_numbers(left_text), normalize_numbers(right_text)))
+    }
+}
+
+/// `ignore_comments`: whole-line comments dropped, in languages known from the new
+/// file's name (see CommentStyle::for_path)
+#[derive(Debug)]
+pub struct CommentNormalizer;
+
+impl Normalizer for CommentNormalizer {
+    fn normalize(
+        &self,
+        _left: &Path,
+        right: &Path,
+        left_text: &str,
+        right_text: &str,
+    ) -> Option<(String, String)> {
+        let style = CommentStyle::for_path(right)?;
+        let code = |text| {
+            code_lines(text, style)
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect()
+        };
+        Some((code(left_text), code(right_text)))
+    }
+}
+
+/// `key=value` files: blank lines dropped and the rest sorted, so key order doesn't count
+fn sort_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = text
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.is_empty())
+        .collect();
+    lines.sort_unstable();
+    lines.iter().map(|l| format!("{}\n", l)).collect()
+}
+
+/// INI-style files: `[section]` blocks sorted as wholes, keeping each block's lines in order
+fn sort_sections(text: &str) -> String {
+    let mut sections: Vec<String> = Vec::new();
+    for line in text.lines().map(str::trim_end).filter(|l| !l.is_empty()) {
+        match sections.last_mut() {
+            Some(section) if !line.trim_start().starts_with('[') => {
+                section.push_str(line);
+                section.push('\n');
+            }
+            _ => sections.push(format!("{}\n", line)),
+        }
+    }
+    sections.sort_unstable();
+    sections.concat()
+}
@@ -0,0 +1,61 @@
+use serde_json::Value;
+use std::path::Path;
+
+/// The cell sources of a Jupyter notebook as one text, without outputs or execution counts
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotebookSource {
+    pub text: String,
+    /// Extension of the kernel's language (".py" when the notebook doesn't say), so delta
+    /// highlights the code cells
+    pub extension: String,
+}
+
+/// Line put before each cell; a `# %%` comment like jupytext's percent format
+fn cell_marker(index: usize, cell_type: &str) -> String {
+    format!("# %% [{}] {}\n", index + 1, cell_type)
+}
+
+/// A cell's `source`, which nbformat allows as one string or a list of lines
+fn cell_source(cell: &Value) -> Option<String> {
+    match cell.get("source")? {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(lines) => lines.iter().map(|l| l.as_str()).collect(),
+        _ => None,
+    }
+}
+
+/// Parse a notebook into its cell sources in order, each after a boundary marker.
+/// None when the content isn't a notebook.
+pub fn notebook_source(content: &str) -> Option<NotebookSource> {
+    let notebook: Value = serde_json::from_str(content).ok()?;
+    let cells = notebook.get("cells")?.as_array()?;
+
+    let mut text = String::new();
+    for (index, cell) in cells.iter().enumerate() {
+        let cell_type = cell
+            .get("cell_type")
+            .and_then(Value::as_str)
+            .unwrap_or("code");
+        text.push_str(&cell_marker(index, cell_type));
+        let source = cell_source(cell)?;
+        text.push_str(&source);
+        if !source.is_empty() && !source.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+
+    let extension = notebook
+        .pointer("/metadata/language_info/file_extension")
+        .and_then(Value::as_str)
+        .filter(|ext| ext.starts_with('.') && ext.len() > 1)
+        .unwrap_or(".py")
+        .to_string();
+
+    Some(NotebookSource { text, extension })
+}
+
+/// Both sides as notebooks, or None if either can't be read or parsed
+pub fn read_notebooks(left: &Path, right: &Path) -> Option<(NotebookSource, NotebookSource)> {
+    let read = |path: &Path| notebook_source(&std::fs::read_to_string(path).ok()?);
+    Some((read(left)?, read(right)?))
+}
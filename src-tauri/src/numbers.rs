@@ -0,0 +1,134 @@
+/// Rewrite decimal literals in `text` to one canonical spelling, so `1.50` and `1.5`, or
+/// `2.0E+03` and `2e3`, read the same. Only formatting changes; values are never converted.
+///
+/// To leave version strings, identifiers and hex alone, a literal is only touched when:
+/// - it has a fraction or an exponent (plain integers are kept as written),
+/// - it doesn't follow a letter, digit, `_` or `.` (so `v1.50` and the `50` in `1.2.50` stay),
+/// - it isn't followed by a letter, digit, `_`, or a `.` or `-` that continues the token
+///   (so `1.50.2`, `1.5x` and `1.0-rc` stay, while the full stop in `was 1.50.` doesn't matter).
+///
+/// Canonical form: trailing fraction zeros dropped (along with the point if none remain),
+/// a lowercase `e`, no `+` or leading zeros in the exponent, and no zero exponent.
+pub fn normalize_numbers(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let starts_token = bytes[i].is_ascii_digit() && (i == 0 || !is_word_byte(bytes[i - 1]));
+        if !starts_token {
+            i += 1;
+            continue;
+        }
+        match scan_literal(bytes, i) {
+            Some(literal) => {
+                if let Some(canonical) = literal.canonical(text) {
+                    out.push_str(&text[copied..i]);
+                    out.push_str(&canonical);
+                    copied = literal.end;
+                }
+                i = literal.end;
+            }
+            // Part of a longer token: skip all of it
+            None => {
+                while i < bytes.len() && is_word_byte(bytes[i]) {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Byte ranges of a decimal literal's parts
+struct Literal {
+    int: (usize, usize),
+    fraction: Option<(usize, usize)>,
+    exponent: Option<(usize, usize)>,
+    negative_exponent: bool,
+    end: usize,
+}
+
+impl Literal {
+    fn canonical(&self, text: &str) -> Option<String> {
+        if self.fraction.is_none() && self.exponent.is_none() {
+            return None;
+        }
+        let mut canonical = text[self.int.0..self.int.1].to_string();
+        if let Some((start, end)) = self.fraction {
+            let digits = text[start..end].trim_end_matches('0');
+            if !digits.is_empty() {
+                canonical.push('.');
+                canonical.push_str(digits);
+            }
+        }
+        if let Some((start, end)) = self.exponent {
+            let digits = text[start..end].trim_start_matches('0');
+            if !digits.is_empty() {
+                canonical.push('e');
+                if self.negative_exponent {
+                    canonical.push('-');
+                }
+                canonical.push_str(digits);
+            }
+        }
+        Some(canonical)
+    }
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || !b.is_ascii()
+}
+
+/// Parse `digits[.digits][(e|E)[+|-]digits]` at `start`, or None when what follows the
+/// longest match shows it's part of a larger token
+fn scan_literal(bytes: &[u8], start: usize) -> Option<Literal> {
+    let digits_end = |from: usize| {
+        from + bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+
+    let int_end = digits_end(start);
+    let mut end = int_end;
+    let mut fraction = None;
+    if bytes.get(end) == Some(&b'.') && bytes.get(end + 1).is_some_and(u8::is_ascii_digit) {
+        let fraction_end = digits_end(end + 1);
+        fraction = Some((end + 1, fraction_end));
+        end = fraction_end;
+    }
+
+    let mut exponent = None;
+    let mut negative_exponent = false;
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        if bytes.get(end + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            negative_exponent = bytes[end + 1] == b'-';
+            let exponent_end = digits_end(end + 1 + sign);
+            exponent = Some((end + 1 + sign, exponent_end));
+            end = exponent_end;
+        }
+    }
+
+    let continues = match bytes.get(end) {
+        Some(b'.') => bytes.get(end + 1).is_some_and(|&b| is_word_byte(b)),
+        Some(b'-') => bytes.get(end + 1).is_some_and(u8::is_ascii_alphabetic),
+        Some(&b) => is_word_byte(b),
+        None => false,
+    };
+    if continues {
+        return None;
+    }
+
+    Some(Literal {
+        int: (start, int_end),
+        fraction,
+        exponent,
+        negative_exponent,
+        end,
+    })
+}
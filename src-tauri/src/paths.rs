@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Prefix an absolute Windows path with `\\?\` (or `\\?\UNC\` for `\\server\share`)
+/// so it isn't limited to MAX_PATH (260 chars)
+#[cfg(any(windows, test))]
+pub fn extended_length_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        path.to_string()
+    } else if let Some(unc) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
+    } else {
+        // The extended prefix disables "/" translation, so normalize separators first
+        format!(r"\\?\{}", path.replace('/', r"\"))
+    }
+}
+
+/// Make a path safe for `std::fs`/WalkDir regardless of length. On Windows this makes
+/// the path absolute and adds the extended-length prefix; elsewhere it's a no-op.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        if let Ok(absolute) = std::path::absolute(path) {
+            return PathBuf::from(extended_length_path(&absolute.to_string_lossy()));
+        }
+    }
+    path.to_path_buf()
+}
+
+/// How a path is shown and stored in entries. On Windows the prefix normalize_path adds
+/// is dropped again where the path doesn't need it (see simplified_path).
+pub fn display_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        simplified_path(&path)
+    } else {
+        path.to_string()
+    }
+}
+
+/// Undo extended_length_path like `dunce::simplified`: a `\\?\C:\...` or `\\?\UNC\...`
+/// path that fits in MAX_PATH without its prefix loses it. Other paths (device paths,
+/// long ones) are kept as they are, since they only work prefixed.
+pub fn simplified_path(path: &str) -> String {
+    let plain = if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else {
+        match path.strip_prefix(r"\\?\") {
+            Some(rest)
+                if rest.get(1..3) == Some(r":\")
+                    && rest.starts_with(|c: char| c.is_ascii_alphabetic()) =>
+            {
+                rest.to_string()
+            }
+            _ => return path.to_string(),
+        }
+    };
+    if plain.len() < MAX_PATH {
+        plain
+    } else {
+        path.to_string()
+    }
+}
+
+/// Longest path Windows accepts without the extended-length prefix
+const MAX_PATH: usize = 260;
+
+/// `path` resolved against the current directory, so spawned tools never see a relative path
+pub fn absolute_path(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Deepest existing directory containing every input (a file counts as its parent
+/// directory), falling back to the temp directory
+pub fn common_ancestor(inputs: &[&Path]) -> PathBuf {
+    let mut common: Option<PathBuf> = None;
+    for input in inputs {
+        let absolute = absolute_path(input);
+        let dir = if absolute.is_dir() {
+            absolute
+        } else {
+            absolute.parent().map(Path::to_path_buf).unwrap_or(absolute)
+        };
+        common = Some(match common {
+            None => dir,
+            Some(prev) => prev
+                .components()
+                .zip(dir.components())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+
+    common
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Spawn `program` from the common ancestor of `inputs` rather than wherever the app was
+/// launched, so its output and any relative-path resolution don't depend on our CWD
+pub fn tool_command(program: &str, inputs: &[&Path]) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.current_dir(common_ancestor(inputs));
+    cmd
+}
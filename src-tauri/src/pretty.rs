@@ -0,0 +1,91 @@
+use std::path::Path;
+
+/// Lines longer than this make a file count as minified
+const MINIFIED_LINE_LENGTH: usize = 1000;
+
+/// JSON by file extension
+pub fn is_json_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Everything on one line (a trailing newline aside), or some line too long to read
+pub fn is_minified(text: &str) -> bool {
+    let text = text.trim_end();
+    !text.is_empty()
+        && (!text.contains('\n') || text.lines().any(|l| l.len() > MINIFIED_LINE_LENGTH))
+}
+
+/// Reformat a JSON document with one member or element per line and two-space indents,
+/// keeping keys, numbers and string escapes exactly as written. None when it isn't valid JSON.
+pub fn pretty_print_json(text: &str) -> Option<String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(text).ok()?;
+
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut depth = 0usize;
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' | '[' => {
+                out.push(c);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                let close = if c == '{' { '}' } else { ']' };
+                // Empty containers stay on one line
+                if chars.next_if_eq(&close).is_some() {
+                    out.push(close);
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+
+    out.push('\n');
+    Some(out)
+}
+
+/// Both sides pretty-printed when either is a minified JSON file and both parse
+pub fn pretty_print_pair(
+    left: &Path,
+    right: &Path,
+    left_text: &str,
+    right_text: &str,
+) -> Option<(String, String)> {
+    let recognized = is_json_path(left) || is_json_path(right);
+    if !recognized || !(is_minified(left_text) || is_minified(right_text)) {
+        return None;
+    }
+    Some((
+        pretty_print_json(left_text)?,
+        pretty_print_json(right_text)?,
+    ))
+}
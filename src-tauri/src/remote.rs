@@ -0,0 +1,164 @@
+#[cfg(feature = "remote")]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "remote")]
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "remote")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "remote")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "remote")]
+use std::time::Duration;
+use thiserror::Error;
+
+/// Largest remote file downloaded for a diff
+pub const MAX_REMOTE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// How long a whole download may take
+#[cfg(feature = "remote")]
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The app's identifier, naming its directory in the user's cache directory (as Tauri's
+/// app_cache_dir does)
+#[cfg(feature = "remote")]
+const APP_IDENTIFIER: &str = "com.yxr.diff-rust";
+
+#[derive(Debug, Error)]
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+pub enum RemoteError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(not(feature = "remote"))]
+    #[error("comparing against a URL needs the `remote` feature: {0}")]
+    Disabled(String),
+    #[error("download of {url} failed: {message}")]
+    Http { url: String, message: String },
+    #[error("{url} is larger than {limit} bytes")]
+    TooLarge { url: String, limit: u64 },
+}
+
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Local file for a diff input: http(s) URLs are downloaded (or taken from the cache),
+/// anything else is returned as-is
+pub fn resolve_input(path: &str) -> Result<PathBuf, RemoteError> {
+    if is_remote(path) {
+        fetch(path, MAX_REMOTE_BYTES)
+    } else {
+        Ok(PathBuf::from(path))
+    }
+}
+
+/// Where a download of `url` and its ETag are cached, under the user's cache directory
+/// (the temp dir where there's none). The file keeps the URL's file name so delta can
+/// still pick a syntax from the extension.
+#[cfg(feature = "remote")]
+fn cache_paths(url: &str) -> (PathBuf, PathBuf) {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let dir = dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_IDENTIFIER)
+        .join("remote")
+        .join(format!("{:016x}", hasher.finish()));
+
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|n| !n.is_empty() && !n.contains(':'))
+        .unwrap_or("download");
+
+    (dir.join(name), dir.join("etag"))
+}
+
+#[cfg(feature = "remote")]
+static PARTIAL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Write `content` to `path` through a uniquely named file beside it, so a concurrent
+/// fetch of the same URL never sees a partly written copy
+#[cfg(feature = "remote")]
+fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let partial = path.with_file_name(format!(
+        ".{}.{}-{}.part",
+        name,
+        std::process::id(),
+        PARTIAL_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&partial, content)?;
+    std::fs::rename(&partial, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&partial);
+    })
+}
+
+#[cfg(not(feature = "remote"))]
+pub(crate) fn fetch(url: &str, _limit: u64) -> Result<PathBuf, RemoteError> {
+    Err(RemoteError::Disabled(url.to_string()))
+}
+
+/// Download `url` into the cache, revalidating a cached copy with its ETag first
+#[cfg(feature = "remote")]
+pub(crate) fn fetch(url: &str, limit: u64) -> Result<PathBuf, RemoteError> {
+    use reqwest::header::{ETAG, IF_NONE_MATCH};
+    use std::io::Read;
+
+    let http_error = |e: reqwest::Error| RemoteError::Http {
+        url: url.to_string(),
+        message: e.to_string(),
+    };
+    let too_large = || RemoteError::TooLarge {
+        url: url.to_string(),
+        limit,
+    };
+
+    let (file, etag_file) = cache_paths(url);
+    let cached_etag = std::fs::read_to_string(&etag_file)
+        .ok()
+        .filter(|_| file.is_file());
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(REMOTE_TIMEOUT)
+        .build()
+        .map_err(http_error)?;
+    let mut request = client.get(url);
+    if let Some(etag) = &cached_etag {
+        request = request.header(IF_NONE_MATCH, etag.as_str());
+    }
+    let response = request.send().map_err(http_error)?;
+
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED && cached_etag.is_some() {
+        return Ok(file);
+    }
+    let response = response.error_for_status().map_err(http_error)?;
+    if response.content_length().is_some_and(|len| len > limit) {
+        return Err(too_large());
+    }
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    // Content-Length can be missing or wrong, so cap the read itself too
+    let mut body = Vec::new();
+    response.take(limit + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > limit {
+        return Err(too_large());
+    }
+
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_atomic(&file, &body)?;
+    match etag {
+        Some(etag) => write_atomic(&etag_file, etag.as_bytes())?,
+        None => {
+            let _ = std::fs::remove_file(&etag_file);
+        }
+    }
+
+    Ok(file)
+}
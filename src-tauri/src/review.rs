@@ -0,0 +1,105 @@
+use crate::delta::{generate_file_diff_bundle, DiffOptions, FileDiffBundle};
+use crate::diff::{
+    classify_entries, CompareOptions, ComparisonCounts, DiffError, FileEntry, FileStatus,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ReviewOptions {
+    pub compare: CompareOptions,
+    /// Diff every changed file once the scan is done, reporting each as it's ready
+    pub precompute_diffs: bool,
+    /// Options for the precomputed diffs
+    pub diff: DiffOptions,
+}
+
+/// One precomputed diff, with how many of the `total` files are done so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileDiffReady {
+    pub path: String,
+    pub bundle: Option<FileDiffBundle>,
+    /// Why the file couldn't be diffed; bundle is None then
+    pub error: Option<String>,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Totals of a finished review
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReviewSummary {
+    pub counts: ComparisonCounts,
+    pub diffs_ready: usize,
+    pub diff_errors: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum ReviewEvent {
+    /// A changed file, as soon as it's classified (unsorted)
+    Entry(FileEntry),
+    DiffReady(FileDiffReady),
+    Complete(ReviewSummary),
+}
+
+/// Compare two directories and, with precompute_diffs, diff each changed file in path order,
+/// handing every step to `emit`: entries first, then ready diffs, then the summary. Setting
+/// `cancel` stops it between files with DiffError::Cancelled, and no Complete is sent.
+pub fn run_review(
+    left_dir: &Path,
+    right_dir: &Path,
+    options: &ReviewOptions,
+    cancel: &AtomicBool,
+    emit: &mut dyn FnMut(ReviewEvent),
+) -> Result<ReviewSummary, DiffError> {
+    let mut summary = ReviewSummary::default();
+    let mut changed = Vec::new();
+    classify_entries(
+        left_dir,
+        right_dir,
+        &options.compare,
+        cancel,
+        &mut |entry| {
+            summary.counts.count(&entry);
+            if entry.status != FileStatus::Unchanged {
+                if options.precompute_diffs && !entry.skip_content {
+                    changed.push(entry.clone());
+                }
+                emit(ReviewEvent::Entry(entry));
+            }
+        },
+    )?;
+
+    changed.sort_by(|a, b| a.path.cmp(&b.path));
+    let total = changed.len();
+    for (done, entry) in changed.into_iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(DiffError::Cancelled);
+        }
+
+        let left = entry.left_path.as_deref().map(Path::new);
+        let right = entry.right_path.as_deref().map(Path::new);
+        let (bundle, error) = match generate_file_diff_bundle(left, right, &options.diff) {
+            Ok(mut bundle) => {
+                bundle.diff.rename_info = entry.rename_info;
+                summary.diffs_ready += 1;
+                (Some(bundle), None)
+            }
+            Err(e) => {
+                summary.diff_errors += 1;
+                (None, Some(e.to_string()))
+            }
+        };
+        emit(ReviewEvent::DiffReady(FileDiffReady {
+            path: entry.path,
+            bundle,
+            error,
+            done: done + 1,
+            total,
+        }));
+    }
+
+    emit(ReviewEvent::Complete(summary.clone()));
+    Ok(summary)
+}
@@ -0,0 +1,140 @@
+use crate::hunks::{DiffLine, DiffLineKind};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A run of text on one line with an optional token kind ("keyword", "string", ...)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub kind: Option<String>,
+}
+
+/// Replace each line's tokens with syntax tokens from the full old/new file, when the
+/// file type is supported. Removed lines come from the old file, the rest from the new one.
+pub fn apply_syntax_tokens(lines: &mut [DiffLine], left: Option<&Path>, right: Option<&Path>) {
+    let highlight = |path: Option<&Path>| -> Option<Vec<Vec<Token>>> {
+        let path = path?;
+        let source = std::fs::read_to_string(path).ok()?;
+        highlight_lines(&path.file_name()?.to_string_lossy(), &source)
+    };
+    let old_tokens = highlight(left);
+    let new_tokens = highlight(right);
+
+    for line in lines.iter_mut() {
+        let (tokens, line_num) = match line.kind {
+            DiffLineKind::Removed => (&old_tokens, line.old_line),
+            DiffLineKind::Added | DiffLineKind::Context => (&new_tokens, line.new_line),
+        };
+        let line_tokens = tokens
+            .as_ref()
+            .zip(line_num)
+            .and_then(|(tokens, n)| tokens.get((n as usize).checked_sub(1)?));
+        if let Some(line_tokens) = line_tokens {
+            line.tokens = line_tokens.clone();
+        }
+    }
+}
+
+/// Split `source` into per-line syntax tokens, or None when the file type isn't supported
+#[cfg(not(feature = "tree-sitter"))]
+pub fn highlight_lines(_file_name: &str, _source: &str) -> Option<Vec<Vec<Token>>> {
+    None
+}
+
+/// Split `source` into per-line syntax tokens, or None when the file type isn't supported
+#[cfg(feature = "tree-sitter")]
+pub fn highlight_lines(file_name: &str, source: &str) -> Option<Vec<Vec<Token>>> {
+    let language: tree_sitter::Language = match Path::new(file_name).extension()?.to_str()? {
+        "rs" => tree_sitter_rust::LANGUAGE.into(),
+        "json" => tree_sitter_json::LANGUAGE.into(),
+        _ => return None,
+    };
+
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(source, None)?;
+
+    let mut spans = Vec::new();
+    collect_spans(tree.root_node(), &mut spans);
+
+    // Fill the gaps between classified spans (whitespace etc.) with plain text
+    let mut lines: Vec<Vec<Token>> = vec![Vec::new()];
+    let mut pos = 0;
+    for (start, end, kind) in spans {
+        if start < pos {
+            continue;
+        }
+        push_text(&mut lines, &source[pos..start], None);
+        push_text(&mut lines, &source[start..end], kind);
+        pos = end;
+    }
+    push_text(&mut lines, &source[pos..], None);
+
+    Some(lines)
+}
+
+/// Append text to the token lines, starting a new line at each newline
+#[cfg(feature = "tree-sitter")]
+fn push_text(lines: &mut Vec<Vec<Token>>, text: &str, kind: Option<&'static str>) {
+    for (i, part) in text.split('\n').enumerate() {
+        if i > 0 {
+            lines.push(Vec::new());
+        }
+        let part = part.trim_end_matches('\r');
+        if !part.is_empty() {
+            if let Some(line) = lines.last_mut() {
+                line.push(Token {
+                    text: part.to_string(),
+                    kind: kind.map(str::to_string),
+                });
+            }
+        }
+    }
+}
+
+/// Collect (start, end, kind) byte spans for leaf tokens in document order.
+/// Strings and comments are kept whole rather than split into their parts.
+#[cfg(feature = "tree-sitter")]
+fn collect_spans(node: tree_sitter::Node, spans: &mut Vec<(usize, usize, Option<&'static str>)>) {
+    let kind = node.kind();
+
+    let whole = if kind.contains("comment") {
+        Some("comment")
+    } else if matches!(
+        kind,
+        "string_literal" | "raw_string_literal" | "char_literal" | "string"
+    ) {
+        Some("string")
+    } else {
+        None
+    };
+
+    if whole.is_some() || node.child_count() == 0 {
+        let token_kind = whole.or_else(|| classify_leaf(node));
+        spans.push((node.start_byte(), node.end_byte(), token_kind));
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_spans(child, spans);
+    }
+}
+
+#[cfg(feature = "tree-sitter")]
+fn classify_leaf(node: tree_sitter::Node) -> Option<&'static str> {
+    let kind = node.kind();
+    match kind {
+        "integer_literal" | "float_literal" | "number" => Some("number"),
+        "true" | "false" | "null" => Some("constant"),
+        "type_identifier" | "primitive_type" => Some("type"),
+        "identifier" | "field_identifier" | "shorthand_field_identifier" => Some("identifier"),
+        "mutable_specifier" | "self" | "crate" | "super" => Some("keyword"),
+        // Anonymous word-like nodes are the grammar's keywords (fn, let, pub, ...)
+        _ if !node.is_named() && kind.chars().all(|c| c.is_ascii_alphabetic() || c == '_') => {
+            Some("keyword")
+        }
+        _ if !node.is_named() => Some("punctuation"),
+        _ => None,
+    }
+}
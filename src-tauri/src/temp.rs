@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A file written to its own temp directory, removed again when dropped
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A fresh, not yet created directory path under the temp dir
+fn unique_temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "diff-rust-{}-{}",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+impl TempFile {
+    /// Write `content` to a fresh temp file called `file_name`, so delta can still
+    /// pick a syntax from the extension
+    pub fn new(file_name: &str, content: &[u8]) -> std::io::Result<Self> {
+        let temp_dir = unique_temp_dir();
+        std::fs::create_dir_all(&temp_dir)?;
+        let path = temp_dir.join(file_name);
+        std::fs::write(&path, content)?;
+
+        Ok(TempFile { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+        if let Some(dir) = self.path.parent() {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+/// An empty temp directory, removed with everything in it when dropped
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> std::io::Result<Self> {
+        let path = unique_temp_dir();
+        std::fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
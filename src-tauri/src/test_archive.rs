@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+    use crate::archive::{extract_entry, ArchiveError};
+    use crate::commands::diff_vs_archive_entry;
+    use crate::delta::DiffOptions;
+    use crate::test_fixtures::temp_dir;
+    use std::path::Path;
+    use std::process::Command;
+
+    #[test]
+    fn test_diff_vs_archive_entry() {
+        let dir = temp_dir("archive");
+        std::fs::create_dir_all(dir.join("release/src")).unwrap();
+        std::fs::write(dir.join("release/src/main.rs"), "fn main() {}\n").unwrap();
+        let archive = dir.join("release.tar.gz");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(dir.join("release"))
+            .arg(".")
+            .status()
+            .expect("tar should be installed");
+        assert!(status.success());
+
+        // tar stores the entry as ./src/main.rs; either spelling finds it
+        let entry = extract_entry(&archive, "src/main.rs").unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(entry.path()).unwrap(),
+            "fn main() {}\n"
+        );
+        assert!(extract_entry(&archive, "./src/main.rs").unwrap().is_some());
+        assert!(extract_entry(&archive, "src/missing.rs").unwrap().is_none());
+        assert!(matches!(
+            extract_entry(&dir.join("release/src/main.rs"), "x"),
+            Err(ArchiveError::Unsupported(_))
+        ));
+        assert!(matches!(
+            extract_entry(&dir.join("none.zip"), "x"),
+            Err(ArchiveError::NotFound(_))
+        ));
+
+        let local = dir.join("main.rs");
+        std::fs::write(&local, "fn main() { run(); }\n").unwrap();
+        let diff = |local: &Path, entry: &str| {
+            diff_vs_archive_entry(
+                &local.to_string_lossy(),
+                &archive.to_string_lossy(),
+                entry,
+                &DiffOptions {
+                    fallback_renderer: true,
+                    ..Default::default()
+                },
+            )
+        };
+        for result in [
+            diff(&local, "src/main.rs"),
+            diff(&local, "src/missing.rs"),
+            diff(&dir.join("gone.rs"), "src/main.rs"),
+        ] {
+            assert!(result.unwrap().has_changes);
+        }
+        assert!(diff(&dir.join("gone.rs"), "src/missing.rs")
+            .unwrap_err()
+            .starts_with("Neither"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
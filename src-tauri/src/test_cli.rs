@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use crate::cli::parse_launch_args;
+
+    fn parse(args: &[&str]) -> Result<crate::cli::LaunchConfig, String> {
+        parse_launch_args(std::iter::once("diff-rust").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_parse_launch_args() {
+        let config = parse(&[]).unwrap();
+        assert_eq!((config.left, config.right), (None, None));
+        assert!(
+            config.options.side_by_side && config.options.line_numbers && config.options.collapsed
+        );
+
+        let positional = parse(&["/tmp/old", "/tmp/new"]).unwrap();
+        assert_eq!(positional.left.as_deref(), Some("/tmp/old"));
+        assert_eq!(positional.right.as_deref(), Some("/tmp/new"));
+
+        let flags = parse(&["--right", "/tmp/new", "--left", "/tmp/old"]).unwrap();
+        assert_eq!(
+            (flags.left, flags.right),
+            (positional.left, positional.right)
+        );
+
+        let options = parse(&[
+            "a",
+            "b",
+            "--inline",
+            "--full-context",
+            "--syntax-theme",
+            "Nord",
+        ])
+        .unwrap()
+        .options;
+        assert!(!options.side_by_side && !options.collapsed && options.line_numbers);
+        assert_eq!(options.syntax_theme.as_deref(), Some("Nord"));
+    }
+
+    #[test]
+    fn test_parse_launch_args_errors() {
+        let unknown = parse(&["a", "b", "--bogus"]).unwrap_err();
+        assert!(unknown.contains("--bogus"), "{}", unknown);
+
+        let one_side = parse(&["--left", "/tmp/old"]).unwrap_err();
+        assert!(one_side.contains("left and a right"), "{}", one_side);
+
+        let both_forms = parse(&["/tmp/a", "/tmp/b", "--left", "/tmp/c"]).unwrap_err();
+        assert!(both_forms.contains("cannot be used with"), "{}", both_forms);
+
+        let extra = parse(&["a", "b", "c"]).unwrap_err();
+        assert!(extra.contains("unexpected argument"), "{}", extra);
+    }
+}
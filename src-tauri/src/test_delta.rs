@@ -0,0 +1,1545 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{
+        ansi_stylesheet, ansi_to_html, ansi_to_html_classed, ansi_to_themed_html, apply_delta_env,
+        build_delta_command, change_fingerprint, diff_legend, display_width, fold_context,
+        generate_diff, generate_diff_both_modes, generate_diff_page, generate_diff_preview,
+        generate_file_diff_bundle, generate_region_diff, hunks_touching_lines, legend_from_ansi,
+        offset_hunk_headers, render_inline_output, run_unified_diff, run_with_input,
+        snap_to_tokens, split_side_by_side_output, strip_ansi_codes, summarize_diff,
+        trim_edge_context, truncate_unified_diff, DeltaError, DiffOptions, DiffTheme,
+        ElidedContext, ThemeMode, WordEmphasis,
+    };
+    use crate::diff::RenameInfo;
+    use crate::hex::{hex_dump, read_byte_range};
+    use crate::hunks::{detect_moved_blocks, diff_lines, hunk_chunks};
+    use crate::test_fixtures::{file_pair, temp_dir};
+    use std::path::Path;
+    use std::process::Command;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn command_args(options: &DiffOptions) -> Vec<String> {
+        build_delta_command(options)
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_theme_mode() {
+        let theme_args = |theme: ThemeMode| {
+            command_args(&DiffOptions {
+                theme,
+                ..Default::default()
+            })
+        };
+        assert!(command_args(&DiffOptions::default()).contains(&"--dark".to_string()));
+        let light = theme_args(ThemeMode::Light);
+        assert!(light.contains(&"--light".to_string()) && !light.contains(&"--dark".to_string()));
+
+        // Basic and 256-color codes 0-15 take the theme's palette; RGB colors are kept as given
+        let ansi = "\x1b[31mred\x1b[0m \x1b[38;5;1mred\x1b[0m \x1b[38;2;1;2;3mrgb\x1b[0m";
+        let dark = ansi_to_html(ansi, ThemeMode::Dark.palette());
+        let light = ansi_to_html(ansi, ThemeMode::Light.palette());
+        assert_eq!(dark.matches("color:#aa0000;").count(), 2);
+        assert_eq!(light.matches("color:#cf222e;").count(), 2);
+        assert!(dark.contains("color:#010203;") && light.contains("color:#010203;"));
+
+        // Auto follows the system appearance it's resolved against, and is dark unresolved
+        assert!(theme_args(ThemeMode::Auto.resolve(true)).contains(&"--light".to_string()));
+        assert!(theme_args(ThemeMode::Auto.resolve(false)).contains(&"--dark".to_string()));
+        assert!(theme_args(ThemeMode::Auto).contains(&"--dark".to_string()));
+        assert_eq!(ThemeMode::Auto.resolve(true), ThemeMode::Light);
+        assert_eq!(ThemeMode::Light.resolve(false), ThemeMode::Light);
+    }
+
+    #[test]
+    fn test_side_by_side_width() {
+        let width_arg = |width: Option<u32>| {
+            let args = command_args(&DiffOptions {
+                side_by_side: true,
+                width,
+                ..Default::default()
+            });
+            args[args.iter().position(|a| a == "--width").unwrap() + 1].clone()
+        };
+        assert_eq!(width_arg(None), "160");
+        assert_eq!(width_arg(Some(97)), "97");
+
+        // With an odd width one panel is a column wider; the split is still between the panels
+        for (left, right) in [("│  1 │ab", "│  1 │abc"), ("│  1 │abc", "│  1 │ab")]
+        {
+            let ansi = format!("{}{}", left, right);
+            let (left_html, right_html, _) =
+                split_side_by_side_output(&ansi, &[], &[], &DiffOptions::default()).unwrap();
+            let content = |html: &str| {
+                let text = html.split("line-content\">").nth(1).unwrap();
+                text[..text.find('<').unwrap()].trim().to_string()
+            };
+            assert_eq!(content(&left_html), left.rsplit('│').next().unwrap());
+            assert_eq!(content(&right_html), right.rsplit('│').next().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_syntax_theme_flag() {
+        let options = DiffOptions {
+            syntax_theme: Some("Monokai Extended".to_string()),
+            ..Default::default()
+        };
+
+        let args = command_args(&options);
+        let pos = args
+            .iter()
+            .position(|a| a == "--syntax-theme")
+            .expect("--syntax-theme should be passed to delta");
+        assert_eq!(args[pos + 1], "Monokai Extended");
+
+        let args = command_args(&DiffOptions::default());
+        assert!(!args.iter().any(|a| a == "--syntax-theme"));
+    }
+
+    #[test]
+    fn test_crlf_resave_detected() {
+        let dir = temp_dir("delta-crlf");
+        let left = dir.join("left.txt");
+        let right = dir.join("right.txt");
+        std::fs::write(&left, "one\ntwo\nthree\n").unwrap();
+        std::fs::write(&right, "one\r\ntwo\r\nthree\r\n").unwrap();
+
+        let result = generate_diff(Some(&left), Some(&right), &DiffOptions::default()).unwrap();
+        assert!(result.has_changes);
+        assert!(result.html.contains("apart from encoding/line endings"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_encoding_metadata_row() {
+        let dir = temp_dir("delta-encoding-meta");
+        let left = dir.join("left.txt");
+        let right = dir.join("right.txt");
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend("one\r\ntwo\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(&left, utf16).unwrap();
+        std::fs::write(&right, "one\ntwo\n").unwrap();
+
+        let options = DiffOptions {
+            encoding_metadata: true,
+            ..Default::default()
+        };
+        let result = generate_diff(Some(&left), Some(&right), &options).unwrap();
+        assert!(result
+            .html
+            .starts_with("<div class=\"diff-meta\">UTF-16LE, CRLF \u{2192} UTF-8, LF</div>"));
+        assert!(result.html.contains("apart from encoding/line endings"));
+
+        let result = generate_diff(Some(&left), Some(&right), &DiffOptions::default()).unwrap();
+        assert!(!result.html.contains("diff-meta"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_large_input_does_not_deadlock() {
+        // Far larger than any pipe buffer, so a write-then-read approach would hang
+        let input: Vec<u8> = "+a line of a very large diff\n"
+            .repeat(200_000)
+            .into_bytes();
+        let expected_len = input.len();
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(run_with_input(Command::new("cat"), input));
+        });
+
+        let output = rx
+            .recv_timeout(Duration::from_secs(30))
+            .expect("process I/O should not hang")
+            .unwrap();
+        assert_eq!(output.stdout.len(), expected_len);
+    }
+
+    #[test]
+    fn test_self_region_diff() {
+        let dir = temp_dir("delta-region");
+        let path = dir.join("blob.bin");
+        let bytes: Vec<u8> = (0u8..64).collect();
+        std::fs::write(&path, &bytes).unwrap();
+
+        // Overlapping regions: the second line of A is the first line of B
+        let a = read_byte_range(&path, 0, 32).unwrap();
+        let b = read_byte_range(&path, 16, 32).unwrap();
+        let dump_a = hex_dump(&a);
+        let dump_b = hex_dump(&b);
+        assert_eq!(dump_a.lines().count(), 2);
+        assert!(dump_a.starts_with("00000000: 0001 0203"));
+        assert_eq!(
+            &dump_a.lines().nth(1).unwrap()[10..],
+            &dump_b.lines().next().unwrap()[10..]
+        );
+
+        let err = read_byte_range(&path, 40, 32).unwrap_err();
+        assert!(matches!(err, DeltaError::InvalidRange(_)));
+        assert!(err.to_string().contains("64 bytes"));
+
+        // Rendered by delta, or the built-in layout without it
+        let options = DiffOptions {
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        assert!(
+            generate_region_diff(&path, (0, 32), (16, 32), &options)
+                .unwrap()
+                .has_changes
+        );
+        assert!(generate_region_diff(&path, (0, 32), (60, 8), &options).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_diff_pages() {
+        let diff_text = "--- a\n+++ b\n@@ -1 +1 @@\n-a\n+b\n@@ -9 +9 @@ f\n-c\n+d\n";
+        let (head, hunks) = hunk_chunks(diff_text);
+        assert_eq!(head, "--- a\n+++ b\n");
+        assert_eq!(
+            hunks.collect::<Vec<_>>(),
+            ["@@ -1 +1 @@\n-a\n+b\n", "@@ -9 +9 @@ f\n-c\n+d\n"]
+        );
+
+        let dir = temp_dir("delta-pages");
+        let left = dir.join("left.txt");
+        let right = dir.join("right.txt");
+        let old: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        // Four changes far enough apart to be separate hunks
+        let new = old
+            .replace("line 10\n", "ten\n")
+            .replace("line 30\n", "thirty\n")
+            .replace("line 50\n", "fifty\n")
+            .replace("line 70\n", "seventy\n");
+        std::fs::write(&left, &old).unwrap();
+        std::fs::write(&right, &new).unwrap();
+        let options = DiffOptions {
+            collapsed: true,
+            fallback_renderer: true,
+            ..Default::default()
+        };
+
+        let page = generate_diff_page(Some(&left), Some(&right), &options, (1, 3)).unwrap();
+        assert_eq!((page.total_hunks, page.start, page.end), (4, 1, 3));
+        assert_eq!(page.diff.hunk_count, 2);
+        let starts: Vec<u32> = page.diff.hunks.iter().map(|h| h.new_start).collect();
+        assert_eq!(starts, [27, 47]);
+        assert!(page.diff.html.contains("thirty") && !page.diff.html.contains("seventy"));
+
+        // Ranges past the end are clamped
+        let page = generate_diff_page(Some(&left), Some(&right), &options, (3, 10)).unwrap();
+        assert_eq!((page.start, page.end, page.diff.hunk_count), (3, 4, 1));
+
+        // Identical files have no hunks to page through
+        let page = generate_diff_page(Some(&left), Some(&left), &options, (0, 5)).unwrap();
+        assert_eq!((page.total_hunks, page.start, page.end), (0, 0, 0));
+        assert!(!page.diff.has_changes);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_context_lines() {
+        use crate::commands::get_diff_lines;
+        use crate::delta::MAX_CONTEXT_LINES;
+        use crate::hunks::DiffLineKind;
+
+        let with = |collapsed, context_lines| DiffOptions {
+            collapsed,
+            context_lines,
+            ..Default::default()
+        };
+        assert_eq!(with(true, None).diff_context(), 3);
+        assert_eq!(with(false, None).diff_context(), 99999);
+        assert_eq!(with(true, Some(5)).diff_context(), 5);
+        assert_eq!(with(false, Some(0)).diff_context(), 0);
+        assert_eq!(
+            with(false, Some(usize::MAX)).diff_context(),
+            MAX_CONTEXT_LINES
+        );
+
+        let dir = temp_dir("delta-context-lines");
+        let left = dir.join("left.txt");
+        let right = dir.join("right.txt");
+        let old: String = (1..=40).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(&left, &old).unwrap();
+        std::fs::write(&right, old.replace("line 20\n", "twenty\n")).unwrap();
+
+        let context = |options| {
+            let (left, right) = (left.to_str(), right.to_str());
+            let lines = get_diff_lines(left, right, options).unwrap();
+            lines
+                .iter()
+                .filter(|l| l.kind == DiffLineKind::Context)
+                .count()
+        };
+        assert_eq!(context(with(true, None)), 6);
+        assert_eq!(context(with(true, Some(10))), 20);
+        assert_eq!(context(with(false, None)), 39);
+
+        // The lines follow the diff options, like the rendered diff
+        std::fs::write(&right, old.replace("line 5\n", "line  5\n")).unwrap();
+        let changed = |ignore_whitespace| {
+            let options = DiffOptions {
+                ignore_whitespace,
+                ..with(true, None)
+            };
+            let lines = get_diff_lines(left.to_str(), right.to_str(), options).unwrap();
+            lines
+                .iter()
+                .filter(|l| l.kind != DiffLineKind::Context)
+                .count()
+        };
+        assert_eq!(changed(false), 2);
+        assert_eq!(changed(true), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_diff_preview_truncation() {
+        let dir = temp_dir("delta-preview");
+        let left = dir.join("left.txt");
+        let right = dir.join("right.txt");
+        let old: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        let new: String = (1..=100)
+            .map(|i| {
+                if i % 10 == 0 {
+                    format!("changed {}\n", i)
+                } else {
+                    format!("line {}\n", i)
+                }
+            })
+            .collect();
+        std::fs::write(&left, old).unwrap();
+        std::fs::write(&right, new).unwrap();
+
+        let diff_text = run_unified_diff(Some(&left), Some(&right), 3).unwrap();
+        let (preview, truncated) = truncate_unified_diff(&diff_text, 20);
+        assert!(truncated);
+
+        let body: Vec<&str> = preview
+            .lines()
+            .filter(|l| !l.starts_with("---") && !l.starts_with("+++"))
+            .collect();
+        // Every hunk header but the first renders as a separator row
+        assert!(body.len() - 1 <= 20);
+        // Opens on the first change rather than its leading context
+        assert!(body[0].starts_with("@@ -10,"));
+        assert_eq!(body[1], "-line 10");
+
+        let (_, truncated) = truncate_unified_diff(&diff_text, 1000);
+        assert!(!truncated);
+
+        let options = DiffOptions {
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let result = generate_diff_preview(Some(&left), Some(&right), 20, &options).unwrap();
+        assert!(result.truncated);
+        assert!(result.html.matches("class=\"diff-line\"").count() <= 20);
+
+        // Binary files get the same message as the full diff, not diff's text rendered
+        let (left_bin, right_bin) = (dir.join("old.bin"), dir.join("new.bin"));
+        std::fs::write(&left_bin, b"\0old").unwrap();
+        std::fs::write(&right_bin, b"\0new").unwrap();
+        let result =
+            generate_diff_preview(Some(&left_bin), Some(&right_bin), 20, &options).unwrap();
+        assert!(result.html.contains("Binary files differ"));
+        assert!(!result.truncated);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// One row of delta's side-by-side layout (without colors)
+    fn sbs_row(left: Option<(u32, &str)>, right: Option<(u32, &str)>) -> String {
+        let side = |s: Option<(u32, &str)>| match s {
+            Some((n, text)) => format!("│{:>3} │{:<20}", n, text),
+            None => format!("│{:>3} │{:<20}", "", ""),
+        };
+        format!("{}{}", side(left), side(right))
+    }
+
+    #[test]
+    fn test_align_moves_annotates_both_panels() {
+        // fn b() moved from the top to the bottom of the file
+        let diff_text = "\
+--- a.rs
++++ b.rs
+@@ -1,6 +1,6 @@
+-fn b() {
+-    2
+-}
+ fn a() {
+     1
+ }
++fn b() {
++    2
++}
+";
+        let moves = detect_moved_blocks(&diff_lines(diff_text));
+        assert_eq!(moves.len(), 1);
+        assert_eq!(
+            (moves[0].old_start, moves[0].new_start, moves[0].len),
+            (1, 4, 3)
+        );
+
+        let ansi = [
+            sbs_row(Some((1, "fn b() {")), None),
+            sbs_row(Some((2, "    2")), None),
+            sbs_row(Some((3, "}")), None),
+            sbs_row(Some((4, "fn a() {")), Some((1, "fn a() {"))),
+            sbs_row(Some((5, "    1")), Some((2, "    1"))),
+            sbs_row(Some((6, "}")), Some((3, "}"))),
+            sbs_row(None, Some((4, "fn b() {"))),
+            sbs_row(None, Some((5, "    2"))),
+            sbs_row(None, Some((6, "}"))),
+        ]
+        .join("\n");
+
+        // Each panel row as (its classes, the line number it shows)
+        let rows = |html: &str| -> Vec<(String, String)> {
+            html.split("<div class=\"diff-line")
+                .skip(1)
+                .map(|row| {
+                    let classes = row[..row.find('"').unwrap()].trim().to_string();
+                    let num = row
+                        .split_once("<span class=\"line-num\">")
+                        .map(|(_, rest)| strip_ansi_codes(&rest[..rest.find("</span>").unwrap()]))
+                        .unwrap_or_default();
+                    (classes, num.trim().to_string())
+                })
+                .collect()
+        };
+
+        let (left_html, right_html, _) =
+            split_side_by_side_output(&ansi, &moves, &[], &DiffOptions::default()).unwrap();
+        let (left, right) = (rows(&left_html), rows(&right_html));
+        assert_eq!(left.len(), 7);
+        assert_eq!(right.len(), 7);
+        // The new lines of the block sit beside the old ones, both marked as the same move
+        for k in 0..3 {
+            assert_eq!(left[k], ("moved".to_string(), (k + 1).to_string()));
+            assert_eq!(right[k], ("moved".to_string(), (k + 4).to_string()));
+        }
+        assert_eq!(left_html.matches("data-move-id=\"0\"").count(), 4);
+        // Context lines are not part of the move and keep their rows
+        for k in 3..6 {
+            assert_eq!(left[k], (String::new(), (k + 1).to_string()));
+            assert_eq!(right[k], (String::new(), (k - 2).to_string()));
+        }
+        // One row in both panels points at where the new lines went
+        assert_eq!(left[6].0, "move-anchor");
+        assert_eq!(right[6].0, "move-anchor");
+        assert!(right_html.contains("Lines 4–6 moved here are shown beside old line 1"));
+
+        let (left_html, right_html, _) =
+            split_side_by_side_output(&ansi, &[], &[], &DiffOptions::default()).unwrap();
+        assert!(!left_html.contains("moved"));
+        assert_eq!(rows(&right_html).len(), 9);
+        assert_eq!(rows(&right_html)[0], (String::new(), String::new()));
+    }
+
+    fn csv_options() -> DiffOptions {
+        DiffOptions {
+            csv: true,
+            fallback_renderer: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_csv_cell_changed() {
+        let dir = temp_dir("delta-csv-cell");
+        let left = dir.join("left.csv");
+        let right = dir.join("right.csv");
+        std::fs::write(
+            &left,
+            "name,city,note\nalice,Paris,\"hello, world\"\nbob,Oslo,x\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &right,
+            "name,city,note\nalice,Paris,\"hello, world\"\nbob,Bergen,x\n",
+        )
+        .unwrap();
+
+        let result = generate_diff(Some(&left), Some(&right), &csv_options()).unwrap();
+        assert!(result.has_changes);
+        assert_eq!(result.hunk_count, 1);
+        assert!(result.html.starts_with("<table class=\"csv-diff\">"));
+        // Only the one cell is highlighted; the quoted field stays a single cell
+        assert_eq!(result.html.matches("csv-cell-changed").count(), 1);
+        assert!(result.html.contains("<del>Oslo</del><ins>Bergen</ins>"));
+        assert!(result.html.contains("<td>hello, world</td>"));
+        assert_eq!(result.html.matches("csv-row-modified").count(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_csv_column_inserted() {
+        let dir = temp_dir("delta-csv-column");
+        let left = dir.join("left.csv");
+        let right = dir.join("right.csv");
+        std::fs::write(&left, "id,name\n1,alice\n2,bob\n").unwrap();
+        std::fs::write(&right, "id,age,name\n1,30,alice\n2,41,bob\n").unwrap();
+
+        let result = generate_diff(Some(&left), Some(&right), &csv_options()).unwrap();
+        assert!(result.has_changes);
+        // Rows still match by header name, so nothing is marked as a changed cell
+        assert!(!result.html.contains("csv-cell-changed"));
+        assert!(!result.html.contains("csv-row-modified"));
+        assert_eq!(
+            result.html.matches("<td class=\"csv-col-added\">").count(),
+            3
+        );
+        assert!(result
+            .html
+            .contains("<td>1</td><td class=\"csv-col-added\">30</td><td>alice</td>"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_csv_max_cell_width() {
+        let dir = temp_dir("delta-csv-cell-width");
+        let left = dir.join("left.csv");
+        let right = dir.join("right.csv");
+        std::fs::write(
+            &left,
+            "id,blob\n1,short\n2,a<b&c de fg hi\n3,日本語のテキスト\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &right,
+            "id,blob\n1,short\n2,a<b&c de fg hi\n3,日本語のテキスト\n4,x\n",
+        )
+        .unwrap();
+
+        let options = DiffOptions {
+            max_cell_width: Some(6),
+            ..csv_options()
+        };
+        let result = generate_diff(Some(&left), Some(&right), &options).unwrap();
+        // Short cells are left alone; long ones keep 5 columns plus the ellipsis
+        assert!(result.html.contains("<td>short</td>"));
+        assert!(result
+            .html
+            .contains("<td title=\"a&lt;b&amp;c de fg hi\">a&lt;b&amp;c…</td>"));
+        // Wide characters take two columns each
+        assert!(result
+            .html
+            .contains("<td title=\"日本語のテキスト\">日本…</td>"));
+
+        let result = generate_diff(Some(&left), Some(&right), &csv_options()).unwrap();
+        assert!(!result.html.contains("title="));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_csv_parse_error_falls_back_to_text() {
+        let dir = temp_dir("delta-csv-invalid");
+        let left = dir.join("left.csv");
+        let right = dir.join("right.csv");
+        std::fs::write(&left, "a,b\n1,2\n").unwrap();
+        std::fs::write(&right, [b'a', b',', b'b', b'\n', 0xff, 0xfe, b'\n']).unwrap();
+
+        // Invalid UTF-8 can't be read as CSV, so the text path handles (and rejects) it
+        match generate_diff(Some(&left), Some(&right), &csv_options()) {
+            Ok(result) => assert!(!result.html.contains("csv-diff")),
+            Err(e) => assert!(matches!(e, DeltaError::Utf8(_))),
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_delta_env_is_controlled() {
+        let run_env = |options: &DiffOptions| {
+            let mut cmd = Command::new("env");
+            // Stands in for a hostile variable inherited from the user's shell
+            cmd.env("DELTA_FEATURES", "+side-by-side navigate")
+                .env("TERM", "dumb");
+            apply_delta_env(&mut cmd, options);
+            String::from_utf8(cmd.output().unwrap().stdout).unwrap()
+        };
+
+        let env = run_env(&DiffOptions::default());
+        assert!(!env.contains("DELTA_FEATURES="));
+        assert!(env.lines().any(|l| l == "TERM=xterm-256color"));
+        assert!(command_args(&DiffOptions::default()).contains(&"--no-gitconfig".to_string()));
+
+        let options = DiffOptions {
+            inherit_env: true,
+            delta_env: [("DELTA_PAGER".to_string(), "cat".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let env = run_env(&options);
+        assert!(env
+            .lines()
+            .any(|l| l == "DELTA_FEATURES=+side-by-side navigate"));
+        assert!(env.lines().any(|l| l == "DELTA_PAGER=cat"));
+        assert!(!command_args(&options).contains(&"--no-gitconfig".to_string()));
+    }
+
+    #[test]
+    fn test_emit_line_ids() {
+        // Inline: context line 1, line 2 replaced
+        let ansi = "  1 ⋮  1 │ctx\n  2 ⋮    │old\n    ⋮  2 │new";
+        let options = DiffOptions {
+            emit_line_ids: true,
+            ..Default::default()
+        };
+        let (html, _) = render_inline_output(ansi, &[], &options);
+        assert!(html.contains(
+            "<div id=\"L1\" class=\"diff-line\"><span class=\"line-num\">  1 ⋮  1 </span>"
+        ));
+        assert!(html.contains(
+            "<div id=\"LR2\" class=\"diff-line\"><span class=\"line-num\">  2 ⋮    </span>"
+        ));
+        assert!(html.contains(
+            "<div id=\"L2\" class=\"diff-line\"><span class=\"line-num\">    ⋮  2 </span>"
+        ));
+        assert!(!render_inline_output(ansi, &[], &DiffOptions::default())
+            .0
+            .contains(" id="));
+
+        // Side-by-side: the old panel never reuses the new panel's ids
+        let ansi = [
+            sbs_row(Some((1, "ctx")), Some((1, "ctx"))),
+            sbs_row(Some((2, "old")), Some((2, "new"))),
+        ]
+        .join("\n");
+        let (left_html, right_html, _) =
+            split_side_by_side_output(&ansi, &[], &[], &options).unwrap();
+        assert!(left_html.contains("id=\"LR1\"") && left_html.contains("id=\"LR2\""));
+        assert!(right_html.contains("id=\"L1\"") && right_html.contains("id=\"L2\""));
+        assert!(!left_html.contains("id=\"L1\""));
+    }
+
+    #[test]
+    fn test_accessible_labels_lines() {
+        let options = DiffOptions {
+            accessible: true,
+            ..Default::default()
+        };
+        let ansi = "  1 ⋮  1 │ctx\n  2 ⋮    │old\n    ⋮  2 │new";
+        let (html, _) = render_inline_output(ansi, &[], &options);
+        assert!(html.starts_with("<div class=\"delta-output\" role=\"list\">"));
+        assert!(html.contains(concat!(
+            "<div role=\"listitem\" aria-label=\"Removed line\" class=\"diff-line\"><span class=\"line-num\">  2 ⋮    </span>",
+            "<span class=\"line-content\"><span class=\"diff-prefix\" data-prefix=\"-\"></span>old"
+        )));
+        assert!(html.contains("aria-label=\"Added line\"") && html.contains("data-prefix=\"+\""));
+        assert!(
+            html.contains("aria-label=\"Unchanged line\"") && html.contains("data-prefix=\" \"")
+        );
+        assert!(!render_inline_output(ansi, &[], &DiffOptions::default())
+            .0
+            .contains("aria-label"));
+
+        // Side-by-side: changed lines are the ones with a background; blank placeholders stay unlabelled
+        let ansi = [
+            sbs_row(Some((1, "ctx")), Some((1, "ctx"))),
+            sbs_row(
+                Some((2, "\x1b[48;2;63;0;1mold\x1b[0m")),
+                Some((2, "\x1b[48;2;0;40;0mnew\x1b[0m")),
+            ),
+            sbs_row(None, Some((3, "\x1b[48;2;0;40;0mextra\x1b[0m"))),
+        ]
+        .join("\n");
+        let (left_html, right_html, _) =
+            split_side_by_side_output(&ansi, &[], &[], &options).unwrap();
+        assert_eq!(
+            left_html.matches("aria-label=\"Unchanged line\"").count(),
+            1
+        );
+        assert_eq!(left_html.matches("aria-label=\"Removed line\"").count(), 1);
+        assert_eq!(left_html.matches("role=\"listitem\"").count(), 2);
+        assert_eq!(right_html.matches("aria-label=\"Added line\"").count(), 2);
+        assert!(!left_html.contains("Added line") && !right_html.contains("Removed line"));
+
+        // Without line numbers the kind comes from the background alone
+        let ansi = "ctx\n\x1b[48;2;63;0;1mold\x1b[0m\n\x1b[48;2;0;40;0mnew\x1b[0m";
+        let (html, _) = render_inline_output(ansi, &[], &options);
+        for label in ["Unchanged line", "Removed line", "Added line"] {
+            assert_eq!(
+                html.matches(&format!("aria-label=\"{}\"", label)).count(),
+                1,
+                "{}",
+                label
+            );
+        }
+        let ansi = [
+            "ctx │ctx",
+            "\x1b[48;2;63;0;1mold\x1b[0m │\x1b[48;2;0;40;0mnew\x1b[0m",
+            "    │\x1b[48;2;0;40;0mextra\x1b[0m",
+        ]
+        .join("\n");
+        let (left_html, right_html, _) =
+            split_side_by_side_output(&ansi, &[], &[], &options).unwrap();
+        assert_eq!(left_html.matches("role=\"listitem\"").count(), 2);
+        assert!(left_html.contains("aria-label=\"Removed line\""));
+        assert!(left_html.contains("aria-label=\"Unchanged line\""));
+        assert_eq!(right_html.matches("aria-label=\"Added line\"").count(), 2);
+
+        // Separators between hunks are list items too
+        let (html, _) = render_inline_output("  1 ⋮  1 │a\n  5 ⋮  5 │b", &[], &options);
+        assert!(html.contains("<div role=\"listitem\" class=\"diff-separator\""));
+    }
+
+    #[test]
+    fn test_mark_script_changes() {
+        let options = DiffOptions {
+            mark_script_changes: true,
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let ansi = "  1 ⋮    │#!/bin/sh\n    ⋮  1 │#!/usr/bin/env bash\n  2 ⋮  2 │echo hi";
+        let (html, _) = render_inline_output(ansi, &[], &options);
+        assert_eq!(
+            html.matches("class=\"diff-line shebang-change\"").count(),
+            2
+        );
+        assert!(!render_inline_output(ansi, &[], &DiffOptions::default())
+            .0
+            .contains("shebang-change"));
+
+        let ansi = [
+            sbs_row(
+                Some((1, "\x1b[48;2;63;0;1m#!/bin/sh\x1b[0m")),
+                Some((1, "\x1b[48;2;0;40;0m#!/bin/bash\x1b[0m")),
+            ),
+            sbs_row(Some((2, "echo hi")), Some((2, "echo hi"))),
+        ]
+        .join("\n");
+        let (left_html, right_html, _) =
+            split_side_by_side_output(&ansi, &[], &[], &options).unwrap();
+        assert_eq!(left_html.matches("shebang-change").count(), 1);
+        assert_eq!(right_html.matches("shebang-change").count(), 1);
+
+        // Interpreter and mode changes are reported together
+        let (old, new) = file_pair(
+            "shebang",
+            "sh",
+            "#!/bin/sh\necho hi\n",
+            "#!/usr/bin/env bash\necho hi\n",
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&new, std::fs::Permissions::from_mode(0o755)).unwrap();
+            std::fs::set_permissions(&old, std::fs::Permissions::from_mode(0o644)).unwrap();
+        }
+        let result = generate_diff(Some(&old), Some(&new), &options).unwrap();
+        let interpreter = "Interpreter changed from '#!/bin/sh' to '#!/usr/bin/env bash'";
+        assert!(result.warnings.contains(&interpreter.to_string()));
+        #[cfg(unix)]
+        assert!(result
+            .warnings
+            .contains(&"File mode changed from 100644 to 100755".to_string()));
+        // A mode-only change needs no text diff
+        #[cfg(unix)]
+        {
+            std::fs::write(&new, "#!/bin/sh\necho hi\n").unwrap();
+            let result = generate_diff(Some(&old), Some(&new), &options).unwrap();
+            assert_eq!(
+                result.warnings,
+                vec!["File mode changed from 100644 to 100755".to_string()]
+            );
+        }
+
+        let _ = std::fs::remove_dir_all(old.parent().unwrap());
+    }
+
+    #[test]
+    fn test_max_output_bytes_truncates() {
+        let ansi = (1..=500)
+            .map(|n| format!("{:>4} ⋮{:>4} │line {}", n, n, n))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let options = DiffOptions {
+            max_output_bytes: Some(4_000),
+            ..Default::default()
+        };
+
+        let (full, truncated) = render_inline_output(&ansi, &[], &DiffOptions::default());
+        assert!(!truncated);
+        assert!(full.len() > 4_000);
+
+        let (html, truncated) = render_inline_output(&ansi, &[], &options);
+        assert!(truncated);
+        assert!(html.len() < 4_000 + 200);
+        assert!(html.contains("class=\"diff-truncated\""));
+        assert!(html.contains("line 1\n"));
+        assert!(!html.contains("line 500"));
+
+        let ansi = (1..=500)
+            .map(|n| sbs_row(Some((n, "old")), Some((n, "new"))))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (left_html, right_html, truncated) =
+            split_side_by_side_output(&ansi, &[], &[], &options).unwrap();
+        assert!(truncated);
+        assert!(left_html.len() + right_html.len() < 4_000 + 400);
+        // Both panels are cut at the same row
+        assert_eq!(
+            left_html.matches("diff-line").count(),
+            right_html.matches("diff-line").count()
+        );
+    }
+
+    #[test]
+    fn test_diff_both_modes() {
+        let dir = temp_dir("delta-both-modes");
+        let left = dir.join("a.txt");
+        let right = dir.join("b.txt");
+        std::fs::write(&left, "one\ntwo\nthree\n").unwrap();
+        std::fs::write(&right, "one\ntwo\nthree\n").unwrap();
+
+        // Results that need no rendering are shared as-is
+        let dual =
+            generate_diff_both_modes(Some(&left), Some(&right), &DiffOptions::default()).unwrap();
+        assert!(!dual.inline.has_changes);
+        assert_eq!(dual.inline.html, dual.side_by_side.html);
+
+        std::fs::write(&right, "one\n2\nthree\n").unwrap();
+        let options = DiffOptions {
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let dual = generate_diff_both_modes(Some(&left), Some(&right), &options).unwrap();
+        assert!(dual.inline.html.contains("delta-output"));
+        assert!(dual.inline.left_html.is_none());
+        assert!(dual.side_by_side.left_html.is_some() && dual.side_by_side.right_html.is_some());
+        assert_eq!(dual.inline.hunk_count, dual.side_by_side.hunk_count);
+        assert_eq!(dual.inline.hunks, dual.side_by_side.hunks);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_diff_output_independent_of_cwd() {
+        // Run again in the fixture directory (see below): diff the relative paths and hand
+        // the output back through a file
+        if let Ok(out) = std::env::var("DIFFR_CWD_TEST_OUTPUT") {
+            let relative =
+                run_unified_diff(Some(Path::new("old.txt")), Some(Path::new("new.txt")), 3)
+                    .unwrap();
+            std::fs::write(out, relative).unwrap();
+            return;
+        }
+
+        let dir = temp_dir("delta-cwd");
+        std::fs::write(dir.join("old.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.join("new.txt"), "one\nthree\n").unwrap();
+        let (left, right) = (dir.join("old.txt"), dir.join("new.txt"));
+
+        let from_root = run_unified_diff(Some(&left), Some(&right), 3).unwrap();
+        assert!(from_root.contains(&format!("--- {}", left.display())));
+
+        // Relative inputs resolved from a different CWD give byte-identical output. The CWD
+        // is per process, so this test runs itself as a child process started in `dir`.
+        let output = dir.join("relative.diff");
+        let name = format!(
+            "{}::test_diff_output_independent_of_cwd",
+            module_path!().split_once("::").unwrap().1
+        );
+        let status = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", &name, "--test-threads=1"])
+            .env("DIFFR_CWD_TEST_OUTPUT", &output)
+            .current_dir(&dir)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), from_root);
+
+        let delta_cwd = build_delta_command(&DiffOptions::default())
+            .get_current_dir()
+            .map(Path::to_path_buf);
+        assert_eq!(delta_cwd, Some(std::env::temp_dir()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_line_range_keeps_touching_hunks() {
+        let dir = temp_dir("delta-line-range");
+        let old: String = (1..=40).map(|i| format!("line {}\n", i)).collect();
+        let new = old
+            .replace("line 5\n", "line five\n")
+            .replace("line 30\n", "")
+            .replace("line 35\n", "line 35b\n");
+        std::fs::write(dir.join("old.txt"), &old).unwrap();
+        std::fs::write(dir.join("new.txt"), &new).unwrap();
+        let diff_text =
+            run_unified_diff(Some(&dir.join("old.txt")), Some(&dir.join("new.txt")), 1).unwrap();
+
+        let hunks = |lines| {
+            let (text, count) = hunks_touching_lines(&diff_text, lines);
+            assert!(text.starts_with("--- "));
+            let headers: Vec<String> = text
+                .lines()
+                .filter(|l| l.starts_with("@@"))
+                .map(str::to_string)
+                .collect();
+            assert_eq!(headers.len(), count);
+            headers
+        };
+        assert_eq!(hunks((5, 5)), ["@@ -4,3 +4,3 @@"]);
+        // Line 30's removal sits after new line 29
+        assert_eq!(hunks((29, 29)), ["@@ -29,3 +29,2 @@"]);
+        assert_eq!(hunks((1, 40)).len(), 3);
+        assert!(hunks((10, 20)).is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_edge_context_trimmed_in_full_mode() {
+        let dir = temp_dir("delta-edge-context");
+        let old: String = (1..=10_000).map(|i| format!("line {}\n", i)).collect();
+        let new = old.replace("line 5000\n", "line five thousand\n");
+        std::fs::write(dir.join("old.txt"), &old).unwrap();
+        std::fs::write(dir.join("new.txt"), &new).unwrap();
+        let (left, right) = (dir.join("old.txt"), dir.join("new.txt"));
+
+        // Full-context diff: one hunk spanning the whole file
+        let full = run_unified_diff(Some(&left), Some(&right), 99999).unwrap();
+        assert_eq!(diff_lines(&full).len(), 10_001);
+
+        let (trimmed, leading, trailing) = trim_edge_context(&full, 20);
+        assert_eq!(
+            leading,
+            Some(ElidedContext {
+                old_start: 1,
+                new_start: 1,
+                len: 4979
+            })
+        );
+        assert_eq!(
+            trailing,
+            Some(ElidedContext {
+                old_start: 5021,
+                new_start: 5021,
+                len: 4980
+            })
+        );
+
+        // 20 lines either side of the change survive, with their real line numbers
+        let lines = diff_lines(&trimmed);
+        assert_eq!(lines.len(), 42);
+        assert_eq!(lines[0].new_line, Some(4980));
+        assert_eq!(lines[0].content, "line 4980");
+        assert_eq!(lines.last().unwrap().new_line, Some(5020));
+        assert!(trimmed.contains("-line 5000\n+line five thousand\n"));
+
+        // Short edges are left alone
+        let (untouched, leading, trailing) = trim_edge_context(&full, 5000);
+        assert_eq!(
+            (untouched.as_str(), leading, trailing),
+            (full.as_str(), None, None)
+        );
+
+        let options = DiffOptions {
+            edge_context_limit: Some(20),
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let result = generate_diff(Some(&left), Some(&right), &options).unwrap();
+        assert!(result.html.contains("data-lines=\"4979\""));
+        assert!(result.html.contains("data-lines=\"4980\""));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_ansi_text_attributes() {
+        let palette = ["#000000"; 16];
+        let input = "\x1b[1;31mbold\x1b[22m red \x1b[3;4;9mall\x1b[24m no-underline\x1b[0m plain";
+        let html = ansi_to_html(input, &palette);
+        assert_eq!(
+            html,
+            "<span style='color:#000000;font-weight:bold;'>bold</span>\
+             <span style='color:#000000;'> red </span>\
+             <span style='color:#000000;font-style:italic;text-decoration:underline line-through;'>all</span>\
+             <span style='color:#000000;font-style:italic;text-decoration:line-through;'> no-underline</span> plain"
+        );
+
+        // Attributes alone open a span too, and their own reset codes close it
+        assert_eq!(
+            ansi_to_html("\x1b[3mslanted\x1b[23m \x1b[9mstruck\x1b[29m", &palette),
+            "<span style='font-style:italic;'>slanted</span> <span style='text-decoration:line-through;'>struck</span>"
+        );
+
+        // Faint composes with color and is ended by 22 along with bold
+        assert_eq!(
+            ansi_to_html("\x1b[2;31mfaded\x1b[1mboth\x1b[22m red\x1b[0m", &palette),
+            "<span style='color:#000000;opacity:0.6;'>faded</span>\
+             <span style='color:#000000;font-weight:bold;opacity:0.6;'>both</span>\
+             <span style='color:#000000;'> red</span>"
+        );
+        assert_eq!(
+            ansi_to_html_classed("\x1b[2;31mfaded"),
+            "<span class='ansi-fg-1 ansi-faint'>faded</span>"
+        );
+    }
+
+    #[test]
+    fn test_ansi_to_html_classed() {
+        // delta's dark removed-line and changed-word backgrounds, a basic, a 256 and a true color
+        let input = "\x1b[48;2;63;0;1mlet \x1b[48;2;144;16;17;1mold\x1b[0m \x1b[31mred\x1b[0m \
+                     \x1b[38;5;208mdim\x1b[0m \x1b[38;2;1;2;3;4mrgb\x1b[0m";
+        assert_eq!(
+            ansi_to_html_classed(input),
+            "<span class='diff-del'>let </span><span class='diff-del-emph ansi-bold'>old</span> \
+             <span class='ansi-fg-1'>red</span> <span class='ansi-fg-208'>dim</span> \
+             <span class='ansi-underline' style='color:#010203;'>rgb</span>"
+        );
+        // Same text and structure as the inline version, just much smaller
+        let inline = ansi_to_html(input, ThemeMode::Dark.palette());
+        assert!(ansi_to_html_classed(input).len() < inline.len());
+
+        let dark = ansi_stylesheet(ThemeMode::Dark);
+        let light = ansi_stylesheet(ThemeMode::Light);
+        assert!(dark.contains(".ansi-fg-1{color:#aa0000}"));
+        assert!(light.contains(".ansi-fg-1{color:#cf222e}"));
+        assert!(dark.contains(".diff-del{background:#3f0001}"));
+        assert!(light.contains(".diff-del{background:#ffe0e0}"));
+        assert!(dark.contains(".ansi-bg-208{background:"));
+
+        // delta --light's backgrounds get the same classes
+        assert_eq!(
+            ansi_to_html_classed("\x1b[48;2;208;255;208mnew \x1b[48;2;160;239;160mword\x1b[0m"),
+            "<span class='diff-add'>new </span><span class='diff-add-emph'>word</span>"
+        );
+    }
+
+    #[test]
+    fn test_strip_non_sgr_escapes() {
+        // SGR color, erase-line, cursor move and a private-mode CSI
+        let line = "\x1b[31mred\x1b[0m\x1b[K plain\x1b[2C\x1b[?25lmore";
+        assert_eq!(strip_ansi_codes(line), "red plainmore");
+        assert_eq!(display_width(line), 13);
+
+        // OSC 8 hyperlinks, ended by ST and by BEL
+        let link = "\x1b]8;;https://example.com/m\x1b\\link\x1b]8;;\x07 text";
+        assert_eq!(strip_ansi_codes(link), "link text");
+
+        // Character set selection and a lone escape at the end
+        assert_eq!(strip_ansi_codes("\x1b(Bwide 日本\x1b"), "wide 日本");
+        assert_eq!(display_width("\x1b(Bwide 日本\x1b[K"), 9);
+    }
+
+    #[test]
+    fn test_ansi_to_themed_html() {
+        let input = "\x1b[31merror\x1b[0m: <bad> \x1b[38;2;0;128;255;48;5;22mhint\x1b[0m";
+        let html = ansi_to_themed_html(input, &DiffTheme::default());
+
+        assert_eq!(
+            html,
+            "<pre class=\"ansi-output\" style=\"color:#c9d1d9;background:#0d1117;\">\
+             <span style='color:#aa0000;'>error</span>: &lt;bad&gt; \
+             <span style='background:#005f00;color:#0080ff;'>hint</span></pre>"
+        );
+
+        let custom = DiffTheme {
+            foreground: "#000000".to_string(),
+            background: "#ffffff".to_string(),
+            mode: ThemeMode::Light,
+        };
+        let html = ansi_to_themed_html(input, &custom);
+        assert!(html.starts_with("<pre class=\"ansi-output\" style=\"color:#000000;"));
+        // Basic colors from the light palette
+        assert!(html.contains("<span style='color:#cf222e;'>error</span>"));
+    }
+
+    #[test]
+    fn test_summarize_diff() {
+        let dir = temp_dir("delta-summary");
+        let body = |name: &str, value: &str| {
+            let filler: String = (0..8)
+                .map(|i| format!("    let pad{} = {};\n", i, i))
+                .collect();
+            format!(
+                "fn {}() {{\n{}    let value = {};\n{}}}\n\n",
+                name, filler, value, filler
+            )
+        };
+        let left = dir.join("lib.rs");
+        let right = dir.join("lib_new.rs");
+        std::fs::write(
+            &left,
+            format!("{}{}", body("parse", "1"), body("render", "2")),
+        )
+        .unwrap();
+        std::fs::write(
+            &right,
+            format!(
+                "{}{}",
+                body("parse", "10"),
+                body("render", "20\n    let extra = 3;")
+            ),
+        )
+        .unwrap();
+
+        let options = DiffOptions {
+            show_function_context: true,
+            ..Default::default()
+        };
+        let same_name = dir.join("copy.rs");
+        std::fs::copy(&right, &same_name).unwrap();
+        let rename = RenameInfo {
+            from: "lib.rs".to_string(),
+            to: "lib_new.rs".to_string(),
+            similarity: 0.9,
+            modified: true,
+        };
+        let renamed = summarize_diff(Some(&left), Some(&right), Some(&rename), &options).unwrap();
+        assert_eq!(
+            renamed,
+            "Renamed; 2 functions changed (parse, render), 3 lines added, 2 removed in 2 hunks"
+        );
+
+        // Different file names alone don't make a rename, nor do matching ones rule it out
+        let modified = summarize_diff(Some(&left), Some(&right), None, &DiffOptions::default());
+        assert_eq!(modified.unwrap(), "3 lines added, 2 removed in 2 hunks");
+        let old_dir = dir.join("old");
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::copy(&left, old_dir.join("lib_new.rs")).unwrap();
+        let summary = summarize_diff(
+            Some(&old_dir.join("lib_new.rs")),
+            Some(&right),
+            Some(&rename),
+            &options,
+        );
+        assert!(summary.unwrap().starts_with("Renamed; 2 functions changed"));
+
+        assert_eq!(
+            summarize_diff(None, Some(&left), None, &options).unwrap(),
+            "New file, 40 lines"
+        );
+        assert_eq!(
+            summarize_diff(Some(&left), None, None, &options).unwrap(),
+            "Deleted file, 40 lines"
+        );
+        let unchanged = summarize_diff(Some(&right), Some(&same_name), Some(&rename), &options);
+        assert_eq!(unchanged.unwrap(), "Renamed; no changes");
+
+        std::fs::write(dir.join("a.bin"), [0u8, 1, 2]).unwrap();
+        std::fs::write(dir.join("b.bin"), [0u8, 1, 3]).unwrap();
+        let binary = summarize_diff(
+            Some(&dir.join("a.bin")),
+            Some(&dir.join("b.bin")),
+            None,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(binary, "Binary file changed");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_word_emphasis_underline() {
+        // A removed line whose middle word delta highlighted on a brighter background
+        let ansi = "  1 ⋮    │\x1b[48;2;63;0;1mlet value = \x1b[48;2;144;16;17mold\x1b[48;2;63;0;1m + 1;\x1b[0m";
+        let underline = DiffOptions {
+            word_emphasis: WordEmphasis::Underline,
+            ..Default::default()
+        };
+
+        let (html, _) = render_inline_output(ansi, &[], &underline);
+        assert!(html.contains("<span style='text-decoration:underline;'>old</span>"));
+        assert!(html.contains("<span style='background:#3f0001;'>let value = </span>"));
+        assert!(!html.contains("#901011"));
+
+        let (html, _) = render_inline_output(ansi, &[], &DiffOptions::default());
+        assert!(html.contains("<span style='background:#901011;'>old</span>"));
+        assert!(!html.contains("underline"));
+
+        // Side by side gets the same treatment
+        let bold = DiffOptions {
+            word_emphasis: WordEmphasis::Bold,
+            ..Default::default()
+        };
+        let sbs = format!("│  1 │{}│  1 │same", &ansi["  1 ⋮    │".len()..]);
+        let (left_html, _, _) = split_side_by_side_output(&sbs, &[], &[], &bold).unwrap();
+        assert!(left_html.contains("<span style='font-weight:bold;'>old</span>"));
+
+        // The changed word stays the emphasized one when it covers most of the line
+        let dominating =
+            "\x1b[48;2;63;0;1m\x1b[48;2;144;16;17mcompletely_new_name\x1b[48;2;63;0;1m;\x1b[0m";
+        let (html, _) = render_inline_output(&format!("  1 ⋮    │{}", dominating), &[], &underline);
+        assert!(
+            html.contains("<span style='text-decoration:underline;'>completely_new_name</span>")
+        );
+        assert!(html.contains("<span style='background:#3f0001;'>;</span>"));
+    }
+
+    #[test]
+    fn test_token_granularity_widens_to_identifier() {
+        // delta highlighted only `new` of `foo_new`
+        let ansi = "  1 ⋮    │\x1b[48;2;0;40;0mlet x = foo_\x1b[48;2;0;96;0mnew\x1b[48;2;0;40;0m(a < b);\x1b[0m";
+        let (html, _) = render_inline_output(ansi, &[], &DiffOptions::default());
+        assert!(html.contains("<span style='background:#006000;'>new</span>"));
+
+        let tokens = DiffOptions {
+            token_granularity: true,
+            ..Default::default()
+        };
+        let (html, _) = render_inline_output(ansi, &[], &tokens);
+        assert!(
+            html.contains("<span style='background:#002800;'>let x = </span>"),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains("<span style='background:#006000;'>foo_new</span>"),
+            "{}",
+            html
+        );
+        assert!(html.contains("(a &lt; b);"), "{}", html);
+
+        // Highlights already on token boundaries are left alone
+        let whole = "<span style='background:#002800;'>let x = </span><span style='background:#006000;'>y</span>";
+        assert_eq!(snap_to_tokens(whole), whole);
+        assert_eq!(snap_to_tokens("plain é text"), "plain é text");
+
+        // A highlight covering most of the line is still the one widened
+        let long = "<span style='background:#002800;'>a</span><span style='background:#006000;'>_longer_name</span>";
+        let expected = "<span style='background:#006000;'>a_longer_name</span>";
+        assert_eq!(snap_to_tokens(long), expected);
+    }
+
+    #[test]
+    fn test_side_by_side_split_with_wide_characters() {
+        // One delta panel: "│ nn │" then content padded to 80 columns, CJK counting as two
+        let panel = |num: u32, content: &str, wide_chars: usize| {
+            let width = content.chars().count() + wide_chars;
+            format!("│{:>3} │{}{}", num, content, " ".repeat(80 - 6 - width))
+        };
+        let cjk = "変更された行の内容です、日本語のテキストが続きます。中文内容也在这里";
+        let wide = cjk.chars().count();
+
+        for (left, left_wide, right, right_wide) in [
+            (cjk, wide, "plain ascii on the right", 0),
+            ("plain ascii on the left", 0, cjk, wide),
+            (cjk, wide, cjk, wide),
+        ] {
+            let line = format!(
+                "{}{}",
+                panel(1, left, left_wide),
+                panel(1, right, right_wide)
+            );
+            let (left_html, right_html, _) =
+                split_side_by_side_output(&line, &[], &[], &DiffOptions::default()).unwrap();
+            assert!(left_html.contains(left), "left panel: {}", left_html);
+            assert!(right_html.contains(right), "right panel: {}", right_html);
+            assert!(left_html.contains("  1 ") && right_html.contains("  1 "));
+        }
+    }
+
+    #[test]
+    fn test_both_highlighted() {
+        use crate::commands::both_highlighted;
+
+        let dir = temp_dir("delta-highlight-both");
+        let left = dir.join("old.rs");
+        let right = dir.join("new.rs");
+        std::fs::write(&left, "fn first() {}\nlet a = 1 < 2;\n").unwrap();
+        std::fs::write(&right, "fn second() {}\nlet a = 1 < 2;\n").unwrap();
+        let (left, right) = (left.to_str().unwrap(), right.to_str().unwrap());
+
+        let (old, new) = both_highlighted(Some(left), Some(right), None, ThemeMode::Dark).unwrap();
+        let (old, new) = (old.expect("left side"), new.expect("right side"));
+        for (html, name) in [(&old, "first"), (&new, "second")] {
+            assert!(html.contains(name));
+            assert_eq!(html.matches("class=\"diff-line\"").count(), 2);
+            assert!(html.contains("&lt;") && !html.contains("1 < 2"));
+        }
+
+        let (old, new) =
+            both_highlighted(None, Some(right), Some("Nord"), ThemeMode::Dark).unwrap();
+        assert!(old.is_none());
+        assert!(new.unwrap().contains("second"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_file_diff_bundle_matches_generate_diff() {
+        let dir = temp_dir("delta-bundle");
+        let lines: Vec<String> = (1..=20).map(|i| format!("line {}", i)).collect();
+        let mut changed = lines.clone();
+        changed[4] = "line five".to_string();
+        changed.insert(15, "inserted".to_string());
+        std::fs::write(dir.join("old.txt"), lines.join("\n") + "\n").unwrap();
+        std::fs::write(dir.join("new.txt"), changed.join("\n") + "\n").unwrap();
+        std::fs::write(dir.join("same.txt"), lines.join("\n") + "\n").unwrap();
+        let (old, new, same) = (
+            dir.join("old.txt"),
+            dir.join("new.txt"),
+            dir.join("same.txt"),
+        );
+
+        let options = DiffOptions {
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let bundle = generate_file_diff_bundle(Some(&old), Some(&same), &options).unwrap();
+        assert!(!bundle.diff.has_changes);
+        assert_eq!(
+            (
+                bundle.summary.added,
+                bundle.summary.removed,
+                bundle.summary.hunk_count
+            ),
+            (0, 0, 0)
+        );
+        assert!(bundle.hunks.is_empty());
+        assert_eq!(bundle.first_change_line, None);
+
+        let bundle = generate_file_diff_bundle(Some(&old), Some(&new), &options).unwrap();
+        let standalone = generate_diff(Some(&old), Some(&new), &options).unwrap();
+        assert_eq!(bundle.diff.left_html, standalone.left_html);
+        assert_eq!(bundle.diff.right_html, standalone.right_html);
+        assert_eq!(bundle.diff.hunk_count, standalone.hunk_count);
+        assert_eq!(bundle.hunks, standalone.hunks);
+        assert_eq!(bundle.summary.hunk_count, standalone.hunk_count);
+        assert_eq!((bundle.summary.added, bundle.summary.removed), (2, 1));
+        assert_eq!(bundle.first_change_line, Some(5));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_diff_legend_matches_rendered_colors() {
+        // Shaped like delta's output: line background, changed word on a brighter one
+        let removed = "\x1b[48;2;63;0;1mlet value = \x1b[48;2;144;16;17m1\x1b[48;2;63;0;1m;\x1b[0m";
+        let added = "\x1b[48;2;0;40;0mlet value = \x1b[48;2;0;96;0m2\x1b[48;2;0;40;0m;\x1b[0m";
+        let ansi = format!("context\n{}\n{}\n", removed, added);
+
+        let legend = legend_from_ansi(&ansi, ThemeMode::Dark).unwrap();
+        let color = |label: &str| {
+            legend
+                .iter()
+                .find(|e| e.label == label)
+                .unwrap()
+                .color
+                .clone()
+        };
+        let palette = ThemeMode::Dark.palette();
+        let (removed_html, added_html) =
+            (ansi_to_html(removed, palette), ansi_to_html(added, palette));
+        assert!(removed_html.starts_with(&format!(
+            "<span style='background:{};",
+            color("Removed line")
+        )));
+        assert!(removed_html.contains(&format!("background:{};'>1<", color("Removed word"))));
+        assert!(
+            added_html.starts_with(&format!("<span style='background:{};", color("Added line")))
+        );
+        assert!(added_html.contains(&format!("background:{};'>2<", color("Added word"))));
+        assert_eq!(color("Unchanged line"), DiffTheme::default().background);
+
+        assert!(legend_from_ansi("context\nlet value = 1;\n", ThemeMode::Dark).is_none());
+
+        // Words covering most of their lines don't swap line and word colors
+        let removed = "\x1b[48;2;63;0;1m\x1b[48;2;144;16;17mlet value = 1\x1b[48;2;63;0;1m;\x1b[0m";
+        let added = "\x1b[48;2;0;40;0m\x1b[48;2;0;96;0mlet value = 2\x1b[48;2;0;40;0m;\x1b[0m";
+        let legend = legend_from_ansi(
+            &format!("context\n{}\n{}\n", removed, added),
+            ThemeMode::Dark,
+        )
+        .unwrap();
+        let colors: Vec<&str> = legend.iter().map(|e| e.color.as_str()).collect();
+        assert_eq!(colors[..4], ["#3f0001", "#901011", "#002800", "#006000"]);
+
+        // With or without delta, every entry is a color, and light ones for a light theme
+        for theme in [ThemeMode::Dark, ThemeMode::Light] {
+            let legend = diff_legend(None, theme);
+            assert_eq!(legend.len(), 5);
+            assert!(legend
+                .iter()
+                .all(|e| e.color.starts_with('#') && e.color.len() == 7));
+            assert_eq!(legend[4].color, DiffTheme::for_mode(theme).background);
+        }
+    }
+
+    #[test]
+    fn test_offset_hunk_headers() {
+        let diff =
+            "--- a\n+++ b\n@@ -0,0 +1,2 @@\n+step 3\n+step 4\n@@ -1 +1 @@ fn main()\n-tw\n+two\n";
+        assert_eq!(
+            offset_hunk_headers(diff, 3),
+            "--- a\n+++ b\n@@ -3,0 +4,2 @@\n+step 3\n+step 4\n@@ -4,1 +4,1 @@ fn main()\n-tw\n+two\n"
+        );
+    }
+
+    #[test]
+    fn test_change_fingerprint_ignores_position() {
+        let dir = temp_dir("delta-fingerprint");
+        let write = |name: &str, content: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        let old_a = write("a_old.rs", "fn a() {}\nlet x = 1;\nfn b() {}\n");
+        let new_a = write("a_new.rs", "fn a() {}\nlet x = 2;\nfn b() {}\n");
+        // Same edit further down, with other lines around it
+        let old_b = write(
+            "b_old.rs",
+            "// header\n\nuse std::io;\n\nlet x = 1;\nfn c() {}\n",
+        );
+        let new_b = write(
+            "b_new.rs",
+            "// header\n\nuse std::io;\n\nlet x = 2;\nfn c() {}\n",
+        );
+        let other = write("other.rs", "fn a() {}\nlet x = 3;\nfn b() {}\n");
+
+        let options = DiffOptions::default();
+        let fingerprint =
+            |l: &Path, r: &Path| change_fingerprint(Some(l), Some(r), &options).unwrap();
+
+        let a = fingerprint(&old_a, &new_a);
+        assert_eq!(a.len(), 16);
+        assert_eq!(a, fingerprint(&old_b, &new_b));
+        assert_ne!(a, fingerprint(&old_a, &other));
+        assert_ne!(a, fingerprint(&new_a, &old_a));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_context_fold_size() {
+        let dir = temp_dir("delta-fold");
+        let old: Vec<String> = (1..=20).map(|n| format!("l{}", n)).collect();
+        let mut new = old.clone();
+        for n in [3, 14, 18] {
+            new[n - 1] = format!("changed {}", n);
+        }
+        std::fs::write(dir.join("old.txt"), old.join("\n") + "\n").unwrap();
+        std::fs::write(dir.join("new.txt"), new.join("\n") + "\n").unwrap();
+        let diff_text = run_unified_diff(
+            Some(&dir.join("old.txt")),
+            Some(&dir.join("new.txt")),
+            99999,
+        )
+        .unwrap();
+
+        // 10 unchanged lines between the first two changes: all but 2 on each end fold.
+        // The 3 between the last two and those before/after the edits stay.
+        let (folded, folds) = fold_context(&diff_text, 4);
+        assert_eq!(
+            folds,
+            vec![ElidedContext {
+                old_start: 6,
+                new_start: 6,
+                len: 6
+            }]
+        );
+        let headers: Vec<&str> = folded.lines().filter(|l| l.starts_with("@@")).collect();
+        assert_eq!(headers, vec!["@@ -1,5 +1,5 @@", "@@ -12,9 +12,9 @@"]);
+        let lines = diff_lines(&folded);
+        assert!(!lines
+            .iter()
+            .any(|l| l.content == "l6" || l.content == "l11"));
+        assert_eq!(
+            lines.iter().find(|l| l.content == "l12").unwrap().old_line,
+            Some(12)
+        );
+
+        // Runs up to the fold size are kept
+        let (unfolded, folds) = fold_context(&diff_text, 10);
+        assert!(folds.is_empty());
+        assert_eq!(unfolded, diff_text);
+
+        // Rendering puts the expandable group where the lines were cut
+        let fold = ElidedContext {
+            old_start: 6,
+            new_start: 6,
+            len: 6,
+        };
+        let ansi = "   5 ⋮   5 │l5\n  12 ⋮  12 │l12";
+        let (html, _) = render_inline_output(ansi, &[fold], &DiffOptions::default());
+        assert!(html.contains("class=\"context-collapsed\" data-old-start=\"6\" data-new-start=\"6\" data-lines=\"6\""));
+        let (html, _) = render_inline_output(ansi, &[], &DiffOptions::default());
+        assert!(html.contains("diff-separator") && !html.contains("context-collapsed"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_intra_line_highlights() {
+        let args = |intra_line| {
+            command_args(&DiffOptions {
+                intra_line,
+                ..Default::default()
+            })
+        };
+        assert!(!args(None).contains(&"--max-line-distance".to_string()));
+        assert!(args(Some(false))
+            .windows(2)
+            .any(|w| w == ["--max-line-distance", "0"]));
+        assert!(args(Some(true))
+            .windows(2)
+            .any(|w| w == ["--max-line-distance", "0.6"]));
+
+        // delta's `let x = 1;` → `let x = 2;`, with the changed digit on a brighter background
+        let removed = "\x1b[48;2;63;0;1mlet x = \x1b[48;2;144;16;17m1\x1b[48;2;63;0;1m;\x1b[0m";
+        let added = "\x1b[48;2;0;40;0mlet x = \x1b[48;2;0;96;0m2\x1b[48;2;0;40;0m;\x1b[0m";
+        let html = ansi_to_html(removed, ThemeMode::Dark.palette());
+        assert!(html.contains("<span style='background:#901011;'>1</span>"));
+        assert!(html.contains("<span style='background:#3f0001;'>let x = </span>"));
+
+        // The line takes the background of most of its text, and the word span survives inside it
+        let ansi = format!("   1 ⋮     │{}\n     ⋮   1 │{}", removed, added);
+        let (html, _) = render_inline_output(&ansi, &[], &DiffOptions::default());
+        assert!(
+            html.contains("style='background:#3f0001'")
+                && html.contains("style='background:#002800'")
+        );
+        assert!(html.contains("<span style='background:#901011;'>1</span>"));
+        assert!(html.contains("<span style='background:#006000;'>2</span>"));
+
+        // Even when the changed word comes first
+        let leading = "\x1b[48;2;144;16;17mx\x1b[48;2;63;0;1m = 1;\x1b[0m";
+        let (html, _) = render_inline_output(
+            &format!("   1 ⋮     │{}", leading),
+            &[],
+            &DiffOptions::default(),
+        );
+        assert!(
+            html.contains("style='background:#3f0001'")
+                && !html.contains("style='background:#901011'")
+        );
+
+        // Or covers most of the line
+        let dominating =
+            "\x1b[48;2;63;0;1m\x1b[48;2;144;16;17mcompletely_new_name\x1b[48;2;63;0;1m;\x1b[0m";
+        let (html, _) = render_inline_output(
+            &format!("   1 ⋮     │{}", dominating),
+            &[],
+            &DiffOptions::default(),
+        );
+        assert!(
+            html.contains("style='background:#3f0001'")
+                && !html.contains("style='background:#901011'>")
+        );
+        assert!(html.contains("<span style='background:#901011;'>completely_new_name</span>"));
+    }
+}
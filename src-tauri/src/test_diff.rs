@@ -1,7 +1,26 @@
 #[cfg(test)]
 mod tests {
-    use crate::diff::{compare_directories, build_file_tree};
-    use std::path::Path;
+    use crate::commands::{
+        cancel_comparison, encode_file_tree, get_change_heatmap, get_changed_extensions,
+        get_diff_stat, get_extension_breakdown, get_file_tree, get_identical_files,
+        import_recursive_diff, FileTreeResult,
+    };
+    use crate::delta::{count_line_changes, run_until};
+    use crate::diff::{
+        build_file_tree, build_file_tree_with_options, classify_comparison, classify_entries,
+        compare_directories, compare_directories_cancellable, compare_directories_with_options,
+        compare_pairs, diff_stats, estimate_comparison, extension_breakdown, files_differ,
+        has_mixed_line_endings, identical_files, is_binary, line_similarity, match_renames,
+        measure_rename, parse_nul_pairs, parse_recursive_diff, review_priorities,
+        status_transitions, CompareOptions, ComparisonCounts, ComparisonKind, DiffError, ExtStats,
+        FileEntry, FileStatus, IdenticalReport, RenameInfo, StatusTransition,
+    };
+    use crate::normalize::Normalizer;
+    use crate::test_fixtures::{fixture, large_pair};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_compare_dirs() {
@@ -18,9 +37,1833 @@ mod tests {
         let tree = build_file_tree(&entries);
         println!("Tree nodes: {}", tree.len());
         for node in &tree {
-            println!("  Node: {} (is_dir: {}, status: {:?})", node.name, node.is_dir, node.status);
+            println!(
+                "  Node: {} (is_dir: {}, status: {:?})",
+                node.name, node.is_dir, node.status
+            );
         }
 
         assert!(!entries.is_empty(), "Should find some entries");
     }
+
+    fn renamed(old: &str, new: &str) -> FileEntry {
+        FileEntry {
+            path: format!("{} → {}", old, new),
+            name: new.rsplit('/').next().unwrap().to_string(),
+            status: FileStatus::Renamed,
+            is_dir: false,
+            left_path: Some(format!("/left/{}", old)),
+            right_path: Some(format!("/right/{}", new)),
+            cross_file_move_hint: None,
+            mixed_line_endings: false,
+            rename_info: Some(RenameInfo {
+                from: old.to_string(),
+                to: new.to_string(),
+                similarity: 1.0,
+                modified: false,
+            }),
+            skip_content: false,
+            truncated_continuation: false,
+            is_binary: false,
+        }
+    }
+
+    #[test]
+    fn test_directory_status_rollup() {
+        let (left, right) = fixture(
+            "dir-rollup",
+            &[
+                ("src/a.rs", "1"),
+                ("src/old/x.rs", "x"),
+                ("src/old/y.rs", "y"),
+                ("keep.txt", "k"),
+            ],
+            &[("src/a.rs", "2"), ("src/new/z.rs", "z"), ("keep.txt", "k")],
+        );
+        let entries = compare_directories(&left, &right).unwrap();
+        let tree = build_file_tree(&entries);
+
+        let src = &tree[0];
+        assert_eq!(
+            (src.name.as_str(), src.status.clone()),
+            ("src", Some(FileStatus::Modified))
+        );
+        assert_eq!((src.added, src.deleted, src.modified), (1, 2, 1));
+        let child = |name: &str| src.children.iter().find(|n| n.name == name).unwrap();
+        assert_eq!(child("new").status, Some(FileStatus::Added));
+        assert_eq!(
+            (child("old").status.clone(), child("old").deleted),
+            (Some(FileStatus::Deleted), 2)
+        );
+        // Files keep their own status and no counts
+        let file = child("a.rs");
+        assert_eq!(file.status, Some(FileStatus::Modified));
+        assert_eq!((file.added, file.deleted, file.modified), (0, 0, 0));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_collapse_uniform_renames() {
+        let mut entries: Vec<FileEntry> = (0..200)
+            .map(|i| {
+                renamed(
+                    &format!("old/file{}.rs", i),
+                    &format!("pkg/new/file{}.rs", i),
+                )
+            })
+            .collect();
+        // A lone rename elsewhere stays a regular leaf
+        entries.push(renamed("a/x.rs", "b/x.rs"));
+
+        let options = CompareOptions {
+            collapse_renames: true,
+            ..Default::default()
+        };
+        let tree = build_file_tree_with_options(&entries, &options);
+
+        let pkg = tree.iter().find(|n| n.name == "pkg").expect("pkg dir");
+        assert_eq!(pkg.children.len(), 1);
+        let summary = &pkg.children[0];
+        assert_eq!(summary.name, "Moved 200 files: old/ → pkg/new/");
+        assert!(summary.is_dir);
+        assert_eq!(summary.status, Some(FileStatus::Renamed));
+        assert_eq!(summary.children.len(), 200);
+
+        let b = tree.iter().find(|n| n.name == "b").expect("b dir");
+        assert_eq!(b.children[0].name, "x.rs");
+
+        // Without the option every file is its own node
+        let tree = build_file_tree(&entries);
+        let pkg = tree.iter().find(|n| n.name == "pkg").unwrap();
+        assert_eq!(pkg.children[0].name, "new");
+        assert_eq!(pkg.children[0].children.len(), 200);
+    }
+
+    #[test]
+    fn test_extension_breakdown() {
+        let (left, right) = fixture(
+            "ext",
+            &[
+                ("a.rs", "1\n2\n"),
+                ("b.rs", "x\n"),
+                ("Cargo.toml", "[package]\n"),
+                ("gone.md", "bye\n"),
+                ("LICENSE", "MIT\n"),
+            ],
+            &[
+                ("a.rs", "1\n3\n4\n"),
+                ("b.rs", "x\n"),
+                ("c.rs", "new\n"),
+                ("Cargo.toml", "[package]\nname\n"),
+                ("LICENSE", "BSD\n"),
+            ],
+        );
+
+        let entries = compare_directories(&left, &right).unwrap();
+        let breakdown = extension_breakdown(&entries, |e| {
+            count_line_changes(
+                e.left_path.as_deref().map(Path::new),
+                e.right_path.as_deref().map(Path::new),
+            )
+            .unwrap()
+        });
+
+        let exts: Vec<&str> = breakdown.iter().map(|(ext, _)| ext.as_str()).collect();
+        assert_eq!(exts, vec![".rs", "(none)", ".md", ".toml"]);
+
+        let get = |ext: &str| breakdown.iter().find(|(e, _)| e == ext).unwrap().1.clone();
+        assert_eq!(
+            get(".rs"),
+            ExtStats {
+                added: 1,
+                modified: 1,
+                lines_added: 3,
+                lines_removed: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            get(".toml"),
+            ExtStats {
+                modified: 1,
+                lines_added: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            get(".md"),
+            ExtStats {
+                deleted: 1,
+                lines_removed: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(get("(none)").modified, 1);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_changed_extensions() {
+        let (left, right) = fixture(
+            "changed-ext",
+            &[
+                ("src/a.RS", "1"),
+                ("b.rs", "x"),
+                ("same.txt", "s"),
+                ("gone.JSON", "{}"),
+                ("Makefile", "all:"),
+            ],
+            &[
+                ("src/a.RS", "2"),
+                ("b.rs", "y"),
+                ("same.txt", "s"),
+                ("new.json", "[]"),
+                ("Makefile", "all: x"),
+            ],
+        );
+
+        let extensions =
+            get_changed_extensions(&left.to_string_lossy(), &right.to_string_lossy(), None)
+                .unwrap();
+        // Unchanged .txt is left out; .RS/.rs and .JSON/.json collapse into one
+        assert_eq!(extensions, vec!["(none)", ".json", ".rs"]);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_ignore_trailing_newline() {
+        let (left, right) = fixture(
+            "eol",
+            &[("a.txt", "hello\nworld\n"), ("b.txt", "same")],
+            &[("a.txt", "hello\nworld"), ("b.txt", "same\n\n")],
+        );
+
+        let status = |options: &CompareOptions, name: &str| {
+            compare_directories_with_options(&left, &right, options)
+                .unwrap()
+                .into_iter()
+                .find(|e| e.name == name)
+                .unwrap()
+                .status
+        };
+
+        let default = CompareOptions::default();
+        assert_eq!(status(&default, "a.txt"), FileStatus::Modified);
+
+        let options = CompareOptions {
+            ignore_trailing_newline: true,
+            ..Default::default()
+        };
+        assert_eq!(status(&options, "a.txt"), FileStatus::Unchanged);
+        // Only a single final newline is ignored
+        assert_eq!(status(&options, "b.txt"), FileStatus::Modified);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        let (left, right) = fixture(
+            "transitions",
+            &[
+                ("fixed.txt", "old"),
+                ("same.txt", "same"),
+                ("gone.txt", "bye"),
+            ],
+            &[
+                ("fixed.txt", "new"),
+                ("same.txt", "same"),
+                ("gone.txt", "bye"),
+            ],
+        );
+        let prev = compare_directories(&left, &right).unwrap();
+
+        // The review fixed one file and added another
+        std::fs::write(right.join("fixed.txt"), "old").unwrap();
+        std::fs::write(right.join("new.txt"), "hi").unwrap();
+        let curr = compare_directories(&left, &right).unwrap();
+
+        assert_eq!(
+            status_transitions(&prev, &curr),
+            vec![
+                StatusTransition {
+                    path: "fixed.txt".to_string(),
+                    from: Some(FileStatus::Modified),
+                    to: None,
+                },
+                StatusTransition {
+                    path: "new.txt".to_string(),
+                    from: None,
+                    to: Some(FileStatus::Added),
+                },
+            ]
+        );
+        assert!(status_transitions(&curr, &curr).is_empty());
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_compare_pairs_nul_separated() {
+        let (left, right) = fixture(
+            "pairs",
+            &[("my file.txt", "a"), ("line\nbreak.txt", "same")],
+            &[("my file.txt", "b"), ("line\nbreak.txt", "same")],
+        );
+        let spaced = (left.join("my file.txt"), right.join("my file.txt"));
+        let newline = (left.join("line\nbreak.txt"), right.join("line\nbreak.txt"));
+
+        let input = format!(
+            "{}\0{}\0{}\0{}\0",
+            spaced.0.display(),
+            spaced.1.display(),
+            newline.0.display(),
+            newline.1.display()
+        );
+        let pairs = parse_nul_pairs(&input).unwrap();
+        assert_eq!(pairs, vec![spaced.clone(), newline.clone()]);
+
+        let entries = compare_pairs(&pairs, &CompareOptions::default()).unwrap();
+        assert_eq!(entries[0].name, "my file.txt");
+        assert_eq!(entries[0].status, FileStatus::Modified);
+        assert_eq!(entries[1].name, "line\nbreak.txt");
+        assert_eq!(entries[1].status, FileStatus::Unchanged);
+        assert_eq!(
+            entries[1].right_path.as_deref(),
+            Some(newline.1.to_string_lossy().as_ref())
+        );
+
+        // A missing side is an addition; an odd number of paths is rejected
+        let added = compare_pairs(
+            &[(left.join("nope"), spaced.1.clone())],
+            &CompareOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(added[0].status, FileStatus::Added);
+        assert!(parse_nul_pairs("a\0b\0c").is_err());
+        assert!(parse_nul_pairs("").unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_skip_known_generated() {
+        let (left, right) = fixture(
+            "generated",
+            &[
+                ("Cargo.lock", "v1"),
+                ("web/api.generated.ts", "v1"),
+                ("src/main.rs", "a"),
+            ],
+            &[
+                ("Cargo.lock", "v2"),
+                ("web/api.generated.ts", "v2"),
+                ("src/main.rs", "b"),
+            ],
+        );
+
+        let paths = |options: &CompareOptions| -> Vec<String> {
+            compare_directories_with_options(&left, &right, options)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.path)
+                .collect()
+        };
+
+        assert_eq!(paths(&CompareOptions::default()).len(), 3);
+
+        let options = CompareOptions {
+            skip_known_generated: true,
+            ..Default::default()
+        };
+        assert_eq!(paths(&options), vec!["src/main.rs"]);
+
+        // A custom list replaces the defaults
+        let options = CompareOptions {
+            skip_known_generated: true,
+            generated_patterns: Some(vec!["*.rs".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(paths(&options), vec!["Cargo.lock", "web/api.generated.ts"]);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_classify_comparison() {
+        let files: Vec<(String, String)> = (0..20)
+            .map(|i| (format!("src/mod{}.rs", i), format!("fn f{}() {{}}\n", i)))
+            .collect();
+        let mut next = files.clone();
+        next[0].1.push_str("fn added() {}\n");
+        next.push(("src/new.rs".to_string(), "fn new() {}\n".to_string()));
+        let old_refs: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(p, c)| (p.as_str(), c.as_str()))
+            .collect();
+        let new_refs: Vec<(&str, &str)> =
+            next.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let options = CompareOptions::default();
+
+        let (left, right) = fixture("classify-same", &old_refs, &new_refs);
+        assert_eq!(
+            classify_comparison(&left, &right, &options).unwrap(),
+            ComparisonKind::SameProjectNewVersion
+        );
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let (left, right) = fixture(
+            "classify-unrelated",
+            &old_refs,
+            &[
+                ("README.md", "# other"),
+                ("lib/main.py", "print(1)"),
+                ("setup.cfg", "[metadata]"),
+            ],
+        );
+        assert_eq!(
+            classify_comparison(&left, &right, &options).unwrap(),
+            ComparisonKind::UnrelatedTrees
+        );
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        // Same layout, every file rewritten
+        let rewritten: Vec<(&str, &str)> = old_refs
+            .iter()
+            .map(|(p, _)| (*p, "// rewritten from scratch\n"))
+            .collect();
+        let (left, right) = fixture("classify-partial", &old_refs, &rewritten);
+        assert_eq!(
+            classify_comparison(&left, &right, &options).unwrap(),
+            ComparisonKind::PartialOverlap
+        );
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let (left, right) = fixture("classify-empty", &[], &[]);
+        assert_eq!(
+            classify_comparison(&left, &right, &options).unwrap(),
+            ComparisonKind::Empty
+        );
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_estimate_comparison() {
+        let (left, right) = fixture(
+            "estimate",
+            &[("a.txt", "one"), ("dir/b.txt", "two")],
+            &[("a.txt", "uno"), ("c.txt", "three")],
+        );
+        let options = CompareOptions::default();
+
+        let small = estimate_comparison(&left, &right, &options).unwrap();
+        assert_eq!(small.file_count, 3);
+        assert_eq!(small.total_bytes, 3 + 3 + 3 + 5);
+
+        // More content never makes the estimate cheaper
+        std::fs::write(right.join("big.bin"), vec![0u8; 4_000_000]).unwrap();
+        let big = estimate_comparison(&left, &right, &options).unwrap();
+        assert_eq!(big.file_count, 4);
+        assert!(big.total_bytes > small.total_bytes);
+        assert!(big.estimated_ms > small.estimated_ms);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_cross_file_move_hint() {
+        let moved = "fn moved() {\n    let x = 1;\n    x + 1\n}\n";
+        let (left, right) = fixture(
+            "cross-move",
+            &[
+                ("a.rs", &format!("fn keep() {{}}\n\n{}", moved)),
+                ("b.rs", "fn other() {}\n"),
+                ("c.rs", "one\n"),
+            ],
+            &[
+                ("a.rs", "fn keep() {}\n"),
+                ("b.rs", &format!("fn other() {{}}\n\n{}", moved)),
+                ("c.rs", "two\n"),
+            ],
+        );
+
+        let hint = |options: &CompareOptions, name: &str| {
+            compare_directories_with_options(&left, &right, options)
+                .unwrap()
+                .into_iter()
+                .find(|e| e.name == name)
+                .unwrap()
+                .cross_file_move_hint
+        };
+
+        assert_eq!(hint(&CompareOptions::default(), "a.rs"), None);
+
+        let options = CompareOptions {
+            detect_cross_file_moves: true,
+            ..Default::default()
+        };
+        assert_eq!(hint(&options, "a.rs").as_deref(), Some("moved to b.rs"));
+        assert_eq!(hint(&options, "b.rs").as_deref(), Some("moved from a.rs"));
+        assert_eq!(hint(&options, "c.rs"), None);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_parallel_walk_shares_ignore_rules() {
+        let (left, right) = fixture(
+            "parallel-ignore",
+            &[
+                ("keep.txt", "a"),
+                ("scratch.tmp", "x"),
+                ("build/out.o", "1"),
+                ("Cargo.lock", "v1"),
+            ],
+            &[
+                ("keep.txt", "b"),
+                ("notes.tmp", "y"),
+                ("build/out.o", "2"),
+                ("Cargo.lock", "v2"),
+            ],
+        );
+
+        let options = CompareOptions {
+            ignore_patterns: vec!["*.tmp".to_string(), "build/**".to_string()],
+            skip_known_generated: true,
+            parallel_walk: true,
+            ..Default::default()
+        };
+
+        let entries = compare_directories_with_options(&left, &right, &options).unwrap();
+
+        // Each rule drops files on both sides alike, so nothing ignored shows up as added/deleted
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["keep.txt"]);
+
+        let sequential = CompareOptions {
+            parallel_walk: false,
+            ..options
+        };
+        let sequential_paths: Vec<String> =
+            compare_directories_with_options(&left, &right, &sequential)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.path)
+                .collect();
+        assert_eq!(sequential_paths, paths);
+
+        // The rules are compiled up front, so a bad one fails the comparison before either walk
+        let invalid = CompareOptions {
+            ignore_patterns: vec!["[".to_string()],
+            parallel_walk: true,
+            ..Default::default()
+        };
+        assert!(compare_directories_with_options(&left, &right, &invalid).is_err());
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_mixed_line_endings_flag() {
+        let (left, right) = fixture(
+            "mixed-endings",
+            &[
+                ("mixed.txt", "one\r\ntwo\nthree\r\n"),
+                ("crlf.txt", "a\r\nb\r\n"),
+                ("lf.txt", "a\nb\n"),
+                ("gone.txt", "x\r\ny\n"),
+            ],
+            &[
+                ("mixed.txt", "one\r\ntwo\nthree\r\n"),
+                ("crlf.txt", "a\r\nc\r\n"),
+                ("lf.txt", "a\nb\n"),
+                ("added.txt", "p\nq\r\n"),
+            ],
+        );
+
+        let entries = compare_directories(&left, &right).unwrap();
+        let flag = |path: &str| {
+            entries
+                .iter()
+                .find(|e| e.path == path)
+                .unwrap()
+                .mixed_line_endings
+        };
+        assert!(flag("mixed.txt"));
+        assert!(!flag("crlf.txt"));
+        assert!(!flag("lf.txt"));
+        // Files on one side only are checked too
+        assert!(flag("gone.txt") && flag("added.txt"));
+        let pairs = [(left.join("gone.txt"), right.join("gone.txt"))];
+        assert!(compare_pairs(&pairs, &CompareOptions::default()).unwrap()[0].mixed_line_endings);
+
+        assert!(has_mixed_line_endings(b"x\ny\r\n"));
+        assert!(!has_mixed_line_endings(b"x\ry\r\n"));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_review_priorities_rank_source_over_lockfile() {
+        let (left, right) = fixture(
+            "review-priority",
+            &[
+                ("src/main.rs", "fn a() {}\n"),
+                ("Cargo.lock", "v1\n"),
+                ("config.toml", "x = 1\n"),
+            ],
+            &[
+                ("src/main.rs", "fn b() {}\n"),
+                ("Cargo.lock", "v2\n"),
+                ("config.toml", "x = 2\n"),
+            ],
+        );
+        let entries = compare_directories(&left, &right).unwrap();
+
+        // Same churn everywhere, so only category and depth decide
+        let ranked = review_priorities(&entries, &CompareOptions::default(), |_| (10, 10)).unwrap();
+        let paths: Vec<&str> = ranked.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(paths, vec!["src/main.rs", "config.toml", "Cargo.lock"]);
+        assert_eq!(ranked[0].1, 1.0);
+        assert!(ranked.iter().all(|(_, score)| (0.0..=1.0).contains(score)));
+
+        // Counted for real, the lockfile still comes last
+        let counted = review_priorities(&entries, &CompareOptions::default(), |e| {
+            let left = e.left_path.as_deref().map(Path::new);
+            let right = e.right_path.as_deref().map(Path::new);
+            count_line_changes(left, right).unwrap()
+        })
+        .unwrap();
+        assert_eq!(counted.last().unwrap().0, "Cargo.lock");
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_file_tree_tree_only() {
+        let (left, right) = fixture(
+            "tree-only",
+            &[("src/a.rs", "a"), ("src/b.rs", "b"), ("old.txt", "x")],
+            &[("src/a.rs", "a2"), ("src/b.rs", "b"), ("src/c.rs", "c")],
+        );
+        let (left_dir, right_dir) = (left.to_string_lossy(), right.to_string_lossy());
+
+        let full = get_file_tree(&left_dir, &right_dir, None, None).unwrap();
+        let options = CompareOptions {
+            tree_only: true,
+            ..Default::default()
+        };
+        let tree_only = get_file_tree(&left_dir, &right_dir, Some(options), None).unwrap();
+
+        assert_eq!(full.files.len(), 3);
+        assert!(tree_only.files.is_empty());
+        assert_eq!(
+            serde_json::to_value(&tree_only.tree).unwrap(),
+            serde_json::to_value(&full.tree).unwrap()
+        );
+        assert_eq!(
+            (
+                tree_only.total_changes,
+                tree_only.added,
+                tree_only.deleted,
+                tree_only.modified
+            ),
+            (3, 1, 1, 1)
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_rename_info_on_diff() {
+        use crate::commands::diff_inputs;
+        use crate::delta::DiffOptions;
+
+        let (left, right) = fixture(
+            "rename-info",
+            &[("old/name.rs", "fn a() {}\n")],
+            &[("new/name.rs", "fn a() {}\n")],
+        );
+        let entries = compare_directories(&left, &right).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let rename = entries[0]
+            .rename_info
+            .clone()
+            .expect("renamed entry carries rename_info");
+        assert_eq!(
+            rename,
+            RenameInfo {
+                from: "old/name.rs".to_string(),
+                to: "new/name.rs".to_string(),
+                similarity: 1.0,
+                modified: false,
+            }
+        );
+
+        let result = diff_inputs(
+            entries[0].left_path.as_deref(),
+            entries[0].right_path.as_deref(),
+            &DiffOptions::default(),
+            Some(rename.clone()),
+        )
+        .unwrap();
+        assert_eq!(result.rename_info, Some(rename));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_ignore_comment_only_changes() {
+        use crate::delta::{generate_diff, DiffOptions};
+
+        let (left, right) = fixture(
+            "ignore-comments",
+            &[
+                (
+                    "lib.rs",
+                    "fn a() {\n    // old note\n    let url = \"http://a\";\n}\n",
+                ),
+                ("code.rs", "fn b() {\n    // note\n    1\n}\n"),
+            ],
+            &[
+                (
+                    "lib.rs",
+                    "fn a() {\n    // reworded note\n    let url = \"http://a\";\n}\n",
+                ),
+                ("code.rs", "fn b() {\n    // note\n    2\n}\n"),
+            ],
+        );
+
+        let status = |entries: &[FileEntry], path: &str| {
+            entries
+                .iter()
+                .find(|e| e.path == path)
+                .unwrap()
+                .status
+                .clone()
+        };
+        let plain = compare_directories(&left, &right).unwrap();
+        assert_eq!(status(&plain, "lib.rs"), FileStatus::Modified);
+
+        let options = CompareOptions {
+            ignore_comments: true,
+            ..Default::default()
+        };
+        let entries = compare_directories_with_options(&left, &right, &options).unwrap();
+        assert_eq!(status(&entries, "lib.rs"), FileStatus::Unchanged);
+        assert_eq!(status(&entries, "code.rs"), FileStatus::Modified);
+
+        // The rendered diff drops the comment-only hunk too
+        let diff_options = DiffOptions {
+            ignore_comments: true,
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let comment_only = generate_diff(
+            Some(&left.join("lib.rs")),
+            Some(&right.join("lib.rs")),
+            &diff_options,
+        );
+        assert!(!comment_only.unwrap().has_changes);
+        let code = generate_diff(
+            Some(&left.join("code.rs")),
+            Some(&right.join("code.rs")),
+            &diff_options,
+        );
+        assert!(code.unwrap().has_changes);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    /// A 1x1 grayscale PNG
+    const PNG: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x3a,
+        0x7e, 0x9b, 0x55, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60,
+        0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x48, 0xaf, 0xa4, 0x71, 0x00, 0x00, 0x00, 0x00, 0x49,
+        0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    #[test]
+    fn test_binary_files() {
+        use crate::delta::{generate_diff, get_file_content, DeltaError, DiffOptions};
+
+        let (left, right) = fixture(
+            "binary",
+            &[("notes.txt", "héllo\n")],
+            &[("notes.txt", "héllo\n")],
+        );
+        std::fs::write(left.join("logo.png"), PNG).unwrap();
+        let mut changed = PNG.to_vec();
+        changed[60] ^= 0xff;
+        std::fs::write(right.join("logo.png"), &changed).unwrap();
+        std::fs::write(right.join("icon.png"), PNG).unwrap();
+
+        assert!(is_binary(&left.join("logo.png")));
+        assert!(!is_binary(&left.join("notes.txt")));
+        // UTF-16 text has NULs but a BOM; a character cut at the sample's end is still UTF-8
+        std::fs::write(left.join("utf16.txt"), [0xFF, 0xFE, b'h', 0, b'i', 0]).unwrap();
+        assert!(!is_binary(&left.join("utf16.txt")));
+        let mut long_text = "a".repeat(8 * 1024 - 1).into_bytes();
+        long_text.extend("é".as_bytes());
+        std::fs::write(left.join("long.txt"), long_text).unwrap();
+        assert!(!is_binary(&left.join("long.txt")));
+        std::fs::write(left.join("latin1.txt"), b"caf\xe9\n").unwrap();
+        assert!(is_binary(&left.join("latin1.txt")));
+        for name in ["utf16.txt", "long.txt", "latin1.txt"] {
+            std::fs::remove_file(left.join(name)).unwrap();
+        }
+
+        let entries = compare_directories(&left, &right).unwrap();
+        let binary = |path: &str| entries.iter().find(|e| e.path == path).unwrap().is_binary;
+        assert!(binary("logo.png") && binary("icon.png"));
+        assert!(!binary("notes.txt"));
+
+        // No text diff is attempted, so delta isn't needed
+        let options = DiffOptions::default();
+        let modified = generate_diff(
+            Some(&left.join("logo.png")),
+            Some(&right.join("logo.png")),
+            &options,
+        )
+        .unwrap();
+        assert!(modified.has_changes);
+        assert!(modified.html.contains("Binary files differ"));
+        let added = generate_diff(None, Some(&right.join("icon.png")), &options).unwrap();
+        assert!(added.html.contains("New binary file"));
+        let same = generate_diff(
+            Some(&left.join("logo.png")),
+            Some(&right.join("icon.png")),
+            &options,
+        )
+        .unwrap();
+        assert!(!same.has_changes);
+
+        assert!(matches!(
+            get_file_content(&left.join("logo.png")),
+            Err(DeltaError::BinaryFile(_))
+        ));
+        assert_eq!(
+            get_file_content(&left.join("notes.txt")).unwrap(),
+            "héllo\n"
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_ignore_whitespace_and_blank_lines() {
+        use crate::delta::{generate_diff, DiffOptions};
+
+        let (left, right) = fixture(
+            "ignore-whitespace",
+            &[
+                ("indent.rs", "fn a() {\n    1\n}\n"),
+                ("blank.rs", "fn b() {\n    2\n}\n"),
+                ("code.rs", "fn c() {\n    3\n}\n"),
+            ],
+            &[
+                ("indent.rs", "fn a() {\n\t1 \n}\n"),
+                ("blank.rs", "fn b() {\n\n    2\n}\n\n"),
+                ("code.rs", "fn c() {\n    4\n}\n"),
+            ],
+        );
+
+        let status = |entries: &[FileEntry], path: &str| {
+            entries
+                .iter()
+                .find(|e| e.path == path)
+                .unwrap()
+                .status
+                .clone()
+        };
+        let options = CompareOptions {
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+        let entries = compare_directories_with_options(&left, &right, &options).unwrap();
+        assert_eq!(status(&entries, "indent.rs"), FileStatus::Unchanged);
+        assert_eq!(status(&entries, "blank.rs"), FileStatus::Unchanged);
+        assert_eq!(status(&entries, "code.rs"), FileStatus::Modified);
+        let whitespace_only = CompareOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let entries = compare_directories_with_options(&left, &right, &whitespace_only).unwrap();
+        assert_eq!(status(&entries, "blank.rs"), FileStatus::Modified);
+
+        // The rendered diff agrees
+        let diff_options = DiffOptions {
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+        for name in ["indent.rs", "blank.rs"] {
+            let result = generate_diff(
+                Some(&left.join(name)),
+                Some(&right.join(name)),
+                &diff_options,
+            );
+            assert!(!result.unwrap().has_changes, "{}", name);
+        }
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_similar_renames() {
+        let body: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        let edited = body.replace("line 5\n", "line five\n");
+        let (left, right) = fixture(
+            "similar-renames",
+            &[("src/util.rs", &body), ("notes.txt", "old notes\n")],
+            &[
+                ("src/helpers.rs", &edited),
+                ("todo.txt", "something else\n"),
+            ],
+        );
+        let statuses = |options: &CompareOptions| -> Vec<(String, FileStatus)> {
+            compare_directories_with_options(&left, &right, options)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.path, e.status))
+                .collect()
+        };
+
+        // Turned off, an edited rename is a deletion and an addition
+        let exact_only = CompareOptions {
+            detect_similar_renames: false,
+            ..Default::default()
+        };
+        assert!(statuses(&exact_only)
+            .iter()
+            .all(|(_, s)| *s != FileStatus::Renamed));
+
+        let options = CompareOptions::default();
+        assert!(options.detect_similar_renames);
+        let entries = compare_directories_with_options(&left, &right, &options).unwrap();
+        let renamed: Vec<&FileEntry> = entries
+            .iter()
+            .filter(|e| e.status == FileStatus::Renamed)
+            .collect();
+        assert_eq!(renamed.len(), 1);
+        let rename = renamed[0].rename_info.as_ref().unwrap();
+        assert_eq!(
+            (rename.from.as_str(), rename.to.as_str()),
+            ("src/util.rs", "src/helpers.rs")
+        );
+        // 9 shared lines out of 11 distinct
+        assert!((rename.similarity - 9.0 / 11.0).abs() < 1e-6);
+        assert!(rename.modified);
+        assert!(renamed[0]
+            .left_path
+            .as_deref()
+            .unwrap()
+            .ends_with("util.rs"));
+        assert!(renamed[0]
+            .right_path
+            .as_deref()
+            .unwrap()
+            .ends_with("helpers.rs"));
+        // Unrelated files stay as they were, and an edited rename isn't "identical"
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| e.status == FileStatus::Added)
+                .count(),
+            1
+        );
+        assert!(identical_files(&entries).moved.is_empty());
+
+        let strict = CompareOptions {
+            rename_threshold: Some(0.9),
+            ..options.clone()
+        };
+        assert!(statuses(&strict)
+            .iter()
+            .all(|(_, s)| *s != FileStatus::Renamed));
+
+        // The same lines reordered score 1.0, yet it's a rename with changes
+        let reordered: String = (1..=10).rev().map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(right.join("src/helpers.rs"), reordered).unwrap();
+        let entries = compare_directories_with_options(&left, &right, &options).unwrap();
+        let rename = entries.iter().find_map(|e| e.rename_info.clone()).unwrap();
+        assert_eq!((rename.similarity, rename.modified), (1.0, true));
+        assert!(identical_files(&entries).moved.is_empty());
+
+        // Files over max_file_size_for_diff aren't read for similarity
+        let small_files = CompareOptions {
+            max_file_size_for_diff: Some(40),
+            ..options
+        };
+        assert!(statuses(&small_files)
+            .iter()
+            .all(|(_, s)| *s != FileStatus::Renamed));
+
+        assert_eq!(line_similarity("a\nb\n", "b\na\n"), 1.0);
+        assert_eq!(line_similarity("", ""), 1.0);
+        assert_eq!(line_similarity("a\n", "b\n"), 0.0);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_rename_matching_by_content_hash() {
+        let (left, right) = fixture(
+            "rename-hash",
+            &[
+                ("a.txt", "alpha\n"),
+                ("b.txt", "one two\n"),
+                ("c.txt", "same size 1"),
+                ("big.txt", "0123456789"),
+            ],
+            &[
+                ("x.txt", "alpha"),
+                ("y.txt", "one  two\n\n"),
+                ("z.txt", "same size 2"),
+                ("huge.txt", "0123456789"),
+            ],
+        );
+        let listed = |dir: &Path, names: &[&str]| -> Vec<(PathBuf, PathBuf)> {
+            names
+                .iter()
+                .map(|n| (PathBuf::from(n), dir.join(n)))
+                .collect()
+        };
+        let deleted = listed(&left, &["a.txt", "b.txt", "c.txt", "big.txt"]);
+        let added = listed(&right, &["x.txt", "y.txt", "z.txt", "huge.txt"]);
+        let not_cancelled = AtomicBool::new(false);
+        let pairs = |options: &CompareOptions| -> Vec<(usize, usize)> {
+            match_renames(&deleted, &added, options, 2, &not_cancelled)
+                .unwrap()
+                .iter()
+                .map(|m| (m.deleted, m.added))
+                .collect()
+        };
+
+        // Same-size files with different bytes never pair up; big files hash by their raw bytes
+        assert_eq!(pairs(&CompareOptions::default()), vec![(3, 3)]);
+        let large = CompareOptions {
+            max_file_size_for_diff: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(pairs(&large), vec![(3, 3)]);
+
+        // The ignore options match what compare_contents would call equal
+        let lenient = CompareOptions {
+            ignore_trailing_newline: true,
+            ..Default::default()
+        };
+        assert_eq!(pairs(&lenient), vec![(0, 0), (3, 3)]);
+        let whitespace = CompareOptions {
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+        assert_eq!(pairs(&whitespace), vec![(0, 0), (1, 1), (3, 3)]);
+
+        // Pairs equal only under the options are renames with changes, scored by their lines
+        let found = match_renames(&deleted, &added, &whitespace, 1, &not_cancelled).unwrap();
+        let scores: Vec<(f32, bool)> = found.iter().map(|m| (m.similarity, m.modified)).collect();
+        assert_eq!(scores, vec![(0.0, true), (0.0, true), (1.0, false)]);
+        std::fs::write(right.join("y.txt"), "one two\nthree\n").unwrap();
+        let measured =
+            measure_rename("b.txt", "y.txt", &left.join("b.txt"), &right.join("y.txt")).unwrap();
+        assert_eq!((measured.similarity, measured.modified), (0.5, true));
+        let measured = measure_rename(
+            "big.txt",
+            "huge.txt",
+            &left.join("big.txt"),
+            &right.join("huge.txt"),
+        )
+        .unwrap();
+        assert_eq!((measured.similarity, measured.modified), (1.0, false));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_parallel_rename_matching_is_deterministic() {
+        // Groups of identical files make the greedy pairing order-sensitive
+        let left: Vec<(String, String)> = (0..60)
+            .map(|i| (format!("old/f{:02}.txt", i), format!("body {}\n", i % 7)))
+            .collect();
+        let right: Vec<(String, String)> = (0..50)
+            .map(|i| {
+                (
+                    format!("new/g{:02}.txt", i),
+                    format!("body {}\n", (i * 3) % 9),
+                )
+            })
+            .collect();
+        let left_files: Vec<(&str, &str)> =
+            left.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let right_files: Vec<(&str, &str)> = right
+            .iter()
+            .map(|(p, c)| (p.as_str(), c.as_str()))
+            .collect();
+        let (left_dir, right_dir) = fixture("parallel-renames", &left_files, &right_files);
+
+        let listed = |dir: &Path, files: &[(&str, &str)]| -> Vec<(PathBuf, PathBuf)> {
+            files
+                .iter()
+                .map(|(p, _)| (PathBuf::from(p), dir.join(p)))
+                .collect()
+        };
+        let deleted = listed(&left_dir, &left_files);
+        let added = listed(&right_dir, &right_files);
+        let options = CompareOptions::default();
+
+        let not_cancelled = AtomicBool::new(false);
+        let sequential = match_renames(&deleted, &added, &options, 1, &not_cancelled).unwrap();
+        assert!(!sequential.is_empty());
+        for workers in [2, 3, 8, 64] {
+            assert_eq!(
+                match_renames(&deleted, &added, &options, workers, &not_cancelled).unwrap(),
+                sequential
+            );
+        }
+
+        // Each deleted file takes the first unmatched added file with the same content
+        let mut taken = Vec::new();
+        let mut expected = Vec::new();
+        for (d, (_, left_body)) in left_files.iter().enumerate() {
+            if let Some(a) = (0..right_files.len())
+                .find(|a| !taken.contains(a) && right_files[*a].1 == *left_body)
+            {
+                taken.push(a);
+                expected.push((d, a));
+            }
+        }
+        let pairs: Vec<(usize, usize)> = sequential.iter().map(|m| (m.deleted, m.added)).collect();
+        assert_eq!(pairs, expected);
+
+        let entries = compare_directories_with_options(&left_dir, &right_dir, &options).unwrap();
+        let renamed = entries
+            .iter()
+            .filter(|e| e.status == FileStatus::Renamed)
+            .count();
+        assert_eq!(renamed, expected.len());
+
+        let _ = std::fs::remove_dir_all(left_dir.parent().unwrap());
+    }
+
+    #[test]
+    fn test_parallel_content_checks_sorted() {
+        let names: Vec<String> = (0..64)
+            .map(|i| format!("dir{}/file{:02}.txt", i % 4, i))
+            .collect();
+        let left: Vec<(&str, String)> = names
+            .iter()
+            .map(|n| (n.as_str(), format!("{} v1", n)))
+            .collect();
+        let right: Vec<(&str, String)> = names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                (
+                    n.as_str(),
+                    format!("{} v{}", n, if i.is_multiple_of(3) { 2 } else { 1 }),
+                )
+            })
+            .collect();
+        let left: Vec<(&str, &str)> = left.iter().map(|(n, c)| (*n, c.as_str())).collect();
+        let right: Vec<(&str, &str)> = right.iter().map(|(n, c)| (*n, c.as_str())).collect();
+        let (left_dir, right_dir) = fixture("parallel-contents", &left, &right);
+
+        let entries = compare_directories(&left_dir, &right_dir).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths, sorted);
+        assert_eq!(entries.len(), 64);
+        for entry in &entries {
+            let index: usize = entry.path[entry.path.len() - 6..entry.path.len() - 4]
+                .parse()
+                .unwrap();
+            let expected = if index.is_multiple_of(3) {
+                FileStatus::Modified
+            } else {
+                FileStatus::Unchanged
+            };
+            assert_eq!(entry.status, expected, "{}", entry.path);
+        }
+
+        // Same result however the checks happen to finish
+        let again = compare_directories(&left_dir, &right_dir).unwrap();
+        let statuses = |entries: &[FileEntry]| -> Vec<(String, FileStatus)> {
+            entries
+                .iter()
+                .map(|e| (e.path.clone(), e.status.clone()))
+                .collect()
+        };
+        assert_eq!(statuses(&again), statuses(&entries));
+
+        let _ = std::fs::remove_dir_all(left_dir.parent().unwrap());
+    }
+
+    #[test]
+    fn test_exclude_exact_paths() {
+        let (left, right) = fixture(
+            "exclude-paths",
+            &[
+                ("build/version.txt", "1.0"),
+                ("build/notes.txt", "a"),
+                ("version.txt", "1.0"),
+            ],
+            &[
+                ("build/version.txt", "1.1"),
+                ("build/notes.txt", "b"),
+                ("version.txt", "1.1"),
+            ],
+        );
+
+        for (exclude, parallel_walk) in
+            [("build/version.txt", false), ("./build\\version.txt", true)]
+        {
+            let options = CompareOptions {
+                exclude_paths: vec![exclude.to_string()],
+                parallel_walk,
+                ..Default::default()
+            };
+            let paths: Vec<String> = compare_directories_with_options(&left, &right, &options)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.path)
+                .collect();
+            // Exact match only: a same-named file elsewhere is still compared
+            assert_eq!(paths, vec!["build/notes.txt", "version.txt"]);
+        }
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_file_tree_max_files() {
+        let (left, right) = fixture(
+            "max-files",
+            &[
+                ("a.txt", "1"),
+                ("b.txt", "1"),
+                ("gone.txt", "x"),
+                ("same.txt", "s"),
+            ],
+            &[
+                ("a.txt", "2"),
+                ("b.txt", "2"),
+                ("c.txt", "new"),
+                ("d.txt", "new"),
+                ("same.txt", "s"),
+            ],
+        );
+        let (left_dir, right_dir) = (left.to_string_lossy(), right.to_string_lossy());
+
+        let options = CompareOptions {
+            max_files: Some(3),
+            ..Default::default()
+        };
+        let capped = get_file_tree(&left_dir, &right_dir, Some(options), None).unwrap();
+        assert!(capped.truncated);
+        assert_eq!(capped.total_available, 5);
+        let paths: Vec<&str> = capped.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(capped.tree.len(), 3);
+        // Counts cover every change, not just the returned ones
+        assert_eq!(
+            (
+                capped.total_changes,
+                capped.added,
+                capped.deleted,
+                capped.modified
+            ),
+            (5, 2, 1, 2)
+        );
+
+        let full = get_file_tree(&left_dir, &right_dir, None, None).unwrap();
+        assert!(!full.truncated);
+        assert_eq!((full.files.len(), full.total_available), (5, 5));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_file_tree_binary_round_trip() {
+        let (left, right) = fixture(
+            "tree-binary",
+            &[
+                ("src/lib.rs", "old"),
+                ("gone.txt", "x"),
+                ("docs/a.md", "same text"),
+            ],
+            &[
+                ("src/lib.rs", "new"),
+                ("src/main.rs", "fn main() {}"),
+                ("docs/b.md", "same text"),
+            ],
+        );
+        let mut result = get_file_tree(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            None,
+            None,
+        )
+        .unwrap();
+        // Cover the struct variant and the optional fields too
+        result.files[0].status = FileStatus::Copied {
+            from: "src/lib.rs".to_string(),
+            similarity: 0.75,
+        };
+        result.files[0].rename_info = Some(RenameInfo {
+            from: "a".to_string(),
+            to: "b".to_string(),
+            similarity: 0.5,
+            modified: true,
+        });
+        result.truncated = true;
+
+        let bytes = encode_file_tree(&result).unwrap();
+        let decoded: FileTreeResult = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, result);
+        assert!(bytes.len() < serde_json::to_vec(&result).unwrap().len());
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_identical_files() {
+        let (left, right) = fixture(
+            "identical",
+            &[
+                ("same.txt", "unchanged\n"),
+                ("old/moved.txt", "moved as-is\n"),
+                ("edit.txt", "before\n"),
+            ],
+            &[
+                ("same.txt", "unchanged\n"),
+                ("new/moved.txt", "moved as-is\n"),
+                ("edit.txt", "after\n"),
+            ],
+        );
+
+        let report = get_identical_files(left.to_str().unwrap(), right.to_str().unwrap()).unwrap();
+        assert_eq!(
+            report,
+            IdenticalReport {
+                same_path: vec!["same.txt".to_string()],
+                moved: vec![("old/moved.txt".to_string(), "new/moved.txt".to_string())],
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_file_size_for_diff() {
+        let big = "x".repeat(4096);
+        let mut big_edited = big.clone();
+        big_edited.replace_range(2000..2001, "y");
+        let (left, right) = fixture(
+            "max-size",
+            &[
+                ("media.bin", &big),
+                ("same.bin", &big),
+                ("gone.bin", &big),
+                ("small.txt", "a\n"),
+            ],
+            &[
+                ("media.bin", &big_edited),
+                ("same.bin", &big),
+                ("new.bin", &format!("{}z", big)),
+                ("small.txt", "b\n"),
+            ],
+        );
+        let options = CompareOptions {
+            max_file_size_for_diff: Some(1024),
+            ..Default::default()
+        };
+
+        let entries = compare_directories_with_options(&left, &right, &options).unwrap();
+        let find = |path: &str| entries.iter().find(|e| e.path == path).unwrap();
+        assert_eq!(
+            (
+                find("media.bin").status.clone(),
+                find("media.bin").skip_content
+            ),
+            (FileStatus::Modified, true)
+        );
+        assert_eq!(
+            (
+                find("same.bin").status.clone(),
+                find("same.bin").skip_content
+            ),
+            (FileStatus::Unchanged, true)
+        );
+        assert_eq!(
+            (
+                find("gone.bin").status.clone(),
+                find("gone.bin").skip_content
+            ),
+            (FileStatus::Deleted, true)
+        );
+        assert_eq!(
+            (find("new.bin").status.clone(), find("new.bin").skip_content),
+            (FileStatus::Added, true)
+        );
+        assert_eq!(
+            (
+                find("small.txt").status.clone(),
+                find("small.txt").skip_content
+            ),
+            (FileStatus::Modified, false)
+        );
+
+        // Not content-diffed: the large files add no line counts
+        let breakdown = get_extension_breakdown(
+            left.to_str().unwrap(),
+            right.to_str().unwrap(),
+            Some(options.clone()),
+        )
+        .unwrap();
+        let stats = |ext: &str| breakdown.iter().find(|(e, _)| e == ext).unwrap().1.clone();
+        assert_eq!(
+            (
+                stats(".bin").modified,
+                stats(".bin").lines_added,
+                stats(".bin").lines_removed
+            ),
+            (1, 0, 0)
+        );
+        assert_eq!(
+            (stats(".txt").lines_added, stats(".txt").lines_removed),
+            (1, 1)
+        );
+
+        let unlimited = compare_directories(&left, &right).unwrap();
+        assert!(unlimited.iter().all(|e| !e.skip_content));
+    }
+
+    /// Sets the cancel flag the first time a file is compared, counting the files it sees
+    #[derive(Debug)]
+    struct CancelOnFirstFile {
+        cancel: Arc<AtomicBool>,
+        seen: AtomicUsize,
+    }
+
+    impl Normalizer for CancelOnFirstFile {
+        fn normalize(
+            &self,
+            _left: &Path,
+            _right: &Path,
+            _old: &str,
+            _new: &str,
+        ) -> Option<(String, String)> {
+            self.seen.fetch_add(1, Ordering::Relaxed);
+            self.cancel.store(true, Ordering::Relaxed);
+            None
+        }
+    }
+
+    #[test]
+    fn test_cancel_comparison_mid_scan() {
+        let files: Vec<(String, String)> = (0..3000)
+            .map(|i| (format!("d{}/f{}.txt", i % 30, i), i.to_string()))
+            .collect();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(p, c)| (p.as_str(), c.as_str()))
+            .collect();
+        let (left, right) = fixture("cancel", &files, &files);
+
+        // Cancelled from inside the comparison, once it's well under way
+        let cancel = Arc::new(AtomicBool::new(false));
+        let canceller = Arc::new(CancelOnFirstFile {
+            cancel: cancel.clone(),
+            seen: AtomicUsize::new(0),
+        });
+        let options = CompareOptions {
+            parallel_walk: true,
+            normalizers: vec![canceller.clone()],
+            ..Default::default()
+        };
+        let result = compare_directories_cancellable(&left, &right, &options, &cancel);
+        assert!(
+            matches!(result, Err(DiffError::Cancelled)),
+            "{:?}",
+            result.map(|e| e.len())
+        );
+        // The queued files are dropped rather than compared
+        assert!(canceller.seen.load(Ordering::Relaxed) < 100);
+
+        // An untouched flag compares as usual
+        let options = CompareOptions {
+            parallel_walk: true,
+            ..Default::default()
+        };
+        let entries =
+            compare_directories_cancellable(&left, &right, &options, &AtomicBool::new(false))
+                .unwrap();
+        assert_eq!(entries.len(), 3000);
+    }
+
+    /// Cancels the comparison started with its token when a file is compared
+    #[derive(Debug)]
+    struct CancelByToken(&'static str);
+
+    impl Normalizer for CancelByToken {
+        fn normalize(
+            &self,
+            _left: &Path,
+            _right: &Path,
+            _old: &str,
+            _new: &str,
+        ) -> Option<(String, String)> {
+            cancel_comparison(Some(self.0.to_string()));
+            None
+        }
+    }
+
+    #[test]
+    fn test_cancel_comparison_by_token() {
+        let names: Vec<String> = (0..200).map(|i| format!("f{}.txt", i)).collect();
+        let old: Vec<(&str, &str)> = names.iter().map(|n| (n.as_str(), "a")).collect();
+        let new: Vec<(&str, &str)> = names.iter().map(|n| (n.as_str(), "b")).collect();
+        let (left, right) = fixture("cancel-token", &old, &new);
+        let (left, right) = (left.to_string_lossy(), right.to_string_lossy());
+        let tree = |token: &'static str| {
+            let options = CompareOptions {
+                normalizers: vec![Arc::new(CancelByToken(token))],
+                ..Default::default()
+            };
+            get_file_tree(&left, &right, Some(options), Some("tree-mine".to_string()))
+        };
+
+        // Another comparison's token leaves this one running
+        assert_eq!(tree("tree-other").unwrap().modified, 200);
+        assert_eq!(
+            tree("tree-mine").unwrap_err(),
+            DiffError::Cancelled.to_string()
+        );
+        assert!(!cancel_comparison(Some("tree-mine".to_string())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_diff_stats_flags_stalled_file() {
+        let (left, right) = fixture(
+            "diff-stats",
+            &[("a.txt", "1\n"), ("slow.txt", "x\n"), ("z.txt", "z\n")],
+            &[
+                ("a.txt", "2\n3\n"),
+                ("slow.txt", "y\n"),
+                ("z.txt", "z\n"),
+                ("new.txt", "n\n"),
+            ],
+        );
+        let entries = compare_directories(&left, &right).unwrap();
+
+        // Stands in for a count that never finishes on one file
+        let stalling_stats = |entry: &FileEntry, deadline: Instant| {
+            if entry.name == "slow.txt" {
+                return run_until(deadline, || {
+                    std::thread::sleep(Duration::from_secs(5));
+                    (0, 0)
+                });
+            }
+            Some((entry.path.len(), 1))
+        };
+        let started = Instant::now();
+        let report = diff_stats(&entries, Duration::from_millis(200), stalling_stats);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let flagged: Vec<(&str, bool)> = report
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.timed_out))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![("a.txt", false), ("new.txt", false), ("slow.txt", true)]
+        );
+        assert_eq!(report.timed_out, 1);
+        assert_eq!(
+            (report.added, report.removed),
+            ("a.txt".len() + "new.txt".len(), 2)
+        );
+
+        let report =
+            get_diff_stat(left.to_str().unwrap(), right.to_str().unwrap(), None, None).unwrap();
+        assert_eq!(report.timed_out, 0);
+        assert_eq!((report.added, report.removed), (4, 2));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_change_heatmap_nested_totals() {
+        let (left, right) = fixture(
+            "heatmap",
+            &[
+                ("README.md", "old\n"),
+                ("src/lib.rs", "a\nb\n"),
+                ("src/ui/view.rs", "x\n"),
+                ("src/ui/gone.rs", "bye\n"),
+                ("docs/guide.md", "same\n"),
+            ],
+            &[
+                ("README.md", "new\n"),
+                ("src/lib.rs", "a\nc\nd\n"),
+                ("src/ui/view.rs", "y\n"),
+                ("src/ui/widgets/button.rs", "click\nme\n"),
+                ("docs/guide.md", "same\n"),
+            ],
+        );
+
+        let heat =
+            get_change_heatmap(left.to_str().unwrap(), right.to_str().unwrap(), None).unwrap();
+        let paths: Vec<&str> = heat.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, vec!["", "src", "src/ui", "src/ui/widgets"]);
+
+        // Every total is the directory's own changes plus its direct children's totals
+        for dir in &heat {
+            let children = heat.iter().filter(|h| {
+                !h.path.is_empty()
+                    && h.path.rsplit_once('/').map_or("", |(parent, _)| parent) == dir.path
+            });
+            let (mut changes, mut additions, mut deletions) =
+                (dir.own_changes, dir.own_additions, dir.own_deletions);
+            for child in children {
+                changes += child.changes;
+                additions += child.additions;
+                deletions += child.deletions;
+            }
+            assert_eq!(
+                (dir.changes, dir.additions, dir.deletions),
+                (changes, additions, deletions),
+                "{}",
+                dir.path
+            );
+        }
+
+        let root = &heat[0];
+        assert_eq!(
+            (
+                root.changes,
+                root.own_changes,
+                root.own_additions,
+                root.own_deletions
+            ),
+            (5, 1, 1, 1)
+        );
+        assert_eq!(
+            (root.additions, root.deletions),
+            (1 + 2 + 1 + 2, 1 + 1 + 1 + 1)
+        );
+        let widgets = &heat[3];
+        assert_eq!(
+            (widgets.changes, widgets.own_changes, widgets.additions),
+            (1, 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_truncated_continuation() {
+        let log = "start\nstep 1\nstep 2\n";
+        let grown = format!("{}step 3\nstep 4\n", log);
+        let (left, right) = fixture(
+            "truncation",
+            &[
+                ("app.log", log),
+                ("cut.log", "one\ntw"),
+                ("edited.log", log),
+            ],
+            &[
+                ("app.log", &grown),
+                ("cut.log", "one\ntwo\n"),
+                ("edited.log", "start\nstep one\n"),
+            ],
+        );
+        let options = CompareOptions {
+            treat_truncation_as_equal: true,
+            ..Default::default()
+        };
+
+        let entries = compare_directories_with_options(&left, &right, &options).unwrap();
+        let find = |path: &str| entries.iter().find(|e| e.path == path).unwrap();
+        assert_eq!(find("app.log").status, FileStatus::Modified);
+        assert!(find("app.log").truncated_continuation);
+        assert!(find("cut.log").truncated_continuation);
+        assert!(!find("edited.log").truncated_continuation);
+
+        let plain = compare_directories(&left, &right).unwrap();
+        assert!(plain.iter().all(|e| !e.truncated_continuation));
+
+        // get_diff with the same flag shows only the appended lines, numbered as in the full file
+        let diff_options = crate::delta::DiffOptions {
+            treat_truncation_as_equal: true,
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let (old, new) = (left.join("app.log"), right.join("app.log"));
+        let result = crate::delta::generate_diff(Some(&old), Some(&new), &diff_options).unwrap();
+        assert_eq!(result.hunk_count, 1);
+        let hunk = &result.hunks[0];
+        assert_eq!(
+            (
+                hunk.old_start,
+                hunk.old_lines,
+                hunk.new_start,
+                hunk.new_lines
+            ),
+            (3, 0, 4, 2)
+        );
+    }
+
+    #[test]
+    fn test_files_differ_large() {
+        let (left, right) = large_pair("same", false);
+        assert!(!files_differ(&left, &right).unwrap());
+        let (changed_left, changed_right) = large_pair("changed", true);
+        assert!(files_differ(&changed_left, &changed_right).unwrap());
+
+        std::fs::write(&right, b"short").unwrap();
+        assert!(files_differ(&left, &right).unwrap());
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+        let _ = std::fs::remove_dir_all(changed_left.parent().unwrap());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mapped_differ_large() {
+        use crate::diff::mapped_differ;
+
+        let len = 8 * 1024 * 1024;
+        let (left, right) = large_pair("mmap-same", false);
+        assert_eq!(mapped_differ(&left, &right, len), Some(false));
+        let (changed_left, changed_right) = large_pair("mmap-changed", true);
+        assert_eq!(
+            mapped_differ(&changed_left, &changed_right, len),
+            Some(true)
+        );
+
+        // A size other than the one compared at falls back to streaming
+        assert_eq!(mapped_differ(&left, &right, len - 1), None);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+        let _ = std::fs::remove_dir_all(changed_left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_classify_entries_streams_all() {
+        let (left, right) = fixture(
+            "stream",
+            &[
+                ("same.txt", "same\n"),
+                ("edit.txt", "old\n"),
+                ("gone.txt", "gone\n"),
+                ("a/moved.txt", "moved\n"),
+            ],
+            &[
+                ("same.txt", "same\n"),
+                ("edit.txt", "new\n"),
+                ("fresh.txt", "fresh\n"),
+                ("b/moved.txt", "moved\n"),
+            ],
+        );
+        let options = CompareOptions::default();
+
+        let mut streamed = Vec::new();
+        let mut counts = ComparisonCounts::default();
+        classify_entries(
+            &left,
+            &right,
+            &options,
+            &AtomicBool::new(false),
+            &mut |entry| {
+                counts.count(&entry);
+                streamed.push(entry);
+            },
+        )
+        .unwrap();
+
+        // Every entry compare_directories returns is emitted, once
+        streamed.sort_by(|a, b| a.path.cmp(&b.path));
+        let collected = compare_directories_with_options(&left, &right, &options).unwrap();
+        let key = |e: &FileEntry| (e.path.clone(), e.status.clone());
+        assert_eq!(
+            streamed.iter().map(key).collect::<Vec<_>>(),
+            collected.iter().map(key).collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            counts,
+            ComparisonCounts {
+                total_changes: 4,
+                added: 1,
+                deleted: 1,
+                modified: 1,
+                renamed: 1,
+                unchanged: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_recursive_diff_only_in_lines() {
+        let (left, right) = fixture(
+            "only-in",
+            &[
+                ("edit.txt", "a\n"),
+                ("gone.txt", "gone\n"),
+                ("pic.png", "\0a"),
+            ],
+            &[
+                ("edit.txt", "b\n"),
+                ("sub/fresh.txt", "fresh\n"),
+                ("pic.png", "\0b"),
+                ("new dir/x.txt", "x\n"),
+            ],
+        );
+        let (l, r) = (left.display(), right.display());
+        let output = format!(
+            "Only in {l}: gone.txt\n\
+             diff -r {l}/edit.txt {r}/edit.txt\n1c1\n< a\n---\n> b\n\
+             Only in {r}/sub: fresh.txt\n\
+             Binary files {l}/pic.png and {r}/pic.png differ\n\
+             Only in {r}: new dir\n"
+        );
+
+        let entries = parse_recursive_diff(&output, &left, &right);
+        let found: Vec<(&str, FileStatus)> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.status.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("edit.txt", FileStatus::Modified),
+                ("gone.txt", FileStatus::Deleted),
+                ("new dir/x.txt", FileStatus::Added),
+                ("pic.png", FileStatus::Modified),
+                ("sub/fresh.txt", FileStatus::Added),
+            ]
+        );
+        let gone = entries.iter().find(|e| e.path == "gone.txt").unwrap();
+        assert_eq!(
+            gone.left_path,
+            Some(left.join("gone.txt").to_string_lossy().to_string())
+        );
+        assert_eq!(gone.right_path, None);
+
+        // Listed in the tree, never passed through as text
+        let result = import_recursive_diff(&l.to_string(), &r.to_string(), output.clone());
+        assert_eq!((result.added, result.deleted, result.modified), (2, 1, 2));
+        assert!(result
+            .files
+            .iter()
+            .all(|e| !e.path.contains("Only in") && !e.name.contains("Only in")));
+    }
 }
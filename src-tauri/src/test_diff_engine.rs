@@ -0,0 +1,312 @@
+#[cfg(test)]
+mod tests {
+    use crate::comments::CommentStyle;
+    use crate::diff_engine::{diff_files, unified_diff, EngineOptions};
+    use crate::test_fixtures::temp_dir;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// unified_diff with just `context`
+    fn diff(left: &str, right: &str, context: usize) -> String {
+        unified_diff(
+            left,
+            right,
+            &EngineOptions {
+                context,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nB\nc\n", 3),
+            "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+        );
+        assert_eq!(diff("same\n", "same\n", 3), "");
+
+        // Single-line and empty ranges are written the way GNU diff writes them
+        assert_eq!(diff("a\nb\n", "a\nc\n", 0), "@@ -2 +2 @@\n-b\n+c\n");
+        assert_eq!(
+            diff("a\nb\n", "a\nb\nc\nd\n", 0),
+            "@@ -2,0 +3,2 @@\n+c\n+d\n"
+        );
+        assert_eq!(diff("", "x\ny\n", 3), "@@ -0,0 +1,2 @@\n+x\n+y\n");
+        assert_eq!(diff("x\n", "", 3), "@@ -1 +0,0 @@\n-x\n");
+
+        // Changes up to 2 * context lines apart share a hunk
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let near = old
+            .replace("\n5\n", "\nfive\n")
+            .replace("\n12\n", "\ntwelve\n");
+        let far = old
+            .replace("\n5\n", "\nfive\n")
+            .replace("\n13\n", "\nthirteen\n");
+        let headers = |diff: String| {
+            diff.lines()
+                .filter(|l| l.starts_with("@@"))
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(headers(diff(&old, &near, 3)), vec!["@@ -2,14 +2,14 @@"]);
+        assert_eq!(
+            headers(diff(&old, &far, 3)),
+            vec!["@@ -2,7 +2,7 @@", "@@ -10,7 +10,7 @@"]
+        );
+
+        // A changed line pairs up with its replacement rather than a later copy of itself
+        assert_eq!(diff("p\np\nq\n", "x\np\nq\n", 0), "@@ -1 +1 @@\n-p\n+x\n");
+    }
+
+    #[test]
+    fn test_unified_diff_matches_gnu_diff() {
+        if Command::new("diff").arg("--version").output().is_err() {
+            eprintln!("skipping: diff is not installed");
+            return;
+        }
+        let dir = temp_dir("engine-gnu");
+        let (old_path, new_path) = (dir.join("old"), dir.join("new"));
+
+        // Small texts over a few repeated lines, where alignment choices matter most
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as usize
+        };
+        const LINES: [&str; 6] = ["a\n", "b\n", "c\n", "\n", "# note\n", "x"];
+        let cases = [
+            (
+                EngineOptions {
+                    context: 0,
+                    ..Default::default()
+                },
+                vec!["-U0"],
+            ),
+            (
+                EngineOptions {
+                    context: 3,
+                    ..Default::default()
+                },
+                vec!["-U3"],
+            ),
+            (
+                EngineOptions {
+                    context: 1,
+                    ignore_blank_lines: true,
+                    ..Default::default()
+                },
+                vec!["-U1", "-B"],
+            ),
+            (
+                EngineOptions {
+                    context: 1,
+                    ignore_comments: Some(CommentStyle::Hash),
+                    ..Default::default()
+                },
+                // Hash comments as a basic regular expression
+                vec!["-U1", "-I", r"^[[:space:]]*#"],
+            ),
+        ];
+
+        for _ in 0..300 {
+            let mut text = || {
+                let mut lines: Vec<&str> = (0..next(12)).map(|_| LINES[next(5)]).collect();
+                if next(4) == 0 {
+                    lines.push(LINES[5]);
+                }
+                lines.concat()
+            };
+            let (old, new) = (text(), text());
+            std::fs::write(&old_path, &old).unwrap();
+            std::fs::write(&new_path, &new).unwrap();
+
+            for (options, args) in &cases {
+                let output = Command::new("diff")
+                    .args(args)
+                    .arg(&old_path)
+                    .arg(&new_path)
+                    .output()
+                    .unwrap();
+                let expected = String::from_utf8(output.stdout).unwrap();
+                // Drop the ---/+++ header, which unified_diff leaves to its caller
+                let expected: String = expected
+                    .split_inclusive('\n')
+                    .skip_while(|l| !l.starts_with("@@"))
+                    .collect();
+                assert_eq!(
+                    unified_diff(&old, &new, options),
+                    expected,
+                    "diff {:?} of {:?} and {:?}",
+                    args,
+                    old,
+                    new
+                );
+            }
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unified_diff_missing_newline() {
+        assert_eq!(
+            diff("a\nb", "a\nc", 3),
+            "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file\n"
+        );
+        // Only the newline changed
+        assert_eq!(
+            diff("a\nb", "a\nb\n", 3),
+            "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_options() {
+        let old = "int main(void)\n{\n    int a = 1;\n\n    // old note\n    return a;\n}\n";
+        let new = "int main(void)\n{\n    int a = 2;\n\n    // new note\n    return a;\n}\n";
+
+        let function = EngineOptions {
+            context: 1,
+            function_context: true,
+            ..Default::default()
+        };
+        assert!(unified_diff(old, new, &function).starts_with("@@ -2,5 +2,5 @@ int main(void)\n"));
+
+        // Hunks made only of comment changes are left out
+        let comments = EngineOptions {
+            context: 0,
+            ignore_comments: Some(CommentStyle::CLike),
+            ..Default::default()
+        };
+        assert_eq!(
+            unified_diff(old, new, &comments),
+            "@@ -3 +3 @@\n-    int a = 1;\n+    int a = 2;\n"
+        );
+
+        // ` * ` lines are comments inside a block only; outside one they're code
+        let block_old = "/*\n * old\n\n */\nint x;\n*p = 1;\n";
+        let block_new = "/*\n * new\n\n   more */\nint x;\n* p = 2;\n";
+        assert_eq!(
+            unified_diff(block_old, block_new, &comments),
+            "@@ -6 +6 @@\n-*p = 1;\n+* p = 2;\n"
+        );
+        let trailing = "int y; /* new */\n/* a */ int z;\n";
+        let changed = unified_diff("int y;\nint z;\n", trailing, &comments);
+        assert_eq!(
+            changed,
+            "@@ -1,2 +1,2 @@\n-int y;\n-int z;\n+int y; /* new */\n+/* a */ int z;\n"
+        );
+
+        // CSS has no `//` comments
+        let css = EngineOptions {
+            ignore_comments: Some(CommentStyle::Css),
+            ..comments
+        };
+        assert_eq!(unified_diff("a {}\n", "/* b */\na {}\n", &css), "");
+        assert_eq!(
+            unified_diff("a {}\n", "// b\na {}\n", &css),
+            "@@ -0,0 +1 @@\n+// b\n"
+        );
+        assert_eq!(
+            CommentStyle::for_path(Path::new("site.css")),
+            Some(CommentStyle::Css)
+        );
+        assert_eq!(
+            CommentStyle::for_path(Path::new("site.scss")),
+            Some(CommentStyle::CLike)
+        );
+
+        // -w matches reindented lines; -B drops hunks that only add or remove blank lines
+        let reformatted = "int main(void) {\n\n  int a = 1;\n\n    // old note\n    return a;\n}\n";
+        let whitespace = EngineOptions {
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            unified_diff(old, reformatted, &whitespace),
+            "@@ -1,2 +1,2 @@\n-int main(void)\n-{\n+int main(void) {\n+\n"
+        );
+        let blank_lines = EngineOptions {
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+        assert_eq!(unified_diff("a\nb\n", "a\n\nb\n", &blank_lines), "");
+        // As with diff, a line of spaces only counts as blank when whitespace is ignored too
+        assert_eq!(
+            unified_diff("a\nb\n", "a\nb\n  \n", &blank_lines),
+            "@@ -2,0 +3 @@\n+  \n"
+        );
+        let blank_and_whitespace = EngineOptions {
+            ignore_whitespace: true,
+            ..blank_lines
+        };
+        assert_eq!(
+            unified_diff("a\nb\n", "a\n\nb\n  \n", &blank_and_whitespace),
+            ""
+        );
+        assert_eq!(
+            unified_diff("a\nb\n", "a\n\nc\n", &blank_lines),
+            "@@ -2 +2,2 @@\n-b\n+\n+c\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let dir = temp_dir("engine");
+        std::fs::write(dir.join("old.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.join("new.txt"), "one\nthree\n").unwrap();
+        std::fs::write(dir.join("a.bin"), [0u8, 1]).unwrap();
+        std::fs::write(dir.join("latin1.txt"), b"caf\xe9\n").unwrap();
+        let options = EngineOptions {
+            context: 3,
+            ..Default::default()
+        };
+
+        let diff = diff_files(
+            &dir.join("old.txt"),
+            &dir.join("new.txt"),
+            "a/x",
+            "b/x",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            diff,
+            "--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n one\n-two\n+three\n"
+        );
+
+        let null = Path::new("/dev/null");
+        let added = diff_files(null, &dir.join("new.txt"), "/dev/null", "b/x", &options).unwrap();
+        assert!(added.starts_with("--- /dev/null\n+++ b/x\n@@ -0,0 +1,2 @@\n"));
+
+        let binary =
+            diff_files(&dir.join("a.bin"), &dir.join("old.txt"), "a", "b", &options).unwrap();
+        assert_eq!(binary, "Binary files a and b differ\n");
+        // Text that isn't UTF-8 can't be diffed line by line without mangling it
+        let latin1 = diff_files(
+            &dir.join("latin1.txt"),
+            &dir.join("old.txt"),
+            "a",
+            "b",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(latin1, "Binary files a and b differ\n");
+        assert_eq!(
+            diff_files(
+                &dir.join("old.txt"),
+                &dir.join("old.txt"),
+                "a",
+                "b",
+                &options
+            )
+            .unwrap(),
+            ""
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{build_delta_command, with_editorconfig, DiffOptions};
+    use crate::editorconfig::tab_width_for;
+    use crate::test_fixtures::temp_dir;
+    use std::path::PathBuf;
+
+    fn project(name: &str) -> PathBuf {
+        let root = temp_dir(&format!("editorconfig-{}", name));
+        std::fs::create_dir_all(root.join("src/vendor")).unwrap();
+        std::fs::write(
+            root.join(".editorconfig"),
+            concat!(
+                "root = true\n\n",
+                "[*]\nindent_size = 2\n\n",
+                "[*.go]\nindent_style = tab\ntab_width = 8\n\n",
+                "[Makefile]\nindent_size = tab\ntab_width = 4\n",
+            ),
+        )
+        .unwrap();
+        // A nested config overrides the outer one for its own directory
+        std::fs::write(
+            root.join("src/vendor/.editorconfig"),
+            "[*.rs]\nindent_size = 3\n",
+        )
+        .unwrap();
+        root
+    }
+
+    #[test]
+    fn test_tab_width_from_editorconfig() {
+        let root = project("width");
+
+        assert_eq!(tab_width_for(&root.join("src/main.rs")), Some(2));
+        assert_eq!(tab_width_for(&root.join("cmd/server.go")), Some(8));
+        assert_eq!(tab_width_for(&root.join("Makefile")), Some(4));
+        assert_eq!(tab_width_for(&root.join("src/vendor/lib.rs")), Some(3));
+
+        let tabs = |options: &DiffOptions| {
+            let args: Vec<String> = build_delta_command(options)
+                .get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect();
+            args.iter()
+                .position(|a| a == "--tabs")
+                .map(|i| args[i + 1].clone())
+        };
+
+        let file = root.join("src/main.rs");
+        let respect = DiffOptions {
+            respect_editorconfig: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            tabs(&with_editorconfig(None, Some(&file), &respect)).as_deref(),
+            Some("2")
+        );
+
+        // Off by default, and an explicit width wins
+        assert_eq!(
+            tabs(&with_editorconfig(
+                None,
+                Some(&file),
+                &DiffOptions::default()
+            )),
+            None
+        );
+        let explicit = DiffOptions {
+            tab_width: Some(6),
+            ..respect
+        };
+        assert_eq!(
+            tabs(&with_editorconfig(None, Some(&file), &explicit)).as_deref(),
+            Some("6")
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
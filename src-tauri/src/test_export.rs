@@ -0,0 +1,231 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::{export_patch, export_review_json};
+    use crate::diff::{compare_directories, FileStatus};
+    use crate::export::{export_files, export_json, redact_patch, ExportOptions};
+    use crate::test_fixtures;
+    use std::path::PathBuf;
+
+    /// One unchanged and one edited file
+    fn fixture(name: &str) -> (PathBuf, PathBuf) {
+        test_fixtures::fixture(
+            &format!("export-{}", name),
+            &[("same.txt", "same\n"), ("edit.txt", "before\n")],
+            &[("same.txt", "same\n"), ("edit.txt", "after\n")],
+        )
+    }
+
+    #[test]
+    fn test_export_include_unchanged() {
+        let (left, right) = fixture("unchanged");
+        let entries = compare_directories(&left, &right).unwrap();
+
+        let files = export_files(&entries, &ExportOptions::default()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "edit.txt");
+        assert!(files[0].patch.contains("--- a/edit.txt"));
+        assert!(files[0].patch.contains("+after"));
+
+        let options = ExportOptions {
+            include_unchanged: true,
+            ..Default::default()
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&export_json(&entries, &options).unwrap()).unwrap();
+        let files = json.as_array().unwrap();
+        assert_eq!(files.len(), 2);
+        let same = files.iter().find(|f| f["path"] == "same.txt").unwrap();
+        assert_eq!(same["status"], "Unchanged");
+        assert_eq!(same["patch"], "");
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_export_patch_applies() {
+        let (left, right) = fixture("patch");
+        std::fs::create_dir_all(left.join("src")).unwrap();
+        std::fs::create_dir_all(right.join("lib")).unwrap();
+        std::fs::write(left.join("gone.txt"), "bye\n").unwrap();
+        std::fs::write(right.join("added.txt"), "hi\nno newline").unwrap();
+        let moved: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(left.join("src/moved.txt"), &moved).unwrap();
+        std::fs::write(right.join("lib/moved.txt"), &moved).unwrap();
+
+        let patch = export_patch(left.to_str().unwrap(), right.to_str().unwrap(), None).unwrap();
+        assert!(
+            patch.contains("diff --git a/edit.txt b/edit.txt\n--- a/edit.txt\n+++ b/edit.txt\n")
+        );
+        assert!(patch
+            .contains("diff --git a/added.txt b/added.txt\nnew file mode 100644\n--- /dev/null\n"));
+        assert!(patch.contains(
+            "diff --git a/gone.txt b/gone.txt\ndeleted file mode 100644\n--- a/gone.txt\n"
+        ));
+        // An unchanged move is all header
+        assert!(patch.contains(
+            "diff --git a/src/moved.txt b/lib/moved.txt\nsimilarity index 100%\n\
+             rename from src/moved.txt\nrename to lib/moved.txt\n"
+        ));
+        assert!(!patch.contains("same.txt"));
+
+        // Replaying it on the left side turns it into the right side
+        let mut apply = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&left)
+            .args(["apply", "-"])
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        use std::io::Write;
+        apply
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(patch.as_bytes())
+            .unwrap();
+        assert!(
+            apply.wait().unwrap().success(),
+            "patch didn't apply:\n{}",
+            patch
+        );
+        let entries = compare_directories(&left, &right).unwrap();
+        assert!(
+            entries.iter().all(|e| e.status == FileStatus::Unchanged),
+            "{:?}",
+            entries
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_export_binary_files() {
+        let (left, right) = fixture("binary");
+        std::fs::write(left.join("image.bin"), b"\x00\x01old").unwrap();
+        std::fs::write(right.join("image.bin"), b"\x00\x01new").unwrap();
+        std::fs::write(right.join("added.bin"), b"\x00\x02").unwrap();
+        std::fs::create_dir_all(right.join("moved")).unwrap();
+        std::fs::write(left.join("blob.bin"), b"\x00\x03blob").unwrap();
+        std::fs::write(right.join("moved/blob.bin"), b"\x00\x03blob").unwrap();
+
+        let patch = export_patch(left.to_str().unwrap(), right.to_str().unwrap(), None).unwrap();
+        assert!(patch.contains(
+            "a/image.bin b/image.bin\nBinary files a/image.bin and b/image.bin differ\n"
+        ));
+        assert!(
+            patch.contains("new file mode 100644\nBinary files /dev/null and b/added.bin differ\n")
+        );
+        assert!(patch.contains("rename to moved/blob.bin\n"));
+        assert!(!patch.contains("blob.bin differ"), "{}", patch);
+        // The only hunk is edit.txt's
+        assert_eq!(patch.matches("\n@@ ").count(), 1, "{}", patch);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_export_redact_content() {
+        let (left, right) = fixture("redact");
+        std::fs::write(
+            left.join("code.rs"),
+            "fn secret_token() {\n    let api_key = 1;\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            right.join("code.rs"),
+            "fn secret_token() {\n    let api_key = 2;\n}\n",
+        )
+        .unwrap();
+        let entries = compare_directories(&left, &right).unwrap();
+
+        let plain = export_files(&entries, &ExportOptions::default()).unwrap();
+        let options = ExportOptions {
+            redact_content: true,
+            ..Default::default()
+        };
+        let redacted = export_files(&entries, &options).unwrap();
+        assert_eq!(plain.len(), redacted.len());
+
+        for (plain, redacted) in plain.iter().zip(&redacted) {
+            for identifier in ["secret_token", "api_key", "before", "after"] {
+                assert!(
+                    !redacted.patch.contains(identifier),
+                    "{} leaked",
+                    identifier
+                );
+            }
+            assert!(redacted.patch.contains(&format!("--- a/{}", plain.path)));
+
+            let plain_lines: Vec<&str> = plain.patch.lines().collect();
+            let redacted_lines: Vec<&str> = redacted.patch.lines().collect();
+            assert_eq!(plain_lines.len(), redacted_lines.len());
+            for (p, r) in plain_lines.iter().zip(&redacted_lines) {
+                assert_eq!(p.chars().count(), r.chars().count());
+                assert_eq!(p.chars().next(), r.chars().next());
+            }
+        }
+
+        // A removed "-- comment" line must not be mistaken for a file header
+        assert_eq!(
+            redact_patch("--- a/x\n+++ b/x\n@@ -1 +1 @@\n--- c\n+ab\n"),
+            "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-\u{2588}\u{2588} \u{2588}\n+\u{2588}\u{2588}\n"
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_export_review_json_snapshot() {
+        let (left, right) = fixture("review");
+        std::fs::write(left.join("edit.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+        std::fs::write(right.join("edit.txt"), "one\nTWO\nthree\nfour\nfive\n").unwrap();
+        std::fs::write(left.join("gone.txt"), "bye\n").unwrap();
+        std::fs::write(right.join("new.txt"), "hi\nthere\n").unwrap();
+
+        let json =
+            export_review_json(left.to_str().unwrap(), right.to_str().unwrap(), None).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let region = |kind: &str, old: (u32, u32), new: (u32, u32)| {
+            serde_json::json!({
+                "kind": kind,
+                "old_start": old.0, "old_lines": old.1,
+                "new_start": new.0, "new_lines": new.1,
+            })
+        };
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "schema": "diff-rust/review",
+                "version": 1,
+                "files": [
+                    {
+                        "path": "edit.txt",
+                        "status": "Modified",
+                        "old_path": "edit.txt",
+                        "new_path": "edit.txt",
+                        "regions": [
+                            region("Modified", (2, 1), (2, 1)),
+                            region("Added", (4, 0), (5, 1)),
+                        ],
+                    },
+                    {
+                        "path": "gone.txt",
+                        "status": "Deleted",
+                        "old_path": "gone.txt",
+                        "new_path": null,
+                        "regions": [region("Removed", (1, 1), (0, 0))],
+                    },
+                    {
+                        "path": "new.txt",
+                        "status": "Added",
+                        "old_path": null,
+                        "new_path": "new.txt",
+                        "regions": [region("Added", (0, 0), (1, 2))],
+                    },
+                ],
+            })
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+}
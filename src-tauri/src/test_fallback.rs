@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{
+        generate_diff, render_inline_output, run_unified_diff, split_side_by_side_output,
+        DiffOptions,
+    };
+    use crate::fallback::delta_like_output;
+    use crate::test_fixtures::file_pair;
+    use std::path::PathBuf;
+
+    fn fixture() -> (PathBuf, PathBuf) {
+        file_pair(
+            "fallback",
+            "rs",
+            "fn main() {\n    let x = 1;\n    drop(x);\n}\n",
+            "fn main() {\n    let x = 2;\n}\n",
+        )
+    }
+
+    #[test]
+    fn test_fallback_inline_layout() {
+        let (left, right) = fixture();
+        let diff_text = run_unified_diff(Some(&left), Some(&right), 3).unwrap();
+        let options = DiffOptions {
+            line_numbers: true,
+            ..Default::default()
+        };
+
+        let ansi = delta_like_output(&diff_text, &options);
+        assert_eq!(ansi.lines().next(), Some("   1 ⋮   1 │fn main() {"));
+        let (html, truncated) = render_inline_output(&ansi, &[], &options);
+        assert!(!truncated);
+        assert!(html.starts_with("<div class=\"delta-output\">"));
+        assert!(html.contains(
+            "<div class=\"diff-line\" style='background:#3f0001'><span class=\"line-num\">   3 ⋮     </span>\
+             <span class=\"line-content\"><span style='background:#3f0001;'>    drop(x);</span>\n</span></div>"
+        ));
+        assert!(html.contains("<div class=\"diff-line\" style='background:#002800'>"));
+        assert_eq!(html.matches("class=\"diff-line\"").count(), 5);
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_fallback_side_by_side_layout() {
+        let (left, right) = fixture();
+        let diff_text = run_unified_diff(Some(&left), Some(&right), 3).unwrap();
+        let options = DiffOptions {
+            side_by_side: true,
+            emit_line_ids: true,
+            ..Default::default()
+        };
+
+        let ansi = delta_like_output(&diff_text, &options);
+        let (left_html, right_html, _) =
+            split_side_by_side_output(&ansi, &[], &[], &options).unwrap();
+        // Removed lines sit next to their replacements; the extra one faces an empty cell
+        assert_eq!(left_html.matches("class=\"diff-line\"").count(), 4);
+        assert_eq!(right_html.matches("class=\"diff-line\"").count(), 4);
+        assert!(left_html.contains("id=\"LR2\" class=\"diff-line\" style='background:#3f0001'"));
+        assert!(right_html.contains("id=\"L2\" class=\"diff-line\" style='background:#002800'"));
+        assert!(left_html.contains("    let x = 1;</span>"));
+        assert!(right_html
+            .contains("<span class=\"line-num\">     </span><span class=\"line-content\"></span>"));
+
+        // With the flag set, diffs render whether or not delta is installed
+        let options = DiffOptions {
+            fallback_renderer: true,
+            ..options
+        };
+        let result = generate_diff(Some(&left), Some(&right), &options).unwrap();
+        assert!(result.has_changes);
+        assert!(result.right_html.unwrap().contains("let x = 2;"));
+
+        // css_classes swaps the inline colors for the classes ansi_stylesheet styles
+        let classed = DiffOptions {
+            css_classes: true,
+            ..options
+        };
+        let result = generate_diff(Some(&left), Some(&right), &classed).unwrap();
+        let left_html = result.left_html.unwrap();
+        assert!(left_html.contains("id=\"LR2\" class=\"diff-line diff-del\">"));
+        assert!(left_html.contains("<span class='diff-del'>    let x = 1;</span>"));
+        assert!(!left_html.contains("background:"));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A fresh, empty `diffr-<name>-<pid>` directory in the system temp dir. Tests run in
+/// parallel in one process, so every caller needs its own name.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("diffr-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write (relative path, content) pairs under `dir`, creating parent directories
+pub fn write_files(dir: &Path, files: &[(&str, &str)]) {
+    for (path, content) in files {
+        let file = dir.join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, content).unwrap();
+    }
+}
+
+/// Fresh `old` and `new` directories under temp_dir(name), populated with (relative path,
+/// content) pairs
+pub fn fixture(name: &str, left: &[(&str, &str)], right: &[(&str, &str)]) -> (PathBuf, PathBuf) {
+    let root = temp_dir(name);
+    for (side, files) in [("old", left), ("new", right)] {
+        let dir = root.join(side);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_files(&dir, files);
+    }
+    (root.join("old"), root.join("new"))
+}
+
+/// `old.<ext>` and `new.<ext>` with the given contents, in temp_dir(name)
+pub fn file_pair(name: &str, ext: &str, left: &str, right: &str) -> (PathBuf, PathBuf) {
+    let dir = temp_dir(name);
+    let (old, new) = (
+        dir.join(format!("old.{}", ext)),
+        dir.join(format!("new.{}", ext)),
+    );
+    std::fs::write(&old, left).unwrap();
+    std::fs::write(&new, right).unwrap();
+    (old, new)
+}
+
+/// Two 8 MiB files, equal or differing only in one byte near the end, in temp_dir(large-<name>)
+pub fn large_pair(name: &str, differ: bool) -> (PathBuf, PathBuf) {
+    let dir = temp_dir(&format!("large-{}", name));
+    let mut content: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    std::fs::write(dir.join("left.bin"), &content).unwrap();
+    if differ {
+        let last = content.len() - 10;
+        content[last] ^= 0xff;
+    }
+    std::fs::write(dir.join("right.bin"), &content).unwrap();
+    (dir.join("left.bin"), dir.join("right.bin"))
+}
+
+/// Run git in `repo` as a fixed test user, failing the test if it fails
+pub fn git(repo: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .expect("git should be installed")
+        .status;
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// A git repository in temp_dir(name) with `files` in its first commit
+pub fn init_repo(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let repo = temp_dir(name);
+    git(&repo, &["init", "-q"]);
+    write_files(&repo, files);
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "init"]);
+    repo
+}
@@ -0,0 +1,389 @@
+#[cfg(test)]
+mod tests {
+    use crate::diff::{CompareOptions, FileStatus};
+    use crate::git::{
+        branch_diff_entries, extract_blob, merge_base, parse_copies, ref_diff_entries,
+        verify_patch, BlobRef, GitError, PatchCheck,
+    };
+    use crate::test_fixtures::{fixture, git, init_repo, temp_dir};
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    /// Content of an entry's BlobRef
+    fn read_input(path: &Option<String>) -> String {
+        let blob = BlobRef::parse(path.as_deref().unwrap())
+            .expect("a blob reference")
+            .read()
+            .unwrap();
+        std::fs::read_to_string(blob.path()).unwrap()
+    }
+
+    /// A repository named git-<name> with `content` committed as src/shared.rs
+    fn shared_repo(name: &str, content: &str) -> PathBuf {
+        init_repo(&format!("git-{}", name), &[("src/shared.rs", content)])
+    }
+
+    #[test]
+    fn test_cross_repo_blobs() {
+        let repo_a = shared_repo("a", "fn main() {\n    println!(\"a\");\n}\n");
+        let repo_b = shared_repo("b", "fn main() {\n    println!(\"b\");\n}\n");
+
+        let left = extract_blob(&repo_a, "HEAD", "src/shared.rs", "left").unwrap();
+        let right = extract_blob(&repo_b, "HEAD", "src/shared.rs", "right").unwrap();
+
+        assert_eq!(left.path().file_name().unwrap(), "shared.rs");
+        assert!(std::fs::read_to_string(left.path())
+            .unwrap()
+            .contains("\"a\""));
+        assert!(std::fs::read_to_string(right.path())
+            .unwrap()
+            .contains("\"b\""));
+
+        // Temp files are cleaned up once the blob is dropped
+        let left_path = left.path().to_path_buf();
+        drop(left);
+        assert!(!left_path.exists());
+
+        // Errors name the side that was wrong
+        let err = extract_blob(&repo_b, "no-such-ref", "src/shared.rs", "right").unwrap_err();
+        assert!(matches!(err, GitError::BadRevision { .. }));
+        assert!(err.to_string().starts_with("right:"));
+
+        let err = extract_blob(&repo_a, "HEAD", "src/missing.rs", "left").unwrap_err();
+        assert!(matches!(err, GitError::MissingPath { .. }));
+
+        let not_repo = temp_dir("git-none");
+        let err = extract_blob(&not_repo, "HEAD", "a.txt", "left").unwrap_err();
+        assert!(matches!(err, GitError::NotARepo { .. }));
+
+        let _ = std::fs::remove_dir_all(&repo_a);
+        let _ = std::fs::remove_dir_all(&repo_b);
+        let _ = std::fs::remove_dir_all(&not_repo);
+    }
+
+    #[test]
+    fn test_branch_diff_since_merge_base() {
+        let repo = shared_repo("branch", "fn main() {}\n");
+        git(&repo, &["branch", "-M", "main"]);
+        std::fs::write(repo.join("src/old.rs"), "// removed on the branch\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "base"]);
+        let base = String::from_utf8(
+            Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["rev-parse", "HEAD"])
+                .output()
+                .unwrap()
+                .stdout,
+        )
+        .unwrap();
+
+        git(&repo, &["checkout", "-q", "-b", "feature"]);
+        std::fs::write(repo.join("src/shared.rs"), "fn main() { feature(); }\n").unwrap();
+        git(&repo, &["rm", "-q", "src/old.rs"]);
+        git(&repo, &["commit", "-q", "-am", "feature work"]);
+
+        // main moves on after the branch point; that must not show up in the diff
+        git(&repo, &["checkout", "-q", "main"]);
+        std::fs::write(repo.join("src/main_only.rs"), "// later on main\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "main work"]);
+        git(&repo, &["checkout", "-q", "feature"]);
+
+        std::fs::write(repo.join("src/untracked.rs"), "// new\n").unwrap();
+        std::fs::write(repo.join(".gitignore"), "*.log\ntarget/\n").unwrap();
+        std::fs::write(repo.join("build.log"), "ignored\n").unwrap();
+        // An ignored copy of the deleted file must not turn the deletion into a rename
+        std::fs::create_dir_all(repo.join("target")).unwrap();
+        std::fs::write(repo.join("target/old.rs"), "// removed on the branch\n").unwrap();
+
+        assert_eq!(merge_base(&repo, "main").unwrap(), base.trim());
+
+        let entries = branch_diff_entries(&repo, "main", &CompareOptions::default()).unwrap();
+        let status = |path: &str| {
+            entries
+                .iter()
+                .find(|e| e.path == path)
+                .map(|e| e.status.clone())
+        };
+        assert_eq!(status("src/shared.rs"), Some(FileStatus::Modified));
+        assert_eq!(status("src/old.rs"), Some(FileStatus::Deleted));
+        assert_eq!(status("src/untracked.rs"), Some(FileStatus::Added));
+        assert_eq!(status(".gitignore"), Some(FileStatus::Added));
+        assert_eq!(status("src/main_only.rs"), None);
+        assert_eq!(status("build.log"), None);
+        assert!(entries
+            .iter()
+            .all(|e| !e.path.starts_with(".git/") && !e.path.starts_with("target/")));
+
+        // Old versions are read from the merge-base when diffed, not from a leftover checkout
+        let shared = entries.iter().find(|e| e.path == "src/shared.rs").unwrap();
+        assert_eq!(read_input(&shared.left_path), "fn main() {}\n");
+        let old_version = BlobRef::parse(shared.left_path.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            (old_version.commit.as_str(), old_version.path.as_str()),
+            (base.trim(), "src/shared.rs")
+        );
+
+        let err =
+            branch_diff_entries(&repo, "no-such-branch", &CompareOptions::default()).unwrap_err();
+        assert!(matches!(err, GitError::BadRevision { .. }));
+
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn test_diff_between_refs() {
+        let original: String = (1..=20)
+            .map(|i| format!("fn item_{}() {{}}\n", i))
+            .collect();
+        let repo = shared_repo("refs", &original);
+        std::fs::write(repo.join("src/gone.rs"), "// deleted later\n").unwrap();
+        std::fs::write(repo.join("src/edit.rs"), "let x = 1;\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "base"]);
+        git(&repo, &["tag", "v1"]);
+
+        git(&repo, &["mv", "src/shared.rs", "src/moved.rs"]);
+        git(&repo, &["rm", "-q", "src/gone.rs"]);
+        std::fs::write(repo.join("src/edit.rs"), "let x = 2;\n").unwrap();
+        std::fs::write(repo.join("src/new.rs"), "// added\n").unwrap();
+        std::fs::write(repo.join("src/mixed.rs"), "let a = 1;\r\nlet b = 2;\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "changes"]);
+        // Uncommitted edits are not part of either ref
+        std::fs::write(repo.join("src/edit.rs"), "let x = 3;\n").unwrap();
+
+        let entries = ref_diff_entries(&repo, "v1", "HEAD", &CompareOptions::default()).unwrap();
+        let entry = |path: &str| entries.iter().find(|e| e.path == path).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert!(entry("src/mixed.rs").mixed_line_endings);
+        assert!(!entry("src/new.rs").mixed_line_endings && !entry("src/new.rs").is_binary);
+        assert_eq!(entry("src/new.rs").status, FileStatus::Added);
+        assert_eq!(entry("src/new.rs").left_path, None);
+        assert_eq!(entry("src/gone.rs").status, FileStatus::Deleted);
+
+        let edit = entry("src/edit.rs");
+        assert_eq!(edit.status, FileStatus::Modified);
+        assert_eq!(read_input(&edit.left_path), "let x = 1;\n");
+        assert_eq!(read_input(&edit.right_path), "let x = 2;\n");
+
+        let moved = entry("src/shared.rs → src/moved.rs");
+        assert_eq!(moved.status, FileStatus::Renamed);
+        let info = moved.rename_info.as_ref().unwrap();
+        assert_eq!(
+            (info.from.as_str(), info.to.as_str(), info.similarity),
+            ("src/shared.rs", "src/moved.rs", 1.0)
+        );
+
+        // Ignore patterns apply as in a directory comparison; a rename to an ignored name
+        // leaves the deletion
+        let options = CompareOptions {
+            ignore_patterns: vec!["mixed.rs".to_string(), "moved.rs".to_string()],
+            ..Default::default()
+        };
+        let entries = ref_diff_entries(&repo, "v1", "HEAD", &options).unwrap();
+        let paths: Vec<(&str, &FileStatus)> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), &e.status))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("src/edit.rs", &FileStatus::Modified),
+                ("src/gone.rs", &FileStatus::Deleted),
+                ("src/new.rs", &FileStatus::Added),
+                ("src/shared.rs", &FileStatus::Deleted),
+            ]
+        );
+
+        let err =
+            ref_diff_entries(&repo, "v1", "no-such-ref", &CompareOptions::default()).unwrap_err();
+        assert!(matches!(err, GitError::BadRevision { .. }));
+        assert!(err.to_string().starts_with("right:"));
+
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn test_verify_exported_patch() {
+        use crate::diff::compare_directories;
+        use crate::export::{export_files, ExportOptions};
+
+        let (left, right) = fixture(
+            "git-verify",
+            &[("a.txt", "one\ntwo\n"), ("src/b.txt", "x\ny\n")],
+            &[("a.txt", "one\n2\n"), ("src/b.txt", "x\nY\n")],
+        );
+
+        let entries = compare_directories(&left, &right).unwrap();
+        let patch: String = export_files(&entries, &ExportOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|f| f.patch)
+            .collect();
+
+        assert_eq!(
+            verify_patch(&left, &patch).unwrap(),
+            PatchCheck {
+                applies: true,
+                rejects: Vec::new(),
+            }
+        );
+
+        // A hunk whose removed line isn't in the tree is rejected, the rest still checks out
+        let corrupted = patch.replace("-y\n", "-not there\n");
+        assert_eq!(
+            verify_patch(&left, &corrupted).unwrap(),
+            PatchCheck {
+                applies: false,
+                rejects: vec!["src/b.txt".to_string()],
+            }
+        );
+
+        // Not a patch at all
+        assert!(matches!(
+            verify_patch(&left, "garbage\n"),
+            Err(GitError::Command(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+
+    #[test]
+    fn test_branch_diff_detects_copies() {
+        let original: String = (1..=20)
+            .map(|i| format!("fn item_{}() -> u32 {{ {} }}\n", i, i))
+            .collect();
+        let repo = shared_repo("copies", &original);
+        std::fs::write(
+            repo.join("src/copy.rs"),
+            original.replace("item_20() -> u32 { 20 }", "item_20() -> u32 { 21 }"),
+        )
+        .unwrap();
+        std::fs::write(repo.join("src/fresh.rs"), "fn unrelated() {}\n").unwrap();
+        git(&repo, &["add", "."]);
+
+        let options = CompareOptions {
+            detect_copies: true,
+            ..Default::default()
+        };
+        let entries = branch_diff_entries(&repo, "HEAD", &options).unwrap();
+        let entry = |path: &str| entries.iter().find(|e| e.path == path).unwrap();
+
+        let copy = entry("src/copy.rs");
+        match &copy.status {
+            FileStatus::Copied { from, similarity } => {
+                assert_eq!(from, "src/shared.rs");
+                assert!(
+                    *similarity > 0.5 && *similarity < 1.0,
+                    "similarity {}",
+                    similarity
+                );
+            }
+            other => panic!("expected a copy, got {:?}", other),
+        }
+        assert_eq!(
+            BlobRef::parse(copy.left_path.as_deref().unwrap())
+                .unwrap()
+                .path,
+            "src/shared.rs"
+        );
+        assert_eq!(entry("src/fresh.rs").status, FileStatus::Added);
+
+        let plain = branch_diff_entries(&repo, "HEAD", &CompareOptions::default()).unwrap();
+        assert!(plain
+            .iter()
+            .all(|e| !matches!(e.status, FileStatus::Copied { .. })));
+
+        assert_eq!(
+            parse_copies(b"M\0a.rs\0R100\0old.rs\0new.rs\0C075\0src.rs\0dst.rs\0A\0b.rs\0"),
+            vec![("src.rs".to_string(), "dst.rs".to_string(), 75)]
+        );
+
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn test_blame_on_branch_diff_lines() {
+        use crate::commands::get_diff_lines;
+        use crate::delta::DiffOptions;
+        use crate::hunks::DiffLineKind;
+
+        let repo = shared_repo("blame", "fn a() {}\nfn b() {}\nfn c() {}\n");
+        git(&repo, &["branch", "-M", "main"]);
+        git(&repo, &["checkout", "-q", "-b", "feature"]);
+        std::fs::write(
+            repo.join("src/shared.rs"),
+            "fn a() {}\nfn b2() {}\nfn c() {}\n",
+        )
+        .unwrap();
+        git(
+            &repo,
+            &["-c", "user.name=Bob", "commit", "-q", "-am", "rename b"],
+        );
+        // Not committed yet
+        std::fs::write(
+            repo.join("src/shared.rs"),
+            "fn a() {}\nfn b2() {}\nfn c() {}\nfn d() {}\n",
+        )
+        .unwrap();
+
+        let entries = branch_diff_entries(&repo, "main", &CompareOptions::default()).unwrap();
+        let entry = entries.iter().find(|e| e.path == "src/shared.rs").unwrap();
+        let left = entry.left_path.as_deref();
+        let right = entry.right_path.as_deref();
+
+        let options = DiffOptions {
+            show_blame: true,
+            ..Default::default()
+        };
+        let lines = get_diff_lines(left, right, options).unwrap();
+        let blame = |kind: DiffLineKind, content: &str| {
+            let line = lines
+                .iter()
+                .find(|l| l.kind == kind && l.content == content)
+                .unwrap();
+            line.blame.clone().expect("changed lines should be blamed")
+        };
+
+        assert_eq!(blame(DiffLineKind::Removed, "fn b() {}").author, "test");
+        assert_eq!(blame(DiffLineKind::Context, "fn a() {}").author, "test");
+        let added = blame(DiffLineKind::Added, "fn b2() {}");
+        assert_eq!(added.author, "Bob");
+        assert_eq!(added.summary, "rename b");
+        assert!(blame(DiffLineKind::Added, "fn d() {}")
+            .commit
+            .chars()
+            .all(|c| c == '0'));
+
+        // Off by default
+        let lines = get_diff_lines(left, right, DiffOptions::default()).unwrap();
+        assert!(lines.iter().all(|l| l.blame.is_none()));
+
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn test_single_file_checks_on_blob_refs() {
+        use crate::commands::{check_mixed_line_endings, get_line_hashes};
+        use crate::hunks::line_hashes;
+
+        let repo = shared_repo("blob-checks", "fn a() {}\r\nfn b() {}\n");
+        let blob = BlobRef {
+            repo: repo.clone(),
+            commit: "HEAD".to_string(),
+            path: "src/shared.rs".to_string(),
+        }
+        .to_string();
+
+        assert_eq!(check_mixed_line_endings(&blob), Ok(true));
+        assert_eq!(
+            get_line_hashes(&blob),
+            Ok(line_hashes(b"fn a() {}\r\nfn b() {}\n"))
+        );
+
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+}
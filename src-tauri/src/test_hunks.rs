@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{generate_diff, run_diff_for_options, run_unified_diff, DiffOptions};
+    use crate::hunks::{
+        changed_lines, line_hashes, parse_hunk_header, parse_hunks, unchanged_ranges, LineChange,
+        LineKind,
+    };
+    use crate::test_fixtures::temp_dir;
+
+    #[test]
+    fn test_parse_hunk_header() {
+        let hunk = parse_hunk_header("@@ -10,4 +12 @@ fn main() {").unwrap();
+        assert_eq!((hunk.old_start, hunk.old_lines), (10, 4));
+        assert_eq!((hunk.new_start, hunk.new_lines), (12, 1));
+        assert_eq!(hunk.header, "fn main() {");
+
+        assert!(parse_hunk_header("--- a/file").is_none());
+    }
+
+    #[test]
+    fn test_changed_lines_small_edit() {
+        let dir = temp_dir("hunks");
+        let left = dir.join("left.txt");
+        let right = dir.join("right.txt");
+        std::fs::write(&left, "a\nb\nc\nd\ne\n").unwrap();
+        // "b" edited, "d" removed, "f" appended
+        std::fs::write(&right, "a\nB\nc\ne\nf\n").unwrap();
+
+        let diff_text = run_unified_diff(Some(&left), Some(&right), 0).unwrap();
+        let changes = changed_lines(&diff_text);
+
+        let added: Vec<u32> = changes
+            .iter()
+            .filter(|c| c.kind == LineKind::Added)
+            .filter_map(|c| c.new_line)
+            .collect();
+        assert_eq!(added, vec![2, 5]);
+
+        let removed: Vec<&LineChange> = changes
+            .iter()
+            .filter(|c| c.kind == LineKind::Removed)
+            .collect();
+        assert_eq!(removed.len(), 2);
+        assert!(removed.iter().all(|c| c.new_line.is_none()));
+        assert_eq!(removed[0].old_line, Some(2));
+        assert_eq!(removed[1].old_line, Some(4));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_function_context_header() {
+        let dir = temp_dir("hunks-fn");
+        let left = dir.join("left.c");
+        let right = dir.join("right.c");
+        let source = |value: &str| {
+            format!(
+                "int compute(int x)\n{{\n    int a = x;\n    int b = a * 2;\n    int c = b + 1;\n    int d = c - 3;\n    return d + {};\n}}\n",
+                value
+            )
+        };
+        std::fs::write(&left, source("1")).unwrap();
+        std::fs::write(&right, source("2")).unwrap();
+
+        let options = DiffOptions {
+            show_function_context: true,
+            collapsed: true,
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let hunks =
+            parse_hunks(&run_diff_for_options(Some(&left), Some(&right), 3, &options).unwrap());
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].header, "int compute(int x)");
+
+        // Without the option the header stays empty
+        let hunks = parse_hunks(&run_unified_diff(Some(&left), Some(&right), 3).unwrap());
+        assert_eq!(hunks[0].header, "");
+
+        let result = generate_diff(Some(&left), Some(&right), &options).unwrap();
+        assert_eq!(result.hunks[0].header, "int compute(int x)");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unchanged_ranges_around_hunk() {
+        let dir = temp_dir("hunks-unchanged");
+        let left = dir.join("left.txt");
+        let right = dir.join("right.txt");
+        std::fs::write(&left, "1\n2\n3\n4\n5\n6\n7\n8\n").unwrap();
+        // Lines 4-5 replaced by three lines
+        std::fs::write(&right, "1\n2\n3\nx\ny\nz\n6\n7\n8\n").unwrap();
+
+        let diff_text = run_unified_diff(Some(&left), Some(&right), 0).unwrap();
+        assert_eq!(unchanged_ranges(&diff_text, 9), vec![(1, 3), (7, 9)]);
+
+        // Identical files are one unchanged range; a fully rewritten file has none
+        assert_eq!(unchanged_ranges("", 9), vec![(1, 9)]);
+        assert_eq!(unchanged_ranges("@@ -1,8 +1,9 @@\n", 9), vec![]);
+        // A pure deletion doesn't split the range
+        assert_eq!(unchanged_ranges("@@ -3,2 +2,0 @@\n", 6), vec![(1, 6)]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_line_hashes() {
+        let before = line_hashes(b"fn main() {\n    let x = 1;\n    x\n}\n");
+        assert_eq!(before.len(), 4);
+        // Same content, same hashes: across calls and whatever the line terminator
+        assert_eq!(
+            line_hashes(b"fn main() {\r\n    let x = 1;\r\n    x\r\n}"),
+            before
+        );
+        // Fixed algorithm (FNV-1a), not a per-process seed
+        assert_eq!(
+            line_hashes(b"a"),
+            vec![0xaf63dc4c8601ec8c & ((1 << 53) - 1)]
+        );
+        assert!(before.iter().all(|h| *h < 1 << 53));
+
+        let after = line_hashes(b"fn main() {\n    let x = 2;\n    x\n}\n");
+        let changed: Vec<usize> = (0..before.len())
+            .filter(|&i| before[i] != after[i])
+            .collect();
+        assert_eq!(changed, vec![1]);
+
+        assert!(line_hashes(b"").is_empty());
+        assert_eq!(line_hashes(b"\n").len(), 1);
+    }
+}
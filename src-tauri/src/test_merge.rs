@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::get_merge_tree;
+    use crate::diff::{CompareOptions, DiffError};
+    use crate::merge::{compare_directories_three_way, MergeStatus};
+    use crate::test_fixtures::temp_dir;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_three_way_comparison() {
+        let root = temp_dir("merge");
+        // Content in base, left and right; None where the file doesn't exist
+        let files: &[(&str, [Option<&str>; 3])] = &[
+            ("same.txt", [Some("s"), Some("s"), Some("s")]),
+            ("src/left.rs", [Some("1"), Some("2"), Some("1")]),
+            ("src/right.rs", [Some("1"), Some("1"), Some("2")]),
+            ("src/both.rs", [Some("1"), Some("2"), Some("2")]),
+            ("src/clash.rs", [Some("1"), Some("2"), Some("3")]),
+            ("new_left.txt", [None, Some("n"), None]),
+            ("gone_right.txt", [Some("g"), Some("g"), None]),
+            ("edit_vs_delete.txt", [Some("e"), Some("e2"), None]),
+            // Only differences the options below ignore
+            ("spaced.txt", [Some("a\n"), Some("a\n\n"), Some("a\n")]),
+            ("build.log", [Some("1"), Some("2"), Some("3")]),
+        ];
+        for (path, contents) in files {
+            for (side, content) in ["base", "left", "right"].into_iter().zip(contents) {
+                if let Some(content) = content {
+                    let file = root.join(side).join(path);
+                    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+                    std::fs::write(file, content).unwrap();
+                }
+            }
+        }
+
+        let (base, left, right) = (root.join("base"), root.join("left"), root.join("right"));
+        let options = CompareOptions {
+            ignore_patterns: vec!["*.log".to_string()],
+            ignore_blank_lines: true,
+            ..Default::default()
+        };
+        let not_cancelled = AtomicBool::new(false);
+        let entries =
+            compare_directories_three_way(&base, &left, &right, &options, &not_cancelled).unwrap();
+        let statuses: Vec<(&str, MergeStatus)> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("edit_vs_delete.txt", MergeStatus::Conflict),
+                ("gone_right.txt", MergeStatus::ChangedRight),
+                ("new_left.txt", MergeStatus::ChangedLeft),
+                ("same.txt", MergeStatus::Unchanged),
+                ("spaced.txt", MergeStatus::Unchanged),
+                ("src/both.rs", MergeStatus::ChangedBoth),
+                ("src/clash.rs", MergeStatus::Conflict),
+                ("src/left.rs", MergeStatus::ChangedLeft),
+                ("src/right.rs", MergeStatus::ChangedRight),
+            ]
+        );
+        assert!(entries[1].right_path.is_none() && entries[1].base_path.is_some());
+        let cancelled = AtomicBool::new(true);
+        let result = compare_directories_three_way(&base, &left, &right, &options, &cancelled);
+        assert!(matches!(result, Err(DiffError::Cancelled)));
+
+        let dir = |side: &str| root.join(side).to_string_lossy().to_string();
+        let merge = get_merge_tree(
+            &dir("base"),
+            &dir("left"),
+            &dir("right"),
+            Some(options),
+            None,
+        )
+        .unwrap();
+        assert_eq!((merge.files.len(), merge.conflicts), (7, 2));
+        // Directories first, each counting the conflicts inside it
+        assert_eq!(
+            (merge.tree[0].name.as_str(), merge.tree[0].conflicts),
+            ("src", 1)
+        );
+        assert_eq!(merge.tree[0].children.len(), 4);
+        assert_eq!(
+            merge.tree[0].children[1].status,
+            Some(MergeStatus::Conflict)
+        );
+        let missing_base = get_merge_tree(&dir("missing"), &dir("left"), &dir("right"), None, None);
+        assert!(missing_base.unwrap_err().starts_with("Base"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
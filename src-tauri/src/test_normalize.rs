@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use crate::diff::{compare_directories_with_options, CompareOptions, FileStatus};
+    use crate::normalize::Normalizer;
+    use crate::test_fixtures::temp_dir;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// Version stamps don't count in `.stamp` files
+    #[derive(Debug)]
+    struct StripDigits;
+
+    impl Normalizer for StripDigits {
+        fn normalize(
+            &self,
+            _left: &Path,
+            right: &Path,
+            left_text: &str,
+            right_text: &str,
+        ) -> Option<(String, String)> {
+            if right.extension()? != "stamp" {
+                return None;
+            }
+            let strip = |text: &str| text.chars().filter(|c| !c.is_ascii_digit()).collect();
+            Some((strip(left_text), strip(right_text)))
+        }
+    }
+
+    #[test]
+    fn test_custom_normalizer_affects_classification() {
+        let dir = temp_dir("normalize");
+        for (side, stamp, props, modules) in [
+            (
+                "old",
+                "build 41\n",
+                "b=2\na=1\n",
+                "[submodule \"x\"]\n\tpath = x\n[submodule \"a\"]\n\tpath = a\n",
+            ),
+            (
+                "new",
+                "build 42\n",
+                "a=1\n\nb=2\n",
+                "[submodule \"a\"]\n\tpath = a\n[submodule \"x\"]\n\tpath = x\n",
+            ),
+        ] {
+            std::fs::create_dir_all(dir.join(side)).unwrap();
+            std::fs::write(dir.join(side).join("app.stamp"), stamp).unwrap();
+            std::fs::write(dir.join(side).join("app.properties"), props).unwrap();
+            std::fs::write(dir.join(side).join(".gitmodules"), modules).unwrap();
+        }
+
+        let statuses = |normalize_by_type, custom: bool| -> Vec<(String, FileStatus)> {
+            let options = CompareOptions {
+                normalize_by_type,
+                normalizers: if custom {
+                    vec![Arc::new(StripDigits) as Arc<dyn Normalizer>]
+                } else {
+                    Vec::new()
+                },
+                ..Default::default()
+            };
+            compare_directories_with_options(&dir.join("old"), &dir.join("new"), &options)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.path, e.status))
+                .collect()
+        };
+        assert!(statuses(false, false)
+            .iter()
+            .all(|(_, s)| *s == FileStatus::Modified));
+        // The built-ins for .properties and .gitmodules, but not the stamp without StripDigits
+        let by_type = statuses(true, false);
+        for (path, status) in &by_type {
+            let expected = if path == "app.stamp" {
+                FileStatus::Modified
+            } else {
+                FileStatus::Unchanged
+            };
+            assert_eq!(*status, expected, "{:?}", by_type);
+        }
+        let all = statuses(true, true);
+        assert!(
+            all.iter().all(|(_, s)| *s == FileStatus::Unchanged),
+            "{:?}",
+            all
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{generate_diff, DiffOptions};
+    use crate::notebook::notebook_source;
+    use crate::test_fixtures::file_pair;
+
+    fn notebook(code: &str, output: &str, execution_count: u32) -> String {
+        serde_json::json!({
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["# Title\n", "Some notes"]},
+                {
+                    "cell_type": "code",
+                    "execution_count": execution_count,
+                    "metadata": {},
+                    "outputs": [{"output_type": "stream", "name": "stdout", "text": [output]}],
+                    "source": code,
+                },
+            ],
+            "metadata": {"language_info": {"name": "python", "file_extension": ".py"}},
+            "nbformat": 4,
+            "nbformat_minor": 5,
+        })
+        .to_string()
+    }
+
+    fn ipynb_options() -> DiffOptions {
+        DiffOptions {
+            ipynb: true,
+            fallback_renderer: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_notebook_source_strips_outputs() {
+        let source = notebook_source(&notebook("x = 1\nprint(x)", "1\n", 3)).unwrap();
+        assert_eq!(
+            source.text,
+            "# %% [1] markdown\n# Title\nSome notes\n# %% [2] code\nx = 1\nprint(x)\n"
+        );
+        assert_eq!(source.extension, ".py");
+
+        assert!(notebook_source("{\"not\": \"a notebook\"}").is_none());
+        assert!(notebook_source("not json").is_none());
+    }
+
+    #[test]
+    fn test_ipynb_diff_changed_code_cell() {
+        // Only outputs and execution counts differ: nothing to show
+        let (left, right) = file_pair(
+            "notebook-outputs",
+            "ipynb",
+            &notebook("print(1)", "1\n", 1),
+            &notebook("print(1)", "one\n", 7),
+        );
+        let result = generate_diff(Some(&left), Some(&right), &ipynb_options()).unwrap();
+        assert!(!result.has_changes);
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let (left, right) = file_pair(
+            "notebook-code",
+            "ipynb",
+            &notebook("x = 1\nprint(x)", "1\n", 1),
+            &notebook("x = 2\nprint(x)", "2\n", 2),
+        );
+        let result = generate_diff(Some(&left), Some(&right), &ipynb_options()).unwrap();
+        assert!(result.has_changes);
+        assert_eq!(result.hunk_count, 1);
+        assert!(!result.html.contains("output_type"));
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        // Unparseable notebooks fall back to the plain text diff
+        let (left, right) = file_pair("notebook-invalid", "ipynb", "{ broken", "{ broken\n");
+        assert!(
+            generate_diff(Some(&left), Some(&right), &ipynb_options())
+                .unwrap()
+                .has_changes
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{generate_diff, DiffOptions};
+    use crate::numbers::normalize_numbers;
+    use crate::test_fixtures::file_pair;
+
+    #[test]
+    fn test_normalize_numbers() {
+        assert_eq!(
+            normalize_numbers("x = 1.50, y = 2.0, z = 0.000"),
+            "x = 1.5, y = 2, z = 0"
+        );
+        assert_eq!(
+            normalize_numbers("1.5E+03 1.50e-007 3e0 -2.10"),
+            "1.5e3 1.5e-7 3 -2.1"
+        );
+        assert_eq!(normalize_numbers("ended at 1.50."), "ended at 1.5.");
+
+        // Version strings, identifiers, hex and plain integers are left alone
+        let untouched = "v1.50 1.2.0 1.50.2 1.5x x1.0 0x1.0p3 release_2.0 10 007 2024-01-05 1.0-rc";
+        assert_eq!(normalize_numbers(untouched), untouched);
+    }
+
+    #[test]
+    fn test_normalize_numbers_diff() {
+        let (left, right) = file_pair(
+            "numbers-format",
+            "csv",
+            "name,value\nmass,1.50\nrate,2.0E+03\n",
+            "name,value\nmass,1.5\nrate,2e3\n",
+        );
+        let options = DiffOptions {
+            normalize_numbers: true,
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let result = generate_diff(Some(&left), Some(&right), &options).unwrap();
+        assert!(!result.has_changes);
+
+        // Without the flag the reformatted numbers are a change
+        let options = DiffOptions {
+            normalize_numbers: false,
+            ..options
+        };
+        assert!(
+            generate_diff(Some(&left), Some(&right), &options)
+                .unwrap()
+                .has_changes
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+}
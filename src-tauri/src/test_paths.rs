@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use crate::paths::{extended_length_path, simplified_path};
+
+    #[test]
+    fn test_extended_length_prefix() {
+        assert_eq!(extended_length_path(r"C:\repo\src"), r"\\?\C:\repo\src");
+        assert_eq!(extended_length_path("C:/repo/src"), r"\\?\C:\repo\src");
+        assert_eq!(
+            extended_length_path(r"\\server\share\repo"),
+            r"\\?\UNC\server\share\repo"
+        );
+        // Already prefixed paths are left alone
+        assert_eq!(extended_length_path(r"\\?\C:\repo"), r"\\?\C:\repo");
+    }
+
+    #[test]
+    fn test_simplified_path() {
+        assert_eq!(simplified_path(r"\\?\C:\repo\src"), r"C:\repo\src");
+        assert_eq!(
+            simplified_path(r"\\?\UNC\server\share\repo"),
+            r"\\server\share\repo"
+        );
+        assert_eq!(simplified_path(r"C:\repo"), r"C:\repo");
+        // Device paths and paths over MAX_PATH need the prefix
+        assert_eq!(
+            simplified_path(r"\\?\Volume{1}\repo"),
+            r"\\?\Volume{1}\repo"
+        );
+        let long = format!(r"\\?\C:\{}", "d\\".repeat(150));
+        assert_eq!(simplified_path(&long), long);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_compare_paths_over_max_path() {
+        use crate::delta::get_file_content;
+        use crate::diff::{compare_directories, FileStatus};
+        use crate::paths::normalize_path;
+
+        let root = std::env::temp_dir().join(format!("diffr-long-{}", std::process::id()));
+        let nested: std::path::PathBuf = (0..30).map(|i| format!("directory_{:02}", i)).collect();
+        let (left, right) = (root.join("old"), root.join("new"));
+        let file = nested.join("file.txt");
+        assert!(left.join(&file).to_string_lossy().len() > 260);
+
+        for (dir, content) in [(&left, "old\n"), (&right, "new\n")] {
+            let target = normalize_path(&dir.join(&file));
+            std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+            std::fs::write(&target, content).unwrap();
+        }
+
+        let entries = compare_directories(&left, &right).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, FileStatus::Modified);
+        // Too long to lose the prefix, which the path still works with
+        let right_path = entries[0].right_path.as_deref().unwrap();
+        assert!(right_path.starts_with(r"\\?\"));
+        assert_eq!(std::fs::read_to_string(right_path).unwrap(), "new\n");
+
+        let content = get_file_content(&right.join(&file)).unwrap();
+        assert_eq!(content, "new\n");
+
+        let _ = std::fs::remove_dir_all(normalize_path(&root));
+    }
+}
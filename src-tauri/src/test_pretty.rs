@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{generate_diff, DiffOptions};
+    use crate::pretty::{is_minified, pretty_print_json, pretty_print_pair};
+    use crate::test_fixtures::file_pair;
+    use std::path::Path;
+
+    const OLD: &str = r#"{"name":"app","version":"1.0.0","deps":{"serde":"1.0","walkdir":"2"},"tags":[],"ports":[80,443],"note":"a \"quoted\", {braced} value"}"#;
+    const NEW: &str = r#"{"name":"app","version":"1.0.0","deps":{"serde":"1.0","walkdir":"3"},"tags":[],"ports":[80,443],"note":"a \"quoted\", {braced} value"}"#;
+
+    #[test]
+    fn test_pretty_print_json() {
+        let pretty = pretty_print_json(OLD).unwrap();
+        assert_eq!(
+            pretty,
+            r#"{
+  "name": "app",
+  "version": "1.0.0",
+  "deps": {
+    "serde": "1.0",
+    "walkdir": "2"
+  },
+  "tags": [],
+  "ports": [
+    80,
+    443
+  ],
+  "note": "a \"quoted\", {braced} value"
+}
+"#
+        );
+        assert!(pretty_print_json("{\"broken\": ").is_none());
+
+        assert!(is_minified(OLD));
+        assert!(!is_minified(&pretty));
+        assert!(pretty_print_pair(Path::new("a.txt"), Path::new("b.txt"), OLD, NEW).is_none());
+        assert!(
+            pretty_print_pair(Path::new("a.json"), Path::new("b.json"), OLD, "{ not json")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_pretty_print_before_diff_localizes_change() {
+        let (left_pretty, right_pretty) =
+            pretty_print_pair(Path::new("a.json"), Path::new("b.json"), OLD, NEW).unwrap();
+        let changed: Vec<_> = left_pretty
+            .lines()
+            .zip(right_pretty.lines())
+            .filter(|(l, r)| l != r)
+            .collect();
+        assert_eq!(
+            changed,
+            vec![(r#"    "walkdir": "2""#, r#"    "walkdir": "3""#)]
+        );
+
+        let (left, right) = file_pair("pretty-minified", "json", OLD, NEW);
+        let options = DiffOptions {
+            pretty_print_before_diff: true,
+            collapsed: true,
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        let result = generate_diff(Some(&left), Some(&right), &options).unwrap();
+        assert_eq!(result.hunk_count, 1);
+        assert_eq!(
+            (result.hunks[0].old_start, result.hunks[0].old_lines),
+            (3, 7)
+        );
+
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use crate::remote::{is_remote, resolve_input};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_local_paths_pass_through() {
+        assert!(is_remote("https://example.com/a.rs"));
+        assert!(is_remote("http://localhost:8080/a.rs"));
+        assert!(!is_remote("/tmp/https/a.rs"));
+        assert_eq!(
+            resolve_input("/tmp/a.rs").unwrap(),
+            PathBuf::from("/tmp/a.rs")
+        );
+    }
+
+    #[cfg(not(feature = "remote"))]
+    #[test]
+    fn test_urls_need_remote_feature() {
+        use crate::remote::RemoteError;
+
+        assert!(matches!(
+            resolve_input("https://example.com/a.rs"),
+            Err(RemoteError::Disabled(_))
+        ));
+    }
+
+    /// Serve canned responses on a local port, recording each request's headers
+    #[cfg(feature = "remote")]
+    fn serve(
+        responses: Vec<String>,
+    ) -> (
+        String,
+        std::sync::mpsc::Receiver<String>,
+        std::thread::JoinHandle<()>,
+    ) {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let handle = std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                tx.send(request).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        (format!("http://{}", addr), rx, handle)
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn test_remote_download_cached_by_etag() {
+        use crate::delta::{generate_diff, DiffOptions};
+        use crate::remote::{fetch, RemoteError};
+        use crate::test_fixtures::temp_dir;
+
+        let body = "fn main() {\n    println!(\"remote\");\n}\n";
+        let ok = format!(
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let not_modified = "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string();
+        let (base, requests, server) = serve(vec![ok.clone(), not_modified, ok]);
+        let url = format!("{}/src/main-{}.rs", base, std::process::id());
+
+        let downloaded = resolve_input(&url).unwrap();
+        assert!(downloaded.to_string_lossy().ends_with(".rs"));
+        assert_eq!(std::fs::read_to_string(&downloaded).unwrap(), body);
+        assert!(!requests
+            .recv()
+            .unwrap()
+            .to_lowercase()
+            .contains("if-none-match"));
+
+        // Second fetch revalidates and reuses the cached copy
+        assert_eq!(resolve_input(&url).unwrap(), downloaded);
+        assert!(requests
+            .recv()
+            .unwrap()
+            .to_lowercase()
+            .contains("if-none-match: \"v1\""));
+
+        // A URL on one side and a local file on the other
+        let local = temp_dir("remote-local").join("local.rs");
+        std::fs::write(&local, "fn main() {}\n").unwrap();
+        let options = DiffOptions {
+            fallback_renderer: true,
+            ..Default::default()
+        };
+        assert!(
+            generate_diff(Some(&local), Some(&downloaded), &options)
+                .unwrap()
+                .has_changes
+        );
+        let _ = std::fs::remove_dir_all(local.parent().unwrap());
+
+        // Over the size limit (with the cache cleared, so it downloads again)
+        let _ = std::fs::remove_dir_all(downloaded.parent().unwrap());
+        assert!(matches!(fetch(&url, 10), Err(RemoteError::TooLarge { .. })));
+        server.join().unwrap();
+    }
+}
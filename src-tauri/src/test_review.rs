@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod tests {
+    use crate::diff::{ComparisonCounts, DiffError, FileStatus};
+    use crate::review::{run_review, ReviewEvent, ReviewOptions};
+    use crate::test_fixtures::temp_dir;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_review_event_sequence() {
+        let root = temp_dir("review");
+        let (left, right) = (root.join("left"), root.join("right"));
+        for (dir, files) in [
+            (
+                &left,
+                [
+                    ("same.txt", "same\n"),
+                    ("src/edit.rs", "let x = 1;\n"),
+                    ("gone.txt", "bye\n"),
+                ],
+            ),
+            (
+                &right,
+                [
+                    ("same.txt", "same\n"),
+                    ("src/edit.rs", "let x = 2;\n"),
+                    ("new.txt", "hi\n"),
+                ],
+            ),
+        ] {
+            for (path, content) in files {
+                std::fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+                std::fs::write(dir.join(path), content).unwrap();
+            }
+        }
+
+        let options = ReviewOptions {
+            precompute_diffs: true,
+            ..Default::default()
+        };
+        let mut events = Vec::new();
+        let summary = run_review(&left, &right, &options, &AtomicBool::new(false), &mut |e| {
+            events.push(e)
+        })
+        .unwrap();
+
+        // Entries, then diffs in path order with running progress, then the summary
+        let order: Vec<String> = events
+            .iter()
+            .map(|e| match e {
+                ReviewEvent::Entry(_) => "entry".to_string(),
+                ReviewEvent::DiffReady(ready) => {
+                    format!("{} {}/{}", ready.path, ready.done, ready.total)
+                }
+                ReviewEvent::Complete(_) => "complete".to_string(),
+            })
+            .collect();
+        let edit = format!("src{}edit.rs", std::path::MAIN_SEPARATOR);
+        assert_eq!(
+            order,
+            [
+                "entry".to_string(),
+                "entry".to_string(),
+                "entry".to_string(),
+                "gone.txt 1/3".to_string(),
+                "new.txt 2/3".to_string(),
+                format!("{} 3/3", edit),
+                "complete".to_string(),
+            ]
+        );
+
+        let mut statuses: Vec<FileStatus> = events
+            .iter()
+            .filter_map(|e| match e {
+                ReviewEvent::Entry(entry) => Some(entry.status.clone()),
+                _ => None,
+            })
+            .collect();
+        statuses.sort_by_key(|s| format!("{:?}", s));
+        assert_eq!(
+            statuses,
+            [FileStatus::Added, FileStatus::Deleted, FileStatus::Modified]
+        );
+
+        // Every file gets a diff or the reason it has none (e.g. delta missing)
+        for event in &events {
+            if let ReviewEvent::DiffReady(ready) = event {
+                assert_ne!(
+                    ready.bundle.is_some(),
+                    ready.error.is_some(),
+                    "{}",
+                    ready.path
+                );
+            }
+        }
+        assert_eq!(
+            summary.counts,
+            ComparisonCounts {
+                total_changes: 3,
+                added: 1,
+                deleted: 1,
+                modified: 1,
+                renamed: 0,
+                unchanged: 1,
+            }
+        );
+        assert_eq!(summary.diffs_ready + summary.diff_errors, 3);
+        assert!(matches!(events.last(), Some(ReviewEvent::Complete(s)) if *s == summary));
+
+        // Without precompute_diffs the scan goes straight to the summary
+        let mut events = Vec::new();
+        run_review(
+            &left,
+            &right,
+            &ReviewOptions::default(),
+            &AtomicBool::new(false),
+            &mut |e| events.push(e),
+        )
+        .unwrap();
+        assert_eq!(events.len(), 4);
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, ReviewEvent::DiffReady(_))));
+
+        // A cancelled review fails without a Complete event
+        let mut events = Vec::new();
+        let err = run_review(&left, &right, &options, &AtomicBool::new(true), &mut |e| {
+            events.push(e)
+        })
+        .unwrap_err();
+        assert!(matches!(err, DiffError::Cancelled));
+        assert!(!events.iter().any(|e| matches!(e, ReviewEvent::Complete(_))));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use crate::hunks::{diff_lines, DiffLineKind};
+    use crate::syntax::highlight_lines;
+
+    #[test]
+    fn test_plain_tokens_without_highlighting() {
+        let diff_text = "--- a\n+++ b\n@@ -1,2 +1,2 @@\n fn main() {\n-    old();\n+    new();\n";
+        let lines = diff_lines(diff_text);
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].kind, DiffLineKind::Removed);
+        assert_eq!(lines[2].new_line, Some(2));
+        assert_eq!(lines[2].tokens.len(), 1);
+        assert_eq!(lines[2].tokens[0].text, "    new();");
+        assert_eq!(lines[2].tokens[0].kind, None);
+
+        // Unsupported extensions are never highlighted
+        assert!(highlight_lines("notes.txt", "fn main() {}").is_none());
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn test_tree_sitter_keywords() {
+        let tokens =
+            highlight_lines("main.rs", "pub fn main() {\n    let x = \"s\"; // hi\n}\n").unwrap();
+
+        let kind_of = |line: usize, text: &str| {
+            tokens[line]
+                .iter()
+                .find(|t| t.text == text)
+                .and_then(|t| t.kind.clone())
+        };
+        assert_eq!(kind_of(0, "pub").as_deref(), Some("keyword"));
+        assert_eq!(kind_of(0, "fn").as_deref(), Some("keyword"));
+        assert_eq!(kind_of(0, "main").as_deref(), Some("identifier"));
+        assert_eq!(kind_of(1, "let").as_deref(), Some("keyword"));
+        assert_eq!(kind_of(1, "\"s\"").as_deref(), Some("string"));
+        assert_eq!(kind_of(1, "// hi").as_deref(), Some("comment"));
+
+        let tokens = highlight_lines("data.json", "{\"a\": true, \"b\": 1}").unwrap();
+        assert!(tokens[0]
+            .iter()
+            .any(|t| t.text == "true" && t.kind.as_deref() == Some("constant")));
+        assert!(tokens[0]
+            .iter()
+            .any(|t| t.text == "1" && t.kind.as_deref() == Some("number")));
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn test_apply_syntax_tokens_to_diff() {
+        use crate::delta::run_unified_diff;
+        use crate::syntax::apply_syntax_tokens;
+        use crate::test_fixtures::temp_dir;
+
+        let dir = temp_dir("syntax");
+        let left = dir.join("old.rs");
+        let right = dir.join("new.rs");
+        std::fs::write(&left, "fn a() {}\n").unwrap();
+        std::fs::write(&right, "pub fn a() {}\n").unwrap();
+
+        let diff_text = run_unified_diff(Some(&left), Some(&right), 3).unwrap();
+        let mut lines = diff_lines(&diff_text);
+        apply_syntax_tokens(&mut lines, Some(&left), Some(&right));
+
+        let added = lines
+            .iter()
+            .find(|l| l.kind == DiffLineKind::Added)
+            .unwrap();
+        assert_eq!(added.tokens[0].text, "pub");
+        assert_eq!(added.tokens[0].kind.as_deref(), Some("keyword"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::temp_dir;
+    use crate::tools::{detect_capabilities, Capabilities, UNKNOWN_VERSION};
+
+    #[cfg(unix)]
+    #[test]
+    fn test_detect_stubbed_versions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin_dir = temp_dir("tools");
+
+        let stubs = [
+            ("delta", "delta 0.16.5"),
+            ("diff", "diff (GNU diffutils) 3.8\nCopyright (C) 2021"),
+            ("git", "git version 2.39.2"),
+        ];
+        for (name, output) in stubs {
+            let path = bin_dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\nprintf '{}\\n'\n", output)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        // difft is deliberately missing
+        assert_eq!(
+            detect_capabilities(Some(&bin_dir)),
+            Capabilities {
+                delta: Some("0.16.5".to_string()),
+                diff: Some("3.8".to_string()),
+                git: Some("2.39.2".to_string()),
+                difftastic: None,
+            }
+        );
+
+        // BSD diff rejects --version and macOS diff prints no number: installed, version unknown
+        let bsd = "printf 'diff: unrecognized option --version\\n' >&2\nexit 2";
+        let apple = "printf 'Apple diff (based on FreeBSD diff)\\n'";
+        for script in [bsd, apple] {
+            let path = bin_dir.join("diff");
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            assert_eq!(
+                detect_capabilities(Some(&bin_dir)).diff.as_deref(),
+                Some(UNKNOWN_VERSION)
+            );
+        }
+
+        let _ = std::fs::remove_dir_all(&bin_dir);
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+/// Versions of the external tools diff-rust can use (None when not installed, UNKNOWN_VERSION
+/// when installed but the version can't be told)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct Capabilities {
+    pub delta: Option<String>,
+    pub diff: Option<String>,
+    pub git: Option<String>,
+    pub difftastic: Option<String>,
+}
+
+/// Reported for tools that run but don't say which version they are
+pub const UNKNOWN_VERSION: &str = "unknown version";
+
+/// Run `program --version` and pull the version number out of its first line. None only
+/// when the program can't be run; BSD diff rejects `--version` and macOS diff prints no
+/// number, and both come back as UNKNOWN_VERSION.
+pub fn probe_version(program: &Path) -> Option<String> {
+    let output = Command::new(program).arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // "delta 0.16.5", "diff (GNU diffutils) 3.8", "git version 2.39.2", "Difftastic 0.56.1"
+    let version = stdout
+        .lines()
+        .next()
+        .filter(|_| output.status.success())
+        .and_then(|first_line| {
+            first_line
+                .split_whitespace()
+                .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        });
+    Some(version.unwrap_or(UNKNOWN_VERSION).to_string())
+}
+
+/// Probe every tool, resolving binaries from `bin_dir` if given, otherwise from PATH
+pub fn detect_capabilities(bin_dir: Option<&Path>) -> Capabilities {
+    let probe = |name: &str| match bin_dir {
+        Some(dir) => probe_version(&dir.join(name)),
+        None => probe_version(Path::new(name)),
+    };
+
+    Capabilities {
+        delta: probe("delta"),
+        diff: probe("diff"),
+        git: probe("git"),
+        difftastic: probe("difft"),
+    }
+}
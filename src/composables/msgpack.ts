@@ -0,0 +1,82 @@
+// Minimal MessagePack decoder for the binary IPC responses (get_file_tree_binary).
+// Covers what rmp_serde produces for our types: maps, arrays, strings, numbers, bools and nil.
+
+const utf8 = new TextDecoder();
+
+export function decodeMsgpack(buffer: ArrayBuffer): unknown {
+  const view = new DataView(buffer);
+  const bytes = new Uint8Array(buffer);
+  let pos = 0;
+
+  function str(length: number): string {
+    const value = utf8.decode(bytes.subarray(pos, pos + length));
+    pos += length;
+    return value;
+  }
+
+  function array(length: number): unknown[] {
+    const items = new Array(length);
+    for (let i = 0; i < length; i++) items[i] = read();
+    return items;
+  }
+
+  function map(length: number): Record<string, unknown> {
+    const object: Record<string, unknown> = {};
+    for (let i = 0; i < length; i++) {
+      const key = String(read());
+      object[key] = read();
+    }
+    return object;
+  }
+
+  function bin(length: number): Uint8Array {
+    const value = bytes.slice(pos, pos + length);
+    pos += length;
+    return value;
+  }
+
+  // Advance past a fixed-size field and return its offset
+  function take(size: number): number {
+    const at = pos;
+    pos += size;
+    return at;
+  }
+
+  function read(): unknown {
+    const byte = bytes[pos++];
+    if (byte <= 0x7f) return byte;
+    if (byte >= 0xe0) return byte - 0x100;
+    if (byte <= 0x8f) return map(byte & 0x0f);
+    if (byte <= 0x9f) return array(byte & 0x0f);
+    if (byte <= 0xbf) return str(byte & 0x1f);
+
+    switch (byte) {
+      case 0xc0: return null;
+      case 0xc2: return false;
+      case 0xc3: return true;
+      case 0xc4: return bin(view.getUint8(take(1)));
+      case 0xc5: return bin(view.getUint16(take(2)));
+      case 0xc6: return bin(view.getUint32(take(4)));
+      case 0xca: return view.getFloat32(take(4));
+      case 0xcb: return view.getFloat64(take(8));
+      case 0xcc: return view.getUint8(take(1));
+      case 0xcd: return view.getUint16(take(2));
+      case 0xce: return view.getUint32(take(4));
+      case 0xcf: return Number(view.getBigUint64(take(8)));
+      case 0xd0: return view.getInt8(take(1));
+      case 0xd1: return view.getInt16(take(2));
+      case 0xd2: return view.getInt32(take(4));
+      case 0xd3: return Number(view.getBigInt64(take(8)));
+      case 0xd9: return str(view.getUint8(take(1)));
+      case 0xda: return str(view.getUint16(take(2)));
+      case 0xdb: return str(view.getUint32(take(4)));
+      case 0xdc: return array(view.getUint16(take(2)));
+      case 0xdd: return array(view.getUint32(take(4)));
+      case 0xde: return map(view.getUint16(take(2)));
+      case 0xdf: return map(view.getUint32(take(4)));
+      default: throw new Error(`Unsupported MessagePack type 0x${byte.toString(16)} at byte ${pos - 1}`);
+    }
+  }
+
+  return read();
+}
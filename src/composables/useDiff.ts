@@ -1,5 +1,7 @@
 import { invoke } from '@tauri-apps/api/core';
-import { useAppStore, type FileEntry, type FileTreeNode } from '../stores/app';
+import { listen } from '@tauri-apps/api/event';
+import { decodeMsgpack } from './msgpack';
+import { useAppStore, EDGE_CONTEXT_LIMIT, type FileEntry, type FileTreeNode, type HunkInfo, type LaunchConfig, type RenameInfo } from '../stores/app';
 
 interface FileTreeResult {
   tree: FileTreeNode[];
@@ -8,6 +10,80 @@ interface FileTreeResult {
   added: number;
   deleted: number;
   modified: number;
+  truncated?: boolean;
+  total_available?: number;
+}
+
+export interface IdenticalReport {
+  same_path: string[];
+  moved: [string, string][];
+}
+
+export interface DirHeat {
+  path: string;
+  changes: number;
+  additions: number;
+  deletions: number;
+  own_changes: number;
+  own_additions: number;
+  own_deletions: number;
+}
+
+export interface ComparisonCounts {
+  total_changes: number;
+  added: number;
+  deleted: number;
+  modified: number;
+  renamed: number;
+  unchanged: number;
+}
+
+export interface FileDiffStat {
+  path: string;
+  status: FileEntry['status'];
+  added: number;
+  removed: number;
+  timed_out: boolean;
+}
+
+export interface DiffStatReport {
+  files: FileDiffStat[];
+  added: number;
+  removed: number;
+  timed_out: number;
+}
+
+export type MergeStatus = 'Unchanged' | 'ChangedLeft' | 'ChangedRight' | 'ChangedBoth' | 'Conflict';
+
+export interface MergeEntry {
+  path: string;
+  name: string;
+  status: MergeStatus;
+  base_path: string | null;
+  left_path: string | null;
+  right_path: string | null;
+}
+
+export interface MergeTreeNode {
+  name: string;
+  path: string;
+  status: MergeStatus | null;
+  is_dir: boolean;
+  children: MergeTreeNode[];
+  conflicts: number;
+}
+
+export interface MergeTreeResult {
+  tree: MergeTreeNode[];
+  files: MergeEntry[];
+  conflicts: number;
+}
+
+export type ComparisonKind = 'SameProjectNewVersion' | 'PartialOverlap' | 'UnrelatedTrees' | 'Empty';
+
+export interface LegendEntry {
+  label: string;
+  color: string;
 }
 
 interface DiffResult {
@@ -16,6 +92,60 @@ interface DiffResult {
   hunk_count: number;
   left_html: string | null;
   right_html: string | null;
+  warnings: string[];
+  truncated: boolean;
+  hunks: HunkInfo[];
+  rename_info?: RenameInfo | null;
+}
+
+interface DiffSummary {
+  added: number;
+  removed: number;
+  hunk_count: number;
+}
+
+export interface DiffPage {
+  diff: DiffResult;
+  total_hunks: number;
+  start: number;
+  end: number;
+}
+
+interface FileDiffBundle {
+  diff: DiffResult;
+  summary: DiffSummary;
+  hunks: HunkInfo[];
+  first_change_line: number | null;
+}
+
+export interface FileDiffReady {
+  path: string;
+  bundle: FileDiffBundle | null;
+  error: string | null;
+  done: number;
+  total: number;
+}
+
+export interface ReviewSummary {
+  counts: ComparisonCounts;
+  diffs_ready: number;
+  diff_errors: number;
+}
+
+// Token of the comparison cancelComparison stops: the one started last
+let comparisonToken: string | undefined;
+
+// Run a comparison under a fresh cancel token, forgetting the token once it's done
+async function withCancelToken<T>(run: (cancelToken: string) => Promise<T>): Promise<T> {
+  const cancelToken = crypto.randomUUID();
+  comparisonToken = cancelToken;
+  try {
+    return await run(cancelToken);
+  } finally {
+    if (comparisonToken === cancelToken) {
+      comparisonToken = undefined;
+    }
+  }
 }
 
 export function useDiff() {
@@ -28,10 +158,13 @@ export function useDiff() {
 
     try {
       console.log('Invoking get_file_tree...');
-      const result = await invoke<FileTreeResult>('get_file_tree', {
-        leftDir,
-        rightDir,
-      });
+      const result = await withCancelToken((cancelToken) =>
+        invoke<FileTreeResult>('get_file_tree', {
+          leftDir,
+          rightDir,
+          cancelToken,
+        })
+      );
       console.log('get_file_tree result:', result);
 
       store.setFileTree(
@@ -54,23 +187,134 @@ export function useDiff() {
     }
   }
 
+  // Compare without waiting for the whole result: changed files reach onEntry as they're
+  // classified (unsorted), and the totals come back once the comparison is done
+  async function streamComparison(
+    leftDir: string,
+    rightDir: string,
+    onEntry: (entry: FileEntry) => void
+  ): Promise<ComparisonCounts> {
+    const unlisten = await listen<FileEntry>('file-entry', (event) => onEntry(event.payload));
+    try {
+      return await withCancelToken((cancelToken) =>
+        invoke<ComparisonCounts>('compare_streaming', { leftDir, rightDir, cancelToken })
+      );
+    } finally {
+      unlisten();
+    }
+  }
+
+  // Full review in one call: changed files reach onEntry as they're classified, then with
+  // precomputeDiffs each file's diff reaches onDiffReady in path order; cancelComparison stops it
+  async function reviewDirectories(
+    leftDir: string,
+    rightDir: string,
+    precomputeDiffs: boolean,
+    onEntry: (entry: FileEntry) => void,
+    onDiffReady: (ready: FileDiffReady) => void
+  ): Promise<ReviewSummary> {
+    const unlistenEntry = await listen<FileEntry>('file-entry', (event) => onEntry(event.payload));
+    const unlistenDiff = await listen<FileDiffReady>('file-diff-ready', (event) => onDiffReady(event.payload));
+    try {
+      return await withCancelToken((cancelToken) =>
+        invoke<ReviewSummary>('review_directories', {
+          leftDir,
+          rightDir,
+          options: { precompute_diffs: precomputeDiffs, diff: store.viewOptions },
+          cancelToken,
+        })
+      );
+    } finally {
+      unlistenEntry();
+      unlistenDiff();
+    }
+  }
+
+  // Same result as get_file_tree, sent as MessagePack bytes; cheaper than JSON for very large trees
+  async function getFileTreeBinary(leftDir: string, rightDir: string): Promise<FileTreeResult> {
+    const bytes = await withCancelToken((cancelToken) =>
+      invoke<ArrayBuffer>('get_file_tree_binary', { leftDir, rightDir, cancelToken })
+    );
+    return decodeMsgpack(bytes) as FileTreeResult;
+  }
+
+  // Three-way comparison: how left and right each changed base, with conflicts counted per directory
+  async function getMergeTree(baseDir: string, leftDir: string, rightDir: string): Promise<MergeTreeResult> {
+    return await withCancelToken((cancelToken) =>
+      invoke<MergeTreeResult>('get_merge_tree', { baseDir, leftDir, rightDir, cancelToken })
+    );
+  }
+
+  // Changes between two commits, branches or tags of the repository at repoPath
+  async function getGitDiff(repoPath: string, leftRef: string, rightRef: string): Promise<FileTreeResult> {
+    return await invoke<FileTreeResult>('get_git_diff', { repoPath, leftRef, rightRef });
+  }
+
+  // Show the files listed in recursive `diff -r`/`diff -rq` output between two directories
+  async function importRecursiveDiff(leftDir: string, rightDir: string, output: string): Promise<void> {
+    store.setTreeLoading(true);
+    store.setDirectories(leftDir, rightDir);
+    try {
+      const result = await invoke<FileTreeResult>('import_recursive_diff', { leftDir, rightDir, output });
+      store.setFileTree(result.tree, result.files, result.total_changes, result.added, result.deleted, result.modified);
+    } catch (error) {
+      store.setTreeError(String(error));
+    }
+  }
+
+  // Stop the comparison started last (e.g. a running loadFileTree); it then reports
+  // "Comparison cancelled" as its error. Others keep running.
+  async function cancelComparison(): Promise<boolean> {
+    if (comparisonToken === undefined) {
+      return false;
+    }
+    return await invoke<boolean>('cancel_comparison', { cancelToken: comparisonToken });
+  }
+
   async function loadDiff(file: FileEntry): Promise<void> {
     store.setDiffLoading(true);
     store.selectFile(file);
 
+    // Over the scan's max_file_size_for_diff: the status is all there is to show
+    if (file.skip_content) {
+      store.setDiff({
+        html: '<div class="no-changes">File too large to diff</div>',
+        has_changes: file.status !== 'Unchanged',
+        hunk_count: 0,
+        left_html: null,
+        right_html: null,
+        warnings: [],
+        truncated: false,
+        hunks: [],
+        rename_info: file.rename_info ?? null,
+      });
+      return;
+    }
+
     try {
-      const result = await invoke<DiffResult>('get_diff', {
+      const bundle = await invoke<FileDiffBundle>('open_file_diff', {
         leftPath: file.left_path,
         rightPath: file.right_path,
-        options: store.viewOptions,
+        // A file that only grew shows just its new tail
+        options: { ...store.viewOptions, treat_truncation_as_equal: file.truncated_continuation ?? false },
       });
 
-      store.setDiff(result);
+      store.setDiff({ ...bundle.diff, rename_info: file.rename_info ?? null });
     } catch (error) {
       store.setDiffError(String(error));
     }
   }
 
+  // Hunks [start, end) of a file's collapsed diff plus the total, for loading huge diffs lazily
+  async function getDiffPage(file: FileEntry, start: number, end: number): Promise<DiffPage> {
+    return await invoke<DiffPage>('get_diff_page', {
+      leftPath: file.left_path,
+      rightPath: file.right_path,
+      options: { ...store.viewOptions, collapsed: true },
+      hunkRange: [start, end],
+    });
+  }
+
   async function refreshDiff(): Promise<void> {
     if (!store.selectedFile) return;
     await loadDiff(store.selectedFile);
@@ -98,6 +342,68 @@ export function useDiff() {
     }
   }
 
+  // Comparison requested on the command line; rejects with a usage message for bad arguments
+  async function getLaunchConfig(): Promise<LaunchConfig> {
+    return await invoke<LaunchConfig>('get_launch_config');
+  }
+
+  // Files left as-is between the two directories, including ones that only moved
+  async function getIdenticalFiles(leftDir: string, rightDir: string): Promise<IdenticalReport> {
+    return await invoke<IdenticalReport>('get_identical_files', { leftDir, rightDir });
+  }
+
+  // Colors for changed/unchanged lines and words with the current syntax and light/dark theme
+  async function getDiffLegend(): Promise<LegendEntry[]> {
+    return await invoke<LegendEntry[]>('get_diff_legend', {
+      theme: store.viewOptions.syntax_theme ?? null,
+      mode: store.viewOptions.theme ?? null,
+    });
+  }
+
+  // Rough relationship between the two directories, from names and sizes only
+  async function classifyComparison(leftDir: string, rightDir: string): Promise<ComparisonKind> {
+    return await invoke<ComparisonKind>('classify_comparison', { leftDir, rightDir });
+  }
+
+  // Extensions of the changed files (".rs", "(none)"), lowercased and sorted, for filter chips
+  async function getChangedExtensions(leftDir: string, rightDir: string): Promise<string[]> {
+    return await invoke<string[]>('get_changed_extensions', { leftDir, rightDir });
+  }
+
+  // Per-directory change totals for the heatmap ("" is the root)
+  async function getChangeHeatmap(leftDir: string, rightDir: string): Promise<DirHeat[]> {
+    return await invoke<DirHeat[]>('get_change_heatmap', { leftDir, rightDir });
+  }
+
+  // Line counts of every changed file; files slower than timeoutMs come back flagged timed_out
+  async function getDiffStat(leftDir: string, rightDir: string, timeoutMs?: number): Promise<DiffStatReport> {
+    return await invoke<DiffStatReport>('get_diff_stat', { leftDir, rightDir, timeoutMs: timeoutMs ?? null });
+  }
+
+  // Same value for the same edit wherever it's made, to spot changes already reviewed
+  async function getDiffFingerprint(file: FileEntry): Promise<string> {
+    return await invoke<string>('diff_fingerprint', {
+      leftPath: file.left_path,
+      rightPath: file.right_path,
+      options: store.viewOptions,
+    });
+  }
+
+  // Diff a local file against its copy inside a zip/tar archive (the archived copy is the old side)
+  async function getDiffVsArchiveEntry(localPath: string, archivePath: string, entryPath: string): Promise<DiffResult> {
+    return await invoke<DiffResult>('get_diff_vs_archive_entry', {
+      localPath,
+      archivePath,
+      entryPath,
+      options: store.viewOptions,
+    });
+  }
+
+  // The whole comparison as one patch text that `git apply` can replay on leftDir
+  async function exportPatch(leftDir: string, rightDir: string): Promise<string> {
+    return await invoke<string>('export_patch', { leftDir, rightDir });
+  }
+
   async function readFileContent(path: string): Promise<string> {
     return await invoke<string>('read_file_content', { path });
   }
@@ -114,6 +420,13 @@ export function useDiff() {
 
   function toggleCollapsed() {
     store.toggleViewOption('collapsed');
+    store.viewOptions.edge_context_limit = EDGE_CONTEXT_LIMIT;
+    refreshDiff();
+  }
+
+  // Show the unchanged lines cut from the start/end of a full-file diff
+  function expandEdgeContext() {
+    store.viewOptions.edge_context_limit = null;
     refreshDiff();
   }
 
@@ -124,14 +437,33 @@ export function useDiff() {
 
   return {
     loadFileTree,
+    getFileTreeBinary,
+    getMergeTree,
+    getGitDiff,
+    streamComparison,
+    reviewDirectories,
+    importRecursiveDiff,
+    cancelComparison,
     loadDiff,
     refreshDiff,
+    getDiffPage,
     checkDeltaInstalled,
     getAppArgs,
+    getLaunchConfig,
+    getIdenticalFiles,
+    getDiffLegend,
+    classifyComparison,
+    getChangedExtensions,
+    getChangeHeatmap,
+    getDiffStat,
+    getDiffFingerprint,
+    getDiffVsArchiveEntry,
+    exportPatch,
     readFileContent,
     toggleSideBySide,
     toggleLineNumbers,
     toggleCollapsed,
     toggleWhitespace,
+    expandEdgeContext,
   };
 }
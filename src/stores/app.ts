@@ -1,30 +1,113 @@
 import { defineStore } from 'pinia';
 import { ref, computed } from 'vue';
 
+// Copies come from git's copy detection in branch diffs and name their source file
+export type FileStatus =
+  | 'Added'
+  | 'Deleted'
+  | 'Modified'
+  | 'Renamed'
+  | 'Unchanged'
+  | { Copied: { from: string; similarity: number } };
+
+export function statusName(status: FileStatus): string {
+  return typeof status === 'string' ? status : 'Copied';
+}
+
 export interface FileEntry {
   path: string;
   name: string;
-  status: 'Added' | 'Deleted' | 'Modified' | 'Renamed' | 'Unchanged';
+  status: FileStatus;
   is_dir: boolean;
   left_path: string | null;
   right_path: string | null;
+  cross_file_move_hint?: string | null;
+  mixed_line_endings?: boolean;
+  rename_info?: RenameInfo | null;
+  skip_content?: boolean;
+  truncated_continuation?: boolean;
+  is_binary?: boolean;
+}
+
+export interface RenameInfo {
+  from: string;
+  to: string;
+  similarity: number;
+  // The content changed too (renamed with changes)
+  modified?: boolean;
 }
 
 export interface FileTreeNode {
   name: string;
   path: string;
-  status: 'Added' | 'Deleted' | 'Modified' | 'Renamed' | null;
+  status: Exclude<FileStatus, 'Unchanged'> | null;
   is_dir: boolean;
   children: FileTreeNode[];
   left_path: string | null;
   right_path: string | null;
+  // Changed files below a directory (0 on files)
+  added?: number;
+  deleted?: number;
+  modified?: number;
 }
 
+// Unchanged lines kept before the first and after the last change in full-file mode
+export const EDGE_CONTEXT_LIMIT = 50;
+
 export interface DiffOptions {
   side_by_side: boolean;
   line_numbers: boolean;
   collapsed: boolean;
+  context_lines?: number | null;
+  fallback_renderer?: boolean;
+  css_classes?: boolean;
+  line_range?: [number, number] | null;
   show_whitespace: boolean;
+  syntax_theme?: string | null;
+  align_moves?: boolean;
+  csv?: boolean;
+  max_cell_width?: number | null;
+  ipynb?: boolean;
+  show_function_context?: boolean;
+  inherit_env?: boolean;
+  delta_env?: Record<string, string>;
+  emit_line_ids?: boolean;
+  max_output_bytes?: number | null;
+  edge_context_limit?: number | null;
+  tab_width?: number | null;
+  respect_editorconfig?: boolean;
+  ignore_comments?: boolean;
+  encoding_metadata?: boolean;
+  word_emphasis?: 'Background' | 'Underline' | 'Bold';
+  intra_line?: boolean | null;
+  normalize_numbers?: boolean;
+  pretty_print_before_diff?: boolean;
+  treat_truncation_as_equal?: boolean;
+  show_blame?: boolean;
+  context_fold_size?: number | null;
+  theme?: 'Dark' | 'Light' | 'Auto';
+  accessible?: boolean;
+  width?: number | null;
+  ignore_whitespace?: boolean;
+  ignore_blank_lines?: boolean;
+  mark_script_changes?: boolean;
+  token_granularity?: boolean;
+  normalize_by_type?: boolean;
+}
+
+// Directories and view options from the command line (get_launch_config)
+export interface LaunchConfig {
+  left: string | null;
+  right: string | null;
+  options: DiffOptions;
+}
+
+export interface HunkInfo {
+  old_start: number;
+  old_lines: number;
+  new_start: number;
+  new_lines: number;
+  header: string;
 }
 
 export interface DiffResult {
@@ -33,6 +116,10 @@ export interface DiffResult {
   hunk_count: number;
   left_html: string | null;
   right_html: string | null;
+  warnings: string[];
+  truncated: boolean;
+  hunks: HunkInfo[];
+  rename_info?: RenameInfo | null;
 }
 
 export const useAppStore = defineStore('app', () => {
@@ -62,6 +149,8 @@ export const useAppStore = defineStore('app', () => {
     line_numbers: true,
     collapsed: true,
     show_whitespace: false,
+    edge_context_limit: EDGE_CONTEXT_LIMIT,
+    fallback_renderer: false,
   });
 
   // Delta availability
@@ -179,6 +268,8 @@ export const useAppStore = defineStore('app', () => {
 
   function setDeltaInstalled(installed: boolean) {
     deltaInstalled.value = installed;
+    // The built-in renderer only stands in for a missing delta
+    viewOptions.value.fallback_renderer = !installed;
   }
 
   function selectPrevFile() {